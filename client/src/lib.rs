@@ -0,0 +1,241 @@
+//! Typed client for the Axum API example.
+//!
+//! Wraps the `/api/v1` routes in async methods that take and return typed payloads.
+//! Error responses are decoded into [`ApiError`], so callers can match on the error code.
+//! The client speaks plain HTTP/1.1; put a TLS terminating proxy in front for HTTPS.
+
+mod types;
+
+use http::header::{CONTENT_TYPE, IF_MATCH};
+use http::{HeaderValue, Method, Request, StatusCode};
+use http_body_util::{BodyExt, Full};
+use hyper::body::Bytes;
+use hyper_util::client::legacy::Client as HttpClient;
+use hyper_util::client::legacy::connect::HttpConnector;
+use hyper_util::rt::TokioExecutor;
+use percent_encoding::{NON_ALPHANUMERIC, utf8_percent_encode};
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+
+pub use crate::types::{
+    ApiError, CreateItem, ErrorCode, FieldError, Health, Item, ItemBatch, ItemBatchQuery, ItemId, ItemList, ListItems,
+    Message, QuotaExceeded, Usage, VersionInfo,
+};
+
+/// Path prefix of the API version this client targets.
+pub const API_PREFIX: &str = "/api/v1";
+
+/// Header the server reads the admin api key from.
+const API_KEY_HEADER: &str = "api-key";
+
+/// Client for one server.
+///
+/// Cheap to clone, clones share the connection pool.
+/// Every call fails with [`ClientError::Api`] when the server returns an error status,
+/// and with the other [`ClientError`] variants when the request does not get a readable response.
+#[derive(Debug, Clone)]
+pub struct Client {
+    base_url: String,
+    api_key: Option<HeaderValue>,
+    http: HttpClient<HttpConnector, Full<Bytes>>,
+}
+
+/// Failed client call.
+#[derive(Debug, thiserror::Error)]
+pub enum ClientError {
+    /// The server answered with an error status and an [`ApiError`] body.
+    #[error("{status}: {} ({})", error.message, error.code)]
+    Api { status: StatusCode, error: ApiError },
+    /// The server answered with a body this client does not understand.
+    #[error("unexpected response body with status {status}: {source}")]
+    Decode {
+        status: StatusCode,
+        source: serde_json::Error,
+    },
+    #[error("invalid request: {0}")]
+    Request(#[from] http::Error),
+    #[error("failed to encode request: {0}")]
+    Encode(String),
+    #[error("request failed: {0}")]
+    Connection(#[from] hyper_util::client::legacy::Error),
+    #[error("failed to read response: {0}")]
+    Body(#[from] hyper::Error),
+}
+
+pub type Result<T> = std::result::Result<T, ClientError>;
+
+// The errors are the same for every call and documented on the struct.
+#[allow(clippy::missing_errors_doc)]
+impl Client {
+    /// Client for the server at the base URL, for example `http://127.0.0.1:3000`.
+    #[must_use]
+    pub fn new(base_url: &str) -> Self {
+        Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            api_key: None,
+            http: HttpClient::builder(TokioExecutor::new()).build_http(),
+        }
+    }
+
+    /// Send the api key with admin requests.
+    ///
+    /// # Panics
+    ///
+    /// If the key is not a valid header value.
+    #[must_use]
+    pub fn with_api_key(mut self, api_key: &str) -> Self {
+        self.api_key = Some(HeaderValue::from_str(api_key).expect("api key should be a valid header value"));
+        self
+    }
+
+    /// `GET /health`
+    pub async fn health(&self) -> Result<Health> {
+        self.send(Method::GET, "/health".to_string(), None, None).await
+    }
+
+    /// `GET /api/v1/version`
+    pub async fn version(&self) -> Result<VersionInfo> {
+        self.send(Method::GET, format!("{API_PREFIX}/version"), None, None)
+            .await
+    }
+
+    /// `GET /api/v1/usage`
+    pub async fn usage(&self) -> Result<Usage> {
+        self.send(Method::GET, format!("{API_PREFIX}/usage"), None, None).await
+    }
+
+    /// `POST /api/v1/items`
+    pub async fn create_item(&self, item: &CreateItem) -> Result<Item> {
+        let body = serde_json::to_vec(item).map_err(|error| ClientError::Encode(error.to_string()))?;
+        self.send(Method::POST, format!("{API_PREFIX}/items"), Some(body), None)
+            .await
+    }
+
+    /// `GET /api/v1/item?name=`
+    pub async fn query_item(&self, name: &str) -> Result<Item> {
+        let query = encode_query(&[("name", name)])?;
+        self.send(Method::GET, format!("{API_PREFIX}/item?{query}"), None, None)
+            .await
+    }
+
+    /// `POST /api/v1/items/query`
+    pub async fn query_items(&self, query: &ItemBatchQuery) -> Result<ItemBatch> {
+        let body = serde_json::to_vec(query).map_err(|error| ClientError::Encode(error.to_string()))?;
+        self.send(Method::POST, format!("{API_PREFIX}/items/query"), Some(body), None)
+            .await
+    }
+
+    /// `GET /api/v1/items`
+    pub async fn list_items(&self, query: &ListItems) -> Result<ItemList> {
+        let query = encode_query(query)?;
+        let path = if query.is_empty() {
+            format!("{API_PREFIX}/items")
+        } else {
+            format!("{API_PREFIX}/items?{query}")
+        };
+        self.send(Method::GET, path, None, None).await
+    }
+
+    /// `DELETE /api/v1/admin/remove/{name}`, only if the item is still at the revision when one is given.
+    pub async fn remove_item(&self, name: &str, revision: Option<u64>) -> Result<Item> {
+        let if_match = revision.map_or_else(|| HeaderValue::from_static("*"), HeaderValue::from);
+        let name = utf8_percent_encode(name, NON_ALPHANUMERIC);
+        self.send(
+            Method::DELETE,
+            format!("{API_PREFIX}/admin/remove/{name}"),
+            None,
+            Some(if_match),
+        )
+        .await
+    }
+
+    /// `DELETE /api/v1/admin/clear_items`
+    pub async fn clear_items(&self) -> Result<Message> {
+        self.send(Method::DELETE, format!("{API_PREFIX}/admin/clear_items"), None, None)
+            .await
+    }
+
+    async fn send<T: DeserializeOwned>(
+        &self,
+        method: Method,
+        path: String,
+        body: Option<Vec<u8>>,
+        if_match: Option<HeaderValue>,
+    ) -> Result<T> {
+        let mut request = Request::builder()
+            .method(method)
+            .uri(format!("{}{path}", self.base_url));
+        if let Some(api_key) = &self.api_key {
+            request = request.header(API_KEY_HEADER, api_key);
+        }
+        if let Some(if_match) = if_match {
+            request = request.header(IF_MATCH, if_match);
+        }
+        let request = match body {
+            Some(body) => request
+                .header(CONTENT_TYPE, "application/json")
+                .body(Full::new(Bytes::from(body)))?,
+            None => request.body(Full::default())?,
+        };
+
+        let response = self.http.request(request).await?;
+        let status = response.status();
+        let bytes = response.into_body().collect().await?.to_bytes();
+        decode(status, &bytes)
+    }
+}
+
+/// Decode a success body as `T` and an error body as [`ApiError`].
+fn decode<T: DeserializeOwned>(status: StatusCode, bytes: &[u8]) -> Result<T> {
+    if status.is_success() {
+        return serde_json::from_slice(bytes).map_err(|source| ClientError::Decode { status, source });
+    }
+    match serde_json::from_slice(bytes) {
+        Ok(error) => Err(ClientError::Api { status, error }),
+        Err(source) => Err(ClientError::Decode { status, source }),
+    }
+}
+
+fn encode_query(query: &impl Serialize) -> Result<String> {
+    serde_urlencoded::to_string(query).map_err(|error| ClientError::Encode(error.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn error_status_decodes_api_error() {
+        let body = br#"{"code":"not_found","message":"Item does not exist: missing","request_id":"abc"}"#;
+
+        let Err(ClientError::Api { status, error }) = decode::<Item>(StatusCode::NOT_FOUND, body) else {
+            panic!("error status should decode as an api error");
+        };
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(error.code, ErrorCode::NotFound);
+        assert_eq!(error.request_id.as_deref(), Some("abc"));
+    }
+
+    #[test]
+    fn unexpected_bodies_are_decode_errors() {
+        assert!(matches!(
+            decode::<Message>(StatusCode::BAD_GATEWAY, b"<html>"),
+            Err(ClientError::Decode { .. })
+        ));
+        assert!(matches!(
+            decode::<Message>(StatusCode::OK, b"{}"),
+            Err(ClientError::Decode { .. })
+        ));
+        assert_eq!(
+            decode::<Message>(StatusCode::OK, br#"{"message":"ok"}"#)
+                .unwrap()
+                .message,
+            "ok"
+        );
+    }
+
+    #[test]
+    fn base_url_trailing_slash_is_trimmed() {
+        assert_eq!(Client::new("http://localhost:3000/").base_url, "http://localhost:3000");
+    }
+}
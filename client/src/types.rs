@@ -0,0 +1,255 @@
+//! Request and response payloads.
+//!
+//! Mirrors the server schemas without depending on the server crate,
+//! so downstream services only pull in the client.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Item identifier, a number or a UUID / ULID string depending on the server id strategy.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ItemId {
+    Numeric(u64),
+    Text(String),
+}
+
+/// Stored item.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Item {
+    pub id: ItemId,
+    pub name: String,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub metadata: HashMap<String, String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data: Option<serde_json::Value>,
+    /// Send this in `If-Match` when changing the item.
+    pub revision: u64,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+/// Payload for creating an item, only the name is required.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CreateItem {
+    pub name: String,
+    /// Id to use instead of a generated one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<ItemId>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub metadata: HashMap<String, String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data: Option<serde_json::Value>,
+    /// The item is removed this many seconds after creation.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ttl_seconds: Option<u64>,
+}
+
+/// Filters and pagination for listing items.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ListItems {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub skip: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub limit: Option<usize>,
+    /// Only list items created after this time.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created_after: Option<DateTime<Utc>>,
+    /// Only list items with this tag.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tag: Option<String>,
+}
+
+/// One page of item names.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ItemList {
+    /// Number of matching items on all pages.
+    pub num_items: usize,
+    pub names: Vec<String>,
+}
+
+/// Names of the items to get in one request.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ItemBatchQuery {
+    pub names: Vec<String>,
+}
+
+/// Found items in the requested order, and the requested names without an item.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ItemBatch {
+    pub items: Vec<Item>,
+    pub missing: Vec<String>,
+}
+
+/// Item count and data size of the item store, with their quotas.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Usage {
+    pub num_items: usize,
+    /// Unset when the item count is not limited.
+    #[serde(default)]
+    pub max_items: Option<usize>,
+    pub data_bytes: usize,
+    /// Unset when the data size is not limited.
+    #[serde(default)]
+    pub max_data_bytes: Option<usize>,
+}
+
+/// Informational message, like the number of cleared items.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Message {
+    pub message: String,
+}
+
+/// Service health.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Health {
+    pub service: String,
+    pub version: String,
+    pub environment: String,
+    pub status: String,
+    pub timestamp: String,
+    pub start_time: String,
+    pub uptime_ms: u64,
+}
+
+/// Server version and build information.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VersionInfo {
+    pub name: String,
+    pub version: String,
+    pub deploy_tag: String,
+    pub build_time: String,
+    pub branch: String,
+    pub commit: String,
+    pub rust_version: String,
+}
+
+/// Error body the server returns for every error status.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct ApiError {
+    pub code: ErrorCode,
+    pub message: String,
+    /// Id of the failed request, for matching with the server logs.
+    #[serde(default)]
+    pub request_id: Option<String>,
+    /// Every violated rule for validation errors.
+    #[serde(default)]
+    pub fields: Vec<FieldError>,
+    /// The exceeded limit for quota errors.
+    #[serde(default)]
+    pub quota: Option<QuotaExceeded>,
+}
+
+/// Storage quota that a write would have exceeded.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct QuotaExceeded {
+    /// `items` or `data_bytes`.
+    pub resource: String,
+    pub limit: usize,
+    /// Usage the write would have led to.
+    pub requested: usize,
+}
+
+/// Single violated validation rule.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct FieldError {
+    pub field: String,
+    pub message: String,
+}
+
+/// Machine readable error category.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    BadRequest,
+    Unauthorized,
+    Forbidden,
+    NotFound,
+    MethodNotAllowed,
+    Conflict,
+    PreconditionFailed,
+    PayloadTooLarge,
+    UnsupportedMediaType,
+    ValidationFailed,
+    PreconditionRequired,
+    RateLimited,
+    Internal,
+    Unavailable,
+    /// Code added to the server after this client version.
+    #[serde(other)]
+    Unknown,
+}
+
+impl ErrorCode {
+    /// The code as the server sends it.
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::BadRequest => "bad_request",
+            Self::Unauthorized => "unauthorized",
+            Self::Forbidden => "forbidden",
+            Self::NotFound => "not_found",
+            Self::MethodNotAllowed => "method_not_allowed",
+            Self::Conflict => "conflict",
+            Self::PreconditionFailed => "precondition_failed",
+            Self::PayloadTooLarge => "payload_too_large",
+            Self::UnsupportedMediaType => "unsupported_media_type",
+            Self::ValidationFailed => "validation_failed",
+            Self::PreconditionRequired => "precondition_required",
+            Self::RateLimited => "rate_limited",
+            Self::Internal => "internal",
+            Self::Unavailable => "unavailable",
+            Self::Unknown => "unknown",
+        }
+    }
+}
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn item_ids_deserialize_from_numbers_and_strings() {
+        assert_eq!(serde_json::from_str::<ItemId>("1234").unwrap(), ItemId::Numeric(1234));
+        assert_eq!(
+            serde_json::from_str::<ItemId>("\"01ARZ3NDEKTSV4RRFFQ69G5FAV\"").unwrap(),
+            ItemId::Text("01ARZ3NDEKTSV4RRFFQ69G5FAV".to_string())
+        );
+    }
+
+    #[test]
+    fn unknown_error_codes_are_accepted() {
+        let error: ApiError = serde_json::from_str(r#"{"code":"teapot","message":"short and stout"}"#).unwrap();
+
+        assert_eq!(error.code, ErrorCode::Unknown);
+        assert_eq!(error.request_id, None);
+        assert!(error.fields.is_empty());
+    }
+
+    #[test]
+    fn list_query_skips_unset_filters() {
+        let query = ListItems {
+            limit: Some(10),
+            tag: Some("rust lang".to_string()),
+            ..ListItems::default()
+        };
+
+        assert_eq!(serde_urlencoded::to_string(&query).unwrap(), "limit=10&tag=rust+lang");
+        assert_eq!(serde_urlencoded::to_string(ListItems::default()).unwrap(), "");
+    }
+}
@@ -0,0 +1,30 @@
+//! `POST /items` payload handling without the router.
+//!
+//! Any body that passes validation and the settings checks has to become an item
+//! that keeps the item invariants, since the handler treats a failure there as a server error.
+
+#![no_main]
+
+use axum_example::schemas::CreateItem;
+use axum_example::settings::Settings;
+use axum_example::types::AppState;
+use axum_example::validation::ValidatedJson;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let payload = match ValidatedJson::<CreateItem>::from_bytes(data) {
+        Ok(ValidatedJson(payload)) => payload,
+        Err(error) => {
+            assert!(error.status().is_client_error(), "{error:?}");
+            return;
+        }
+    };
+    let settings = Settings::default();
+    if !payload.settings_violations(&settings).is_empty() {
+        return;
+    }
+    let item = payload
+        .into_item(settings.id_strategy, &AppState::new())
+        .expect("valid payload should become an item");
+    assert_eq!(item.check_invariants(settings.id_strategy), Ok(()));
+});
@@ -0,0 +1,53 @@
+//! Rejection mapping for request bodies that are not valid `CreateItem` JSON.
+//!
+//! The first byte picks the content type and the rest is the body.
+//! Every rejection has to be a client error with an `ApiError` body of the same status.
+
+#![no_main]
+
+use axum::body::Body;
+use axum::extract::{FromRequest, Request};
+use axum::http::header::CONTENT_TYPE;
+use axum::response::IntoResponse;
+use axum_example::schemas::{ApiError, CreateItem};
+use axum_example::validation::ValidatedJson;
+use futures_util::FutureExt;
+use libfuzzer_sys::fuzz_target;
+
+const CONTENT_TYPES: [Option<&str>; 5] = [
+    Some("application/json"),
+    Some("application/json; charset=utf-8"),
+    Some("application/vnd.api+json"),
+    Some("text/plain"),
+    None,
+];
+
+fuzz_target!(|data: &[u8]| {
+    let Some((selector, body)) = data.split_first() else {
+        return;
+    };
+    let mut request = Request::builder();
+    if let Some(content_type) = CONTENT_TYPES[usize::from(*selector) % CONTENT_TYPES.len()] {
+        request = request.header(CONTENT_TYPE, content_type);
+    }
+    let request = request.body(Body::from(body.to_vec())).expect("request should build");
+
+    // The body is already in memory, so extraction and collecting the response finish without a runtime.
+    let Err(rejection) = ValidatedJson::<CreateItem>::from_request(request, &())
+        .now_or_never()
+        .expect("extraction should not wait")
+    else {
+        return;
+    };
+    let status = rejection.status();
+    assert!(status.is_client_error(), "{rejection:?}");
+
+    let response = rejection.into_response();
+    assert_eq!(response.status(), status);
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .now_or_never()
+        .expect("body should be ready")
+        .expect("body should be readable");
+    let error: ApiError = serde_json::from_slice(&bytes).expect("rejection body should be an ApiError");
+    assert_eq!(error.status(), status);
+});
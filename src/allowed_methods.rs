@@ -0,0 +1,149 @@
+//! `OPTIONS` and method-not-allowed responses.
+//!
+//! Axum already answers `HEAD` for every `GET` route and lists the methods of a path
+//! in the `Allow` header of its `405 Method Not Allowed` responses.
+//! This middleware turns that `405` into a `204 No Content` for `OPTIONS` requests,
+//! so every path answers `OPTIONS` with its methods without registering a handler per route,
+//! and names the allowed methods in the `ApiError` body of other `405` responses.
+//! Axum adds the `Allow` header after every route layer has run,
+//! so this has to wrap the whole router instead of being a per-route fallback.
+//! CORS preflight requests, which carry `Access-Control-Request-Method`, are still answered by the CORS layer.
+
+use axum::Json;
+use axum::body::Body;
+use axum::extract::Request;
+use axum::http::header::{ALLOW, CONTENT_LENGTH, CONTENT_TYPE};
+use axum::http::{HeaderValue, Method, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+
+use crate::i18n;
+use crate::request_id::REQUEST_ID_HEADER;
+use crate::schemas::{ApiError, ErrorCode};
+
+/// Answer `OPTIONS` with the methods of the path, and list the allowed methods in every `405` response.
+pub async fn allowed_methods_middleware(request: Request, next: Next) -> Response {
+    let method = request.method().clone();
+    let response = next.run(request).await;
+    if response.status() != StatusCode::METHOD_NOT_ALLOWED {
+        return response;
+    }
+    let Some(methods) = response.headers().get(ALLOW).and_then(allowed_methods) else {
+        return response;
+    };
+    let Ok(allow) = HeaderValue::from_str(&methods.join(",")) else {
+        return response;
+    };
+    // Keep the request id and other shared headers, only the body is replaced.
+    let (mut parts, _) = response.into_parts();
+    parts.headers.insert(ALLOW, allow);
+    parts.headers.remove(CONTENT_LENGTH);
+    if method == Method::OPTIONS {
+        parts.status = StatusCode::NO_CONTENT;
+        parts.headers.remove(CONTENT_TYPE);
+        return Response::from_parts(parts, Body::empty());
+    }
+    let mut error = ApiError::new(
+        ErrorCode::MethodNotAllowed,
+        i18n::message(
+            "method_not_allowed",
+            &[("method", &method), ("allowed", &methods.join(", "))],
+        ),
+    );
+    // The request id scope has already ended out here, so take the id from the response.
+    error.request_id = parts
+        .headers
+        .get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .map(ToString::to_string);
+    let (json_parts, body) = Json(error).into_response().into_parts();
+    parts.headers.extend(json_parts.headers);
+    Response::from_parts(parts, body)
+}
+
+/// Methods listed in an `Allow` header value, with `OPTIONS` added.
+fn allowed_methods(allow: &HeaderValue) -> Option<Vec<String>> {
+    let mut methods: Vec<String> = allow
+        .to_str()
+        .ok()?
+        .split(',')
+        .map(str::trim)
+        .filter(|method| !method.is_empty())
+        .map(ToString::to_string)
+        .collect();
+    if !methods.iter().any(|method| method == Method::OPTIONS.as_str()) {
+        methods.push(Method::OPTIONS.to_string());
+    }
+    Some(methods)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use axum::http::header::CONTENT_TYPE;
+
+    use crate::test_utils::TestApp;
+
+    fn request(method: Method, uri: &str) -> Request {
+        Request::builder().method(method).uri(uri).body(Body::empty()).unwrap()
+    }
+
+    #[test]
+    fn options_is_added_once() {
+        assert_eq!(
+            allowed_methods(&HeaderValue::from_static("GET,HEAD")).unwrap(),
+            ["GET", "HEAD", "OPTIONS"]
+        );
+        assert_eq!(
+            allowed_methods(&HeaderValue::from_static("GET, OPTIONS")).unwrap(),
+            ["GET", "OPTIONS"]
+        );
+    }
+
+    #[tokio::test]
+    async fn head_requests_are_answered_without_a_body() {
+        let app = TestApp::new();
+        let response = app.send(request(Method::HEAD, "/health")).await;
+        assert_eq!(response.status, StatusCode::OK);
+        assert_eq!(response.headers[CONTENT_TYPE], "application/json");
+        assert!(response.body.is_empty());
+
+        // A cached `HEAD` response must not be served to a later `GET`.
+        assert!(app.send(request(Method::HEAD, "/api/v1/items")).await.body.is_empty());
+        let response = app.get("/api/v1/items").await;
+        assert_eq!(response.status, StatusCode::OK);
+        assert!(!response.body.is_empty());
+    }
+
+    #[tokio::test]
+    async fn options_lists_the_methods_of_the_path() {
+        let app = TestApp::new();
+        let response = app.send(request(Method::OPTIONS, "/api/v1/items")).await;
+        assert_eq!(response.status, StatusCode::NO_CONTENT);
+        assert_eq!(response.headers[ALLOW], "GET,HEAD,POST,OPTIONS");
+        assert!(response.body.is_empty());
+        assert!(response.headers.contains_key("x-request-id"));
+        assert!(!response.headers.contains_key(CONTENT_TYPE));
+
+        let response = app.send(request(Method::OPTIONS, "/health")).await;
+        assert_eq!(response.headers[ALLOW], "GET,HEAD,OPTIONS");
+
+        let response = app.send(request(Method::PUT, "/api/v1/items")).await;
+        assert_eq!(response.status, StatusCode::METHOD_NOT_ALLOWED);
+        assert_eq!(response.headers[ALLOW], "GET,HEAD,POST,OPTIONS");
+        let error: ApiError = response.json();
+        assert_eq!(error.code, ErrorCode::MethodNotAllowed);
+        assert_eq!(
+            error.message,
+            "Method PUT is not allowed, use one of: GET, HEAD, POST, OPTIONS"
+        );
+        assert_eq!(
+            error.request_id.as_deref(),
+            response.headers["x-request-id"].to_str().ok()
+        );
+
+        let response = app.send(request(Method::OPTIONS, "/missing")).await;
+        assert_eq!(response.status, StatusCode::NOT_FOUND);
+    }
+}
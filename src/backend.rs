@@ -0,0 +1,535 @@
+//! Backing store for the default collection.
+//!
+//! With a `backend` configured, the in-memory default collection becomes a cache over an external store.
+//! Reads of an item that is not cached, or was cached longer than `backend.cache_ttl_seconds` ago,
+//! fall through to the backend and refresh the cached copy,
+//! and creates, removes and admin clears write to both the backend and the cache.
+//! The `directory` backend keeps one JSON file per item, for local runs and shared volumes,
+//! and the `dynamodb` backend stores items in a `DynamoDB` table keyed by the string attribute `name`.
+//! Listings, named collections and tenant stores only see the in-memory items.
+//! The backend also keeps the locks of [`crate::lock`], since the replicas sharing it are the ones to coordinate.
+
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+use aws_sdk_dynamodb::operation::delete_item::DeleteItemError;
+use aws_sdk_dynamodb::operation::put_item::PutItemError;
+use aws_sdk_dynamodb::types::AttributeValue;
+use chrono::{DateTime, Utc};
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use tokio::io::AsyncWriteExt;
+
+use crate::types::{BackendConfig, BackendProvider, Collection, Item, SharedState};
+
+/// `DynamoDB` attribute holding the item as JSON.
+const ITEM_ATTRIBUTE: &str = "item";
+
+/// `DynamoDB` partition key attribute.
+const NAME_ATTRIBUTE: &str = "name";
+
+/// `DynamoDB` attribute with the owner of a lock.
+const OWNER_ATTRIBUTE: &str = "owner";
+
+/// `DynamoDB` attribute with the Unix time when a lock expires.
+const EXPIRES_AT_ATTRIBUTE: &str = "expires_at";
+
+/// Prefix of lock keys in `DynamoDB`, which item names can not contain.
+const LOCK_KEY_PREFIX: &str = "lock#";
+
+/// File the `directory` backend check writes, which the item and lock files never clash with.
+const PROBE_FILE: &str = ".check";
+
+/// External item store behind the default collection.
+#[derive(Debug)]
+pub struct Backend {
+    source: BackendSource,
+    /// When each cached item was last loaded from or written to the backend.
+    loaded_at: DashMap<String, Instant>,
+    /// Zero keeps cached items until they change.
+    cache_ttl: Duration,
+}
+
+/// Lock file contents of the `directory` backend.
+#[derive(Debug, Serialize, Deserialize)]
+struct LockRecord {
+    owner: String,
+    expires_at: DateTime<Utc>,
+}
+
+/// Where items are stored.
+#[derive(Debug)]
+enum BackendSource {
+    Directory(PathBuf),
+    DynamoDb {
+        client: aws_sdk_dynamodb::Client,
+        table: String,
+    },
+}
+
+impl Backend {
+    /// Connect to the configured backend, `None` when no backend is configured.
+    ///
+    /// AWS credentials and region come from the standard AWS environment and config files.
+    pub async fn connect(config: &BackendConfig) -> Result<Option<Self>> {
+        let Some(provider) = config.provider else {
+            return Ok(None);
+        };
+        let source = match provider {
+            BackendProvider::Directory => {
+                let directory = config
+                    .directory
+                    .clone()
+                    .context("BACKEND_DIRECTORY is required for the directory backend")?;
+                tokio::fs::create_dir_all(&directory)
+                    .await
+                    .with_context(|| format!("Failed to create backend directory {}", directory.display()))?;
+                BackendSource::Directory(directory)
+            }
+            BackendProvider::Dynamodb => BackendSource::DynamoDb {
+                client: aws_sdk_dynamodb::Client::new(&aws_config::load_from_env().await),
+                table: config
+                    .table
+                    .clone()
+                    .context("BACKEND_TABLE is required for the dynamodb backend")?,
+            },
+        };
+        Ok(Some(Self::new(source, config.cache_ttl())))
+    }
+
+    /// Backend that stores items as JSON files in the directory.
+    #[must_use]
+    pub fn directory(directory: PathBuf, cache_ttl: Duration) -> Self {
+        Self::new(BackendSource::Directory(directory), cache_ttl)
+    }
+
+    fn new(source: BackendSource, cache_ttl: Duration) -> Self {
+        Self {
+            source,
+            loaded_at: DashMap::new(),
+            cache_ttl,
+        }
+    }
+
+    /// Item with the given name from the backend.
+    pub async fn get(&self, name: &str) -> Result<Option<Item>> {
+        let json = match &self.source {
+            BackendSource::Directory(directory) => match tokio::fs::read(item_path(directory, name)).await {
+                Ok(bytes) => bytes,
+                Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+                Err(error) => return Err(error).with_context(|| format!("Failed to read item {name} from backend")),
+            },
+            BackendSource::DynamoDb { client, table } => {
+                let output = client
+                    .get_item()
+                    .table_name(table)
+                    .key(NAME_ATTRIBUTE, AttributeValue::S(name.to_string()))
+                    .consistent_read(true)
+                    .send()
+                    .await
+                    .with_context(|| format!("Failed to get item {name} from DynamoDB"))?;
+                let Some(attribute) = output.item().and_then(|attributes| attributes.get(ITEM_ATTRIBUTE)) else {
+                    return Ok(None);
+                };
+                attribute
+                    .as_s()
+                    .map_err(|_| anyhow::anyhow!("Item {name} in DynamoDB is not a string"))?
+                    .clone()
+                    .into_bytes()
+            }
+        };
+        let item = serde_json::from_slice(&json).with_context(|| format!("Invalid item {name} in backend"))?;
+        Ok(Some(item))
+    }
+
+    /// Write the item to the backend, replacing any item with the same name.
+    pub async fn put(&self, item: &Item) -> Result<()> {
+        let json = serde_json::to_string(item)?;
+        match &self.source {
+            BackendSource::Directory(directory) => {
+                // Write a temporary file first so readers never see a partially written item.
+                let path = item_path(directory, &item.name);
+                let temporary = path.with_extension("json.tmp");
+                tokio::fs::write(&temporary, json).await?;
+                tokio::fs::rename(&temporary, &path)
+                    .await
+                    .with_context(|| format!("Failed to write item {} to backend", item.name))?;
+            }
+            BackendSource::DynamoDb { client, table } => {
+                client
+                    .put_item()
+                    .table_name(table)
+                    .item(NAME_ATTRIBUTE, AttributeValue::S(item.name.clone()))
+                    .item(ITEM_ATTRIBUTE, AttributeValue::S(json))
+                    .send()
+                    .await
+                    .with_context(|| format!("Failed to put item {} to DynamoDB", item.name))?;
+            }
+        }
+        self.loaded_at.insert(item.name.clone(), Instant::now());
+        Ok(())
+    }
+
+    /// Remove the named item from the backend.
+    pub async fn delete(&self, name: &str) -> Result<()> {
+        match &self.source {
+            BackendSource::Directory(directory) => match tokio::fs::remove_file(item_path(directory, name)).await {
+                Err(error) if error.kind() != std::io::ErrorKind::NotFound => {
+                    return Err(error).with_context(|| format!("Failed to remove item {name} from backend"));
+                }
+                _ => {}
+            },
+            BackendSource::DynamoDb { client, table } => {
+                client
+                    .delete_item()
+                    .table_name(table)
+                    .key(NAME_ATTRIBUTE, AttributeValue::S(name.to_string()))
+                    .send()
+                    .await
+                    .with_context(|| format!("Failed to delete item {name} from DynamoDB"))?;
+            }
+        }
+        self.loaded_at.remove(name);
+        Ok(())
+    }
+
+    /// Remove every item from the backend and return how many there were.
+    pub async fn clear(&self) -> Result<usize> {
+        let mut count = 0;
+        match &self.source {
+            BackendSource::Directory(directory) => {
+                let mut entries = tokio::fs::read_dir(directory).await?;
+                while let Some(entry) = entries.next_entry().await? {
+                    if entry.path().extension().is_some_and(|extension| extension == "json") {
+                        tokio::fs::remove_file(entry.path()).await?;
+                        count += 1;
+                    }
+                }
+            }
+            BackendSource::DynamoDb { client, table } => {
+                let mut start_key = None;
+                loop {
+                    let output = client
+                        .scan()
+                        .table_name(table)
+                        .projection_expression("#name")
+                        .filter_expression("attribute_exists(#item)")
+                        .expression_attribute_names("#name", NAME_ATTRIBUTE)
+                        .expression_attribute_names("#item", ITEM_ATTRIBUTE)
+                        .set_exclusive_start_key(start_key)
+                        .send()
+                        .await
+                        .context("Failed to scan DynamoDB table")?;
+                    for key in output.items() {
+                        client
+                            .delete_item()
+                            .table_name(table)
+                            .set_key(Some(key.clone()))
+                            .send()
+                            .await
+                            .context("Failed to delete item from DynamoDB")?;
+                        count += 1;
+                    }
+                    start_key = output.last_evaluated_key().cloned();
+                    if start_key.is_none() {
+                        break;
+                    }
+                }
+            }
+        }
+        self.loaded_at.clear();
+        Ok(count)
+    }
+
+    /// Take the named lock for the owner until the TTL runs out, `false` if another owner holds it.
+    ///
+    /// An expired lock is taken over.
+    pub async fn try_lock(&self, name: &str, owner: &str, ttl: Duration) -> Result<bool> {
+        let now = Utc::now();
+        let expires_at = now + ttl;
+        match &self.source {
+            BackendSource::Directory(directory) => {
+                let path = lock_path(directory, name);
+                let record = serde_json::to_vec(&LockRecord {
+                    owner: owner.to_string(),
+                    expires_at,
+                })?;
+                // Retry once after removing an expired lock.
+                for _ in 0..2 {
+                    match tokio::fs::OpenOptions::new()
+                        .write(true)
+                        .create_new(true)
+                        .open(&path)
+                        .await
+                    {
+                        Ok(mut file) => {
+                            file.write_all(&record).await?;
+                            return Ok(true);
+                        }
+                        Err(error) if error.kind() == std::io::ErrorKind::AlreadyExists => {
+                            // A lock that is still being written does not parse and counts as held.
+                            let held = tokio::fs::read(&path)
+                                .await
+                                .ok()
+                                .and_then(|bytes| serde_json::from_slice::<LockRecord>(&bytes).ok())
+                                .is_none_or(|record| record.expires_at > now);
+                            if held {
+                                return Ok(false);
+                            }
+                            tokio::fs::remove_file(&path).await.ok();
+                        }
+                        Err(error) => return Err(error).with_context(|| format!("Failed to create lock {name}")),
+                    }
+                }
+                Ok(false)
+            }
+            BackendSource::DynamoDb { client, table } => {
+                let result = client
+                    .put_item()
+                    .table_name(table)
+                    .item(NAME_ATTRIBUTE, AttributeValue::S(format!("{LOCK_KEY_PREFIX}{name}")))
+                    .item(OWNER_ATTRIBUTE, AttributeValue::S(owner.to_string()))
+                    .item(
+                        EXPIRES_AT_ATTRIBUTE,
+                        AttributeValue::N(expires_at.timestamp().to_string()),
+                    )
+                    .condition_expression("attribute_not_exists(#name) OR #expires_at < :now")
+                    .expression_attribute_names("#name", NAME_ATTRIBUTE)
+                    .expression_attribute_names("#expires_at", EXPIRES_AT_ATTRIBUTE)
+                    .expression_attribute_values(":now", AttributeValue::N(now.timestamp().to_string()))
+                    .send()
+                    .await;
+                match result {
+                    Ok(_) => Ok(true),
+                    Err(error)
+                        if error
+                            .as_service_error()
+                            .is_some_and(PutItemError::is_conditional_check_failed_exception) =>
+                    {
+                        Ok(false)
+                    }
+                    Err(error) => Err(error).with_context(|| format!("Failed to put lock {name} to DynamoDB")),
+                }
+            }
+        }
+    }
+
+    /// Release the named lock if the owner still holds it.
+    pub async fn unlock(&self, name: &str, owner: &str) -> Result<()> {
+        match &self.source {
+            BackendSource::Directory(directory) => {
+                let path = lock_path(directory, name);
+                let is_owner = tokio::fs::read(&path)
+                    .await
+                    .ok()
+                    .and_then(|bytes| serde_json::from_slice::<LockRecord>(&bytes).ok())
+                    .is_some_and(|record| record.owner == owner);
+                if is_owner {
+                    tokio::fs::remove_file(&path)
+                        .await
+                        .with_context(|| format!("Failed to remove lock {name}"))?;
+                }
+            }
+            BackendSource::DynamoDb { client, table } => {
+                let result = client
+                    .delete_item()
+                    .table_name(table)
+                    .key(NAME_ATTRIBUTE, AttributeValue::S(format!("{LOCK_KEY_PREFIX}{name}")))
+                    .condition_expression("#owner = :owner")
+                    .expression_attribute_names("#owner", OWNER_ATTRIBUTE)
+                    .expression_attribute_values(":owner", AttributeValue::S(owner.to_string()))
+                    .send()
+                    .await;
+                if let Err(error) = result
+                    && !error
+                        .as_service_error()
+                        .is_some_and(DeleteItemError::is_conditional_check_failed_exception)
+                {
+                    return Err(error).with_context(|| format!("Failed to delete lock {name} from DynamoDB"));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Check that the backend can be reached and written to.
+    ///
+    /// The `directory` backend writes and removes a probe file,
+    /// and the `dynamodb` backend describes the table.
+    pub async fn check(&self) -> Result<()> {
+        match &self.source {
+            BackendSource::Directory(directory) => {
+                let path = directory.join(PROBE_FILE);
+                tokio::fs::write(&path, b"")
+                    .await
+                    .with_context(|| format!("Backend directory {} is not writable", directory.display()))?;
+                tokio::fs::remove_file(&path)
+                    .await
+                    .with_context(|| format!("Failed to remove {}", path.display()))?;
+            }
+            BackendSource::DynamoDb { client, table } => {
+                client
+                    .describe_table()
+                    .table_name(table)
+                    .send()
+                    .await
+                    .with_context(|| format!("Failed to describe DynamoDB table {table}"))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// The cached copy of the named item is missing or older than the cache TTL.
+    ///
+    /// Items that were never loaded from or written to the backend, like seeded ones, are kept as they are.
+    fn is_stale(&self, collection: &Collection, name: &str) -> bool {
+        if !collection.db.contains_key(name) {
+            return true;
+        }
+        self.loaded_at
+            .get(name)
+            .is_some_and(|loaded_at| !self.cache_ttl.is_zero() && loaded_at.elapsed() >= self.cache_ttl)
+    }
+}
+
+/// Backend of the collection, if it is the default collection and a backend is configured.
+pub fn for_collection<'a>(state: &'a SharedState, collection: &Collection) -> Option<&'a Backend> {
+    state.backend().filter(|_| std::ptr::eq(collection, state.items()))
+}
+
+/// Refresh the cached copy of the named item from the backend when it is missing or stale.
+///
+/// Items removed from the backend are removed from the cache too.
+/// Backend errors are logged and leave the cache as it was.
+pub async fn read_through(state: &SharedState, collection: &Collection, name: &str) {
+    let Some(backend) = for_collection(state, collection) else {
+        return;
+    };
+    if !backend.is_stale(collection, name) {
+        return;
+    }
+    match backend.get(name).await {
+        Ok(Some(item)) => {
+            collection.remove_item_if(name, |_| true);
+            if let Err(duplicate) = collection.insert_item(&item) {
+                crate::log_warn!("Backend item {name} conflicts with a cached item: {duplicate:?}");
+                return;
+            }
+            backend.loaded_at.insert(name.to_string(), Instant::now());
+        }
+        Ok(None) => {
+            collection.remove_item_if(name, |_| true);
+            backend.loaded_at.remove(name);
+        }
+        Err(error) => crate::log_error!("Backend read failed, using the cached item: {error:#}"),
+    }
+}
+
+/// File of the named item, with the name hex encoded so any name is a valid file name.
+fn item_path(directory: &Path, name: &str) -> PathBuf {
+    let encoded = name.bytes().fold(String::new(), |mut encoded, byte| {
+        let _ = write!(encoded, "{byte:02x}");
+        encoded
+    });
+    directory.join(format!("{encoded}.json"))
+}
+
+/// Lock file of the `directory` backend, which the item files never clash with.
+fn lock_path(directory: &Path, name: &str) -> PathBuf {
+    directory.join(format!("{name}.lock"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::Arc;
+
+    use axum::body::Body;
+    use axum::extract::Request;
+    use axum::http::StatusCode;
+    use serde_json::json;
+
+    use crate::schemas::ItemBatchResponse;
+    use crate::settings::Settings;
+    use crate::test_utils::TestApp;
+    use crate::types::{AppState, DEFAULT_API_KEY, IdStrategy};
+
+    fn backend_app(name: &str, cache_ttl: Duration) -> (TestApp, PathBuf) {
+        let directory = std::env::temp_dir().join(format!("axum-example-backend-{}-{name}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let state = AppState::new().with_backend(Some(Backend::directory(directory.clone(), cache_ttl)));
+        (TestApp::with_state(Arc::new(state), Settings::default()), directory)
+    }
+
+    #[tokio::test]
+    async fn reads_fall_through_and_writes_update_both() {
+        let (app, directory) = backend_app("through", Duration::ZERO);
+        let backend = app.state.backend().expect("backend");
+
+        app.create_item(json!({ "name": "written" })).await;
+        assert_eq!(backend.get("written").await.unwrap().unwrap().name, "written");
+
+        backend
+            .put(&Item::new_with_random_id("stored".to_string(), IdStrategy::Uuid))
+            .await
+            .unwrap();
+        assert!(!app.state.items().db.contains_key("stored"));
+        assert_eq!(app.get("/item?name=stored").await.status, StatusCode::OK);
+        assert!(app.state.items().db.contains_key("stored"));
+
+        let response = app.post_json("/items", &json!({ "name": "stored" })).await;
+        assert_eq!(response.status, StatusCode::CONFLICT);
+
+        let remove = Request::builder()
+            .method("DELETE")
+            .uri("/admin/remove/stored")
+            .header("api-key", DEFAULT_API_KEY)
+            .header("If-Match", "*")
+            .body(Body::empty())
+            .unwrap();
+        assert_eq!(app.send(remove).await.status, StatusCode::OK);
+        assert!(backend.get("stored").await.unwrap().is_none());
+
+        let response = app.post_json("/items/query", &json!({ "names": ["written"] })).await;
+        assert_eq!(response.json::<ItemBatchResponse>().items.len(), 1);
+
+        assert_eq!(app.delete("/admin/clear_items").await.status, StatusCode::OK);
+        assert!(backend.get("written").await.unwrap().is_none());
+        assert_eq!(app.get("/item?name=written").await.status, StatusCode::NOT_FOUND);
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[tokio::test]
+    async fn stale_items_are_reloaded() {
+        let (app, directory) = backend_app("stale", Duration::from_millis(1));
+        let backend = app.state.backend().expect("backend");
+
+        app.create_item(json!({ "name": "changed", "tags": ["old"] })).await;
+        let mut item = backend.get("changed").await.unwrap().unwrap();
+        item.tags = vec!["new".to_string()];
+        backend.put(&item).await.unwrap();
+        // Another replica removed this one.
+        app.create_item(json!({ "name": "removed" })).await;
+        std::fs::remove_file(item_path(&directory, "removed")).unwrap();
+
+        tokio::time::sleep(Duration::from_millis(5)).await;
+        let response = app.get("/item?name=changed").await;
+        assert_eq!(response.json::<Item>().tags, ["new"]);
+        assert_eq!(app.get("/item?name=removed").await.status, StatusCode::NOT_FOUND);
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[tokio::test]
+    async fn check_needs_a_writable_directory() {
+        let (app, directory) = backend_app("check", Duration::ZERO);
+        let backend = app.state.backend().expect("backend");
+        backend.check().await.unwrap();
+        assert!(!directory.join(PROBE_FILE).exists());
+
+        std::fs::remove_dir_all(&directory).unwrap();
+        assert!(backend.check().await.is_err());
+    }
+}
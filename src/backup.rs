@@ -0,0 +1,104 @@
+//! Backups to a directory.
+//!
+//! Writes a snapshot of the state to the configured backup directory,
+//! from the scheduled `backup` task or a `POST /admin/backups` job,
+//! in the same gzip JSON format as `GET /admin/backup`, so any of them can be loaded with `POST /admin/restore`.
+//! Older backups beyond the retention count are removed after each write.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+
+use crate::snapshot::{SNAPSHOT_FILE_SUFFIX, Snapshot, snapshot_file_prefix};
+use crate::types::AppState;
+
+/// Write a snapshot to the directory, remove backups beyond the retention count, and return the new file.
+///
+/// The snapshot is written to a temporary file first and renamed,
+/// so an interrupted write never leaves a partial backup behind.
+pub fn write_backup(state: &AppState, directory: &Path, retention: usize) -> Result<PathBuf> {
+    std::fs::create_dir_all(directory)
+        .with_context(|| format!("Failed to create backup directory {}", directory.display()))?;
+    let snapshot = Snapshot::capture(state.items());
+    let path = directory.join(snapshot.file_name());
+    let partial = path.with_extension("partial");
+    std::fs::write(&partial, snapshot.to_gzip()?)
+        .with_context(|| format!("Failed to write backup {}", partial.display()))?;
+    std::fs::rename(&partial, &path).with_context(|| format!("Failed to write backup {}", path.display()))?;
+    prune(directory, retention)?;
+    Ok(path)
+}
+
+/// Remove the oldest backups so at most `retention` remain, zero keeps every backup.
+///
+/// Only files named like snapshots are touched, other files in the directory are left alone.
+fn prune(directory: &Path, retention: usize) -> Result<()> {
+    if retention == 0 {
+        return Ok(());
+    }
+    let prefix = snapshot_file_prefix();
+    let mut backups: Vec<PathBuf> = std::fs::read_dir(directory)
+        .with_context(|| format!("Failed to list backup directory {}", directory.display()))?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.starts_with(&prefix) && name.ends_with(SNAPSHOT_FILE_SUFFIX))
+        })
+        .collect();
+    // Timestamps in the names sort oldest first.
+    backups.sort();
+    let excess = backups.len().saturating_sub(retention);
+    for path in &backups[..excess] {
+        std::fs::remove_file(path).with_context(|| format!("Failed to remove old backup {}", path.display()))?;
+        crate::log_debug!("Removed old backup {}", path.display());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::types::{IdStrategy, Item};
+
+    #[test]
+    fn backups_are_written_and_pruned() {
+        let directory = std::env::temp_dir().join(format!("axum-example-backups-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let prefix = snapshot_file_prefix();
+        for timestamp in ["20200101T000000Z", "20200102T000000Z", "20200103T000000Z"] {
+            std::fs::write(
+                directory.join(format!("{prefix}{timestamp}{SNAPSHOT_FILE_SUFFIX}")),
+                b"old",
+            )
+            .unwrap();
+        }
+        std::fs::write(directory.join("notes.txt"), b"keep").unwrap();
+
+        let state = AppState::new();
+        state
+            .items()
+            .insert_item(&Item::new_with_random_id("backed-up".to_string(), IdStrategy::Numeric))
+            .unwrap();
+        let path = write_backup(&state, &directory, 2).unwrap();
+
+        let snapshot = Snapshot::from_gzip(&std::fs::read(&path).unwrap()).unwrap();
+        assert_eq!(snapshot.items[0].name, "backed-up");
+        let mut names: Vec<String> = std::fs::read_dir(&directory)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        assert_eq!(
+            names,
+            [
+                format!("{prefix}20200103T000000Z{SNAPSHOT_FILE_SUFFIX}"),
+                path.file_name().unwrap().to_string_lossy().into_owned(),
+                "notes.txt".to_string(),
+            ]
+        );
+
+        std::fs::remove_dir_all(directory).ok();
+    }
+}
@@ -0,0 +1,377 @@
+//! Load generation.
+//!
+//! The `bench` subcommand fires a weighted mix of create, query and list requests,
+//! either at the router running in-process or at a running server with `--url`,
+//! and reports latency percentiles for each operation.
+//! In-process runs skip the network, so they isolate the cost of the handlers and the state layer.
+
+use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+use axum::Router;
+use axum::body::{Body, Bytes};
+use axum::http::header::CONTENT_TYPE;
+use axum::http::{Method, Request, StatusCode};
+use http_body_util::{BodyExt, Full};
+use hyper_util::client::legacy::Client;
+use hyper_util::client::legacy::connect::HttpConnector;
+use hyper_util::rt::TokioExecutor;
+use rand::RngExt;
+use strum::{Display, EnumIter, IntoEnumIterator};
+use tower::ServiceExt;
+
+use crate::router::{API_V1_PREFIX, build_router};
+use crate::settings::Settings;
+use crate::telemetry::Telemetry;
+use crate::types::AppState;
+
+/// Load generation options.
+#[derive(clap::Args, Debug, Clone)]
+pub struct BenchArgs {
+    /// Base URL of a running server, for example `http://127.0.0.1:3000`.
+    /// The router runs in-process when not given.
+    #[arg(long, value_name = "URL")]
+    pub url: Option<String>,
+
+    /// Total number of requests
+    #[arg(short = 'n', long, value_name = "N", default_value_t = 10_000)]
+    pub requests: usize,
+
+    /// Requests in flight at once
+    #[arg(long, value_name = "N", default_value_t = 32)]
+    pub concurrency: usize,
+
+    /// Relative share of `POST /items` requests
+    #[arg(long, value_name = "WEIGHT", default_value_t = 1)]
+    pub create_weight: u32,
+
+    /// Relative share of `GET /item` requests
+    #[arg(long, value_name = "WEIGHT", default_value_t = 4)]
+    pub query_weight: u32,
+
+    /// Relative share of `GET /items` requests
+    #[arg(long, value_name = "WEIGHT", default_value_t = 1)]
+    pub list_weight: u32,
+}
+
+/// Latencies and failures for one operation.
+#[derive(Debug, Clone, Default)]
+pub struct OperationStats {
+    latencies: Vec<Duration>,
+    errors: usize,
+}
+
+/// Result of a benchmark run.
+#[derive(Debug)]
+pub struct BenchReport {
+    target: String,
+    concurrency: usize,
+    elapsed: Duration,
+    operations: Vec<(Operation, OperationStats)>,
+}
+
+/// Request kind in the load mix.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Display, EnumIter)]
+#[strum(serialize_all = "lowercase")]
+pub enum Operation {
+    Create,
+    Query,
+    List,
+}
+
+/// Where requests are sent.
+enum Target {
+    InProcess(Router),
+    Remote {
+        client: Box<Client<HttpConnector, Full<Bytes>>>,
+        base_url: String,
+    },
+}
+
+/// Shared state for the request workers.
+struct Workload {
+    target: Target,
+    weights: [(Operation, u32); 3],
+    requests: usize,
+    next_request: AtomicUsize,
+    /// Prefix that keeps item names unique across runs against the same server.
+    run_prefix: String,
+    created: Mutex<Vec<String>>,
+}
+
+impl OperationStats {
+    /// Latency at the given percentile, zero without samples.
+    ///
+    /// Expects the latencies to be sorted.
+    fn percentile(&self, percentile: usize) -> Duration {
+        if self.latencies.is_empty() {
+            return Duration::ZERO;
+        }
+        let rank = (self.latencies.len() * percentile).div_ceil(100).max(1);
+        self.latencies[rank - 1]
+    }
+}
+
+impl BenchReport {
+    /// Total requests sent.
+    #[must_use]
+    pub fn num_requests(&self) -> usize {
+        self.operations.iter().map(|(_, stats)| stats.latencies.len()).sum()
+    }
+
+    /// Total requests that did not get a success status.
+    #[must_use]
+    pub fn num_errors(&self) -> usize {
+        self.operations.iter().map(|(_, stats)| stats.errors).sum()
+    }
+}
+
+impl fmt::Display for BenchReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let seconds = self.elapsed.as_secs_f64();
+        writeln!(f, "Target: {}", self.target)?;
+        #[allow(clippy::cast_precision_loss)]
+        let throughput = self.num_requests() as f64 / seconds.max(f64::EPSILON);
+        writeln!(
+            f,
+            "Requests: {} in {seconds:.2} s ({throughput:.0} req/s), concurrency {}, errors {}",
+            self.num_requests(),
+            self.concurrency,
+            self.num_errors()
+        )?;
+        writeln!(f)?;
+        writeln!(
+            f,
+            "{:<10} {:>8} {:>8} {:>10} {:>10} {:>10} {:>10}",
+            "operation", "count", "errors", "p50 ms", "p90 ms", "p99 ms", "max ms"
+        )?;
+        let milliseconds = |duration: Duration| duration.as_secs_f64() * 1000.0;
+        for (operation, stats) in &self.operations {
+            writeln!(
+                f,
+                "{:<10} {:>8} {:>8} {:>10.3} {:>10.3} {:>10.3} {:>10.3}",
+                operation.to_string(),
+                stats.latencies.len(),
+                stats.errors,
+                milliseconds(stats.percentile(50)),
+                milliseconds(stats.percentile(90)),
+                milliseconds(stats.percentile(99)),
+                milliseconds(stats.latencies.last().copied().unwrap_or_default()),
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl Target {
+    fn in_process(settings: Settings) -> Self {
+        let state = Arc::new(AppState::new_with_telemetry(Telemetry::noop(), &settings.storage));
+        Self::InProcess(build_router(&state, &settings.into_shared()))
+    }
+
+    fn remote(base_url: &str) -> Self {
+        Self::Remote {
+            client: Box::new(Client::builder(TokioExecutor::new()).build_http()),
+            base_url: base_url.trim_end_matches('/').to_string(),
+        }
+    }
+
+    fn describe(&self) -> String {
+        match self {
+            Self::InProcess(_) => "in-process router".to_string(),
+            Self::Remote { base_url, .. } => base_url.clone(),
+        }
+    }
+
+    /// Send a request, read the whole response body, and return the status.
+    async fn send(&self, method: Method, path: &str, body: Option<String>) -> Result<StatusCode> {
+        let body = body.unwrap_or_default();
+        match self {
+            Self::InProcess(router) => {
+                let request = Request::builder()
+                    .method(method)
+                    .uri(path)
+                    .header(CONTENT_TYPE, "application/json")
+                    .body(Body::from(body))?;
+                let response = router.clone().oneshot(request).await?;
+                let status = response.status();
+                response.into_body().collect().await?;
+                Ok(status)
+            }
+            Self::Remote { client, base_url } => {
+                let request = Request::builder()
+                    .method(method)
+                    .uri(format!("{base_url}{path}"))
+                    .header(CONTENT_TYPE, "application/json")
+                    .body(Full::new(Bytes::from(body)))?;
+                let response = client.request(request).await?;
+                let status = response.status();
+                response.into_body().collect().await?;
+                Ok(status)
+            }
+        }
+    }
+}
+
+impl Workload {
+    /// Pick the next operation by weight.
+    ///
+    /// Queries need an existing item, so they turn into creates until one exists.
+    fn pick_operation(&self) -> Operation {
+        let total: u32 = self.weights.iter().map(|(_, weight)| weight).sum();
+        let mut roll = rand::rng().random_range(0..total);
+        let mut operation = Operation::Create;
+        for (candidate, weight) in self.weights {
+            if roll < weight {
+                operation = candidate;
+                break;
+            }
+            roll -= weight;
+        }
+        if operation == Operation::Query && self.random_created_name().is_none() {
+            return Operation::Create;
+        }
+        operation
+    }
+
+    fn random_created_name(&self) -> Option<String> {
+        let created = self.created.lock().ok()?;
+        if created.is_empty() {
+            return None;
+        }
+        Some(created[rand::rng().random_range(0..created.len())].clone())
+    }
+
+    /// Send one request and return its latency and if it succeeded.
+    async fn run_operation(&self, operation: Operation, request_number: usize) -> (Duration, bool) {
+        let name = format!("{}-{request_number}", self.run_prefix);
+        let (method, path, body) = match operation {
+            Operation::Create => (
+                Method::POST,
+                format!("{API_V1_PREFIX}/items"),
+                Some(serde_json::json!({ "name": name }).to_string()),
+            ),
+            Operation::Query => {
+                let name = self.random_created_name().unwrap_or_default();
+                (Method::GET, format!("{API_V1_PREFIX}/item?name={name}"), None)
+            }
+            Operation::List => (Method::GET, format!("{API_V1_PREFIX}/items?limit=100"), None),
+        };
+        let start = Instant::now();
+        let result = self.target.send(method, &path, body).await;
+        let latency = start.elapsed();
+        let success = result.as_ref().is_ok_and(StatusCode::is_success);
+        if operation == Operation::Create
+            && success
+            && let Ok(mut created) = self.created.lock()
+        {
+            created.push(name);
+        }
+        (latency, success)
+    }
+
+    /// Take requests from the shared counter until all have been sent.
+    async fn worker(self: Arc<Self>) -> Vec<(Operation, Duration, bool)> {
+        let mut samples = Vec::new();
+        loop {
+            let request_number = self.next_request.fetch_add(1, Ordering::Relaxed);
+            if request_number >= self.requests {
+                return samples;
+            }
+            let operation = self.pick_operation();
+            let (latency, success) = self.run_operation(operation, request_number).await;
+            samples.push((operation, latency, success));
+        }
+    }
+}
+
+/// Run the load mix and collect latencies.
+///
+/// Settings are only used for the in-process router.
+pub async fn run(args: &BenchArgs, settings: Settings) -> Result<BenchReport> {
+    if args.create_weight + args.query_weight + args.list_weight == 0 {
+        anyhow::bail!("At least one operation weight must be greater than zero");
+    }
+    let target = args
+        .url
+        .as_deref()
+        .map_or_else(|| Target::in_process(settings), Target::remote);
+    let workload = Arc::new(Workload {
+        target,
+        weights: [
+            (Operation::Create, args.create_weight),
+            (Operation::Query, args.query_weight),
+            (Operation::List, args.list_weight),
+        ],
+        requests: args.requests,
+        next_request: AtomicUsize::new(0),
+        run_prefix: format!("bench-{:08x}", rand::rng().random::<u32>()),
+        created: Mutex::new(Vec::new()),
+    });
+
+    let start = Instant::now();
+    let workers: Vec<_> = (0..args.concurrency.max(1))
+        .map(|_| tokio::spawn(Arc::clone(&workload).worker()))
+        .collect();
+    let mut operations: Vec<(Operation, OperationStats)> = Operation::iter()
+        .map(|operation| (operation, OperationStats::default()))
+        .collect();
+    for worker in workers {
+        for (operation, latency, success) in worker.await.context("Benchmark worker failed")? {
+            if let Some((_, stats)) = operations.iter_mut().find(|(candidate, _)| *candidate == operation) {
+                stats.latencies.push(latency);
+                stats.errors += usize::from(!success);
+            }
+        }
+    }
+    let elapsed = start.elapsed();
+    for (_, stats) in &mut operations {
+        stats.latencies.sort_unstable();
+    }
+    Ok(BenchReport {
+        target: workload.target.describe(),
+        concurrency: args.concurrency.max(1),
+        elapsed,
+        operations,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn percentiles_use_nearest_rank() {
+        let stats = OperationStats {
+            latencies: (1..=100).map(Duration::from_millis).collect(),
+            errors: 0,
+        };
+        assert_eq!(stats.percentile(50), Duration::from_millis(50));
+        assert_eq!(stats.percentile(99), Duration::from_millis(99));
+        assert_eq!(stats.percentile(100), Duration::from_millis(100));
+        assert_eq!(OperationStats::default().percentile(50), Duration::ZERO);
+    }
+
+    #[tokio::test]
+    async fn in_process_run_sends_every_request() {
+        let args = BenchArgs {
+            url: None,
+            requests: 200,
+            concurrency: 4,
+            create_weight: 1,
+            query_weight: 2,
+            list_weight: 1,
+        };
+
+        let report = run(&args, Settings::default()).await.expect("benchmark should run");
+
+        assert_eq!(report.num_requests(), 200);
+        assert_eq!(report.num_errors(), 0);
+        let text = report.to_string();
+        assert!(text.contains("in-process router"));
+        assert!(text.contains("query"));
+    }
+}
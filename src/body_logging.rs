@@ -0,0 +1,190 @@
+//! Request and response body logging.
+//!
+//! With `body_logging.enabled` set and the log level at DEBUG,
+//! the body of every request and its response is logged with the request id,
+//! for debugging client integrations.
+//! Values of JSON object fields named in `body_logging.redact_fields` are replaced at any depth,
+//! and bodies are cut to `body_logging.max_bytes`.
+//! Streamed responses are left as they are, since buffering them would defeat the streaming.
+//! The settings are read on every request, so logging can be turned on without a restart.
+
+use axum::body::{Body, Bytes, HttpBody, to_bytes};
+use axum::extract::{Request, State};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use serde_json::Value;
+use tracing::Level;
+
+use crate::i18n;
+use crate::schemas::{ApiError, ErrorCode};
+use crate::settings::SharedSettings;
+use crate::types::BodyLoggingConfig;
+
+/// Largest request body that is read for logging, the same as the axum default body limit.
+const MAX_REQUEST_BODY_BYTES: usize = 2 * 1024 * 1024;
+
+/// Placeholder that replaces redacted field values.
+const REDACTED: &str = "<redacted>";
+
+/// Log request and response bodies at DEBUG when enabled in the settings.
+pub async fn body_logging_middleware(State(settings): State<SharedSettings>, request: Request, next: Next) -> Response {
+    let config = settings.load().body_logging.clone();
+    if !config.enabled || !tracing::enabled!(Level::DEBUG) {
+        return next.run(request).await;
+    }
+
+    let (parts, body) = request.into_parts();
+    let Ok(body) = to_bytes(body, MAX_REQUEST_BODY_BYTES).await else {
+        return ApiError::new(ErrorCode::PayloadTooLarge, i18n::message("request_body_too_large", &[])).into_response();
+    };
+    crate::log_debug!(
+        request_id = crate::request_id::current(),
+        "Request body of {} {}: {}",
+        parts.method,
+        parts.uri.path(),
+        loggable_body(&body, &config)
+    );
+    let response = next.run(Request::from_parts(parts, Body::from(body))).await;
+
+    if response.body().size_hint().exact().is_none() {
+        crate::log_debug!(
+            request_id = crate::request_id::current(),
+            "Response body with status {} is streamed and not logged",
+            response.status()
+        );
+        return response;
+    }
+    let (parts, body) = response.into_parts();
+    let body = match to_bytes(body, usize::MAX).await {
+        Ok(body) => body,
+        Err(error) => {
+            crate::log_error!("Failed to read response body for logging: {error}");
+            return ApiError::new(ErrorCode::Internal, i18n::message("response_body_unreadable", &[])).into_response();
+        }
+    };
+    crate::log_debug!(
+        request_id = crate::request_id::current(),
+        "Response body with status {}: {}",
+        parts.status,
+        loggable_body(&body, &config)
+    );
+    Response::from_parts(parts, Body::from(body))
+}
+
+/// Body as text for the logs, with the configured fields redacted and cut to the size limit.
+fn loggable_body(body: &Bytes, config: &BodyLoggingConfig) -> String {
+    if body.is_empty() {
+        return "<empty>".to_string();
+    }
+    let text = match serde_json::from_slice::<Value>(body) {
+        Ok(mut json) => {
+            redact(&mut json, &config.redact_fields);
+            json.to_string()
+        }
+        Err(_) => match std::str::from_utf8(body) {
+            Ok(text) => text.to_string(),
+            Err(_) => return format!("<{} bytes of binary data>", body.len()),
+        },
+    };
+    truncate(text, config.max_bytes)
+}
+
+/// Replace the values of object fields with the given names, compared case-insensitively.
+fn redact(value: &mut Value, fields: &[String]) {
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map.iter_mut() {
+                if fields.iter().any(|field| field.eq_ignore_ascii_case(key)) {
+                    *value = Value::String(REDACTED.to_string());
+                } else {
+                    redact(value, fields);
+                }
+            }
+        }
+        Value::Array(values) => {
+            for value in values {
+                redact(value, fields);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Cut the text to at most `max_bytes` on a character boundary and note how long it was.
+fn truncate(mut text: String, max_bytes: usize) -> String {
+    if text.len() <= max_bytes {
+        return text;
+    }
+    let length = text.len();
+    let end = (0..=max_bytes)
+        .rev()
+        .find(|&index| text.is_char_boundary(index))
+        .unwrap_or_default();
+    text.truncate(end);
+    format!("{text}... ({length} bytes)")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use axum::http::StatusCode;
+    use serde_json::json;
+
+    use crate::settings::Settings;
+    use crate::test_utils::TestApp;
+
+    fn config(max_bytes: usize) -> BodyLoggingConfig {
+        BodyLoggingConfig {
+            enabled: true,
+            max_bytes,
+            ..BodyLoggingConfig::default()
+        }
+    }
+
+    #[test]
+    fn bodies_are_redacted_and_truncated() {
+        let body = json!({
+            "name": "client",
+            "API_KEY": "secret-key",
+            "data": { "tokens": [{ "token": "abc" }], "password": 1 },
+        });
+        assert_eq!(
+            loggable_body(&Bytes::from(body.to_string()), &config(1024)),
+            r#"{"API_KEY":"<redacted>","data":{"password":"<redacted>","tokens":[{"token":"<redacted>"}]},"name":"client"}"#
+        );
+        assert_eq!(
+            loggable_body(&Bytes::from("plain text"), &config(5)),
+            "plain... (10 bytes)"
+        );
+        assert_eq!(loggable_body(&Bytes::from("ääkkönen"), &config(3)), "ä... (11 bytes)");
+        assert_eq!(loggable_body(&Bytes::new(), &config(5)), "<empty>");
+        assert_eq!(
+            loggable_body(&Bytes::from_static(&[0xff, 0xfe]), &config(5)),
+            "<2 bytes of binary data>"
+        );
+    }
+
+    #[tokio::test]
+    async fn logged_bodies_reach_the_handlers_and_clients() {
+        let settings = Settings {
+            body_logging: config(16),
+            ..Settings::default()
+        };
+        let app = TestApp::with_settings(settings);
+        let subscriber = tracing_subscriber::fmt()
+            .with_max_level(Level::DEBUG)
+            .with_test_writer()
+            .finish();
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        let item = app
+            .create_item(json!({ "name": "logged", "data": { "password": "hunter2" } }))
+            .await;
+        assert_eq!(item.data, Some(json!({ "password": "hunter2" })));
+
+        let response = app.get("/api/v1/items?stream=true").await;
+        assert_eq!(response.status, StatusCode::OK);
+        assert!(response.text().contains("hunter2"));
+    }
+}
@@ -0,0 +1,210 @@
+//! Response cache for read routes.
+//!
+//! Successful `GET /items` and `GET /item` responses are kept in memory for a short TTL,
+//! keyed by tenant, path, query, `Accept` header and response language,
+//! so clients polling the listing do not all iterate and lock the item map.
+//! Every item write bumps the state generation, which invalidates all cached responses at once.
+//! Items read by a cached response are recorded as accessed on every hit,
+//! so idle and LRU eviction do not remove items that are only served from the cache.
+//! The TTL is read on every request, so settings changes apply without a restart.
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use axum::body::{Body, Bytes, HttpBody};
+use axum::extract::{Request, State};
+use axum::http::header::ACCEPT;
+use axum::http::{HeaderMap, HeaderName, HeaderValue, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use dashmap::DashMap;
+
+use crate::i18n;
+use crate::settings::SharedSettings;
+use crate::tenant;
+use crate::types::SharedState;
+
+/// Header telling whether the response came from the cache.
+pub const CACHE_STATUS_HEADER: HeaderName = HeaderName::from_static("x-cache");
+
+/// Most distinct responses kept at once, so varied query strings can not grow the cache without limit.
+const MAX_CACHED_RESPONSES: usize = 1024;
+
+/// Largest response body that is cached.
+const MAX_CACHED_BODY_BYTES: usize = 1024 * 1024;
+
+/// Response extension naming the item that the handler read,
+/// so cache hits record the access like the handler does.
+#[derive(Debug, Clone)]
+pub struct AccessedItem(pub String);
+
+/// Cached responses for the read routes.
+#[derive(Debug)]
+pub struct ResponseCache {
+    settings: SharedSettings,
+    state: SharedState,
+    entries: DashMap<String, CachedResponse>,
+}
+
+#[derive(Debug, Clone)]
+struct CachedResponse {
+    /// State generation the response was built from.
+    generation: u64,
+    stored_at: Instant,
+    headers: HeaderMap,
+    body: Bytes,
+    /// Item that the response was read from.
+    accessed_item: Option<String>,
+}
+
+impl ResponseCache {
+    pub fn new(settings: SharedSettings, state: SharedState) -> Self {
+        Self {
+            settings,
+            state,
+            entries: DashMap::new(),
+        }
+    }
+
+    /// Cached response for the key if it is still fresh and no item has changed since.
+    fn get(&self, key: &str, ttl: Duration, now: Instant) -> Option<CachedResponse> {
+        let generation = self.state.tenant_items().generation();
+        self.entries
+            .get(key)
+            .map(|entry| entry.clone())
+            .filter(|entry| entry.generation == generation && now.duration_since(entry.stored_at) < ttl)
+    }
+
+    /// Store a response, making room by dropping stale entries when the cache is full.
+    fn insert(&self, key: String, response: CachedResponse, ttl: Duration) {
+        if self.entries.len() >= MAX_CACHED_RESPONSES {
+            let generation = self.state.tenant_items().generation();
+            let now = Instant::now();
+            self.entries
+                .retain(|_, entry| entry.generation == generation && now.duration_since(entry.stored_at) < ttl);
+            if self.entries.len() >= MAX_CACHED_RESPONSES {
+                return;
+            }
+        }
+        self.entries.insert(key, response);
+    }
+}
+
+impl CachedResponse {
+    fn into_response(self, status: &'static str) -> Response {
+        let mut response = Response::new(Body::from(self.body));
+        *response.headers_mut() = self.headers;
+        response
+            .headers_mut()
+            .insert(CACHE_STATUS_HEADER, HeaderValue::from_static(status));
+        response
+    }
+}
+
+/// Serve fresh cached responses, and cache successful responses from the handler.
+///
+/// A zero `response_cache_ttl_ms` setting disables the cache.
+pub async fn response_cache_middleware(
+    State(cache): State<Arc<ResponseCache>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let ttl = Duration::from_millis(cache.settings.load().response_cache_ttl_ms);
+    if ttl.is_zero() {
+        return next.run(request).await;
+    }
+    let key = cache_key(&request);
+    // Read the generation before running the handler,
+    // so a write during the request leaves the stored response already invalid.
+    let generation = cache.state.tenant_items().generation();
+    if let Some(cached) = cache.get(&key, ttl, Instant::now()) {
+        if let Some(name) = &cached.accessed_item {
+            cache.state.tenant_items().record_access(name, cache.state.now());
+        }
+        return cached.into_response("HIT");
+    }
+
+    let response = next.run(request).await;
+    // Only buffer complete bodies of known size, large or streamed responses pass through uncached.
+    let cacheable_size = response
+        .body()
+        .size_hint()
+        .exact()
+        .is_some_and(|size| size <= MAX_CACHED_BODY_BYTES as u64);
+    if response.status() != StatusCode::OK || !cacheable_size {
+        return response;
+    }
+    let (parts, body) = response.into_parts();
+    let body = match axum::body::to_bytes(body, MAX_CACHED_BODY_BYTES).await {
+        Ok(body) => body,
+        Err(error) => {
+            crate::log_error!("Failed to buffer response for caching: {error}");
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+    let cached = CachedResponse {
+        generation,
+        stored_at: Instant::now(),
+        headers: parts.headers,
+        body,
+        accessed_item: parts
+            .extensions
+            .get::<AccessedItem>()
+            .map(|AccessedItem(name)| name.clone()),
+    };
+    cache.insert(key, cached.clone(), ttl);
+    cached.into_response("MISS")
+}
+
+/// Responses differ by tenant, path, query, negotiated format and language.
+fn cache_key(request: &Request) -> String {
+    let accept = request
+        .headers()
+        .get(ACCEPT)
+        .and_then(|accept| accept.to_str().ok())
+        .unwrap_or_default();
+    let language: &'static str = i18n::current().into();
+    format!(
+        "{} {}?{} {accept} {language}",
+        tenant::current().unwrap_or_default(),
+        request.uri().path(),
+        request.uri().query().unwrap_or_default()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::settings::Settings;
+    use crate::types::{AppState, IdStrategy, Item};
+
+    fn cached(generation: u64) -> CachedResponse {
+        CachedResponse {
+            generation,
+            stored_at: Instant::now(),
+            headers: HeaderMap::new(),
+            body: Bytes::from_static(b"{}"),
+            accessed_item: None,
+        }
+    }
+
+    #[test]
+    fn entries_expire_and_are_invalidated_by_writes() {
+        let state = AppState::new_shared_state();
+        let cache = ResponseCache::new(Settings::default().into_shared(), Arc::clone(&state));
+        let ttl = Duration::from_secs(1);
+        cache.insert("/items".to_string(), cached(state.items().generation()), ttl);
+
+        let now = Instant::now();
+        assert!(cache.get("/items", ttl, now).is_some());
+        assert!(cache.get("/items", ttl, now + ttl).is_none());
+        assert!(cache.get("/item?name=other", ttl, now).is_none());
+
+        state
+            .items()
+            .insert_item(&Item::new_with_random_id("new".to_string(), IdStrategy::Numeric))
+            .unwrap();
+        assert!(cache.get("/items", ttl, now).is_none());
+    }
+}
@@ -0,0 +1,231 @@
+//! Client subcommand.
+//!
+//! `client` calls a running server through the typed `axum-example-client` crate
+//! and prints the response as pretty JSON,
+//! so it doubles as runnable documentation of the API and as a smoke test for deployments.
+//! The URL and api key default to the local server from the settings.
+
+use anyhow::{Context, Result};
+use axum_example_client::{Client, CreateItem, ItemId, ListItems};
+use serde::Serialize;
+
+use crate::settings::Settings;
+
+/// Client options.
+#[derive(clap::Args, Debug, Clone)]
+pub struct ClientArgs {
+    /// Base URL of the server [default: localhost with the port from the settings]
+    #[arg(long, value_name = "URL")]
+    pub url: Option<String>,
+
+    /// Api key for admin commands [default: api key from the settings]
+    #[arg(long, value_name = "KEY")]
+    pub api_key: Option<String>,
+
+    #[command(subcommand)]
+    pub command: ClientCommand,
+}
+
+/// Request to send.
+#[derive(clap::Subcommand, Debug, Clone, PartialEq, Eq)]
+pub enum ClientCommand {
+    /// Print the service health
+    Health,
+
+    /// Print the server version and build information
+    Version,
+
+    /// Create an item
+    Create {
+        /// Item name
+        #[arg(long)]
+        name: String,
+
+        /// Id to use instead of a generated one
+        #[arg(long)]
+        id: Option<String>,
+
+        /// Tag for grouping items, can be given multiple times
+        #[arg(long = "tag", value_name = "TAG")]
+        tags: Vec<String>,
+
+        /// Remove the item this many seconds after creation
+        #[arg(long, value_name = "SECONDS")]
+        ttl_seconds: Option<u64>,
+    },
+
+    /// Print an item
+    Get {
+        /// Item name
+        name: String,
+    },
+
+    /// List item names
+    List {
+        /// Number of items to skip
+        #[arg(long)]
+        skip: Option<usize>,
+
+        /// Maximum number of items to list
+        #[arg(long)]
+        limit: Option<usize>,
+
+        /// Only list items with this tag
+        #[arg(long)]
+        tag: Option<String>,
+    },
+
+    /// Remove an item
+    Remove {
+        /// Item name
+        name: String,
+
+        /// Only remove the item if it is still at this revision
+        #[arg(long)]
+        revision: Option<u64>,
+    },
+
+    /// Remove every item
+    Clear,
+}
+
+/// Send the request and return the response as pretty JSON.
+///
+/// Error responses are returned as errors with the status, message and error code.
+pub async fn run(args: ClientArgs, settings: &Settings) -> Result<String> {
+    let url = args
+        .url
+        .unwrap_or_else(|| format!("http://127.0.0.1:{}", settings.port));
+    let client = Client::new(&url).with_api_key(args.api_key.as_deref().unwrap_or(&settings.api_key));
+    let context = || format!("Request to {url} failed");
+    match args.command {
+        ClientCommand::Health => to_json(&client.health().await.with_context(context)?),
+        ClientCommand::Version => to_json(&client.version().await.with_context(context)?),
+        ClientCommand::Create {
+            name,
+            id,
+            tags,
+            ttl_seconds,
+        } => {
+            let item = CreateItem {
+                name,
+                id: id.map(parse_id),
+                tags,
+                ttl_seconds,
+                ..CreateItem::default()
+            };
+            to_json(&client.create_item(&item).await.with_context(context)?)
+        }
+        ClientCommand::Get { name } => to_json(&client.query_item(&name).await.with_context(context)?),
+        ClientCommand::List { skip, limit, tag } => {
+            let query = ListItems {
+                skip,
+                limit,
+                tag,
+                ..ListItems::default()
+            };
+            to_json(&client.list_items(&query).await.with_context(context)?)
+        }
+        ClientCommand::Remove { name, revision } => {
+            to_json(&client.remove_item(&name, revision).await.with_context(context)?)
+        }
+        ClientCommand::Clear => to_json(&client.clear_items().await.with_context(context)?),
+    }
+}
+
+/// Numeric ids are sent as numbers, UUID and ULID ids as strings.
+fn parse_id(id: String) -> ItemId {
+    id.parse().map_or(ItemId::Text(id), ItemId::Numeric)
+}
+
+fn to_json(value: &impl Serialize) -> Result<String> {
+    serde_json::to_string_pretty(value).context("Failed to serialize response")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::Arc;
+
+    use crate::router::build_router;
+    use crate::server::serve;
+    use crate::types::{AppState, HttpProtocol, ServerConfig};
+
+    #[test]
+    fn numeric_ids_are_sent_as_numbers() {
+        assert_eq!(parse_id("1234".to_string()), ItemId::Numeric(1234));
+        assert_eq!(
+            parse_id("01ARZ3NDEKTSV4RRFFQ69G5FAV".to_string()),
+            ItemId::Text("01ARZ3NDEKTSV4RRFFQ69G5FAV".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn commands_print_server_responses() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let settings = Settings::default();
+        let (shutdown, shutdown_signal) = tokio::sync::oneshot::channel::<()>();
+        let server = tokio::spawn(serve(
+            listener,
+            build_router(&Arc::new(AppState::new()), &settings.clone().into_shared()),
+            HttpProtocol::default(),
+            ServerConfig::default(),
+            None,
+            async {
+                shutdown_signal.await.ok();
+            },
+        ));
+        let client = |command| ClientArgs {
+            url: Some(format!("http://{address}")),
+            api_key: None,
+            command,
+        };
+
+        let output = run(
+            client(ClientCommand::Create {
+                name: "cli".to_string(),
+                id: Some("1234".to_string()),
+                tags: vec!["smoke".to_string()],
+                ttl_seconds: None,
+            }),
+            &settings,
+        )
+        .await
+        .expect("create should succeed");
+        let item: serde_json::Value = serde_json::from_str(&output).expect("output should be JSON");
+        assert_eq!(item["id"], 1234);
+        assert_eq!(item["tags"], serde_json::json!(["smoke"]));
+
+        let output = run(
+            client(ClientCommand::List {
+                skip: None,
+                limit: None,
+                tag: Some("smoke".to_string()),
+            }),
+            &settings,
+        )
+        .await
+        .expect("list should succeed");
+        assert!(output.contains("\"cli\""));
+
+        let error = run(
+            client(ClientCommand::Get {
+                name: "missing".to_string(),
+            }),
+            &settings,
+        )
+        .await
+        .expect_err("missing item should fail");
+        assert!(format!("{error:#}").contains("404"));
+
+        let output = run(client(ClientCommand::Clear), &settings)
+            .await
+            .expect("clear should succeed");
+        assert!(output.contains("message"));
+
+        shutdown.send(()).ok();
+        server.await.unwrap().unwrap();
+    }
+}
@@ -0,0 +1,44 @@
+//! Time source for handlers and item timestamps.
+//!
+//! The system clock is used normally.
+//! Deterministic mode fixes the clock to a configured time,
+//! so integration tests and recorded fixtures get the same timestamps on every run.
+
+use chrono::{DateTime, Utc};
+
+/// Where the current time comes from.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Clock {
+    #[default]
+    System,
+    /// Always returns the same time.
+    Fixed(DateTime<Utc>),
+}
+
+impl Clock {
+    /// Current time from this clock.
+    #[must_use]
+    pub fn now(self) -> DateTime<Utc> {
+        match self {
+            Self::System => Utc::now(),
+            Self::Fixed(time) => time,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use chrono::TimeZone;
+
+    #[test]
+    fn fixed_clock_does_not_advance() {
+        let time = Utc.with_ymd_and_hms(2026, 1, 2, 3, 4, 5).unwrap();
+        let clock = Clock::Fixed(time);
+
+        assert_eq!(clock.now(), time);
+        assert_eq!(clock.now(), time);
+        assert!(Clock::System.now() > time);
+    }
+}
@@ -0,0 +1,511 @@
+//! Commented config file template.
+//!
+//! `generate-config` writes an `axum-example.toml` that lists every setting with an explanation,
+//! its environment variable and its default for the runtime environment,
+//! so the file format does not have to be read from the `Settings` source.
+//! Every value is commented out, so the generated file changes nothing until a line is uncommented.
+//! Settings that are unset by default show an example value instead.
+//! The values come from the serialized default settings,
+//! and a test checks that every setting has an entry here.
+
+use std::fmt::Write;
+
+use anyhow::{Context, Result};
+
+use crate::settings::Settings;
+use crate::types::Environment;
+
+/// One setting in the template.
+struct Entry {
+    /// Dotted settings key, where everything before the last dot is the table.
+    key: &'static str,
+    /// Explanation written as comment lines above the value.
+    doc: &'static str,
+    /// Value shown for settings that are unset by default.
+    example: Option<&'static str>,
+}
+
+const fn entry(key: &'static str, doc: &'static str) -> Entry {
+    Entry {
+        key,
+        doc,
+        example: None,
+    }
+}
+
+const fn example(key: &'static str, doc: &'static str, example: &'static str) -> Entry {
+    Entry {
+        key,
+        doc,
+        example: Some(example),
+    }
+}
+
+/// Every setting in the order it is written, top-level keys before the tables.
+const ENTRIES: &[Entry] = &[
+    entry("api_key", "Api key for admin routes, API_KEY."),
+    entry(
+        "host",
+        "Host IPs to listen to, HOST or --host. Empty listens on localhost.\nUse \"IP:PORT\" to listen on a different port for that address.",
+    ),
+    entry("port", "Port to listen to, PORT or --port."),
+    entry(
+        "http",
+        "HTTP protocol versions to accept, HTTP_PROTOCOL or --http: \"auto\", \"http1\" or \"http2\".",
+    ),
+    entry(
+        "cors_origins",
+        "Origins allowed to make cross-origin requests, CORS_ORIGINS. \"*\" allows any origin.",
+    ),
+    entry(
+        "id_strategy",
+        "How new item ids are generated, ITEM_ID_STRATEGY: \"numeric\", \"uuid\" or \"ulid\".",
+    ),
+    entry(
+        "max_item_data_bytes",
+        "Largest accepted item data document as serialized JSON, ITEM_DATA_MAX_BYTES.",
+    ),
+    example(
+        "item_name_pattern",
+        "Regular expression that new item names must fully match on top of the built-in rules, ITEM_NAME_PATTERN.",
+        "\"^[a-z0-9-]+$\"",
+    ),
+    entry(
+        "drain_delay_seconds",
+        "Seconds between POST /admin/drain or a termination signal and closing the listeners, DRAIN_DELAY_SECONDS.",
+    ),
+    example(
+        "seed_file",
+        "JSON or TOML file with items to load at startup in local and development environments, SEED_FILE.",
+        "\"seed.toml\"",
+    ),
+    example(
+        "record_file",
+        "File that every request is appended to for the replay subcommand, RECORD_FILE.",
+        "\"requests.jsonl\"",
+    ),
+    example(
+        "state_file",
+        "Snapshot file that the items are saved to on shutdown and loaded from at startup, STATE_FILE.",
+        "\"state.json.gz\"",
+    ),
+    entry(
+        "response_cache_ttl_ms",
+        "Milliseconds that GET /items and GET /item responses are cached, RESPONSE_CACHE_TTL_MS. Zero disables the cache.",
+    ),
+    entry(
+        "slow_request_ms",
+        "Milliseconds after which a finished request is logged as slow, SLOW_REQUEST_MS. Zero disables the warnings.",
+    ),
+    entry(
+        "docs_require_auth",
+        "Require the api key for the documentation UIs and spec outside the local environment, DOCS_REQUIRE_AUTH.",
+    ),
+    entry(
+        "docs_uis",
+        "Documentation UIs to serve outside production, DOCS_UIS.\nThe raw spec is served even when the list is empty.",
+    ),
+    entry("log_routes", "Log the route table at startup, LOG_ROUTES."),
+    entry(
+        "log_format",
+        "Format of the stdout logs, LOG_FORMAT or --log-format: \"pretty\", \"json\" or \"logfmt\".",
+    ),
+    entry(
+        "error_format",
+        "Body format of error responses, ERROR_FORMAT: \"api_error\" or \"problem\".",
+    ),
+    entry(
+        "response_envelope",
+        "Wrap JSON response bodies in {\"data\": ..., \"meta\": ...}, RESPONSE_ENVELOPE.",
+    ),
+    entry(
+        "tenants",
+        "Tenant ids accepted in the x-tenant-id header, TENANTS. Each tenant gets its own item store.",
+    ),
+    entry(
+        "server.http1_keep_alive",
+        "Keep HTTP/1.1 connections open for further requests, HTTP1_KEEP_ALIVE.",
+    ),
+    example(
+        "server.header_read_timeout_seconds",
+        "Seconds a client has to send the complete request headers, HEADER_READ_TIMEOUT_SECONDS.\nKeep it above the idle timeout of a load balancer in front of the server.",
+        "75",
+    ),
+    example(
+        "server.http2_keep_alive_interval_seconds",
+        "Seconds between HTTP/2 keep-alive pings, HTTP2_KEEP_ALIVE_INTERVAL_SECONDS. Unset disables the pings.",
+        "30",
+    ),
+    entry(
+        "server.http2_keep_alive_timeout_seconds",
+        "Seconds to wait for a ping acknowledgement before closing the HTTP/2 connection, HTTP2_KEEP_ALIVE_TIMEOUT_SECONDS.",
+    ),
+    entry(
+        "server.http2_max_concurrent_streams",
+        "Most concurrent requests on one HTTP/2 connection, HTTP2_MAX_CONCURRENT_STREAMS.",
+    ),
+    entry(
+        "server.shutdown_timeout_seconds",
+        "Seconds that open connections get to finish their requests at shutdown, SHUTDOWN_TIMEOUT_SECONDS.",
+    ),
+    example(
+        "server.hard_shutdown_timeout_seconds",
+        "Seconds after the first shutdown signal to exit even if the shutdown has not finished,\nHARD_SHUTDOWN_TIMEOUT_SECONDS. Unset waits for every shutdown phase.",
+        "60",
+    ),
+    entry(
+        "server.reuse_port",
+        "Bind with SO_REUSEPORT so a new process can listen on the same port before this one stops, REUSE_PORT.",
+    ),
+    example(
+        "tls.cert",
+        "PEM certificate chain to serve HTTPS with, TLS_CERT or --tls-cert. Needs tls.key as well.",
+        "\"certs/server.pem\"",
+    ),
+    example(
+        "tls.key",
+        "PEM private key of the TLS certificate, TLS_KEY or --tls-key.",
+        "\"certs/server.key\"",
+    ),
+    example(
+        "tls.redirect_port",
+        "Port that redirects plain HTTP requests to HTTPS, TLS_REDIRECT_PORT. Unset serves no redirect.",
+        "8080",
+    ),
+    entry(
+        "rate_limit.requests_per_second",
+        "Sustained requests per second allowed for each client IP, RATE_LIMIT_PER_SECOND. Zero turns throttling off.",
+    ),
+    entry(
+        "rate_limit.burst",
+        "Requests a client can make at once before being throttled, RATE_LIMIT_BURST.",
+    ),
+    entry(
+        "admin_access.allowed",
+        "Networks allowed to reach admin routes, ADMIN_ALLOWED_NETWORKS. Empty allows every network.",
+    ),
+    entry(
+        "admin_access.denied",
+        "Networks always rejected from admin routes, even when also allowed, ADMIN_DENIED_NETWORKS.",
+    ),
+    entry(
+        "admin_access.trusted_proxies",
+        "Proxies whose X-Forwarded-For header is trusted, TRUSTED_PROXIES.",
+    ),
+    entry(
+        "secrets.provider",
+        "Where secrets are fetched from, SECRETS_PROVIDER: \"env\", \"secrets-manager\" or \"ssm\".",
+    ),
+    example(
+        "secrets.api_key_id",
+        "Secret id, parameter name or env variable holding the api key, API_KEY_SECRET_ID.\nUnset keeps the api_key setting.",
+        "\"axum-example/api-key\"",
+    ),
+    entry(
+        "secrets.refresh_seconds",
+        "Seconds between secret refreshes, SECRETS_REFRESH_SECONDS. Zero only fetches secrets at startup.",
+    ),
+    example(
+        "backup.directory",
+        "Directory that snapshots are written to, BACKUP_DIRECTORY. Unset disables scheduled backups.",
+        "\"backups\"",
+    ),
+    entry(
+        "backup.retention",
+        "Newest backups to keep in the directory, BACKUP_RETENTION. Zero keeps every backup.",
+    ),
+    entry(
+        "tasks.backup.enabled",
+        "Write a backup to backup.directory on the schedule.",
+    ),
+    entry(
+        "tasks.backup.schedule",
+        "Interval like \"30s\" or \"6h\", or a five-field cron expression in UTC.\nBACKUP_INTERVAL_SECONDS sets an interval in seconds.",
+    ),
+    entry(
+        "tasks.backup.jitter_seconds",
+        "Random delay of up to this many seconds added to every wait.",
+    ),
+    entry("tasks.expiry_sweep.enabled", "Remove expired items on the schedule."),
+    entry(
+        "tasks.expiry_sweep.schedule",
+        "Interval like \"30s\" or \"6h\", or a five-field cron expression in UTC.",
+    ),
+    entry(
+        "tasks.expiry_sweep.jitter_seconds",
+        "Random delay of up to this many seconds added to every wait.",
+    ),
+    entry(
+        "tasks.eviction_sweep.enabled",
+        "Evict items by the eviction policy on the schedule.",
+    ),
+    entry(
+        "tasks.eviction_sweep.schedule",
+        "Interval like \"30s\" or \"6h\", or a five-field cron expression in UTC.",
+    ),
+    entry(
+        "tasks.eviction_sweep.jitter_seconds",
+        "Random delay of up to this many seconds added to every wait.",
+    ),
+    entry(
+        "tasks.state_log.enabled",
+        "Log the item count and map capacity on the schedule.",
+    ),
+    entry(
+        "tasks.state_log.schedule",
+        "Interval like \"30s\" or \"6h\", or a five-field cron expression in UTC.",
+    ),
+    entry(
+        "tasks.state_log.jitter_seconds",
+        "Random delay of up to this many seconds added to every wait.",
+    ),
+    entry(
+        "storage.initial_capacity",
+        "Number of items the maps are pre-allocated for, STORAGE_INITIAL_CAPACITY.",
+    ),
+    example(
+        "storage.shard_amount",
+        "Number of independently locked map shards, a power of two, STORAGE_SHARD_AMOUNT.\nUnset uses four per CPU core.",
+        "64",
+    ),
+    example(
+        "quota.max_items",
+        "Most items in one item store, QUOTA_MAX_ITEMS. Unset is not enforced.",
+        "100000",
+    ),
+    example(
+        "quota.max_data_bytes",
+        "Largest total size of the item data documents in one store, QUOTA_MAX_DATA_BYTES. Unset is not enforced.",
+        "104857600",
+    ),
+    example(
+        "eviction.max_age_seconds",
+        "Seconds since an item was created after which it is evicted, EVICTION_MAX_AGE_SECONDS.",
+        "604800",
+    ),
+    example(
+        "eviction.max_idle_seconds",
+        "Seconds since an item was last read or changed after which it is evicted, EVICTION_MAX_IDLE_SECONDS.",
+        "86400",
+    ),
+    example(
+        "eviction.max_items",
+        "Most items in one store, the least recently accessed are evicted past it, EVICTION_MAX_ITEMS.",
+        "50000",
+    ),
+    example(
+        "backend.provider",
+        "External store behind the default collection, BACKEND_PROVIDER: \"directory\" or \"dynamodb\".\nUnset keeps items only in memory.",
+        "\"directory\"",
+    ),
+    example(
+        "backend.directory",
+        "Directory of the directory backend, BACKEND_DIRECTORY.",
+        "\"items\"",
+    ),
+    example(
+        "backend.table",
+        "Table of the dynamodb backend, BACKEND_TABLE.",
+        "\"axum-example-items\"",
+    ),
+    entry(
+        "backend.cache_ttl_seconds",
+        "Seconds before a cached item is read from the backend again, BACKEND_CACHE_TTL_SECONDS.\nZero keeps cached items until they change.",
+    ),
+    entry(
+        "replication.peers",
+        "Base URLs of the peers that item changes are sent to, REPLICATION_PEERS. Empty disables replication.",
+    ),
+    entry(
+        "kafka.brokers",
+        "Kafka bootstrap brokers like \"localhost:9092\" that item events are produced to, KAFKA_BROKERS.\nEmpty disables producing.",
+    ),
+    entry(
+        "kafka.topic",
+        "Topic that events are produced to, keyed by item name, KAFKA_TOPIC.",
+    ),
+    example(
+        "nats.url",
+        "NATS server that item events are published to, NATS_URL. Unset disables publishing.",
+        "\"nats://localhost:4222\"",
+    ),
+    entry(
+        "nats.subject_prefix",
+        "Subject prefix that the event type is appended to, NATS_SUBJECT_PREFIX.",
+    ),
+    example(
+        "mqtt.host",
+        "MQTT broker that item events are published to, MQTT_HOST. Unset disables publishing.",
+        "\"localhost\"",
+    ),
+    entry("mqtt.port", "MQTT broker port, MQTT_PORT."),
+    example(
+        "mqtt.client_id",
+        "Client id, which must be unique for each instance, MQTT_CLIENT_ID. Unset generates one at startup.",
+        "\"axum-example-1\"",
+    ),
+    entry(
+        "mqtt.topic",
+        "Topic that events are published to, or the parent of the item topics, MQTT_TOPIC.",
+    ),
+    entry(
+        "mqtt.topic_per_item",
+        "Publish events about an item to {topic}/{item name} instead of the topic, MQTT_TOPIC_PER_ITEM.",
+    ),
+    example(
+        "sqs.queue_url",
+        "SQS queue that item commands are consumed from with the sqs cargo feature, SQS_QUEUE_URL. Unset disables consuming.",
+        "\"https://sqs.eu-north-1.amazonaws.com/123456789012/items\"",
+    ),
+    example(
+        "log_file.directory",
+        "Directory that log files are written to next to the stdout logs, LOG_FILE_DIRECTORY. Unset only logs to stdout.",
+        "\"logs\"",
+    ),
+    entry(
+        "log_file.rotation",
+        "How often a new log file is started, LOG_FILE_ROTATION: \"hourly\", \"daily\" or \"never\".",
+    ),
+    entry(
+        "log_file.max_files",
+        "Newest log files to keep in the directory, LOG_FILE_MAX_FILES. Zero keeps every file.",
+    ),
+    entry(
+        "body_logging.enabled",
+        "Log request and response bodies at DEBUG, BODY_LOGGING_ENABLED.",
+    ),
+    entry(
+        "body_logging.max_bytes",
+        "Longest logged body in bytes, BODY_LOGGING_MAX_BYTES. Longer bodies are cut.",
+    ),
+    entry(
+        "body_logging.redact_fields",
+        "JSON fields whose values are left out of logged bodies, BODY_LOGGING_REDACT_FIELDS.",
+    ),
+    example(
+        "deterministic.seed",
+        "Seed for the random number generator of new item ids, DETERMINISTIC_SEED. Not allowed in production.",
+        "42",
+    ),
+    example(
+        "deterministic.time",
+        "Fixed time used as the current time, DETERMINISTIC_TIME. Not allowed in production.",
+        "\"2026-01-01T00:00:00Z\"",
+    ),
+];
+
+/// Commented config file with every setting and its default for the environment.
+pub fn render(env: Environment) -> Result<String> {
+    render_with(env, true)
+}
+
+/// Render the template, with the values commented out or not.
+fn render_with(env: Environment, commented: bool) -> Result<String> {
+    let defaults = toml::Table::try_from(Settings::for_environment(env)).context("Failed to serialize settings")?;
+    let prefix = if commented { "# " } else { "" };
+    let mut output = format!(
+        "# Settings for axum-example with the defaults of the {} environment.\n\
+         # Uncomment a line to change the setting, environment variables and CLI arguments override this file.\n\
+         # Tables named after an environment, like [production] or [production.rate_limit],\n\
+         # override the values here when API_ENV matches.\n",
+        env.to_string().to_lowercase()
+    );
+    let mut current_table = "";
+    for entry in ENTRIES {
+        let (table, name) = entry.key.rsplit_once('.').unwrap_or(("", entry.key));
+        if table != current_table {
+            let _ = writeln!(output, "\n[{table}]");
+            current_table = table;
+        }
+        let value = match lookup(&defaults, entry.key) {
+            Some(value) => value.to_string(),
+            None => entry
+                .example
+                .with_context(|| format!("No default or example for {}", entry.key))?
+                .to_string(),
+        };
+        output.push('\n');
+        for line in entry.doc.lines() {
+            let _ = writeln!(output, "# {line}");
+        }
+        let _ = writeln!(output, "{prefix}{name} = {value}");
+    }
+    Ok(output)
+}
+
+/// Value of a dotted key in the table.
+fn lookup<'a>(table: &'a toml::Table, key: &str) -> Option<&'a toml::Value> {
+    let (first, rest) = key.split_once('.').unwrap_or((key, ""));
+    let value = table.get(first)?;
+    if rest.is_empty() {
+        Some(value)
+    } else {
+        lookup(value.as_table()?, rest)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::settings::{CliSettings, SettingsLoader};
+
+    /// Dotted keys of every value in the table.
+    fn keys(table: &toml::Table, prefix: &str) -> Vec<String> {
+        table
+            .iter()
+            .flat_map(|(key, value)| {
+                let key = if prefix.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{prefix}.{key}")
+                };
+                match value {
+                    toml::Value::Table(table) => keys(table, &key),
+                    _ => vec![key],
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn every_setting_has_an_entry() {
+        let defaults = toml::Table::try_from(Settings::default()).unwrap();
+        for key in keys(&defaults, "") {
+            assert!(
+                key == "env" || ENTRIES.iter().any(|entry| entry.key == key),
+                "{key} is missing from the config template"
+            );
+        }
+        for entry in ENTRIES {
+            assert_eq!(
+                lookup(&defaults, entry.key).is_some(),
+                entry.example.is_none(),
+                "{} should have an example exactly when it is unset by default",
+                entry.key
+            );
+        }
+    }
+
+    #[test]
+    fn template_loads_as_settings() {
+        let env = Environment::from_env();
+        let directory = std::env::temp_dir().join(format!("axum-example-template-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let path = directory.join("axum-example.toml");
+        let loader = SettingsLoader::new(path.clone(), CliSettings::default());
+
+        std::fs::write(&path, render(env).unwrap()).unwrap();
+        let commented = loader.load().unwrap();
+
+        std::fs::write(&path, render_with(env, false).unwrap()).unwrap();
+        let uncommented = loader.load().unwrap();
+        std::fs::remove_dir_all(directory).unwrap();
+
+        assert_eq!(commented.port, uncommented.port);
+        assert_eq!(commented.rate_limit, uncommented.rate_limit);
+        assert_eq!(uncommented.server.hard_shutdown_timeout_seconds, Some(60));
+        assert_eq!(uncommented.backend.table.as_deref(), Some("axum-example-items"));
+        assert_eq!(uncommented.tasks.backup.schedule, commented.tasks.backup.schedule);
+    }
+}
@@ -0,0 +1,300 @@
+//! Deprecation registry and response headers.
+//!
+//! Routes and request fields can be marked deprecated with a sunset date.
+//! The middleware announces deprecated usage to clients with the
+//! `Deprecation` (RFC 9745), `Sunset` (RFC 8594) and `Link` response headers,
+//! and counts every use in metrics so we know when a sunset is safe.
+
+use std::collections::{HashMap, HashSet};
+
+use axum::body::{Body, to_bytes};
+use axum::extract::{MatchedPath, Query, Request, State};
+use axum::http::header::{CONTENT_TYPE, LINK};
+use axum::http::{HeaderMap, HeaderName, HeaderValue, Method};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use chrono::{DateTime, Utc};
+
+use crate::schemas::{ApiError, Deprecation, ErrorCode};
+use crate::types::SharedState;
+
+/// `Deprecation` response header from RFC 9745.
+pub const DEPRECATION_HEADER: HeaderName = HeaderName::from_static("deprecation");
+
+/// `Sunset` response header from RFC 8594.
+pub const SUNSET_HEADER: HeaderName = HeaderName::from_static("sunset");
+
+/// Largest request body inspected for deprecated JSON fields.
+const MAX_INSPECTED_BODY_BYTES: usize = 2 * 1024 * 1024;
+
+/// All deprecated routes and fields known to the service.
+#[derive(Debug, Clone, Default)]
+pub struct DeprecationRegistry {
+    entries: Vec<Deprecation>,
+}
+
+impl DeprecationRegistry {
+    /// Mark a whole route deprecated.
+    ///
+    /// `route` is the Axum route pattern, for example `/admin/remove/{name}`.
+    /// A `None` method applies the deprecation to every method of the route.
+    /// `deprecated_at` is when the deprecation was announced, which clients get in the `Deprecation` header,
+    /// so it should be a fixed date instead of the time the registry is built.
+    #[must_use]
+    pub fn with_route(
+        mut self,
+        method: Option<Method>,
+        route: &str,
+        deprecated_at: DateTime<Utc>,
+        sunset: DateTime<Utc>,
+        message: &str,
+        link: Option<&str>,
+    ) -> Self {
+        self.entries.push(Deprecation {
+            method: method.map(|method| method.to_string()),
+            route: route.to_string(),
+            field: None,
+            deprecated_at,
+            sunset,
+            message: message.to_string(),
+            link: link.map(ToString::to_string),
+        });
+        self
+    }
+
+    /// Mark a query parameter or top-level JSON body field of a route deprecated.
+    #[must_use]
+    #[allow(clippy::too_many_arguments)]
+    pub fn with_field(
+        mut self,
+        method: Option<Method>,
+        route: &str,
+        field: &str,
+        deprecated_at: DateTime<Utc>,
+        sunset: DateTime<Utc>,
+        message: &str,
+        link: Option<&str>,
+    ) -> Self {
+        self.entries.push(Deprecation {
+            method: method.map(|method| method.to_string()),
+            route: route.to_string(),
+            field: Some(field.to_string()),
+            deprecated_at,
+            sunset,
+            message: message.to_string(),
+            link: link.map(ToString::to_string),
+        });
+        self
+    }
+
+    /// Return all registered deprecations.
+    #[must_use]
+    pub fn entries(&self) -> &[Deprecation] {
+        &self.entries
+    }
+
+    /// Return deprecations registered for the given method and route.
+    pub fn matching<'a>(&'a self, method: &'a Method, route: &'a str) -> impl Iterator<Item = &'a Deprecation> {
+        self.entries.iter().filter(move |entry| {
+            entry.route == route && entry.method.as_deref().is_none_or(|value| value == method.as_str())
+        })
+    }
+
+    /// Check if the registry is empty.
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+impl Deprecation {
+    /// Value for the `Deprecation` header: an RFC 9651 date as Unix seconds.
+    #[must_use]
+    pub fn deprecation_header_value(&self) -> String {
+        format!("@{}", self.deprecated_at.timestamp())
+    }
+
+    /// Value for the `Sunset` header: an HTTP-date.
+    #[must_use]
+    pub fn sunset_header_value(&self) -> String {
+        self.sunset.format("%a, %d %b %Y %H:%M:%S GMT").to_string()
+    }
+}
+
+/// Add deprecation headers and usage metrics for deprecated routes and fields.
+///
+/// Request bodies are only buffered for routes that have deprecated fields registered.
+pub async fn deprecation_middleware(State(state): State<SharedState>, request: Request, next: Next) -> Response {
+    let registry = state.deprecations();
+    if registry.is_empty() {
+        return next.run(request).await;
+    }
+    let Some(route) = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_string())
+    else {
+        return next.run(request).await;
+    };
+    let method = request.method().clone();
+    let matching: Vec<&Deprecation> = registry.matching(&method, &route).collect();
+    if matching.is_empty() {
+        return next.run(request).await;
+    }
+
+    let (request, used_fields) = match used_fields(request, &matching).await {
+        Ok(result) => result,
+        Err(response) => return response,
+    };
+
+    let metrics = state.telemetry().metrics();
+    let used: Vec<&Deprecation> = matching
+        .into_iter()
+        .filter(|entry| entry.field.as_ref().is_none_or(|field| used_fields.contains(field)))
+        .collect();
+    for entry in &used {
+        metrics.record_deprecated_usage(&route, method.as_str(), entry.field.as_deref());
+    }
+
+    let mut response = next.run(request).await;
+    insert_deprecation_headers(response.headers_mut(), &used);
+    response
+}
+
+/// Add `Deprecation`, `Sunset` and `Link` headers for the used deprecations.
+///
+/// With several matches, the earliest sunset wins so clients see the nearest deadline.
+fn insert_deprecation_headers(headers: &mut HeaderMap, used: &[&Deprecation]) {
+    let Some(first) = used.iter().min_by_key(|entry| entry.sunset) else {
+        return;
+    };
+    if let Ok(value) = HeaderValue::from_str(&first.deprecation_header_value()) {
+        headers.insert(DEPRECATION_HEADER, value);
+    }
+    if let Ok(value) = HeaderValue::from_str(&first.sunset_header_value()) {
+        headers.insert(SUNSET_HEADER, value);
+    }
+    for link in used.iter().filter_map(|entry| entry.link.as_deref()) {
+        if let Ok(value) = HeaderValue::from_str(&format!("<{link}>; rel=\"deprecation\"")) {
+            headers.append(LINK, value);
+        }
+    }
+}
+
+/// Collect the deprecated field names present in the query string or JSON body.
+///
+/// Returns the request with its body restored.
+async fn used_fields(request: Request, matching: &[&Deprecation]) -> Result<(Request, HashSet<String>), Response> {
+    let fields: HashSet<&str> = matching.iter().filter_map(|entry| entry.field.as_deref()).collect();
+    let mut used = HashSet::new();
+    if fields.is_empty() {
+        return Ok((request, used));
+    }
+
+    if let Ok(Query(query)) = Query::<HashMap<String, String>>::try_from_uri(request.uri()) {
+        used.extend(query.into_keys().filter(|key| fields.contains(key.as_str())));
+    }
+
+    let is_json = request
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("application/json"));
+    if !is_json {
+        return Ok((request, used));
+    }
+
+    let (parts, body) = request.into_parts();
+    let bytes = to_bytes(body, MAX_INSPECTED_BODY_BYTES).await.map_err(|error| {
+        ApiError::new(ErrorCode::BadRequest, format!("Failed to read request body: {error}")).into_response()
+    })?;
+    if let Ok(serde_json::Value::Object(object)) = serde_json::from_slice::<serde_json::Value>(&bytes) {
+        used.extend(object.keys().filter(|key| fields.contains(key.as_str())).cloned());
+    }
+    Ok((Request::from_parts(parts, Body::from(bytes)), used))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use chrono::TimeZone;
+
+    fn deprecated_at() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2029, 6, 1, 0, 0, 0)
+            .single()
+            .expect("valid deprecation date")
+    }
+
+    fn sunset() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2030, 1, 31, 12, 0, 0)
+            .single()
+            .expect("valid sunset date")
+    }
+
+    #[test]
+    fn registry_matches_route_and_method() {
+        let registry = DeprecationRegistry::default()
+            .with_route(
+                Some(Method::GET),
+                "/item",
+                deprecated_at(),
+                sunset(),
+                "Use /items instead",
+                None,
+            )
+            .with_field(
+                None,
+                "/items",
+                "id",
+                deprecated_at(),
+                sunset(),
+                "Ids are server generated",
+                None,
+            );
+
+        assert_eq!(registry.matching(&Method::GET, "/item").count(), 1);
+        assert_eq!(registry.matching(&Method::POST, "/item").count(), 0);
+        assert_eq!(registry.matching(&Method::POST, "/items").count(), 1);
+        assert_eq!(registry.matching(&Method::GET, "/health").count(), 0);
+    }
+
+    #[test]
+    fn header_values_use_rfc_formats() {
+        let registry = DeprecationRegistry::default().with_route(None, "/item", deprecated_at(), sunset(), "old", None);
+        let entry = &registry.entries()[0];
+
+        assert_eq!(entry.sunset_header_value(), "Thu, 31 Jan 2030 12:00:00 GMT");
+        assert_eq!(entry.deprecation_header_value(), "@1874966400");
+    }
+
+    #[test]
+    fn headers_use_earliest_sunset_and_every_link() {
+        let later = sunset() + chrono::Duration::days(30);
+        let registry = DeprecationRegistry::default()
+            .with_route(
+                None,
+                "/item",
+                deprecated_at(),
+                later,
+                "later",
+                Some("https://example.com/later"),
+            )
+            .with_field(
+                None,
+                "/item",
+                "name",
+                deprecated_at(),
+                sunset(),
+                "sooner",
+                Some("https://example.com/sooner"),
+            );
+        let used: Vec<&Deprecation> = registry.entries().iter().collect();
+        let mut headers = HeaderMap::new();
+
+        insert_deprecation_headers(&mut headers, &used);
+
+        assert_eq!(headers[SUNSET_HEADER], "Thu, 31 Jan 2030 12:00:00 GMT");
+        assert_eq!(headers.get_all(LINK).iter().count(), 2);
+    }
+}
@@ -0,0 +1,104 @@
+//! Documentation access control.
+//!
+//! With `docs_require_auth` enabled, the documentation UIs and the raw `OpenAPI` spec
+//! require the api key outside the local environment.
+//! The key can be given in the `api-key` header like for admin routes,
+//! or as the password of HTTP Basic auth so a browser can open the UIs after a login prompt.
+
+use axum::extract::{Request, State};
+use axum::http::header::{AUTHORIZATION, WWW_AUTHENTICATE};
+use axum::http::{HeaderMap, HeaderValue};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+
+use crate::i18n;
+use crate::schemas::{ApiError, ErrorCode};
+use crate::settings::SharedSettings;
+use crate::types::Environment;
+
+/// Basic auth challenge that makes browsers show a login prompt.
+const BASIC_CHALLENGE: &str = "Basic realm=\"API documentation\", charset=\"UTF-8\"";
+
+/// Reject documentation requests without the api key when docs auth is enabled.
+///
+/// Settings are read on every request, so toggling the setting applies without a restart.
+pub async fn docs_auth_middleware(State(settings): State<SharedSettings>, request: Request, next: Next) -> Response {
+    let settings = settings.load();
+    if !settings.docs_require_auth
+        || settings.env == Environment::Local
+        || has_api_key(request.headers(), &settings.api_key)
+    {
+        return next.run(request).await;
+    }
+
+    crate::log_warn!("Unauthorized documentation request: {}", request.uri().path());
+    (
+        [(WWW_AUTHENTICATE, HeaderValue::from_static(BASIC_CHALLENGE))],
+        ApiError::new(ErrorCode::Unauthorized, i18n::message("docs_require_api_key", &[])),
+    )
+        .into_response()
+}
+
+/// Check the `api-key` header, and the password of Basic auth with any user name.
+fn has_api_key(headers: &HeaderMap, api_key: &str) -> bool {
+    if headers
+        .get("api-key")
+        .is_some_and(|value| value.as_bytes() == api_key.as_bytes())
+    {
+        return true;
+    }
+    headers
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(basic_password)
+        .is_some_and(|password| password == api_key)
+}
+
+/// Password from a `Basic` authorization header value.
+fn basic_password(value: &str) -> Option<String> {
+    let (scheme, credentials) = value.trim().split_once(' ')?;
+    if !scheme.eq_ignore_ascii_case("basic") {
+        return None;
+    }
+    let decoded = STANDARD.decode(credentials.trim()).ok()?;
+    let decoded = String::from_utf8(decoded).ok()?;
+    decoded.split_once(':').map(|(_, password)| password.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(name: &'static str, value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(name, HeaderValue::from_str(value).unwrap());
+        headers
+    }
+
+    #[test]
+    fn basic_password_ignores_user_name() {
+        let encoded = STANDARD.encode("anyone:secret:with:colons");
+        assert_eq!(
+            basic_password(&format!("Basic {encoded}")).as_deref(),
+            Some("secret:with:colons")
+        );
+        assert_eq!(
+            basic_password(&format!("basic {encoded}")).as_deref(),
+            Some("secret:with:colons")
+        );
+        assert_eq!(basic_password(&format!("Bearer {encoded}")), None);
+        assert_eq!(basic_password("Basic not-base64!"), None);
+        assert_eq!(basic_password(&format!("Basic {}", STANDARD.encode("no-colon"))), None);
+    }
+
+    #[test]
+    fn api_key_is_accepted_from_header_or_basic_auth() {
+        assert!(has_api_key(&headers("api-key", "key"), "key"));
+        assert!(!has_api_key(&headers("api-key", "wrong"), "key"));
+        let basic = format!("Basic {}", STANDARD.encode("user:key"));
+        assert!(has_api_key(&headers("authorization", &basic), "key"));
+        assert!(!has_api_key(&HeaderMap::new(), "key"));
+    }
+}
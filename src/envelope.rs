@@ -0,0 +1,153 @@
+//! Response envelope.
+//!
+//! With `response_envelope` enabled, every JSON response body is wrapped as
+//! `{"data": ..., "meta": {"request_id": ..., "duration_ms": ...}}`
+//! for clients that require the same top-level shape from every route.
+//! The wrapping happens in a layer around the whole router,
+//! so handlers, the response cache and the error middleware keep producing the plain bodies.
+//! Empty bodies, problem details and other media types are not wrapped.
+//! The setting is read on every request, so it can be toggled without a restart.
+
+use std::time::Instant;
+
+use axum::Json;
+use axum::body::to_bytes;
+use axum::extract::{Request, State};
+use axum::http::StatusCode;
+use axum::http::header::{CONTENT_LENGTH, CONTENT_TYPE};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use utoipa::ToSchema;
+
+use crate::request_id::REQUEST_ID_HEADER;
+use crate::settings::SharedSettings;
+
+/// JSON response wrapped with request metadata.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ResponseEnvelope {
+    /// The unwrapped response body.
+    pub data: Value,
+    pub meta: ResponseMeta,
+}
+
+/// Metadata of the request that produced an enveloped response.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ResponseMeta {
+    #[schema(example = "5b3c6f0e-8d5a-4f6e-9a51-0c1d2e3f4a5b")]
+    pub request_id: Option<String>,
+    /// Time spent handling the request in milliseconds.
+    #[schema(example = 3)]
+    pub duration_ms: u64,
+}
+
+/// Wrap JSON response bodies in a [`ResponseEnvelope`] when enabled in the settings.
+pub async fn envelope_middleware(State(settings): State<SharedSettings>, request: Request, next: Next) -> Response {
+    if !settings.load().response_envelope {
+        return next.run(request).await;
+    }
+    let started = Instant::now();
+    let response = next.run(request).await;
+    let is_json = response
+        .headers()
+        .get(CONTENT_TYPE)
+        .is_some_and(|value| value.as_bytes().starts_with(b"application/json"));
+    if !is_json {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let bytes = match to_bytes(body, usize::MAX).await {
+        Ok(bytes) => bytes,
+        Err(error) => {
+            crate::log_error!("Failed to read response body for the envelope: {error}");
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+    // `HEAD` responses have the JSON content type without a body.
+    let Ok(data) = serde_json::from_slice::<Value>(&bytes) else {
+        return Response::from_parts(parts, bytes.into());
+    };
+    let envelope = ResponseEnvelope {
+        data,
+        meta: ResponseMeta {
+            request_id: parts
+                .headers
+                .get(REQUEST_ID_HEADER)
+                .and_then(|value| value.to_str().ok())
+                .map(ToString::to_string),
+            duration_ms: u64::try_from(started.elapsed().as_millis()).unwrap_or(u64::MAX),
+        },
+    };
+    let (json_parts, json_body) = Json(envelope).into_response().into_parts();
+    parts.headers.remove(CONTENT_LENGTH);
+    parts.headers.extend(json_parts.headers);
+    Response::from_parts(parts, json_body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use axum::body::Body;
+    use axum::http::Method;
+    use serde_json::json;
+
+    use crate::problem::PROBLEM_JSON_CONTENT_TYPE;
+    use crate::settings::Settings;
+    use crate::test_utils::TestApp;
+
+    fn enveloped_app() -> TestApp {
+        TestApp::with_settings(Settings {
+            response_envelope: true,
+            ..Settings::default()
+        })
+    }
+
+    #[tokio::test]
+    async fn json_responses_are_wrapped() {
+        let app = enveloped_app();
+        let response = app.post_json("/items", &json!({ "name": "wrapped" })).await;
+        assert_eq!(response.status, StatusCode::CREATED);
+        let envelope: ResponseEnvelope = response.json();
+        assert_eq!(envelope.data["name"], "wrapped");
+        assert_eq!(
+            envelope.meta.request_id.as_deref(),
+            response.headers["x-request-id"].to_str().ok()
+        );
+
+        let response = app.get("/item?name=missing").await;
+        assert_eq!(response.status, StatusCode::NOT_FOUND);
+        let envelope: ResponseEnvelope = response.json();
+        assert_eq!(envelope.data["code"], "not_found");
+    }
+
+    #[tokio::test]
+    async fn other_responses_are_not_wrapped() {
+        let app = enveloped_app();
+        let response = app.get("/metrics").await;
+        assert_eq!(response.status, StatusCode::OK);
+        assert!(!response.text().starts_with("{\"data\""));
+
+        let head = Request::builder()
+            .method(Method::HEAD)
+            .uri("/health")
+            .body(Body::empty())
+            .unwrap();
+        let response = app.send(head).await;
+        assert_eq!(response.status, StatusCode::OK);
+        assert!(response.body.is_empty());
+
+        let problem = Request::builder()
+            .uri("/missing")
+            .header("accept", PROBLEM_JSON_CONTENT_TYPE)
+            .body(Body::empty())
+            .unwrap();
+        let response = app.send(problem).await;
+        assert_eq!(response.json::<Value>()["type"], "about:blank");
+
+        let response = TestApp::new().get("/health").await;
+        assert!(response.json::<Value>().get("data").is_none());
+    }
+}
@@ -0,0 +1,550 @@
+//! Item events.
+//!
+//! Item changes are published as typed events on a broadcast channel owned by the state,
+//! so consumers like metrics and webhooks subscribe to the channel
+//! instead of every handler calling each of them.
+//! Publishing never blocks the request:
+//! events go to a bounded buffer that drops the oldest event when full,
+//! and are flushed to the channel as one batch after [`EVENT_FLUSH_INTERVAL`],
+//! so a burst like a bulk import takes a single channel slot instead of one per item.
+//! A subscriber that falls more than the capacity behind skips the oldest events,
+//! which are logged and counted per consumer in `axum_example_item_events_skipped_total`.
+//! Skipped events are not resent, so a lagging consumer misses them for good:
+//! webhooks are not delivered for them and replication peers do not get those changes.
+//!
+//! Delivery is at most once: events only live in memory, so a crash loses the ones not yet consumed.
+//! There is no outbox written together with the backend,
+//! since webhook and replication sends finish after the event has left the bus
+//! and would need to acknowledge each event before an outbox entry could be removed,
+//! the `directory` backend can not write an item and an outbox entry in one operation,
+//! and webhook registrations are kept in memory, so a restarted instance has nobody to deliver old events to.
+//! Consumers that must not miss changes should reconcile against `GET /items` now and then.
+
+use std::collections::VecDeque;
+use std::pin::pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use strum::{Display, IntoStaticStr};
+use tokio::sync::broadcast::error::{RecvError, TryRecvError};
+use tokio::sync::{Notify, broadcast};
+use tokio::task::JoinHandle;
+use utoipa::ToSchema;
+
+use crate::telemetry::TelemetryMetrics;
+use crate::types::{EvictionReason, Item, SharedState};
+
+/// Events waiting for the next flush, and batches kept for subscribers that have not received them yet.
+pub const EVENT_CHANNEL_CAPACITY: usize = 1024;
+
+/// How long published events are collected before they are flushed to the subscribers as one batch.
+pub const EVENT_FLUSH_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Item change, who made it and when it happened.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct ItemEvent {
+    pub occurred_at: DateTime<Utc>,
+    pub actor: Actor,
+    #[serde(flatten)]
+    pub change: ItemChange,
+}
+
+/// What changed, tagged with `type` in JSON.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ItemChange {
+    Created { item: Item },
+    Removed { item: Item },
+    Expired { item: Item },
+    Evicted { item: Item, reason: EvictionReason },
+    Cleared { count: usize },
+}
+
+/// Item event type, the `type` field of the event JSON.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, ToSchema, Display, IntoStaticStr)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum ItemEventType {
+    Created,
+    Removed,
+    Expired,
+    Evicted,
+    Cleared,
+}
+
+/// Who made an item change.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema, Display)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum Actor {
+    /// Request to a public route.
+    Client,
+    /// Request authenticated with the API key.
+    Admin,
+    /// The expiry sweep.
+    Expiry,
+    /// The eviction sweep.
+    Eviction,
+    /// Change received from a peer instance.
+    Replication,
+    /// Command received from the SQS queue.
+    Queue,
+}
+
+/// Broadcast channel for item events.
+#[derive(Debug)]
+pub struct EventBus {
+    shared: Arc<BusShared>,
+}
+
+/// Subscription to the item events published after it was created.
+#[derive(Debug)]
+pub struct EventReceiver {
+    shared: Arc<BusShared>,
+    receiver: broadcast::Receiver<Arc<EventBatch>>,
+    received: VecDeque<ItemEvent>,
+    /// Sequence number of the next event this subscriber expects.
+    next_sequence: u64,
+    /// Events missed since the last received event, reported before the next one.
+    skipped: u64,
+}
+
+#[derive(Debug)]
+struct BusShared {
+    sender: broadcast::Sender<Arc<EventBatch>>,
+    pending: Mutex<PendingEvents>,
+    capacity: usize,
+    /// Wakes subscribers waiting for events when new ones are pending.
+    published: Notify,
+    closed: AtomicBool,
+}
+
+/// Events published since the last flush, numbered consecutively from `first_sequence`.
+#[derive(Debug, Default)]
+struct PendingEvents {
+    first_sequence: u64,
+    events: VecDeque<ItemEvent>,
+}
+
+/// Consecutive events sent to the subscribers at once.
+#[derive(Debug)]
+struct EventBatch {
+    first_sequence: u64,
+    events: Vec<ItemEvent>,
+}
+
+impl ItemChange {
+    #[must_use]
+    pub const fn event_type(&self) -> ItemEventType {
+        match self {
+            Self::Created { .. } => ItemEventType::Created,
+            Self::Removed { .. } => ItemEventType::Removed,
+            Self::Expired { .. } => ItemEventType::Expired,
+            Self::Evicted { .. } => ItemEventType::Evicted,
+            Self::Cleared { .. } => ItemEventType::Cleared,
+        }
+    }
+}
+
+impl EventBus {
+    #[must_use]
+    pub fn new(capacity: usize) -> Self {
+        let (sender, _) = broadcast::channel(capacity);
+        Self {
+            shared: Arc::new(BusShared {
+                sender,
+                pending: Mutex::new(PendingEvents::default()),
+                capacity,
+                published: Notify::new(),
+                closed: AtomicBool::new(false),
+            }),
+        }
+    }
+
+    /// Queue the event for every current subscriber without waiting for them.
+    ///
+    /// Events published while nobody is subscribed are dropped,
+    /// and the oldest pending event is dropped when the buffer is full.
+    pub fn publish(&self, event: ItemEvent) {
+        {
+            let mut pending = self.shared.pending();
+            if self.shared.sender.receiver_count() == 0 {
+                return;
+            }
+            if pending.events.len() == self.shared.capacity {
+                pending.events.pop_front();
+                pending.first_sequence += 1;
+            }
+            pending.events.push_back(event);
+        }
+        self.shared.published.notify_waiters();
+    }
+
+    /// Receive every event published from now on.
+    #[must_use]
+    pub fn subscribe(&self) -> EventReceiver {
+        let pending = self.shared.pending();
+        EventReceiver {
+            shared: Arc::clone(&self.shared),
+            receiver: self.shared.sender.subscribe(),
+            received: VecDeque::new(),
+            next_sequence: pending.next_sequence(),
+            skipped: 0,
+        }
+    }
+
+    #[must_use]
+    pub fn num_subscribers(&self) -> usize {
+        self.shared.sender.receiver_count()
+    }
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        Self::new(EVENT_CHANNEL_CAPACITY)
+    }
+}
+
+impl Drop for EventBus {
+    fn drop(&mut self) {
+        self.shared.flush();
+        self.shared.closed.store(true, Ordering::Release);
+        self.shared.published.notify_waiters();
+    }
+}
+
+impl BusShared {
+    fn pending(&self) -> MutexGuard<'_, PendingEvents> {
+        self.pending.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Send the pending events to the subscribers as one batch.
+    ///
+    /// The lock is held while sending so concurrent flushes keep the batches in order.
+    #[allow(clippy::significant_drop_tightening)]
+    fn flush(&self) {
+        let mut pending = self.pending();
+        if pending.events.is_empty() {
+            return;
+        }
+        let batch = EventBatch {
+            first_sequence: pending.first_sequence,
+            events: pending.events.drain(..).collect(),
+        };
+        pending.first_sequence = batch.next_sequence();
+        self.sender.send(Arc::new(batch)).ok();
+    }
+
+    fn has_pending(&self) -> bool {
+        !self.pending().events.is_empty()
+    }
+}
+
+impl PendingEvents {
+    fn next_sequence(&self) -> u64 {
+        self.first_sequence + self.events.len() as u64
+    }
+}
+
+impl EventBatch {
+    const fn next_sequence(&self) -> u64 {
+        self.first_sequence + self.events.len() as u64
+    }
+}
+
+impl EventReceiver {
+    /// Wait for the next event.
+    ///
+    /// Returns [`RecvError::Lagged`] with the number of missed events before the event after them,
+    /// and [`RecvError::Closed`] once the bus is gone and every event has been received.
+    pub async fn recv(&mut self) -> Result<ItemEvent, RecvError> {
+        let shared = Arc::clone(&self.shared);
+        loop {
+            match self.try_take() {
+                Ok(event) => return Ok(event),
+                Err(TryRecvError::Lagged(skipped)) => return Err(RecvError::Lagged(skipped)),
+                Err(TryRecvError::Closed) => return Err(RecvError::Closed),
+                Err(TryRecvError::Empty) => {}
+            }
+            let mut published = pin!(shared.published.notified());
+            published.as_mut().enable();
+            if shared.closed.load(Ordering::Acquire) {
+                return Err(RecvError::Closed);
+            }
+            if shared.has_pending() {
+                // Let the rest of a burst arrive so it is sent as one batch.
+                tokio::time::sleep(EVENT_FLUSH_INTERVAL).await;
+                shared.flush();
+                continue;
+            }
+            tokio::select! {
+                result = self.receiver.recv() => match result {
+                    Ok(batch) => self.accept(&batch),
+                    Err(RecvError::Lagged(_)) => {}
+                    Err(RecvError::Closed) => return Err(RecvError::Closed),
+                },
+                () = published => {}
+            }
+        }
+    }
+
+    /// Receive an event that has already been published, flushing the pending events right away.
+    pub fn try_recv(&mut self) -> Result<ItemEvent, TryRecvError> {
+        match self.try_take() {
+            Err(TryRecvError::Empty) if self.shared.has_pending() => {
+                self.shared.flush();
+                self.try_take()
+            }
+            result => result,
+        }
+    }
+
+    /// Next event from the batches already sent to this subscriber.
+    fn try_take(&mut self) -> Result<ItemEvent, TryRecvError> {
+        loop {
+            if self.skipped > 0 {
+                return Err(TryRecvError::Lagged(std::mem::take(&mut self.skipped)));
+            }
+            if let Some(event) = self.received.pop_front() {
+                return Ok(event);
+            }
+            match self.receiver.try_recv() {
+                Ok(batch) => self.accept(&batch),
+                // Missed batches show up as a gap in the sequence numbers of the next one.
+                Err(TryRecvError::Lagged(_)) => {}
+                Err(error) => return Err(error),
+            }
+        }
+    }
+
+    /// Queue the events of the batch this subscriber has not seen yet and note any gap before them.
+    fn accept(&mut self, batch: &EventBatch) {
+        if batch.next_sequence() <= self.next_sequence {
+            return;
+        }
+        self.skipped += batch.first_sequence.saturating_sub(self.next_sequence);
+        let already_seen = self.next_sequence.saturating_sub(batch.first_sequence);
+        self.received.extend(
+            batch
+                .events
+                .iter()
+                .skip(usize::try_from(already_seen).unwrap_or(usize::MAX))
+                .cloned(),
+        );
+        self.next_sequence = batch.next_sequence();
+    }
+}
+
+/// Receive the next event, logging and counting how many were skipped if the subscriber fell behind.
+///
+/// Returns `None` once the channel is closed.
+pub async fn next_event(
+    receiver: &mut EventReceiver,
+    consumer: &'static str,
+    metrics: &TelemetryMetrics,
+) -> Option<ItemEvent> {
+    loop {
+        match receiver.recv().await {
+            Ok(event) => return Some(event),
+            Err(RecvError::Lagged(skipped)) => {
+                crate::log_warn!("Item event consumer {consumer} fell behind and skipped {skipped} events");
+                metrics.record_item_events_skipped(consumer, skipped);
+            }
+            Err(RecvError::Closed) => return None,
+        }
+    }
+}
+
+/// Count item events by type in the `item_events_total` metric.
+pub fn spawn_metrics_consumer(state: &SharedState) -> JoinHandle<()> {
+    let mut receiver = state.events().subscribe();
+    let metrics = state.telemetry().metrics();
+    tokio::spawn(async move {
+        while let Some(event) = next_event(&mut receiver, "metrics", &metrics).await {
+            metrics.record_item_event(event.change.event_type().into());
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serde_json::json;
+
+    use crate::telemetry::Telemetry;
+    use crate::test_utils::TestApp;
+    use crate::types::{AppState, IdStrategy};
+
+    #[test]
+    fn events_serialize_with_type_tag() {
+        let occurred_at = Utc::now();
+        let event = ItemEvent {
+            occurred_at,
+            actor: Actor::Admin,
+            change: ItemChange::Cleared { count: 3 },
+        };
+        let value = serde_json::to_value(&event).unwrap();
+        assert_eq!(
+            value,
+            json!({ "occurred_at": occurred_at, "actor": "admin", "type": "cleared", "count": 3 })
+        );
+        assert_eq!(serde_json::from_value::<ItemEvent>(value).unwrap(), event);
+        assert_eq!(event.change.event_type().to_string(), "cleared");
+        assert_eq!(serde_json::to_value(ItemEventType::Cleared).unwrap(), "cleared");
+    }
+
+    #[tokio::test]
+    async fn subscribers_receive_published_events() {
+        let metrics = Telemetry::noop().metrics();
+        let bus = EventBus::new(4);
+        let event = |count| ItemEvent {
+            occurred_at: Utc::now(),
+            actor: Actor::Admin,
+            change: ItemChange::Cleared { count },
+        };
+        bus.publish(event(0));
+
+        let mut receiver = bus.subscribe();
+        assert_eq!(bus.num_subscribers(), 1);
+        bus.publish(event(1));
+        assert_eq!(
+            next_event(&mut receiver, "test", &metrics).await.unwrap().change,
+            ItemChange::Cleared { count: 1 }
+        );
+    }
+
+    #[tokio::test]
+    async fn lagging_subscriber_skips_the_oldest_events() {
+        let telemetry = Telemetry::noop();
+        let metrics = telemetry.metrics();
+        let bus = EventBus::new(2);
+        let mut receiver = bus.subscribe();
+        for count in 0..5 {
+            bus.publish(ItemEvent {
+                occurred_at: Utc::now(),
+                actor: Actor::Admin,
+                change: ItemChange::Cleared { count },
+            });
+        }
+        assert_eq!(
+            next_event(&mut receiver, "test", &metrics).await.unwrap().change,
+            ItemChange::Cleared { count: 3 }
+        );
+        assert_eq!(
+            next_event(&mut receiver, "test", &metrics).await.unwrap().change,
+            ItemChange::Cleared { count: 4 }
+        );
+
+        let (body, _) = telemetry.render_prometheus().expect("metrics render");
+        let skipped = body
+            .lines()
+            .find(|line| line.starts_with("axum_example_item_events_skipped_total"))
+            .expect("skipped events should be counted");
+        assert!(skipped.contains("consumer=\"test\""), "{skipped}");
+        assert!(skipped.ends_with(" 3"), "{skipped}");
+    }
+
+    #[tokio::test]
+    async fn flooded_bus_keeps_the_newest_events_without_blocking() {
+        let telemetry = Telemetry::noop();
+        let metrics = telemetry.metrics();
+        let bus = EventBus::new(16);
+        let mut receiver = bus.subscribe();
+        let mut consumer = bus.subscribe();
+        let collector = tokio::spawn(async move {
+            let mut counts = Vec::new();
+            while let Some(event) = next_event(&mut consumer, "flood", &metrics).await {
+                if let ItemChange::Cleared { count } = event.change {
+                    counts.push(count);
+                }
+                if counts.last() == Some(&9_999) {
+                    break;
+                }
+            }
+            counts
+        });
+
+        for count in 0..10_000 {
+            bus.publish(ItemEvent {
+                occurred_at: Utc::now(),
+                actor: Actor::Admin,
+                change: ItemChange::Cleared { count },
+            });
+        }
+        assert_eq!(receiver.try_recv(), Err(TryRecvError::Lagged(9_984)));
+        assert_eq!(
+            receiver.try_recv().unwrap().change,
+            ItemChange::Cleared { count: 9_984 }
+        );
+
+        let counts = tokio::time::timeout(Duration::from_secs(5), collector)
+            .await
+            .expect("consumer should catch up")
+            .unwrap();
+        assert_eq!(counts, (9_984..10_000).collect::<Vec<_>>());
+        let (body, _) = telemetry.render_prometheus().expect("metrics render");
+        assert!(
+            body.lines()
+                .any(|line| line.contains("consumer=\"flood\"") && line.ends_with(" 9984")),
+            "{body}"
+        );
+    }
+
+    #[tokio::test]
+    async fn events_published_in_a_burst_arrive_in_one_batch() {
+        let bus = EventBus::default();
+        let mut receiver = bus.subscribe();
+        let waiting = tokio::spawn(async move {
+            let first = receiver.recv().await.unwrap();
+            let rest: Vec<ItemEvent> = std::iter::from_fn(|| receiver.receiver.try_recv().ok())
+                .flat_map(|batch| batch.events.clone())
+                .collect();
+            (first, receiver.received.len(), rest.len())
+        });
+        tokio::task::yield_now().await;
+        for count in 0..3 {
+            bus.publish(ItemEvent {
+                occurred_at: Utc::now(),
+                actor: Actor::Admin,
+                change: ItemChange::Cleared { count },
+            });
+        }
+
+        let (first, buffered, later_batches) = waiting.await.unwrap();
+        assert_eq!(first.change, ItemChange::Cleared { count: 0 });
+        assert_eq!(buffered, 2);
+        assert_eq!(later_batches, 0);
+    }
+
+    #[tokio::test]
+    async fn state_publishes_with_its_clock() {
+        let state = AppState::new_shared_state();
+        let mut receiver = state.events().subscribe();
+        let item = Item::new_with_random_id("event".to_string(), IdStrategy::Numeric);
+        state.publish(Actor::Client, ItemChange::Created { item: item.clone() });
+
+        let event = receiver.recv().await.unwrap();
+        assert_eq!(event.actor, Actor::Client);
+        assert_eq!(event.change, ItemChange::Created { item });
+        assert!(event.occurred_at <= state.now());
+    }
+
+    #[tokio::test]
+    async fn item_routes_publish_events() {
+        let app = TestApp::new();
+        let mut receiver = app.state.events().subscribe();
+
+        let item = app.create_item(json!({ "name": "published" })).await;
+        app.delete("/admin/clear_items").await;
+
+        let event = receiver.recv().await.unwrap();
+        assert_eq!(event.change, ItemChange::Created { item });
+        let event = receiver.recv().await.unwrap();
+        assert_eq!(event.actor, Actor::Admin);
+        assert_eq!(event.change, ItemChange::Cleared { count: 1 });
+    }
+}
@@ -0,0 +1,208 @@
+//! Global item eviction.
+//!
+//! Independent of per-item TTLs, the `eviction` settings limit how old items can get,
+//! how long they can go without being read or changed and how many one item store can hold,
+//! so long-running instances do not grow without bound.
+//! Reading an item with `GET /item`, the collection item route or the batch get route counts as an access,
+//! listings and cached responses do not.
+//! Creating an item in a store that is then over `max_items` evicts its least recently accessed items right away,
+//! and the scheduled `eviction_sweep` task removes the items past any limit from every item store.
+
+use std::collections::HashMap;
+
+use crate::events::{Actor, ItemChange};
+use crate::types::{Collection, EvictionConfig, EvictionReason, Item, SharedState};
+
+/// Evict items past the policy limits from every item store now and return how many were removed.
+pub fn sweep(state: &SharedState, policy: &EvictionConfig) -> usize {
+    if policy.is_disabled() {
+        return 0;
+    }
+    let now = state.now();
+    state
+        .collections()
+        .into_iter()
+        .map(|(_, collection)| collection)
+        .chain(state.tenant_stores())
+        .map(|collection| {
+            let evicted = collection.remove_evicted_items(policy, now);
+            report(state, &collection, evicted) + evict_least_recently_used(state, &collection, policy, None)
+        })
+        .sum()
+}
+
+/// Evict the least recently accessed items while the collection is over `max_items`, except for `keep`.
+///
+/// Returns how many items were removed.
+pub fn evict_least_recently_used(
+    state: &SharedState,
+    collection: &Collection,
+    policy: &EvictionConfig,
+    keep: Option<&str>,
+) -> usize {
+    let Some(max_items) = policy.max_items else {
+        return 0;
+    };
+    let evicted = collection
+        .remove_least_recently_used(max_items, keep)
+        .into_iter()
+        .map(|item| (item, EvictionReason::MaxItems))
+        .collect();
+    report(state, collection, evicted)
+}
+
+/// Log and count the evicted items and return how many there were.
+///
+/// Only evictions from the default collection are published as `evicted` item events,
+/// like the other item changes.
+fn report(state: &SharedState, collection: &Collection, evicted: Vec<(Item, EvictionReason)>) -> usize {
+    let count = evicted.len();
+    let mut counts: HashMap<EvictionReason, u64> = HashMap::new();
+    for (item, reason) in &evicted {
+        crate::log_info!("Item evicted: {} (id {}, {reason})", item.name, item.id);
+        *counts.entry(*reason).or_default() += 1;
+    }
+    let metrics = state.telemetry().metrics();
+    for (reason, count) in counts {
+        metrics.record_items_evicted(count, reason.into());
+    }
+    if std::ptr::eq(collection, state.items()) {
+        for (item, reason) in evicted {
+            state.publish(Actor::Eviction, ItemChange::Evicted { item, reason });
+        }
+    }
+    count
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use axum::http::StatusCode;
+    use chrono::{TimeDelta, Utc};
+    use serde_json::json;
+
+    use crate::events::ItemEventType;
+    use crate::settings::Settings;
+    use crate::test_utils::TestApp;
+    use crate::types::{AppState, IdStrategy};
+
+    fn item_created_at(name: &str, age: TimeDelta) -> Item {
+        let mut item = Item::new_with_random_id(name.to_string(), IdStrategy::Uuid);
+        item.created_at = Utc::now() - age;
+        item.updated_at = item.created_at;
+        item
+    }
+
+    #[test]
+    fn sweep_evicts_old_and_idle_items() {
+        let state = AppState::new_shared_state();
+        let policy = EvictionConfig {
+            max_age_seconds: Some(24 * 60 * 60),
+            max_idle_seconds: Some(60 * 60),
+            max_items: None,
+        };
+        let items = state.items();
+        items
+            .insert_item(&item_created_at("old", TimeDelta::days(2)))
+            .expect("new item");
+        items
+            .insert_item(&item_created_at("idle", TimeDelta::hours(2)))
+            .expect("new item");
+        items
+            .insert_item(&item_created_at("read", TimeDelta::hours(2)))
+            .expect("new item");
+        items
+            .insert_item(&item_created_at("new", TimeDelta::zero()))
+            .expect("new item");
+        items.record_access("read", Utc::now());
+
+        let archive = state.create_collection("archive").expect("new collection");
+        archive
+            .insert_item(&item_created_at("idle", TimeDelta::hours(2)))
+            .expect("new item");
+
+        let mut events = state.events().subscribe();
+        assert_eq!(sweep(&state, &EvictionConfig::default()), 0);
+        assert_eq!(sweep(&state, &policy), 3);
+
+        let mut names: Vec<String> = items.db.iter().map(|entry| entry.key().clone()).collect();
+        names.sort_unstable();
+        assert_eq!(names, ["new", "read"]);
+        assert!(archive.db.is_empty());
+
+        let mut reasons = Vec::new();
+        while let Ok(event) = events.try_recv() {
+            assert_eq!(event.actor, Actor::Eviction);
+            assert_eq!(event.change.event_type(), ItemEventType::Evicted);
+            if let ItemChange::Evicted { item, reason } = event.change {
+                reasons.push((item.name, reason));
+            }
+        }
+        reasons.sort_by(|a, b| a.0.cmp(&b.0));
+        assert_eq!(
+            reasons,
+            [
+                ("idle".to_string(), EvictionReason::MaxIdle),
+                ("old".to_string(), EvictionReason::MaxAge)
+            ]
+        );
+        assert_eq!(sweep(&state, &policy), 0);
+    }
+
+    #[tokio::test]
+    async fn reading_an_item_records_access() {
+        let app = TestApp::new();
+        let response = app.post_json("/items", &json!({ "name": "esgrove" })).await;
+        assert_eq!(response.status, StatusCode::CREATED);
+        let created = app
+            .state
+            .items()
+            .last_accessed("esgrove")
+            .expect("access recorded on create");
+
+        assert_eq!(app.get("/items").await.status, StatusCode::OK);
+        assert_eq!(app.state.items().last_accessed("esgrove"), Some(created));
+
+        assert_eq!(app.get("/item?name=esgrove").await.status, StatusCode::OK);
+        assert!(app.state.items().last_accessed("esgrove") > Some(created));
+        assert_eq!(app.state.items().last_accessed("missing"), None);
+    }
+
+    #[tokio::test]
+    async fn creating_past_max_items_evicts_the_least_recently_accessed() {
+        let app = TestApp::with_settings(Settings {
+            eviction: EvictionConfig {
+                max_items: Some(2),
+                ..EvictionConfig::default()
+            },
+            ..Settings::default()
+        });
+        let mut events = app.state.events().subscribe();
+        app.create_item(json!({ "name": "first" })).await;
+        app.create_item(json!({ "name": "second" })).await;
+        assert_eq!(app.get("/item?name=first").await.status, StatusCode::OK);
+        app.create_item(json!({ "name": "third" })).await;
+
+        assert_eq!(app.get_items("").await.names, ["first", "third"]);
+        let evicted: Vec<ItemChange> = std::iter::from_fn(|| events.try_recv().ok())
+            .filter(|event| event.actor == Actor::Eviction)
+            .map(|event| event.change)
+            .collect();
+        assert!(matches!(
+            evicted.as_slice(),
+            [ItemChange::Evicted { item, reason: EvictionReason::MaxItems }] if item.name == "second"
+        ));
+        let metrics = app.get("/metrics").await;
+        assert!(metrics.text().contains("axum_example_items_evicted_total"));
+        assert!(metrics.text().contains("reason=\"max_items\""));
+
+        // Lowering the limit takes effect on the next sweep.
+        let policy = EvictionConfig {
+            max_items: Some(1),
+            ..EvictionConfig::default()
+        };
+        assert_eq!(sweep(&app.state, &policy), 1);
+        assert_eq!(app.get_items("").await.names, ["third"]);
+    }
+}
@@ -0,0 +1,77 @@
+//! Item expiry.
+//!
+//! Items created with `ttl_seconds` get an `expires_at` timestamp.
+//! Read routes hide expired items right away,
+//! and the scheduled `expiry_sweep` task removes them from the state so they stop taking memory.
+
+use crate::events::{Actor, ItemChange};
+use crate::types::{DEFAULT_COLLECTION, SharedState};
+
+/// Remove expired items now and return how many were removed.
+///
+/// Every removed item is logged, counted in the `items_expired_total` metric
+/// and published as an `expired` item event.
+/// Expired items of named collections and tenant stores are removed and counted too.
+pub fn sweep(state: &SharedState) -> usize {
+    let expired = state.items().remove_expired_items(state.now());
+    let expired_unpublished: usize = state
+        .collections()
+        .into_iter()
+        .filter(|(name, _)| name != DEFAULT_COLLECTION)
+        .map(|(_, collection)| collection)
+        .chain(state.tenant_stores())
+        .map(|collection| collection.remove_expired_items(state.now()).len())
+        .sum();
+    for item in &expired {
+        crate::log_info!(
+            "Item expired: {} (id {}, expired at {})",
+            item.name,
+            item.id,
+            item.expires_at
+                .map(|expires_at| expires_at.to_rfc3339())
+                .unwrap_or_default()
+        );
+    }
+    let count = expired.len() + expired_unpublished;
+    if count > 0 {
+        state.telemetry().metrics().record_items_expired(count as u64);
+    }
+    for item in expired {
+        state.publish(Actor::Expiry, ItemChange::Expired { item });
+    }
+    count
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use chrono::Utc;
+
+    use crate::types::{AppState, IdStrategy, Item};
+
+    #[test]
+    fn sweep_removes_expired_items() {
+        let state = AppState::new_shared_state();
+        let mut item = Item::new_with_random_id("ephemeral".to_string(), IdStrategy::Numeric).with_ttl(Some(60));
+        item.expires_at = Some(Utc::now());
+        state.items().insert_item(&item).expect("new item");
+        state
+            .items()
+            .insert_item(&Item::new_with_random_id("kept".to_string(), IdStrategy::Uuid))
+            .expect("new item");
+
+        let archive = state.create_collection("archive").expect("new collection");
+        archive.insert_item(&item).expect("new item");
+
+        assert_eq!(sweep(&state), 2);
+
+        assert!(
+            !state.items().db.contains_key("ephemeral"),
+            "expired item was not removed"
+        );
+        assert!(state.items().db.contains_key("kept"));
+        assert!(archive.db.is_empty());
+        assert_eq!(sweep(&state), 0);
+    }
+}
@@ -0,0 +1,214 @@
+//! Config file layer.
+//!
+//! Reads `axum-example.toml` for the settings file layer and watches it for changes.
+//! Environment tables such as `[production]` or `[local]` override the base values
+//! for the matching `API_ENV`, so one file can describe every environment.
+//! The file contents are validated when merged into `Settings`.
+
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use notify::{Event, RecommendedWatcher, RecursiveMode, Watcher};
+use tokio::sync::mpsc;
+
+use crate::types::Environment;
+
+/// Default config file name, looked up from the working directory.
+pub const CONFIG_FILE_NAME: &str = "axum-example.toml";
+
+/// Top-level tables that hold per-environment overrides.
+const ENVIRONMENT_SECTIONS: [Environment; 4] = [
+    Environment::Production,
+    Environment::Test,
+    Environment::Development,
+    Environment::Local,
+];
+
+/// Wait for writes to settle before reloading.
+const RELOAD_DEBOUNCE: Duration = Duration::from_millis(200);
+
+/// Read the config file values for the environment, or nothing if the file does not exist.
+pub fn read(path: &Path, env: Environment) -> Result<toml::Table> {
+    if !path.exists() {
+        return Ok(toml::Table::new());
+    }
+    let contents =
+        std::fs::read_to_string(path).with_context(|| format!("Failed to read config file {}", path.display()))?;
+    parse(&contents, env).with_context(|| format!("Invalid config file {}", path.display()))
+}
+
+/// Parse config file values for the environment from TOML.
+///
+/// The table named after the environment is merged over the base values,
+/// and the tables for other environments are ignored.
+pub fn parse(contents: &str, env: Environment) -> Result<toml::Table> {
+    let mut base: toml::Table = toml::from_str(contents)?;
+    let mut overrides = None;
+    for section in ENVIRONMENT_SECTIONS {
+        let name = section_name(section);
+        match base.remove(&name) {
+            Some(toml::Value::Table(table)) if section == env => overrides = Some(table),
+            Some(toml::Value::Table(_)) | None => {}
+            Some(_) => anyhow::bail!("Environment section '{name}' must be a table"),
+        }
+    }
+    if let Some(overrides) = overrides {
+        merge_tables(&mut base, overrides);
+    }
+    Ok(base)
+}
+
+/// Watch the config file and call `on_change` when it changes.
+///
+/// The parent directory is watched instead of the file itself,
+/// so the file can be created later and editors that replace the file on save work.
+/// Events are debounced so a half-written file is not loaded.
+/// The watcher stops when the returned handle is dropped.
+pub fn watch<F>(path: &Path, on_change: F) -> Result<RecommendedWatcher>
+where
+    F: Fn() + Send + 'static,
+{
+    let directory = path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+        .unwrap_or_else(|| Path::new("."));
+    let file_name = path.file_name().map(ToOwned::to_owned);
+    let (sender, mut receiver) = mpsc::unbounded_channel();
+
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<Event>| match event {
+        Ok(event) => {
+            if !event.kind.is_access()
+                && event
+                    .paths
+                    .iter()
+                    .any(|changed| changed.file_name() == file_name.as_deref())
+            {
+                sender.send(()).ok();
+            }
+        }
+        Err(error) => crate::log_error!("Config file watch error: {error}"),
+    })
+    .context("Failed to create config file watcher")?;
+    watcher
+        .watch(directory, RecursiveMode::NonRecursive)
+        .with_context(|| format!("Failed to watch config directory {}", directory.display()))?;
+
+    tokio::spawn(async move {
+        while receiver.recv().await.is_some() {
+            tokio::time::sleep(RELOAD_DEBOUNCE).await;
+            while receiver.try_recv().is_ok() {}
+            on_change();
+        }
+    });
+    Ok(watcher)
+}
+
+/// Resolve the config file path from an optional CLI argument.
+#[must_use]
+pub fn config_path(path: Option<PathBuf>) -> PathBuf {
+    path.unwrap_or_else(|| PathBuf::from(CONFIG_FILE_NAME))
+}
+
+/// Table name for the environment, for example `production`.
+fn section_name(env: Environment) -> String {
+    env.to_string().to_lowercase()
+}
+
+/// Recursively merge override values into the base table.
+///
+/// Nested tables are merged key by key, any other value replaces the base value.
+fn merge_tables(base: &mut toml::Table, overrides: toml::Table) {
+    for (key, value) in overrides {
+        match (base.get_mut(&key), value) {
+            (Some(toml::Value::Table(base_table)), toml::Value::Table(override_table)) => {
+                merge_tables(base_table, override_table);
+            }
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn missing_file_is_empty() {
+        let values = read(Path::new("does-not-exist.toml"), Environment::Local).expect("missing file is not an error");
+
+        assert!(values.is_empty());
+    }
+
+    #[test]
+    fn environment_section_overrides_base_values() {
+        let contents = r#"
+            cors_origins = ["https://app.example"]
+
+            [rate_limit]
+            requests_per_second = 5
+            burst = 10
+
+            [production.rate_limit]
+            burst = 50
+
+            [local]
+            cors_origins = ["*"]
+            "#;
+
+        let production = parse(contents, Environment::Production).expect("config should parse");
+        assert_eq!(
+            production.get("cors_origins"),
+            Some(&toml::Value::Array(vec!["https://app.example".into()]))
+        );
+        assert_eq!(production["rate_limit"]["requests_per_second"].as_integer(), Some(5));
+        assert_eq!(production["rate_limit"]["burst"].as_integer(), Some(50));
+        assert!(!production.contains_key("production"));
+        assert!(!production.contains_key("local"));
+
+        let local = parse(contents, Environment::Local).expect("config should parse");
+        assert_eq!(local.get("cors_origins"), Some(&toml::Value::Array(vec!["*".into()])));
+        assert_eq!(local["rate_limit"]["burst"].as_integer(), Some(10));
+
+        let development = parse(contents, Environment::Development).expect("config should parse");
+        assert_eq!(development["rate_limit"]["burst"].as_integer(), Some(10));
+    }
+
+    #[test]
+    fn environment_section_must_be_a_table() {
+        assert!(parse("local = 1", Environment::Local).is_err());
+        assert!(parse("local = ", Environment::Local).is_err());
+    }
+
+    #[tokio::test]
+    async fn watcher_calls_back_on_change() {
+        let directory = std::env::temp_dir().join(format!("axum-example-config-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).expect("create temp dir");
+        let path = directory.join(CONFIG_FILE_NAME);
+        std::fs::write(&path, "port = 3000").expect("write config");
+        let changes = Arc::new(AtomicUsize::new(0));
+        let _watcher = watch(&path, {
+            let changes = Arc::clone(&changes);
+            move || {
+                changes.fetch_add(1, Ordering::Relaxed);
+            }
+        })
+        .expect("watch config");
+
+        std::fs::write(&path, "port = 4000").expect("write config");
+        for _ in 0..50 {
+            if changes.load(Ordering::Relaxed) > 0 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        assert!(changes.load(Ordering::Relaxed) > 0, "config change was not noticed");
+
+        std::fs::remove_dir_all(&directory).ok();
+    }
+}
@@ -0,0 +1,215 @@
+//! Item change history.
+//!
+//! Every published item event is also recorded in a short history per item name,
+//! so `GET /items/{name}/history` can show how an item was created and removed over time.
+//! Each history keeps the last [`MAX_HISTORY_ENTRIES`] changes.
+//! Histories of removed items are kept after the removal,
+//! and the oldest of them are forgotten once more than [`MAX_REMOVED_HISTORIES`] names have been removed.
+
+use std::collections::VecDeque;
+use std::sync::{Mutex, PoisonError};
+
+use dashmap::DashMap;
+
+use crate::events::{ItemChange, ItemEvent, ItemEventType};
+use crate::schemas::ItemHistoryEntry;
+
+/// Changes kept per item name, the oldest are dropped first.
+pub const MAX_HISTORY_ENTRIES: usize = 20;
+
+/// Histories of removed items kept before the oldest are forgotten.
+pub const MAX_REMOVED_HISTORIES: usize = 1000;
+
+/// Recent changes per item name.
+#[derive(Debug, Default)]
+pub struct ItemHistory {
+    entries: DashMap<String, VecDeque<ItemHistoryEntry>>,
+    /// Removed names in removal order, so their histories can be forgotten oldest first.
+    removed: Mutex<VecDeque<String>>,
+}
+
+impl ItemHistory {
+    /// Add the event to the history of every item it changed.
+    ///
+    /// Clearing all items is recorded for every item whose history ends in a create.
+    pub fn record(&self, event: &ItemEvent) {
+        let entry = |revision| ItemHistoryEntry {
+            occurred_at: event.occurred_at,
+            event_type: event.change.event_type(),
+            actor: event.actor,
+            revision,
+        };
+        match &event.change {
+            ItemChange::Created { item } => self.push(&item.name, entry(Some(item.revision))),
+            ItemChange::Removed { item } | ItemChange::Expired { item } | ItemChange::Evicted { item, .. } => {
+                self.push(&item.name, entry(Some(item.revision)));
+                self.mark_removed(item.name.clone());
+            }
+            ItemChange::Cleared { .. } => {
+                let mut cleared = Vec::new();
+                for mut history in self.entries.iter_mut() {
+                    if is_live(history.value()) {
+                        append(history.value_mut(), entry(None));
+                        cleared.push(history.key().clone());
+                    }
+                }
+                for name in cleared {
+                    self.mark_removed(name);
+                }
+            }
+        }
+    }
+
+    /// Recorded changes of the named item, oldest first.
+    #[must_use]
+    pub fn get(&self, name: &str) -> Option<Vec<ItemHistoryEntry>> {
+        self.entries.get(name).map(|history| history.iter().cloned().collect())
+    }
+
+    fn push(&self, name: &str, entry: ItemHistoryEntry) {
+        append(&mut self.entries.entry(name.to_string()).or_default(), entry);
+    }
+
+    /// Remember the removal and forget the oldest removed histories past the limit.
+    fn mark_removed(&self, name: String) {
+        let mut removed = self.removed.lock().unwrap_or_else(PoisonError::into_inner);
+        removed.push_back(name);
+        while removed.len() > MAX_REMOVED_HISTORIES {
+            if let Some(oldest) = removed.pop_front() {
+                // The name may have been created again since, then its history is still needed.
+                self.entries.remove_if(&oldest, |_, history| !is_live(history));
+            }
+        }
+    }
+}
+
+/// The last recorded change created the item.
+fn is_live(history: &VecDeque<ItemHistoryEntry>) -> bool {
+    history
+        .back()
+        .is_some_and(|entry| entry.event_type == ItemEventType::Created)
+}
+
+fn append(history: &mut VecDeque<ItemHistoryEntry>, entry: ItemHistoryEntry) {
+    history.push_back(entry);
+    if history.len() > MAX_HISTORY_ENTRIES {
+        history.pop_front();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use axum::body::Body;
+    use axum::http::{Request, StatusCode};
+    use chrono::Utc;
+    use serde_json::json;
+
+    use crate::events::Actor;
+    use crate::schemas::ItemHistoryResponse;
+    use crate::test_utils::TestApp;
+    use crate::types::{DEFAULT_API_KEY, IdStrategy, Item};
+
+    fn event(actor: Actor, change: ItemChange) -> ItemEvent {
+        ItemEvent {
+            occurred_at: Utc::now(),
+            actor,
+            change,
+        }
+    }
+
+    fn item(name: &str) -> Item {
+        Item::new_with_random_id(name.to_string(), IdStrategy::Numeric)
+    }
+
+    fn event_types(history: &ItemHistory, name: &str) -> Vec<ItemEventType> {
+        history
+            .get(name)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|entry| entry.event_type)
+            .collect()
+    }
+
+    #[test]
+    fn records_changes_per_item() {
+        let history = ItemHistory::default();
+        history.record(&event(Actor::Client, ItemChange::Created { item: item("alpha") }));
+        history.record(&event(Actor::Client, ItemChange::Created { item: item("bravo") }));
+        history.record(&event(Actor::Admin, ItemChange::Removed { item: item("alpha") }));
+        history.record(&event(Actor::Client, ItemChange::Created { item: item("alpha") }));
+        history.record(&event(Actor::Admin, ItemChange::Cleared { count: 2 }));
+
+        assert_eq!(
+            event_types(&history, "alpha"),
+            [
+                ItemEventType::Created,
+                ItemEventType::Removed,
+                ItemEventType::Created,
+                ItemEventType::Cleared
+            ]
+        );
+        let bravo = history.get("bravo").unwrap();
+        assert_eq!(bravo[0].actor, Actor::Client);
+        assert_eq!(bravo[0].revision, Some(1));
+        assert_eq!(bravo[1].actor, Actor::Admin);
+        assert_eq!(bravo[1].revision, None);
+        assert!(history.get("charlie").is_none());
+    }
+
+    #[test]
+    fn keeps_only_recent_entries_and_removed_histories() {
+        let history = ItemHistory::default();
+        for _ in 0..MAX_HISTORY_ENTRIES {
+            history.record(&event(Actor::Client, ItemChange::Created { item: item("alpha") }));
+        }
+        history.record(&event(Actor::Expiry, ItemChange::Expired { item: item("alpha") }));
+        let alpha = event_types(&history, "alpha");
+        assert_eq!(alpha.len(), MAX_HISTORY_ENTRIES);
+        assert_eq!(alpha.last(), Some(&ItemEventType::Expired));
+
+        history.record(&event(Actor::Client, ItemChange::Created { item: item("live") }));
+        for index in 0..MAX_REMOVED_HISTORIES {
+            let item = item(&format!("item-{index}"));
+            history.record(&event(Actor::Client, ItemChange::Created { item: item.clone() }));
+            history.record(&event(Actor::Admin, ItemChange::Removed { item }));
+        }
+        assert!(history.get("alpha").is_none());
+        assert!(history.get("item-0").is_some());
+        assert!(history.get("live").is_some());
+    }
+
+    #[tokio::test]
+    async fn history_route_lists_item_changes() {
+        let app = TestApp::new();
+        app.create_item(json!({ "name": "tracked" })).await;
+        let remove = Request::builder()
+            .method("DELETE")
+            .uri("/admin/remove/tracked")
+            .header("api-key", DEFAULT_API_KEY)
+            .header("If-Match", "*")
+            .body(Body::empty())
+            .unwrap();
+        assert_eq!(app.send(remove).await.status, StatusCode::OK);
+
+        let response = app.get("/api/v1/items/tracked/history").await;
+        assert_eq!(response.status, StatusCode::OK);
+        let history: ItemHistoryResponse = response.json();
+        assert_eq!(history.name, "tracked");
+        let changes: Vec<(ItemEventType, Actor)> = history
+            .entries
+            .iter()
+            .map(|entry| (entry.event_type, entry.actor))
+            .collect();
+        assert_eq!(
+            changes,
+            [
+                (ItemEventType::Created, Actor::Client),
+                (ItemEventType::Removed, Actor::Admin)
+            ]
+        );
+
+        assert_eq!(app.get("/items/missing/history").await.status, StatusCode::NOT_FOUND);
+    }
+}
@@ -0,0 +1,223 @@
+//! Localized messages.
+//!
+//! `MessageResponse` and `ApiError` texts are looked up from message catalogs
+//! in the language the client asked for with `Accept-Language`.
+//! The catalogs are TOML files in `locales/` embedded at compile time,
+//! and a message missing from a catalog falls back to English.
+//! The language is picked once per request by [`language_middleware`] and read with [`current`],
+//! like the request id, so messages can be built anywhere without passing the language around.
+//! Error codes, field names and validation rule messages stay in English,
+//! since clients match on those.
+
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::sync::LazyLock;
+
+use axum::extract::Request;
+use axum::http::header::{ACCEPT_LANGUAGE, CONTENT_LANGUAGE, CONTENT_TYPE};
+use axum::http::{HeaderMap, HeaderValue};
+use axum::middleware::Next;
+use axum::response::Response;
+use strum::{EnumIter, EnumString, IntoEnumIterator, IntoStaticStr};
+
+/// Languages with a message catalog.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, EnumIter, EnumString, IntoStaticStr)]
+#[strum(serialize_all = "lowercase", ascii_case_insensitive)]
+pub enum Language {
+    #[default]
+    En,
+    Fi,
+    Sv,
+}
+
+/// Messages by key for every language.
+static CATALOGS: LazyLock<HashMap<Language, HashMap<String, String>>> = LazyLock::new(|| {
+    Language::iter()
+        .map(|language| {
+            let catalog = toml::from_str(language.catalog()).expect("embedded message catalog should be valid TOML");
+            (language, catalog)
+        })
+        .collect()
+});
+
+tokio::task_local! {
+    static LANGUAGE: Language;
+}
+
+impl Language {
+    /// Catalog source embedded in the binary.
+    const fn catalog(self) -> &'static str {
+        match self {
+            Self::En => include_str!("../locales/en.toml"),
+            Self::Fi => include_str!("../locales/fi.toml"),
+            Self::Sv => include_str!("../locales/sv.toml"),
+        }
+    }
+
+    /// The supported language the client prefers most, English if none match.
+    ///
+    /// Region subtags are ignored, so `fi-FI` selects Finnish,
+    /// and ranges with `q=0` are never selected.
+    #[must_use]
+    pub fn from_accept_language(headers: &HeaderMap) -> Self {
+        let mut ranges: Vec<(Self, f32)> = headers
+            .get_all(ACCEPT_LANGUAGE)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .filter_map(|range| {
+                let mut parameters = range.split(';').map(str::trim);
+                let tag = parameters.next()?;
+                let quality = parameters
+                    .find_map(|parameter| parameter.strip_prefix("q="))
+                    .map_or(Some(1.0), |quality| quality.parse::<f32>().ok())?;
+                let primary = tag.split('-').next()?;
+                let language = if primary == "*" {
+                    Self::default()
+                } else {
+                    primary.parse().ok()?
+                };
+                (quality > 0.0).then_some((language, quality))
+            })
+            .collect();
+        // Stable sort, so equal weights keep the client's order.
+        ranges.sort_by(|a, b| b.1.total_cmp(&a.1));
+        ranges.first().map_or_else(Self::default, |(language, _)| *language)
+    }
+}
+
+/// Language of the request being handled, English outside [`language_middleware`].
+#[must_use]
+pub fn current() -> Language {
+    LANGUAGE.try_with(|language| *language).unwrap_or_default()
+}
+
+/// Message for the key in the current language, with `{name}` placeholders filled from `args`.
+///
+/// Falls back to the English message, and to the key itself if English does not have it either.
+pub fn message(key: &str, args: &[(&str, &dyn Display)]) -> String {
+    let template = [current(), Language::En]
+        .into_iter()
+        .find_map(|language| CATALOGS.get(&language)?.get(key))
+        .map_or_else(
+            || {
+                crate::log_error!("Missing message for key: {key}");
+                key
+            },
+            String::as_str,
+        );
+    args.iter().fold(template.to_string(), |text, (name, value)| {
+        text.replace(&format!("{{{name}}}"), &value.to_string())
+    })
+}
+
+/// Pick the response language from `Accept-Language` and make it available to everything that handles the request.
+///
+/// Responses with a body get a `Content-Language` header naming the language.
+pub async fn language_middleware(request: Request, next: Next) -> Response {
+    let language = Language::from_accept_language(request.headers());
+    let mut response = LANGUAGE.scope(language, next.run(request)).await;
+    if response.headers().contains_key(CONTENT_TYPE) {
+        let tag: &'static str = language.into();
+        response
+            .headers_mut()
+            .insert(CONTENT_LANGUAGE, HeaderValue::from_static(tag));
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::collections::BTreeSet;
+
+    use axum::body::Body;
+    use axum::http::{Method, StatusCode};
+
+    use crate::schemas::ApiError;
+    use crate::test_utils::TestApp;
+
+    fn accept_language(value: &'static str) -> Language {
+        Language::from_accept_language(&HeaderMap::from_iter([(
+            ACCEPT_LANGUAGE,
+            HeaderValue::from_static(value),
+        )]))
+    }
+
+    fn placeholders(template: &str) -> BTreeSet<&str> {
+        template
+            .split('{')
+            .skip(1)
+            .filter_map(|part| part.split_once('}').map(|(name, _)| name))
+            .collect()
+    }
+
+    #[test]
+    fn accept_language_selects_the_preferred_supported_language() {
+        assert_eq!(accept_language("fi"), Language::Fi);
+        assert_eq!(accept_language("sv-FI, en;q=0.5"), Language::Sv);
+        assert_eq!(accept_language("de, fi;q=0.8, en;q=0.9"), Language::En);
+        assert_eq!(accept_language("en;q=0.1, FI-fi;q=0.2"), Language::Fi);
+        assert_eq!(accept_language("fi;q=0"), Language::En);
+        assert_eq!(accept_language("de"), Language::En);
+        assert_eq!(accept_language("*"), Language::En);
+        assert_eq!(Language::from_accept_language(&HeaderMap::new()), Language::En);
+    }
+
+    #[test]
+    fn catalogs_translate_english_messages_with_the_same_placeholders() {
+        let english = &CATALOGS[&Language::En];
+        for language in Language::iter() {
+            let catalog = &CATALOGS[&language];
+            assert_eq!(catalog.len(), english.len(), "{language:?} is missing messages");
+            for (key, template) in catalog {
+                let source = english
+                    .get(key)
+                    .unwrap_or_else(|| panic!("{language:?} has unknown key {key}"));
+                assert_eq!(placeholders(template), placeholders(source), "{language:?} {key}");
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn messages_use_the_request_language() {
+        assert_eq!(
+            message("item_not_found", &[("name", &"esgrove")]),
+            "Item does not exist: esgrove"
+        );
+        let finnish = LANGUAGE.scope(Language::Fi, async {
+            message("item_not_found", &[("name", &"esgrove")])
+        });
+        assert_eq!(finnish.await, "Kohdetta ei ole olemassa: esgrove");
+        assert_eq!(message("missing_key", &[]), "missing_key");
+    }
+
+    #[tokio::test]
+    async fn error_responses_are_localized() {
+        let app = TestApp::new();
+        let request = |method: Method, uri: &str, language: &str| {
+            Request::builder()
+                .method(method)
+                .uri(uri)
+                .header(ACCEPT_LANGUAGE, language)
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        let response = app.send(request(Method::GET, "/item?name=missing", "fi-FI")).await;
+        assert_eq!(response.status, StatusCode::NOT_FOUND);
+        assert_eq!(response.headers[CONTENT_LANGUAGE], "fi");
+        assert_eq!(response.json::<ApiError>().message, "Kohdetta ei ole olemassa: missing");
+
+        let response = app.send(request(Method::PUT, "/items", "sv")).await;
+        assert_eq!(
+            response.json::<ApiError>().message,
+            "Metoden PUT är inte tillåten, använd en av: GET, HEAD, POST, OPTIONS"
+        );
+
+        let response = app.send(request(Method::GET, "/missing", "de")).await;
+        assert_eq!(response.headers[CONTENT_LANGUAGE], "en");
+        assert_eq!(response.json::<ApiError>().message, "Path does not exist");
+    }
+}
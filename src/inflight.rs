@@ -0,0 +1,108 @@
+//! In-flight request tracking.
+//!
+//! Every request is registered when it enters the middleware stack and removed when its response is ready,
+//! or when it is dropped by a timeout or a closed connection,
+//! so `GET /admin/inflight` shows what a hanging instance is busy with.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use axum::extract::{Request, State};
+use axum::middleware::Next;
+use axum::response::Response;
+use chrono::{DateTime, Utc};
+use dashmap::DashMap;
+
+use crate::schemas::InflightRequest;
+use crate::types::SharedState;
+
+/// Requests that are currently being handled.
+#[derive(Debug, Default)]
+pub struct InflightRequests {
+    next_id: AtomicU64,
+    requests: DashMap<u64, StartedRequest>,
+}
+
+/// Removes the request from the registry when dropped.
+struct InflightGuard<'a> {
+    registry: &'a InflightRequests,
+    id: u64,
+}
+
+#[derive(Debug, Clone)]
+struct StartedRequest {
+    method: String,
+    path: String,
+    started_at: DateTime<Utc>,
+}
+
+impl InflightRequests {
+    /// Currently running requests, oldest first.
+    pub fn snapshot(&self) -> Vec<InflightRequest> {
+        let now = Utc::now();
+        let mut requests: Vec<InflightRequest> = self
+            .requests
+            .iter()
+            .map(|entry| InflightRequest {
+                id: *entry.key(),
+                method: entry.method.clone(),
+                path: entry.path.clone(),
+                started_at: entry.started_at,
+                elapsed_ms: u64::try_from((now - entry.started_at).num_milliseconds()).unwrap_or_default(),
+            })
+            .collect();
+        requests.sort_by_key(|request| request.id);
+        requests
+    }
+
+    fn start(&self, method: String, path: String) -> InflightGuard<'_> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        self.requests.insert(
+            id,
+            StartedRequest {
+                method,
+                path,
+                started_at: Utc::now(),
+            },
+        );
+        InflightGuard { registry: self, id }
+    }
+}
+
+impl Drop for InflightGuard<'_> {
+    fn drop(&mut self) {
+        self.registry.requests.remove(&self.id);
+    }
+}
+
+/// Register the request as in flight until its response is ready.
+pub async fn inflight_middleware(State(state): State<SharedState>, request: Request, next: Next) -> Response {
+    let _guard = state
+        .inflight()
+        .start(request.method().to_string(), request.uri().path().to_string());
+    next.run(request).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn requests_are_listed_until_guard_is_dropped() {
+        let registry = InflightRequests::default();
+        let first = registry.start("GET".to_string(), "/items".to_string());
+        let second = registry.start("POST".to_string(), "/items".to_string());
+
+        let requests = registry.snapshot();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[0].method, "GET");
+        assert_eq!(requests[1].method, "POST");
+
+        drop(first);
+        let requests = registry.snapshot();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].id, second.id);
+
+        drop(second);
+        assert!(registry.snapshot().is_empty());
+    }
+}
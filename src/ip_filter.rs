@@ -0,0 +1,282 @@
+//! IP allowlist and denylist for admin routes.
+//!
+//! Admin routes can be restricted to CIDR ranges, for example the VPN,
+//! so a leaked api key alone is not enough to reach them.
+//! `X-Forwarded-For` is only used when the connecting peer is a trusted proxy.
+
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::str::FromStr;
+
+use anyhow::{Context, anyhow};
+use axum::extract::{ConnectInfo, Request, State};
+use axum::http::HeaderMap;
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use serde::{Deserialize, Serialize};
+
+use crate::i18n;
+use crate::schemas::{ApiError, ErrorCode};
+use crate::settings::SharedSettings;
+use crate::types::IpFilterConfig;
+
+const FORWARDED_FOR_HEADER: &str = "x-forwarded-for";
+
+/// IP network in CIDR notation, for example `10.0.0.0/8` or `fd00::/8`.
+///
+/// A bare address is treated as a single host network.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct IpNetwork {
+    address: IpAddr,
+    prefix: u8,
+}
+
+impl IpNetwork {
+    /// Check if the address belongs to this network.
+    ///
+    /// IPv4-mapped IPv6 addresses are compared as IPv4.
+    #[must_use]
+    pub fn contains(&self, address: IpAddr) -> bool {
+        match (self.address, address.to_canonical()) {
+            (IpAddr::V4(network), IpAddr::V4(address)) => {
+                let mask = u32::MAX.checked_shl(32 - u32::from(self.prefix)).unwrap_or(0);
+                u32::from(network) & mask == u32::from(address) & mask
+            }
+            (IpAddr::V6(network), IpAddr::V6(address)) => {
+                let mask = u128::MAX.checked_shl(128 - u32::from(self.prefix)).unwrap_or(0);
+                u128::from(network) & mask == u128::from(address) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+impl FromStr for IpNetwork {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let value = value.trim();
+        let (address, prefix) = value.split_once('/').map_or((value, None), |(a, p)| (a, Some(p)));
+        let address: IpAddr = address
+            .parse()
+            .with_context(|| format!("Invalid IP address in network '{value}'"))?;
+        let address = address.to_canonical();
+        let max_prefix = if address.is_ipv4() { 32 } else { 128 };
+        let prefix = match prefix {
+            Some(prefix) => prefix
+                .parse::<u8>()
+                .with_context(|| format!("Invalid prefix length in network '{value}'"))?,
+            None => max_prefix,
+        };
+        if prefix > max_prefix {
+            return Err(anyhow!("Prefix length {prefix} is too long for network '{value}'"));
+        }
+        Ok(Self { address, prefix })
+    }
+}
+
+impl TryFrom<String> for IpNetwork {
+    type Error = anyhow::Error;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<IpNetwork> for String {
+    fn from(network: IpNetwork) -> Self {
+        network.to_string()
+    }
+}
+
+impl fmt::Display for IpNetwork {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.address, self.prefix)
+    }
+}
+
+impl IpFilterConfig {
+    /// Check if the client address may access admin routes.
+    ///
+    /// The denylist wins over the allowlist, and an empty allowlist allows everyone.
+    #[must_use]
+    pub fn is_allowed(&self, client: IpAddr) -> bool {
+        if self.denied.iter().any(|network| network.contains(client)) {
+            return false;
+        }
+        self.allowed.is_empty() || self.allowed.iter().any(|network| network.contains(client))
+    }
+
+    /// Check if any rules are configured.
+    #[must_use]
+    pub const fn is_enabled(&self) -> bool {
+        !self.allowed.is_empty() || !self.denied.is_empty()
+    }
+
+    /// Resolve the client address from the peer and `X-Forwarded-For`.
+    ///
+    /// Forwarded addresses are walked from the nearest hop backwards,
+    /// and the first address that is not a trusted proxy is the client.
+    /// A hop that is not an address stops the walk at the last trusted hop,
+    /// since the entries left of it cannot be attributed to a trusted proxy.
+    #[must_use]
+    pub fn client_address(&self, peer: IpAddr, headers: &HeaderMap) -> IpAddr {
+        let is_trusted = |address: IpAddr| self.trusted_proxies.iter().any(|network| network.contains(address));
+        if !is_trusted(peer) {
+            return peer;
+        }
+        let forwarded: Vec<&str> = headers
+            .get_all(FORWARDED_FOR_HEADER)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .collect();
+        let mut client = peer;
+        for hop in forwarded.into_iter().rev() {
+            let Some(address) = parse_hop(hop) else {
+                break;
+            };
+            client = address;
+            if !is_trusted(address) {
+                break;
+            }
+        }
+        client
+    }
+}
+
+/// Parse an `X-Forwarded-For` hop written as `ip`, `ip:port`, `[ipv6]` or `[ipv6]:port`.
+fn parse_hop(hop: &str) -> Option<IpAddr> {
+    let hop = hop.trim();
+    hop.parse()
+        .ok()
+        .or_else(|| hop.parse::<SocketAddr>().ok().map(|address| address.ip()))
+        .or_else(|| hop.strip_prefix('[')?.strip_suffix(']')?.parse().ok())
+}
+
+/// Reject requests from client addresses outside the allowed networks with `403 Forbidden`.
+pub async fn ip_filter_middleware(State(settings): State<SharedSettings>, request: Request, next: Next) -> Response {
+    let settings = settings.load();
+    let config = &settings.admin_access;
+    if !config.is_enabled() {
+        return next.run(request).await;
+    }
+
+    let peer = request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map_or(IpAddr::V4(Ipv4Addr::UNSPECIFIED), |ConnectInfo(address)| address.ip());
+    let client = config.client_address(peer, request.headers());
+    if config.is_allowed(client) {
+        return next.run(request).await;
+    }
+
+    crate::log_warn!(
+        "Blocked admin request from {client}: {} {}",
+        request.method(),
+        request.uri().path()
+    );
+    ApiError::new(
+        ErrorCode::Forbidden,
+        i18n::message("access_denied", &[("client", &client)]),
+    )
+    .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use axum::http::HeaderValue;
+
+    fn network(value: &str) -> IpNetwork {
+        value.parse().expect("valid network")
+    }
+
+    fn address(value: &str) -> IpAddr {
+        value.parse().expect("valid address")
+    }
+
+    #[test]
+    fn parses_networks_and_single_addresses() {
+        assert_eq!(network("10.0.0.0/8").to_string(), "10.0.0.0/8");
+        assert_eq!(network("192.168.1.10").to_string(), "192.168.1.10/32");
+        assert_eq!(network("fd00::/8").to_string(), "fd00::/8");
+        assert!("10.0.0.0/33".parse::<IpNetwork>().is_err());
+        assert!("vpn".parse::<IpNetwork>().is_err());
+        assert!("10.0.0.0/x".parse::<IpNetwork>().is_err());
+    }
+
+    #[test]
+    fn network_contains_addresses_in_range() {
+        assert!(network("10.0.0.0/8").contains(address("10.20.30.40")));
+        assert!(!network("10.0.0.0/8").contains(address("11.0.0.1")));
+        assert!(network("0.0.0.0/0").contains(address("203.0.113.1")));
+        assert!(network("fd00::/8").contains(address("fd12::1")));
+        assert!(!network("fd00::/8").contains(address("10.0.0.1")));
+        assert!(network("10.0.0.0/8").contains(address("::ffff:10.0.0.1")));
+    }
+
+    #[test]
+    fn denylist_wins_over_allowlist() {
+        let config = IpFilterConfig {
+            allowed: vec![network("10.0.0.0/8")],
+            denied: vec![network("10.0.0.13")],
+            trusted_proxies: Vec::new(),
+        };
+
+        assert!(config.is_allowed(address("10.0.0.1")));
+        assert!(!config.is_allowed(address("10.0.0.13")));
+        assert!(!config.is_allowed(address("192.168.0.1")));
+    }
+
+    #[test]
+    fn forwarded_for_is_only_used_from_trusted_proxies() {
+        let config = IpFilterConfig {
+            allowed: Vec::new(),
+            denied: Vec::new(),
+            trusted_proxies: vec![network("172.16.0.0/12")],
+        };
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            FORWARDED_FOR_HEADER,
+            HeaderValue::from_static("198.51.100.7, 10.1.2.3, 172.16.0.9"),
+        );
+
+        assert_eq!(
+            config.client_address(address("172.16.0.1"), &headers),
+            address("10.1.2.3")
+        );
+        assert_eq!(
+            config.client_address(address("203.0.113.5"), &headers),
+            address("203.0.113.5")
+        );
+    }
+
+    #[test]
+    fn forwarded_for_accepts_ports_and_stops_at_unparseable_hops() {
+        let config = IpFilterConfig {
+            allowed: Vec::new(),
+            denied: Vec::new(),
+            trusted_proxies: vec![network("172.16.0.0/12")],
+        };
+        let client_address = |value: &'static str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(FORWARDED_FOR_HEADER, HeaderValue::from_static(value));
+            config.client_address(address("172.16.0.1"), &headers)
+        };
+
+        // The spoofed left entry is not reached when the real client hop has a port.
+        assert_eq!(client_address("10.0.0.1, 198.51.100.7:4711"), address("198.51.100.7"));
+        assert_eq!(client_address("10.0.0.1, [2001:db8::7]:4711"), address("2001:db8::7"));
+        assert_eq!(client_address("10.0.0.1, [2001:db8::7]"), address("2001:db8::7"));
+        assert_eq!(
+            client_address("10.0.0.1, 198.51.100.7:4711, 172.16.0.9:80"),
+            address("198.51.100.7")
+        );
+        // An unparseable hop falls back to the last trusted hop or the peer.
+        assert_eq!(client_address("10.0.0.1, unknown, 172.16.0.9"), address("172.16.0.9"));
+        assert_eq!(client_address("10.0.0.1, unknown"), address("172.16.0.1"));
+    }
+}
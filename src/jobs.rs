@@ -0,0 +1,326 @@
+//! Background jobs.
+//!
+//! Long running admin work like restoring a large snapshot or writing a backup
+//! is queued as a job instead of running inside the request handler.
+//! The route returns the job status right away with `202 Accepted`,
+//! and clients poll `GET /admin/jobs/{id}` until the job has finished.
+//! The queue is bounded, so a full queue rejects new jobs instead of piling up work,
+//! and a fixed number of worker tasks run the jobs in the order they were queued.
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex, PoisonError};
+
+use chrono::Utc;
+use dashmap::DashMap;
+use futures_util::future::BoxFuture;
+use serde_json::Value;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use ulid::Ulid;
+
+use crate::schemas::{JobKind, JobState, JobStatus};
+use crate::types::SharedState;
+
+/// Jobs that can wait in the queue before new jobs are rejected.
+pub const JOB_QUEUE_CAPACITY: usize = 64;
+
+/// Worker tasks running jobs concurrently.
+pub const JOB_WORKERS: usize = 2;
+
+/// Finished jobs kept for status polling, the oldest are forgotten first.
+pub const MAX_FINISHED_JOBS: usize = 100;
+
+/// Work that produces the JSON result of a job.
+pub type JobWork = BoxFuture<'static, anyhow::Result<Value>>;
+
+/// Bounded job queue and the status of recent jobs.
+#[derive(Debug)]
+pub struct JobQueue {
+    sender: mpsc::Sender<QueuedJob>,
+    /// Taken by [`spawn_workers`], so only one set of workers reads the queue.
+    receiver: Mutex<Option<mpsc::Receiver<QueuedJob>>>,
+    statuses: DashMap<String, JobStatus>,
+    finished: Mutex<VecDeque<String>>,
+}
+
+/// The queue already holds [`JOB_QUEUE_CAPACITY`] jobs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QueueFull;
+
+struct QueuedJob {
+    id: String,
+    work: JobWork,
+}
+
+impl JobQueue {
+    #[must_use]
+    pub fn new(capacity: usize) -> Self {
+        let (sender, receiver) = mpsc::channel(capacity);
+        Self {
+            sender,
+            receiver: Mutex::new(Some(receiver)),
+            statuses: DashMap::new(),
+            finished: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    /// Queue the work and return the status of the new job.
+    pub fn submit(&self, kind: JobKind, work: JobWork) -> Result<JobStatus, QueueFull> {
+        let status = JobStatus {
+            id: Ulid::generate().to_string(),
+            kind,
+            state: JobState::Queued,
+            queued_at: Utc::now(),
+            started_at: None,
+            finished_at: None,
+            result: None,
+            error: None,
+        };
+        // Insert first so a worker that picks the job up right away always finds its status.
+        self.statuses.insert(status.id.clone(), status.clone());
+        let job = QueuedJob {
+            id: status.id.clone(),
+            work,
+        };
+        if self.sender.try_send(job).is_err() {
+            self.statuses.remove(&status.id);
+            return Err(QueueFull);
+        }
+        Ok(status)
+    }
+
+    #[must_use]
+    pub fn status(&self, id: &str) -> Option<JobStatus> {
+        self.statuses.get(id).map(|status| status.clone())
+    }
+
+    fn start(&self, id: &str) {
+        if let Some(mut status) = self.statuses.get_mut(id) {
+            status.state = JobState::Running;
+            status.started_at = Some(Utc::now());
+        }
+    }
+
+    fn finish(&self, id: &str, result: anyhow::Result<Value>) {
+        if let Some(mut status) = self.statuses.get_mut(id) {
+            status.finished_at = Some(Utc::now());
+            match result {
+                Ok(value) => {
+                    status.state = JobState::Succeeded;
+                    status.result = Some(value);
+                }
+                Err(error) => {
+                    crate::log_error!("Job {id} ({}) failed: {error:#}", status.kind);
+                    status.state = JobState::Failed;
+                    status.error = Some(format!("{error:#}"));
+                }
+            }
+        }
+        let mut finished = self.finished.lock().unwrap_or_else(PoisonError::into_inner);
+        finished.push_back(id.to_string());
+        while finished.len() > MAX_FINISHED_JOBS {
+            if let Some(oldest) = finished.pop_front() {
+                self.statuses.remove(&oldest);
+            }
+        }
+    }
+}
+
+impl Default for JobQueue {
+    fn default() -> Self {
+        Self::new(JOB_QUEUE_CAPACITY)
+    }
+}
+
+impl std::fmt::Debug for QueuedJob {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("QueuedJob")
+            .field("id", &self.id)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Start the workers that run queued jobs.
+///
+/// Returns no workers if they were already started for this state.
+pub fn spawn_workers(state: &SharedState, count: usize) -> Vec<JoinHandle<()>> {
+    let Some(receiver) = state
+        .jobs()
+        .receiver
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .take()
+    else {
+        return Vec::new();
+    };
+    let receiver = Arc::new(tokio::sync::Mutex::new(receiver));
+    (0..count)
+        .map(|_| {
+            let receiver = Arc::clone(&receiver);
+            let state = Arc::clone(state);
+            tokio::spawn(async move {
+                loop {
+                    let Some(job) = receiver.lock().await.recv().await else {
+                        return;
+                    };
+                    state.jobs().start(&job.id);
+                    // Run in a separate task so a panicking job fails instead of stopping the worker.
+                    let result = tokio::spawn(job.work)
+                        .await
+                        .unwrap_or_else(|error| Err(anyhow::anyhow!("Job panicked: {error}")));
+                    state.jobs().finish(&job.id, result);
+                }
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::time::Duration;
+
+    use axum::body::Body;
+    use axum::http::header::LOCATION;
+    use axum::http::{Request, StatusCode};
+    use serde_json::json;
+
+    use crate::snapshot::Snapshot;
+    use crate::test_utils::TestApp;
+    use crate::types::{AppState, DEFAULT_API_KEY, IdStrategy, Item};
+
+    async fn wait_until_finished(state: &SharedState, id: &str) -> JobStatus {
+        for _ in 0..100 {
+            let status = state.jobs().status(id).expect("job should exist");
+            if matches!(status.state, JobState::Succeeded | JobState::Failed) {
+                return status;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("job {id} did not finish");
+    }
+
+    #[tokio::test]
+    async fn workers_run_jobs_and_record_results() {
+        let state = AppState::new_shared_state();
+        let workers = spawn_workers(&state, 2);
+        assert!(spawn_workers(&state, 2).is_empty());
+
+        let succeeded = state
+            .jobs()
+            .submit(JobKind::Backup, Box::pin(async { Ok(json!({ "written": true })) }))
+            .unwrap();
+        assert_eq!(succeeded.state, JobState::Queued);
+        let failed = state
+            .jobs()
+            .submit(
+                JobKind::Restore,
+                Box::pin(async { Err(anyhow::anyhow!("broken snapshot")) }),
+            )
+            .unwrap();
+        let panicked = state
+            .jobs()
+            .submit(JobKind::Restore, Box::pin(async { panic!("job bug") }))
+            .unwrap();
+
+        let status = wait_until_finished(&state, &succeeded.id).await;
+        assert_eq!(status.state, JobState::Succeeded);
+        assert_eq!(status.result, Some(json!({ "written": true })));
+        assert!(status.started_at.is_some() && status.finished_at.is_some());
+
+        let status = wait_until_finished(&state, &failed.id).await;
+        assert_eq!(status.state, JobState::Failed);
+        assert_eq!(status.error.as_deref(), Some("broken snapshot"));
+
+        let status = wait_until_finished(&state, &panicked.id).await;
+        assert_eq!(status.state, JobState::Failed);
+        assert!(status.error.unwrap().starts_with("Job panicked"));
+
+        for worker in workers {
+            worker.abort();
+        }
+    }
+
+    #[test]
+    fn full_queue_rejects_jobs() {
+        let queue = JobQueue::new(1);
+        let first = queue.submit(JobKind::Backup, Box::pin(async { Ok(Value::Null) }));
+        assert!(first.is_ok());
+        assert_eq!(
+            queue.submit(JobKind::Backup, Box::pin(async { Ok(Value::Null) })),
+            Err(QueueFull)
+        );
+        assert_eq!(queue.statuses.len(), 1);
+    }
+
+    #[test]
+    fn only_recent_finished_jobs_are_kept() {
+        let queue = JobQueue::new(1);
+        let ids: Vec<String> = (0..=MAX_FINISHED_JOBS)
+            .map(|_| {
+                let status = queue
+                    .submit(JobKind::Backup, Box::pin(async { Ok(Value::Null) }))
+                    .unwrap();
+                // Drain the channel so the next submit fits.
+                queue.receiver.lock().unwrap().as_mut().unwrap().try_recv().unwrap();
+                queue.finish(&status.id, Ok(Value::Null));
+                status.id
+            })
+            .collect();
+
+        assert!(queue.status(&ids[0]).is_none());
+        assert_eq!(queue.status(&ids[1]).unwrap().state, JobState::Succeeded);
+        assert_eq!(queue.statuses.len(), MAX_FINISHED_JOBS);
+    }
+
+    #[tokio::test]
+    async fn background_restore_is_polled_through_the_job_route() {
+        let source = AppState::new();
+        source
+            .items()
+            .insert_item(&Item::new_with_random_id("restored".to_string(), IdStrategy::Numeric))
+            .unwrap();
+        let backup = Snapshot::capture(source.items()).to_gzip().unwrap();
+        let app = TestApp::new();
+        let workers = spawn_workers(&app.state, 1);
+        let restore = |body: Vec<u8>| {
+            Request::builder()
+                .method("POST")
+                .uri("/admin/restore?background=true")
+                .header("api-key", DEFAULT_API_KEY)
+                .header("Content-Type", "application/gzip")
+                .body(Body::from(body))
+                .unwrap()
+        };
+
+        let response = app.send(restore(backup)).await;
+        assert_eq!(response.status, StatusCode::ACCEPTED, "{}", response.text());
+        let job: JobStatus = response.json();
+        assert_eq!(job.kind, JobKind::Restore);
+        assert_eq!(response.headers[LOCATION], format!("/api/v1/admin/jobs/{}", job.id));
+        let status = wait_until_finished(&app.state, &job.id).await;
+        assert_eq!(status.state, JobState::Succeeded);
+        assert_eq!(status.result.unwrap()["restored"], 1);
+        assert!(app.state.items().db.contains_key("restored"));
+
+        let response = app.get(&format!("/admin/jobs/{}", job.id)).await;
+        assert_eq!(response.json::<JobStatus>().state, JobState::Succeeded);
+
+        let job: JobStatus = app.send(restore(b"not a snapshot".to_vec())).await.json();
+        let status = wait_until_finished(&app.state, &job.id).await;
+        assert_eq!(status.state, JobState::Failed);
+
+        assert_eq!(app.get("/admin/jobs/missing").await.status, StatusCode::NOT_FOUND);
+        for worker in workers {
+            worker.abort();
+        }
+    }
+
+    #[tokio::test]
+    async fn backup_job_requires_a_backup_directory() {
+        let app = TestApp::new();
+        let response = app.post_json("/admin/backups", &json!({})).await;
+        assert_eq!(response.status, StatusCode::CONFLICT);
+    }
+}
@@ -0,0 +1,192 @@
+//! Kafka publishing of item events.
+//!
+//! With `kafka.brokers` set, every item change is produced as the item event JSON to `kafka.topic`,
+//! keyed by the item name so all events of an item land on the same partition in order.
+//! Clear events have no key and go to any partition.
+//! Changes applied from peers are not produced, so each change is produced once by the instance that made it.
+//! librdkafka queues the messages and retries failed sends in the background,
+//! so a broker that is down does not hold up startup or requests.
+//! Delivery is at most once like the rest of the event bus:
+//! events are skipped when the bus overflows and dropped when the send times out after five minutes.
+
+use std::sync::Arc;
+
+use rdkafka::ClientConfig;
+use rdkafka::producer::{FutureProducer, FutureRecord};
+use tokio::task::JoinHandle;
+
+use crate::events::{Actor, ItemChange, ItemEvent, next_event};
+use crate::settings::SharedSettings;
+use crate::types::SharedState;
+
+/// Partition key of the event, the item name.
+#[must_use]
+pub fn record_key(event: &ItemEvent) -> Option<&str> {
+    match &event.change {
+        ItemChange::Created { item }
+        | ItemChange::Removed { item }
+        | ItemChange::Expired { item }
+        | ItemChange::Evicted { item, .. } => Some(&item.name),
+        ItemChange::Cleared { .. } => None,
+    }
+}
+
+/// Produce the item changes made on this instance to Kafka when `kafka.brokers` is set.
+///
+/// The brokers are only read at startup, the topic is read for every event.
+pub fn spawn_producer(state: &SharedState, settings: &SharedSettings) -> Option<JoinHandle<()>> {
+    let brokers = settings.load().kafka.brokers.join(",");
+    if brokers.is_empty() {
+        return None;
+    }
+    let producer: FutureProducer = match ClientConfig::new()
+        .set("bootstrap.servers", brokers)
+        .set("client.id", env!("CARGO_PKG_NAME"))
+        .create()
+    {
+        Ok(producer) => producer,
+        Err(error) => {
+            crate::log_error!("Failed to create the Kafka producer: {error}");
+            return None;
+        }
+    };
+    let mut receiver = state.events().subscribe();
+    let metrics = state.telemetry().metrics();
+    let settings = Arc::clone(settings);
+    Some(tokio::spawn(async move {
+        while let Some(event) = next_event(&mut receiver, "kafka", &metrics).await {
+            if event.actor == Actor::Replication {
+                continue;
+            }
+            let topic = settings.load().kafka.topic.clone();
+            let payload = match serde_json::to_vec(&event) {
+                Ok(payload) => payload,
+                Err(error) => {
+                    crate::log_error!("Failed to serialize item event for Kafka: {error}");
+                    continue;
+                }
+            };
+            let mut record = FutureRecord::to(&topic).payload(&payload);
+            if let Some(key) = record_key(&event) {
+                record = record.key(key);
+            }
+            match producer.send_result(record) {
+                Ok(delivery) => {
+                    tokio::spawn(async move {
+                        if let Ok(Err((error, message))) = delivery.await {
+                            crate::log_warn!(
+                                "Failed to deliver item event to Kafka topic {}: {error}",
+                                rdkafka::Message::topic(&message)
+                            );
+                        }
+                    });
+                }
+                Err((error, _)) => crate::log_warn!("Failed to queue item event for Kafka topic {topic}: {error}"),
+            }
+        }
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::time::Duration;
+
+    use rdkafka::Message;
+    use rdkafka::consumer::{Consumer, StreamConsumer};
+    use rdkafka::mocking::MockCluster;
+
+    use crate::settings::Settings;
+    use crate::types::{AppState, IdStrategy, Item, ItemId, KafkaConfig};
+
+    fn item(name: &str, id: u64) -> Item {
+        Item::new(name.to_string(), ItemId::Numeric(id), IdStrategy::Numeric).unwrap()
+    }
+
+    #[tokio::test]
+    async fn item_events_are_produced_keyed_by_item_name() {
+        let cluster = MockCluster::new(1).unwrap();
+        cluster.create_topic("test.items", 3, 1).unwrap();
+        let settings = Settings {
+            kafka: KafkaConfig {
+                brokers: vec![cluster.bootstrap_servers()],
+                topic: "test.items".to_string(),
+            },
+            ..Settings::default()
+        }
+        .into_shared();
+        let state = AppState::new_shared_state();
+        let producer = spawn_producer(&state, &settings).expect("producer with brokers");
+
+        state.publish(
+            Actor::Replication,
+            ItemChange::Created {
+                item: item("first", 1001),
+            },
+        );
+        state.publish(
+            Actor::Client,
+            ItemChange::Created {
+                item: item("first", 1001),
+            },
+        );
+        state.publish(
+            Actor::Client,
+            ItemChange::Created {
+                item: item("second", 1002),
+            },
+        );
+        state.publish(
+            Actor::Admin,
+            ItemChange::Removed {
+                item: item("first", 1001),
+            },
+        );
+        state.publish(Actor::Admin, ItemChange::Cleared { count: 1 });
+
+        let consumer: StreamConsumer = ClientConfig::new()
+            .set("bootstrap.servers", cluster.bootstrap_servers())
+            .set("group.id", "test")
+            .set("auto.offset.reset", "earliest")
+            .create()
+            .unwrap();
+        consumer.subscribe(&["test.items"]).unwrap();
+        let mut messages = Vec::new();
+        while messages.len() < 4 {
+            let message = tokio::time::timeout(Duration::from_secs(30), consumer.recv())
+                .await
+                .expect("event should be produced")
+                .unwrap();
+            let key = message.key().map(|key| String::from_utf8(key.to_vec()).unwrap());
+            let event: ItemEvent = serde_json::from_slice(message.payload().unwrap()).unwrap();
+            messages.push((key, message.partition(), event));
+        }
+        // Stop the producer before the mock cluster goes away so it does not log connection errors.
+        producer.abort();
+        producer.await.ok();
+
+        let first: Vec<_> = messages
+            .iter()
+            .filter(|(key, ..)| key.as_deref() == Some("first"))
+            .collect();
+        assert_eq!(first.len(), 2);
+        assert_eq!(first[0].1, first[1].1, "events of an item share a partition");
+        assert!(matches!(first[0].2.change, ItemChange::Created { .. }));
+        assert_eq!(first[0].2.actor, Actor::Client);
+        assert!(matches!(first[1].2.change, ItemChange::Removed { .. }));
+        assert!(messages.iter().any(|(key, ..)| key.as_deref() == Some("second")));
+        assert!(
+            messages
+                .iter()
+                .any(|(key, _, event)| key.is_none() && matches!(event.change, ItemChange::Cleared { count: 1 }))
+        );
+    }
+
+    #[test]
+    fn producer_is_off_without_brokers() {
+        let state = AppState::new_shared_state();
+
+        assert!(spawn_producer(&state, &Settings::default().into_shared()).is_none());
+    }
+}
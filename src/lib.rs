@@ -0,0 +1,76 @@
+//! Axum API example library.
+//!
+//! Contains the router, handlers, middleware, settings and application state,
+//! so integration tests and other services can build and embed the app without spawning the binary.
+//! The `axum-example` binary only parses CLI arguments and runs the server.
+
+// Errors carry `anyhow` context that describes the failure,
+// and panics are limited to startup invariants, so the doc sections would only repeat the summaries.
+#![allow(clippy::missing_errors_doc, clippy::missing_panics_doc)]
+
+pub mod allowed_methods;
+pub mod backend;
+pub mod backup;
+pub mod bench;
+pub mod body_logging;
+pub mod cache;
+pub mod client_command;
+pub mod clock;
+pub mod config_template;
+pub mod deprecation;
+pub mod docs_auth;
+pub mod envelope;
+pub mod events;
+pub mod eviction;
+pub mod expiry;
+pub mod file_config;
+pub mod history;
+pub mod i18n;
+pub mod inflight;
+pub mod ip_filter;
+pub mod jobs;
+pub mod kafka;
+pub mod lifecycle;
+pub mod lock;
+pub mod logging;
+pub mod middleware;
+pub mod mqtt;
+pub mod nats;
+pub mod negotiation;
+pub mod openapi;
+pub mod persistence;
+pub mod precomputed;
+pub mod precondition;
+pub mod problem;
+pub mod rate_limit;
+pub mod recording;
+pub mod replication;
+pub mod request_id;
+pub mod route_table;
+pub mod router;
+pub mod scheduler;
+pub mod schemas;
+pub mod secrets;
+pub mod seed;
+pub mod server;
+pub mod settings;
+pub mod slow_request;
+pub mod snapshot;
+pub mod spec_diff;
+#[cfg(feature = "sqs")]
+pub mod sqs;
+pub mod telemetry;
+pub mod tenant;
+#[cfg(test)]
+pub mod test_utils;
+pub mod types;
+pub mod utils;
+pub mod validation;
+pub mod version;
+pub mod webhooks;
+#[cfg(windows)]
+pub mod windows;
+pub mod routing {
+    pub mod admin;
+    pub mod routes;
+}
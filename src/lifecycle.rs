@@ -0,0 +1,146 @@
+//! Readiness and shutdown control.
+//!
+//! `POST /admin/drain` and termination signals mark the instance as draining so `/readyz` fails
+//! and the load balancer stops routing new traffic here,
+//! then trigger the graceful shutdown once the drain delay has passed.
+//!
+//! Shutdown then runs in phases:
+//! the listeners stop accepting and open connections get `server.shutdown_timeout_seconds` to finish,
+//! scheduled tasks finish a run in progress and stop while the other background tasks are stopped right away,
+//! and finally the state file is written.
+//! While shutting down, the requests still in flight are logged every few seconds.
+//! A second termination signal, or `server.hard_shutdown_timeout_seconds` passing after the first,
+//! exits right away without waiting for the remaining phases.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use futures_util::future::join_all;
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+
+use crate::inflight::InflightRequests;
+use crate::schemas::InflightRequest;
+
+/// How often the requests still in flight are logged during shutdown.
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Shared readiness state and shutdown trigger for the server.
+#[derive(Debug, Default)]
+pub struct Lifecycle {
+    draining: AtomicBool,
+    shutdown: CancellationToken,
+}
+
+impl Lifecycle {
+    /// Check if the instance should receive new traffic.
+    pub fn is_ready(&self) -> bool {
+        !self.draining.load(Ordering::Relaxed)
+    }
+
+    /// Token that is cancelled when the server should shut down.
+    pub fn shutdown_token(&self) -> CancellationToken {
+        self.shutdown.clone()
+    }
+
+    /// Mark the instance as draining and shut down after the delay.
+    ///
+    /// The delay gives the load balancer time to notice the failing readiness check.
+    /// Returns false if a drain was already started.
+    pub fn start_drain(&self, delay: Duration) -> bool {
+        if self.draining.swap(true, Ordering::Relaxed) {
+            return false;
+        }
+        let shutdown = self.shutdown.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(delay).await;
+            crate::log_info!("Drain delay passed, shutting down");
+            shutdown.cancel();
+        });
+        true
+    }
+}
+
+/// Wait for the tasks to end on their own and abort those still running after the timeout.
+pub async fn stop_tasks(tasks: Vec<JoinHandle<()>>, timeout: Duration) {
+    let abort_handles: Vec<_> = tasks.iter().map(JoinHandle::abort_handle).collect();
+    if tokio::time::timeout(timeout, join_all(tasks)).await.is_err() {
+        crate::log_warn!(
+            "Background tasks still running {} seconds after shutdown, stopping them",
+            timeout.as_secs()
+        );
+        for handle in abort_handles {
+            handle.abort();
+        }
+    }
+}
+
+/// Log the requests still in flight every few seconds. Runs until cancelled.
+pub async fn log_shutdown_progress(inflight: &InflightRequests) {
+    let mut interval = tokio::time::interval(PROGRESS_INTERVAL);
+    interval.tick().await;
+    loop {
+        interval.tick().await;
+        if let Some(message) = progress_message(&inflight.snapshot()) {
+            crate::log_info!("{message}");
+        }
+    }
+}
+
+/// Describe the remaining requests and the one that has been running longest.
+fn progress_message(requests: &[InflightRequest]) -> Option<String> {
+    let oldest = requests.iter().max_by_key(|request| request.elapsed_ms)?;
+    Some(format!(
+        "Waiting for {} in-flight requests, oldest is {} {} running for {} ms",
+        requests.len(),
+        oldest.method,
+        oldest.path,
+        oldest.elapsed_ms
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn drain_fails_readiness_and_then_shuts_down() {
+        let lifecycle = Lifecycle::default();
+        let shutdown = lifecycle.shutdown_token();
+        assert!(lifecycle.is_ready());
+
+        assert!(lifecycle.start_drain(Duration::from_millis(10)));
+        assert!(!lifecycle.is_ready());
+        assert!(!lifecycle.start_drain(Duration::ZERO));
+
+        tokio::time::timeout(Duration::from_secs(1), shutdown.cancelled())
+            .await
+            .expect("drain should trigger shutdown");
+    }
+
+    #[tokio::test]
+    async fn stopping_tasks_aborts_those_that_do_not_end() {
+        let finished = tokio::spawn(async {});
+        let stuck = tokio::spawn(std::future::pending::<()>());
+        let stuck_handle = stuck.abort_handle();
+        stop_tasks(vec![finished, stuck], Duration::from_millis(10)).await;
+        tokio::task::yield_now().await;
+        assert!(stuck_handle.is_finished());
+    }
+
+    #[test]
+    fn progress_names_the_oldest_request() {
+        assert_eq!(progress_message(&[]), None);
+        let request = |id, path: &str, elapsed_ms| InflightRequest {
+            id,
+            method: "GET".to_string(),
+            path: path.to_string(),
+            started_at: chrono::Utc::now(),
+            elapsed_ms,
+        };
+        assert_eq!(
+            progress_message(&[request(1, "/items", 200), request(2, "/slow", 7000)]).as_deref(),
+            Some("Waiting for 2 in-flight requests, oldest is GET /slow running for 7000 ms")
+        );
+    }
+}
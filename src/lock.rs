@@ -0,0 +1,116 @@
+//! Locks for destructive admin operations.
+//!
+//! Replicas that share a backing store share its items,
+//! so `DELETE /admin/clear_items` and `POST /admin/restore` running on two replicas at once would interleave.
+//! Both take the [`ITEMS_LOCK`] in the backend first,
+//! and a request that finds it held by another replica gets `409 Conflict` instead of waiting.
+//! The `directory` backend keeps a lock as a file that is only created if it does not exist,
+//! and the `dynamodb` backend as a conditionally written table entry.
+//! Locks expire after [`LOCK_TTL`], so a lock left behind by a replica that stopped is taken over.
+//! Without a backend every replica has its own items and no lock is taken.
+
+use std::time::Duration;
+
+use ulid::Ulid;
+
+use crate::i18n;
+use crate::schemas::{ApiError, ErrorCode};
+use crate::types::SharedState;
+
+/// Lock held while replacing or removing the items of the default collection.
+pub const ITEMS_LOCK: &str = "items";
+
+/// How long a lock is held at most.
+pub const LOCK_TTL: Duration = Duration::from_mins(5);
+
+/// Run the operation while holding the named lock in the backend, if one is configured.
+///
+/// The lock is released after the operation, or expires if the request is cancelled before that.
+pub async fn with_lock<T>(
+    state: &SharedState,
+    name: &str,
+    operation: impl Future<Output = Result<T, ApiError>>,
+) -> Result<T, ApiError> {
+    let Some(backend) = state.backend() else {
+        return operation.await;
+    };
+    let owner = Ulid::generate().to_string();
+    // The backend requests are boxed to keep the futures of the locked handlers small.
+    if !Box::pin(backend.try_lock(name, &owner, LOCK_TTL)).await? {
+        crate::log_warn!("Lock {name} is held by another instance");
+        return Err(ApiError::new(
+            ErrorCode::Conflict,
+            i18n::message("lock_held", &[("name", &name)]),
+        ));
+    }
+    let result = operation.await;
+    if let Err(error) = Box::pin(backend.unlock(name, &owner)).await {
+        crate::log_error!(
+            "Failed to release lock {name}, it expires in {} seconds: {error:#}",
+            LOCK_TTL.as_secs()
+        );
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::path::PathBuf;
+    use std::sync::Arc;
+
+    use axum::http::StatusCode;
+    use serde_json::json;
+
+    use crate::backend::Backend;
+    use crate::settings::Settings;
+    use crate::test_utils::TestApp;
+    use crate::types::AppState;
+
+    fn backend_app(name: &str) -> (TestApp, PathBuf) {
+        let directory = std::env::temp_dir().join(format!("axum-example-lock-{}-{name}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let state = AppState::new().with_backend(Some(Backend::directory(directory.clone(), Duration::ZERO)));
+        (TestApp::with_state(Arc::new(state), Settings::default()), directory)
+    }
+
+    #[tokio::test]
+    async fn held_lock_rejects_clearing_items() {
+        let (app, directory) = backend_app("held");
+        let backend = app.state.backend().expect("backend");
+        app.create_item(json!({ "name": "locked" })).await;
+
+        assert!(backend.try_lock(ITEMS_LOCK, "other", LOCK_TTL).await.unwrap());
+        assert!(!backend.try_lock(ITEMS_LOCK, "another", LOCK_TTL).await.unwrap());
+        let response = app.delete("/admin/clear_items").await;
+        assert_eq!(response.status, StatusCode::CONFLICT);
+        assert_eq!(
+            response.json::<ApiError>().message,
+            "Another instance holds the items lock, try again later"
+        );
+        assert!(backend.get("locked").await.unwrap().is_some());
+
+        // Only the owner releases the lock.
+        backend.unlock(ITEMS_LOCK, "another").await.unwrap();
+        assert_eq!(app.delete("/admin/clear_items").await.status, StatusCode::CONFLICT);
+        backend.unlock(ITEMS_LOCK, "other").await.unwrap();
+        assert_eq!(app.delete("/admin/clear_items").await.status, StatusCode::OK);
+        assert!(backend.get("locked").await.unwrap().is_none());
+        assert!(backend.try_lock(ITEMS_LOCK, "other", LOCK_TTL).await.unwrap());
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[tokio::test]
+    async fn expired_lock_is_taken_over() {
+        let (app, directory) = backend_app("expired");
+        let backend = app.state.backend().expect("backend");
+        assert!(backend.try_lock(ITEMS_LOCK, "stopped", Duration::ZERO).await.unwrap());
+        assert!(backend.try_lock(ITEMS_LOCK, "running", LOCK_TTL).await.unwrap());
+        assert!(!backend.try_lock(ITEMS_LOCK, "stopped", LOCK_TTL).await.unwrap());
+
+        let result = with_lock(&app.state, "other", async { Ok(1) }).await;
+        assert_eq!(result.unwrap(), 1);
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+}
@@ -2,10 +2,29 @@
 //!
 //! The helper macros attach build metadata to each event so JSON logs can be
 //! correlated with the exact binary version that emitted them.
+//! Panics are logged through the same subscriber with their backtrace.
+//! Stdout logs are pretty, JSON or logfmt by the `log_format` setting.
+//! With `log_file.directory` set, the same events are also written as JSON lines
+//! to files in that directory that are rotated and pruned by the `log_file` settings,
+//! for hosts without a log shipper collecting stdout.
 
-use tracing_subscriber::EnvFilter;
+use std::backtrace::Backtrace;
+use std::panic::PanicHookInfo;
 
-use crate::types::LogLevel;
+use anyhow::{Context, Result};
+use tracing_appender::non_blocking::WorkerGuard;
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use tracing_subscriber::fmt::MakeWriter;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{EnvFilter, Layer};
+
+use crate::types::{LogFileConfig, LogFormat, LogLevel, LogRotation};
+use crate::version;
+
+/// Extension of the log files, which are named `<package>.<date>.log`.
+const LOG_FILE_SUFFIX: &str = "log";
 
 /// Log at DEBUG level with compile-time build metadata attached.
 #[macro_export]
@@ -103,38 +122,191 @@ macro_rules! log_error {
     };
 }
 
-/// Initialize tracing logging.
-pub fn initialize_logging(log_level: Option<&LogLevel>, use_json_format: bool) {
+/// Initialize tracing logging to stdout, and to rotated files if a log directory is configured.
+///
+/// Events for the files are written on a background thread,
+/// keep the returned guard until exit so the last ones are flushed.
+pub fn initialize_logging(
+    log_level: Option<&LogLevel>,
+    log_format: LogFormat,
+    log_file: &LogFileConfig,
+) -> Result<Option<WorkerGuard>> {
     let mut filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
     if let Some(level) = log_level {
         filter = filter.add_directive(level.to_filter().into());
     }
 
-    if use_json_format {
-        tracing_subscriber::fmt()
+    let stdout = format_layer(log_format, std::io::stdout);
+    let (file, guard) = match file_appender(log_file)?.map(tracing_appender::non_blocking) {
+        Some((writer, guard)) => {
+            let layer = tracing_subscriber::fmt::layer()
+                .json()
+                .flatten_event(true)
+                .with_span_list(false)
+                .with_target(true)
+                .with_level(true)
+                .with_ansi(false)
+                .with_writer(writer);
+            (Some(layer), Some(guard))
+        }
+        None => (None, None),
+    };
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(stdout)
+        .with(file)
+        .try_init()
+        .context("Failed to initialize logging")?;
+    if let Some(directory) = &log_file.directory {
+        crate::log_info!(
+            "Writing log files to {} with {} rotation",
+            directory.display(),
+            log_file.rotation
+        );
+    }
+    std::panic::set_hook(Box::new(log_panic));
+    Ok(guard)
+}
+
+/// Layer that writes events in the given format.
+///
+/// Timestamps are left out, since the runtime or log shipper that collects the output adds them.
+fn format_layer<S, W>(log_format: LogFormat, writer: W) -> Box<dyn Layer<S> + Send + Sync>
+where
+    S: tracing::Subscriber + for<'a> LookupSpan<'a>,
+    W: for<'writer> MakeWriter<'writer> + Send + Sync + 'static,
+{
+    match log_format {
+        LogFormat::Pretty => tracing_subscriber::fmt::layer()
+            .with_target(true)
+            .with_ansi(true)
+            .with_level(true)
+            .with_file(true)
+            .with_line_number(true)
+            .with_writer(writer)
+            .boxed(),
+        LogFormat::Json => tracing_subscriber::fmt::layer()
             .json()
             .flatten_event(true)
             .with_span_list(false)
-            .with_env_filter(filter)
             .with_target(true)
             .with_level(true)
             .with_ansi(false)
             .without_time()
-            .init();
-    } else {
-        tracing_subscriber::fmt()
-            .with_env_filter(filter)
+            .with_writer(writer)
+            .boxed(),
+        LogFormat::Logfmt => tracing_logfmt::builder()
             .with_target(true)
-            .with_ansi(true)
             .with_level(true)
-            .with_file(true)
-            .with_line_number(true)
-            .init();
+            .with_timestamp(false)
+            .layer()
+            .with_writer(writer)
+            .boxed(),
     }
 }
 
+/// Rolling appender for the log directory, which is created if it does not exist.
+fn file_appender(log_file: &LogFileConfig) -> Result<Option<RollingFileAppender>> {
+    let Some(directory) = &log_file.directory else {
+        return Ok(None);
+    };
+    let rotation = match log_file.rotation {
+        LogRotation::Hourly => Rotation::HOURLY,
+        LogRotation::Daily => Rotation::DAILY,
+        LogRotation::Never => Rotation::NEVER,
+    };
+    RollingFileAppender::builder()
+        .rotation(rotation)
+        .filename_prefix(version::PACKAGE_NAME)
+        .filename_suffix(LOG_FILE_SUFFIX)
+        .max_log_files(log_file.max_files)
+        .build(directory)
+        .map(Some)
+        .with_context(|| format!("Failed to open log directory {}", directory.display()))
+}
+
+/// Log a panic with its location, backtrace and the id of the request being handled.
+fn log_panic(info: &PanicHookInfo) {
+    let message = info
+        .payload()
+        .downcast_ref::<String>()
+        .map(String::as_str)
+        .or_else(|| info.payload().downcast_ref::<&str>().copied())
+        .unwrap_or("unknown panic");
+    let location = info
+        .location()
+        .map_or_else(String::new, |location| format!(" at {location}"));
+    crate::log_error!(
+        request_id = crate::request_id::current(),
+        backtrace = %Backtrace::force_capture(),
+        "Panicked{location}: {message}"
+    );
+}
+
 #[cfg(test)]
 mod tests {
+    use super::*;
+
+    use std::io::Write;
+    use std::sync::{Arc, Mutex};
+
+    use tracing_subscriber::Registry;
+
+    /// Writer that collects the formatted events.
+    #[derive(Clone, Default)]
+    struct Output(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Output {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn formatted(log_format: LogFormat) -> String {
+        let output = Output::default();
+        let writer = output.clone();
+        let subscriber = Registry::default().with(format_layer(log_format, move || writer.clone()));
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info!(target: "example", status = 200, "request done");
+        });
+        String::from_utf8(output.0.lock().unwrap().clone()).unwrap()
+    }
+
+    #[test]
+    fn events_are_formatted_by_log_format() {
+        assert_eq!(
+            formatted(LogFormat::Logfmt),
+            "level=info target=example message=\"request done\" status=200\n"
+        );
+        let json: serde_json::Value = serde_json::from_str(&formatted(LogFormat::Json)).unwrap();
+        assert_eq!(json["message"], "request done");
+        assert_eq!(json["status"], 200);
+        assert!(formatted(LogFormat::Pretty).contains("request done"));
+    }
+
+    #[test]
+    fn file_appender_writes_to_the_log_directory() {
+        assert!(file_appender(&LogFileConfig::default()).unwrap().is_none());
+
+        let directory = std::env::temp_dir().join(format!("axum-example-logs-{}", std::process::id()));
+        let log_file = LogFileConfig {
+            directory: Some(directory.clone()),
+            rotation: LogRotation::Never,
+            max_files: 2,
+        };
+        let mut appender = file_appender(&log_file).unwrap().expect("appender");
+        appender.write_all(b"{\"message\":\"logged\"}\n").unwrap();
+        appender.flush().unwrap();
+
+        let path = directory.join(format!("{}.{LOG_FILE_SUFFIX}", version::PACKAGE_NAME));
+        assert_eq!(std::fs::read_to_string(path).unwrap(), "{\"message\":\"logged\"}\n");
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+
     #[test]
     fn logging_macros_accept_structured_fields() {
         crate::log_debug!(route = "/health", "debug log");
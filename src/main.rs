@@ -3,93 +3,608 @@
 //! Parses CLI arguments, configures logging, builds shared application state,
 //! and runs the Axum HTTP server with graceful shutdown.
 
-mod logging;
-mod middleware;
-mod openapi;
-mod router;
-mod schemas;
-mod telemetry;
-mod types;
-mod utils;
-mod version;
-mod routing {
-    pub mod admin;
-    pub mod routes;
-}
-
+use std::io::Write;
 use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use axum::Extension;
+use axum::middleware::from_fn_with_state;
+use clap::{CommandFactory, Parser, Subcommand};
+use clap_complete::Shell;
+use futures_util::future::try_join_all;
+use listenfd::ListenFd;
+use tokio::net::TcpListener;
+use tokio::runtime::Runtime;
 
-use anyhow::Result;
-use clap::Parser;
+use axum_example::backend::Backend;
+use axum_example::bench::{self, BenchArgs};
+use axum_example::client_command::{self, ClientArgs};
+use axum_example::logging::initialize_logging;
+use axum_example::openapi::{SpecFormat, api_doc, render_spec};
+use axum_example::recording::{self, RequestRecorder, recording_middleware};
+use axum_example::route_table::RouteTable;
+use axum_example::router::{build_router, legacy_route_deprecations};
+use axum_example::schemas::VERSION_INFO;
+use axum_example::secrets::{self, SecretStore};
+use axum_example::settings::{self, CliSettings, CliTlsSettings, Settings, SettingsLoader, SharedSettings};
+use axum_example::spec_diff;
+use axum_example::types::{
+    AppState, Environment, HttpProtocol, IdStrategy, LogFormat, LogLevel, ServerConfig, SharedState, generate_api_key,
+};
+use axum_example::{
+    config_template, events, file_config, jobs, kafka, lifecycle, log_info, log_warn, mqtt, nats, persistence,
+    replication, scheduler, seed, server, utils, version, webhooks,
+};
 
-use crate::logging::initialize_logging;
-use crate::router::build_router;
-use crate::schemas::VERSION_INFO;
-use crate::types::{AppState, Config, Environment, LogLevel};
+/// Output path that means stdout.
+const STDOUT_PATH: &str = "-";
+
+/// Exit code when a second signal or the hard timeout cuts the shutdown short.
+const FORCED_EXIT_CODE: i32 = 1;
 
 #[derive(Parser)]
-#[command(author, about, arg_required_else_help = false, disable_version_flag = true)]
+#[command(
+    author,
+    about,
+    arg_required_else_help = false,
+    disable_version_flag = true,
+    args_conflicts_with_subcommands = true
+)]
 struct Args {
-    /// Optional host IP to listen to (for example "0.0.0.0")
-    #[arg(short = 'H', long, value_name = "IP", env = "HOST")]
-    host: Option<String>,
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Server options when no subcommand is given
+    #[command(flatten)]
+    serve: ServeArgs,
+
+    /// Config file with settings that are reloaded on change [default: axum-example.toml]
+    #[arg(short, long, global = true, value_name = "PATH", env = "CONFIG_FILE")]
+    config: Option<PathBuf>,
 
     /// Log level to use
-    #[arg(short, long, value_enum, value_name = "LEVEL", default_value = "info")]
+    #[arg(short, long, global = true, value_enum, value_name = "LEVEL", default_value = "info")]
     log: Option<LogLevel>,
 
-    /// Optional port number to use
-    #[arg(short, long, value_name = "PORT", default_value_t = 3000, env = "PORT")]
-    port: u16,
+    // Without a path the spec is printed to stdout, which is also what "-" means
+    #[arg(
+        long,
+        value_name = "PATH",
+        num_args = 0..=1,
+        default_missing_value = STDOUT_PATH,
+        conflicts_with = "openapi_yaml",
+        help = "Write the OpenAPI spec as JSON to the file or stdout and exit"
+    )]
+    openapi_json: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "PATH",
+        num_args = 0..=1,
+        default_missing_value = STDOUT_PATH,
+        help = "Write the OpenAPI spec as YAML to the file or stdout and exit"
+    )]
+    openapi_yaml: Option<PathBuf>,
+
+    /// Print the routes with their auth and timeout and exit
+    #[arg(long)]
+    print_routes: bool,
 
     // Custom version flag instead of clap default
     #[arg(short, long, help = "Print version info and exit")]
     version: bool,
 }
 
-#[tokio::main]
-async fn main() -> Result<()> {
+#[derive(Subcommand)]
+enum Command {
+    /// Run the HTTP server (default)
+    Serve(ServeArgs),
+
+    /// Run the HTTP server as a Windows service started by the service control manager
+    #[cfg(windows)]
+    WindowsService(ServeArgs),
+
+    /// Parse and print the effective config and exit
+    CheckConfig,
+
+    /// Write a commented config file with every setting and its default and exit
+    GenerateConfig {
+        /// Output file, which must not exist yet [default: stdout]
+        path: Option<PathBuf>,
+    },
+
+    /// Generate a random api key and print how to configure it
+    GenerateKey,
+
+    /// Print shell completions and exit
+    Completions {
+        /// Shell to complete in
+        shell: Shell,
+    },
+
+    // Plain about text since backticks would show up in the help output
+    #[command(about = "Write the OpenAPI spec to a file and exit")]
+    ExportOpenapi {
+        /// Output file path, written as YAML for a .yaml or .yml extension and JSON otherwise
+        path: PathBuf,
+    },
+
+    #[command(about = "Compare the OpenAPI spec against a JSON snapshot and fail on removed operations or schemas")]
+    DiffOpenapi {
+        /// Spec snapshot written by export-openapi
+        path: PathBuf,
+    },
+
+    /// Send a mix of create, query and list requests and report latency percentiles
+    Bench(BenchArgs),
+
+    /// Call a running server and print the response
+    Client(ClientArgs),
+
+    /// Send requests recorded with the record file through the router and report status changes
+    Replay {
+        /// Record file written by a server with `record_file` set
+        path: PathBuf,
+    },
+}
+
+/// Server options, which override the config file and environment variables.
+#[derive(clap::Args)]
+struct ServeArgs {
+    /// Optional host IP to listen to (for example "0.0.0.0"), can be given multiple times.
+    /// Use "IP:PORT" to listen on a different port for that address.
+    #[arg(short = 'H', long, value_name = "IP", value_delimiter = ',')]
+    host: Vec<String>,
+
+    /// HTTP protocol versions to accept [default: auto]
+    #[arg(long, value_enum, value_name = "PROTOCOL")]
+    http: Option<HttpProtocol>,
+
+    /// Format of the stdout logs [default: pretty locally, json elsewhere]
+    #[arg(long, value_enum, value_name = "FORMAT")]
+    log_format: Option<LogFormat>,
+
+    /// Port number to use [default: 3000]
+    #[arg(short, long, value_name = "PORT")]
+    port: Option<u16>,
+
+    /// PEM certificate chain to serve HTTPS with, needs --tls-key
+    #[arg(long, value_name = "FILE", requires = "tls_key")]
+    tls_cert: Option<PathBuf>,
+
+    /// PEM private key of the TLS certificate, needs --tls-cert
+    #[arg(long, value_name = "FILE", requires = "tls_cert")]
+    tls_key: Option<PathBuf>,
+
+    /// Async worker threads [default: number of CPU cores]
+    #[arg(long, value_name = "N")]
+    workers: Option<NonZeroUsize>,
+
+    /// Maximum threads for blocking tasks [default: 512]
+    #[arg(long, value_name = "N")]
+    blocking_threads: Option<NonZeroUsize>,
+
+    /// Run the startup steps without serving and exit with an error if any of them fails
+    #[arg(long)]
+    check: bool,
+}
+
+impl From<ServeArgs> for CliSettings {
+    fn from(args: ServeArgs) -> Self {
+        Self {
+            host: args.host,
+            port: args.port,
+            http: args.http,
+            log_format: args.log_format,
+            tls: CliTlsSettings {
+                cert: args.tls_cert,
+                key: args.tls_key,
+            },
+        }
+    }
+}
+
+fn main() -> Result<()> {
     let args = Args::parse();
     if args.version {
         println!("{}", version::VERSION_STRING);
         return Ok(());
     }
+    let config_path = file_config::config_path(args.config);
+    if let Some(path) = args.openapi_json {
+        return dump_openapi(SpecFormat::Json, &path, configured_id_strategy(config_path));
+    }
+    if let Some(path) = args.openapi_yaml {
+        return dump_openapi(SpecFormat::Yaml, &path, configured_id_strategy(config_path));
+    }
+
+    if args.print_routes {
+        let settings = SettingsLoader::new(config_path, CliSettings::default()).load()?;
+        print!("{}", RouteTable::new(&settings));
+        return Ok(());
+    }
 
-    let run_environment = Environment::from_env();
-    let use_json_logging = run_environment != Environment::Local;
-    initialize_logging(args.log.as_ref(), use_json_logging);
+    match args.command.unwrap_or(Command::Serve(args.serve)) {
+        Command::Serve(serve_args) => serve(args.log.as_ref(), config_path, serve_args),
+        #[cfg(windows)]
+        Command::WindowsService(serve_args) => {
+            axum_example::windows::run(move || serve(args.log.as_ref(), config_path, serve_args))
+        }
+        Command::CheckConfig => {
+            let settings = SettingsLoader::new(config_path.clone(), CliSettings::default()).load()?;
+            println!("# Config file: {}", config_path.display());
+            print!("{}", settings.to_toml_redacted()?);
+            Ok(())
+        }
+        Command::GenerateConfig { path } => generate_config(path.as_deref()),
+        Command::Completions { shell } => {
+            print!("{}", completions(shell));
+            Ok(())
+        }
+        Command::GenerateKey => {
+            print!("{}", api_key_snippet(&generate_api_key()));
+            Ok(())
+        }
+        Command::ExportOpenapi { path } => {
+            dump_openapi(SpecFormat::from_path(&path), &path, configured_id_strategy(config_path))
+        }
+        Command::DiffOpenapi { path } => {
+            let spec = api_doc(configured_id_strategy(config_path), &legacy_route_deprecations());
+            let diff = spec_diff::diff_snapshot(&path, &spec)?;
+            print!("{diff}");
+            let breaking = diff.breaking().count();
+            anyhow::ensure!(breaking == 0, "{breaking} breaking OpenAPI changes");
+            Ok(())
+        }
+        Command::Bench(bench_args) => {
+            let settings = SettingsLoader::new(config_path, CliSettings::default()).load()?;
+            let report = build_runtime(None, None)?.block_on(bench::run(&bench_args, settings))?;
+            print!("{report}");
+            Ok(())
+        }
+        Command::Client(client_args) => {
+            let settings = SettingsLoader::new(config_path, CliSettings::default()).load()?;
+            let output = build_runtime(None, None)?.block_on(client_command::run(client_args, &settings))?;
+            println!("{output}");
+            Ok(())
+        }
+        Command::Replay { path } => {
+            let settings = SettingsLoader::new(config_path, CliSettings::default()).load()?;
+            let report = build_runtime(None, None)?.block_on(recording::replay(&path, settings))?;
+            print!("{report}");
+            Ok(())
+        }
+    }
+}
 
-    log_info!("Starting {} {}", version::PACKAGE_NAME, run_environment);
-    if use_json_logging {
+/// Start logging and run the server, or only the startup check with `--check`.
+fn serve(log: Option<&LogLevel>, config_path: PathBuf, serve_args: ServeArgs) -> Result<()> {
+    let runtime = build_runtime(serve_args.workers, serve_args.blocking_threads)?;
+    let check = serve_args.check;
+    let loader = SettingsLoader::new(config_path, serve_args.into());
+    // The log settings are needed before logging starts, the rest is loaded again with secrets.
+    let settings = loader.load()?;
+    let _log_guard = initialize_logging(log, settings.log_format, &settings.log_file)?;
+
+    log_info!("Starting {} {}", version::PACKAGE_NAME, settings.env);
+    if settings.log_format == LogFormat::Pretty {
+        log_info!("{}", VERSION_INFO.to_string_pretty());
+    } else {
         log_info!("{}", VERSION_INFO);
+    }
+
+    if check {
+        runtime.block_on(check_startup(loader))
     } else {
-        log_info!("{}", VERSION_INFO.to_string_pretty());
+        runtime.block_on(run_server(loader))
     }
+}
 
-    run_server(args).await
+/// Run the startup steps that can fail without serving, as a deployment preflight check.
+///
+/// Settings are parsed, secrets fetched, the backend reached, the state and seed files read,
+/// and every listen address bound and released again.
+async fn check_startup(loader: SettingsLoader) -> Result<()> {
+    let secrets = Arc::new(SecretStore::connect(&loader.load()?.secrets).await?);
+    let settings = loader.with_secrets(secrets).load()?;
+    log_info!("Settings and secrets loaded");
+    if let Some(backend) = Backend::connect(&settings.backend).await? {
+        backend.check().await?;
+        log_info!("Backend is reachable");
+    }
+    let state = AppState::new();
+    if let Some(path) = &settings.state_file {
+        persistence::load(&state, path)?;
+    }
+    seed::load(&state, &settings)?;
+    let tls_files = settings.tls.files()?;
+    if let Some((cert, key)) = tls_files {
+        server::tls_acceptor(cert, key, settings.http)?;
+        log_info!("TLS certificate and key are valid");
+    }
+    let redirect_port = tls_files.and(settings.tls.redirect_port);
+    for port in std::iter::once(settings.port).chain(redirect_port) {
+        for address in get_addresses(settings.host.clone(), port) {
+            let listener = server::bind(address, settings.server.reuse_port)
+                .with_context(|| format!("Failed to bind {address}"))?;
+            log_info!("Address {} is available", listener.local_addr()?);
+        }
+    }
+    log_info!("Startup check passed");
+    Ok(())
 }
 
 /// Set up application state, spawn background tasks and run the HTTP server.
-async fn run_server(args: Args) -> Result<()> {
-    let shared_state = AppState::new_shared_state_from_env()?;
-    let config = Arc::new(Config::new_from_env());
+async fn run_server(loader: SettingsLoader) -> Result<()> {
+    let secrets = Arc::new(SecretStore::connect(&loader.load()?.secrets).await?);
+    let loader = loader.with_secrets(Arc::clone(&secrets));
+    let settings = loader.load()?.into_shared();
+    let startup_settings = settings.load_full();
+    let backend = Backend::connect(&startup_settings.backend).await?;
+    if let Some(provider) = startup_settings.backend.provider {
+        log_info!("Caching items over the {provider} backend");
+    }
+    let shared_state = AppState::new_shared_state_from_env(
+        &startup_settings.storage,
+        &startup_settings.deterministic,
+        legacy_route_deprecations(),
+        backend,
+    )?;
+    if startup_settings.deterministic.is_enabled() {
+        log_info!("Deterministic mode: {:?}", startup_settings.deterministic);
+    }
+    let state_file = startup_settings.state_file.clone();
+    let restored = match &state_file {
+        Some(path) => persistence::load(&shared_state, path)?,
+        None => None,
+    };
+    if restored.is_none() {
+        seed::load(&shared_state, &settings.load())?;
+    }
+    let _settings_watcher = settings::watch(loader.clone(), Arc::clone(&settings))?;
+    let scheduled_tasks = scheduler::spawn(&shared_state, &settings);
+    let mut background_tasks = jobs::spawn_workers(&shared_state, jobs::JOB_WORKERS);
+    background_tasks.extend(secrets::spawn_refresh(secrets, loader.clone(), Arc::clone(&settings)));
+    background_tasks.push(events::spawn_metrics_consumer(&shared_state));
+    background_tasks.push(webhooks::spawn_delivery_worker(
+        &shared_state,
+        webhooks::RetryPolicy::default(),
+        webhooks::MAX_CONCURRENT_DELIVERIES,
+    ));
+    background_tasks.push(replication::spawn_sender(
+        &shared_state,
+        &settings,
+        webhooks::RetryPolicy::default(),
+    ));
+    background_tasks.extend(kafka::spawn_producer(&shared_state, &settings));
+    background_tasks.extend(nats::spawn_publisher(&shared_state, &settings));
+    background_tasks.extend(mqtt::spawn_publisher(&shared_state, &settings));
+    #[cfg(feature = "sqs")]
+    background_tasks.extend(axum_example::sqs::spawn_consumer(&shared_state, &settings));
+    #[cfg(not(feature = "sqs"))]
+    if startup_settings.sqs.queue_url.is_some() {
+        log_warn!("Not consuming sqs.queue_url since the server was built without the sqs feature");
+    }
 
     // Build application with routes
-    let app = build_router(&shared_state, &config);
+    let mut app = build_router(&shared_state, &settings).layer(Extension(loader));
+    if startup_settings.log_routes {
+        for route in RouteTable::new(&startup_settings).routes {
+            log_info!("Route {route}");
+        }
+    }
+    if let Some(path) = &startup_settings.record_file {
+        let recorder = Arc::new(RequestRecorder::create(path)?);
+        log_info!("Recording requests to {}", path.display());
+        app = app.layer(from_fn_with_state(recorder, recording_middleware));
+    }
+
+    // Listen addresses, protocol, connection tuning and TLS are not reloaded
+    let http = startup_settings.http;
+    let server_config = startup_settings.server;
+    let tls = match startup_settings.tls.files()? {
+        Some((cert, key)) => Some(server::tls_acceptor(cert, key, http)?),
+        None => None,
+    };
+    let (listeners, redirect_listeners) = bind_listeners(&startup_settings, tls.is_some())?;
+
+    // A shutdown signal drains like the admin route, then the shutdown stops every listener
+    let shutdown = shared_state.lifecycle().shutdown_token();
+    spawn_signal_handler(&shared_state, &settings, server_config);
+
+    // Run server app with Hyper on all addresses
+    let redirect = server::https_redirect(startup_settings.port);
+    let servers = listeners
+        .into_iter()
+        .map(|listener| (listener, app.clone(), http, tls.clone()))
+        .chain(
+            redirect_listeners
+                .into_iter()
+                .map(|listener| (listener, redirect.clone(), HttpProtocol::Auto, None)),
+        );
+    try_join_all(servers.map(|(listener, app, protocol, tls)| {
+        server::serve(
+            listener,
+            app,
+            protocol,
+            server_config,
+            tls,
+            shutdown.clone().cancelled_owned(),
+        )
+    }))
+    .await?;
+
+    // Scheduled tasks finish a run in progress, the rest are stopped right away
+    log_info!("Stopping background tasks");
+    for task in &background_tasks {
+        task.abort();
+    }
+    lifecycle::stop_tasks(scheduled_tasks, server_config.shutdown_timeout()).await;
+
+    // Requests and tasks have finished, so the items no longer change
+    if let Some(path) = &state_file {
+        persistence::save(&shared_state, path)?;
+    }
+    Ok(())
+}
+
+/// Bind the API listeners, taking over inherited sockets first,
+/// and with TLS the plain HTTP listeners that redirect to HTTPS.
+fn bind_listeners(settings: &Settings, tls: bool) -> Result<(Vec<TcpListener>, Vec<TcpListener>)> {
+    let scheme = if tls { "https" } else { "http" };
+    let mut listeners = Vec::new();
+    let mut listen_fds = ListenFd::from_env();
+    for (index, address) in get_addresses(settings.host.clone(), settings.port)
+        .into_iter()
+        .enumerate()
+    {
+        let listener = server::inherited_or_bind(&mut listen_fds, index, address, settings.server.reuse_port)
+            .with_context(|| format!("Failed to bind {address}"))?;
+        log_info!("listening on {scheme}://{} ({})", listener.local_addr()?, settings.http);
+        listeners.push(listener);
+    }
+    let mut redirect_listeners = Vec::new();
+    if let Some(port) = settings.tls.redirect_port.filter(|_| tls) {
+        for address in get_addresses(settings.host.clone(), port) {
+            let listener = server::bind(address, settings.server.reuse_port)
+                .with_context(|| format!("Failed to bind {address}"))?;
+            log_info!("redirecting http://{} to HTTPS", listener.local_addr()?);
+            redirect_listeners.push(listener);
+        }
+    }
+    Ok((listeners, redirect_listeners))
+}
+
+/// Start draining on the first shutdown signal,
+/// and exit without waiting for the shutdown to finish on a second signal or after the hard timeout.
+fn spawn_signal_handler(shared_state: &SharedState, settings: &SharedSettings, server_config: ServerConfig) {
+    let state = Arc::clone(shared_state);
+    let settings = Arc::clone(settings);
+    tokio::spawn(async move {
+        utils::shutdown_signal().await;
+        let delay = Duration::from_secs(settings.load().drain_delay_seconds);
+        if state.lifecycle().start_drain(delay) {
+            log_info!("Shutdown signal received, draining for {} seconds", delay.as_secs());
+        } else {
+            log_info!("Shutdown signal received while already draining");
+        }
+        let hard_timeout = async {
+            match server_config.hard_shutdown_timeout() {
+                Some(timeout) => tokio::time::sleep(timeout).await,
+                None => std::future::pending().await,
+            }
+        };
+        tokio::select! {
+            () = utils::shutdown_signal() => log_warn!("Second shutdown signal received, exiting now"),
+            () = hard_timeout => log_warn!(
+                "Shutdown did not finish in {} seconds, exiting now",
+                server_config.hard_shutdown_timeout_seconds.unwrap_or_default()
+            ),
+            () = lifecycle::log_shutdown_progress(state.inflight()) => {}
+        }
+        std::process::exit(FORCED_EXIT_CODE);
+    });
+}
 
-    let address = get_address(args.host, args.port);
-    let listener = tokio::net::TcpListener::bind(address).await?;
-    log_info!("listening on {}", listener.local_addr()?);
+/// Build the multi-threaded Tokio runtime, using Tokio defaults for unset sizes.
+///
+/// Small containers often report the host CPU count,
+/// so the thread counts can be sized to the container limits instead.
+fn build_runtime(workers: Option<NonZeroUsize>, blocking_threads: Option<NonZeroUsize>) -> Result<Runtime> {
+    let mut builder = tokio::runtime::Builder::new_multi_thread();
+    builder.enable_all();
+    if let Some(workers) = workers {
+        builder.worker_threads(workers.get());
+    }
+    if let Some(blocking_threads) = blocking_threads {
+        builder.max_blocking_threads(blocking_threads.get());
+    }
+    builder.build().context("Failed to build Tokio runtime")
+}
+
+/// Item id strategy from the settings, or the default if the settings are invalid.
+fn configured_id_strategy(config_path: PathBuf) -> IdStrategy {
+    SettingsLoader::new(config_path, CliSettings::default())
+        .load()
+        .map_or_else(|_| IdStrategy::default(), |settings| settings.id_strategy)
+}
 
-    // Run server app with Hyper
-    axum::serve(listener, app)
-        .with_graceful_shutdown(utils::shutdown_signal())
-        .await?;
+/// Write the `OpenAPI` spec to a file, or print it to stdout for `-`.
+fn dump_openapi(format: SpecFormat, path: &Path, id_strategy: IdStrategy) -> Result<()> {
+    let spec = render_spec(format, id_strategy, &legacy_route_deprecations())?;
+    if path == Path::new(STDOUT_PATH) {
+        print!("{spec}");
+    } else {
+        std::fs::write(path, spec).with_context(|| format!("Failed to write {}", path.display()))?;
+        eprintln!("Wrote OpenAPI spec to {}", path.display());
+    }
+    Ok(())
+}
 
+/// Write the commented config template for the runtime environment to the file or stdout.
+///
+/// An existing file is not overwritten.
+fn generate_config(path: Option<&Path>) -> Result<()> {
+    let template = config_template::render(Environment::from_env())?;
+    match path.filter(|path| *path != Path::new(STDOUT_PATH)) {
+        None => print!("{template}"),
+        Some(path) => {
+            let mut file = std::fs::File::create_new(path)
+                .with_context(|| format!("Failed to create config file {}", path.display()))?;
+            file.write_all(template.as_bytes())
+                .with_context(|| format!("Failed to write {}", path.display()))?;
+            eprintln!("Wrote config file to {}", path.display());
+        }
+    }
     Ok(())
 }
 
+/// Completion script for the shell.
+fn completions(shell: Shell) -> String {
+    let mut command = Args::command();
+    let name = command.get_name().to_string();
+    let mut script = Vec::new();
+    clap_complete::generate(shell, &mut command, name, &mut script);
+    String::from_utf8_lossy(&script).into_owned()
+}
+
+/// Settings snippets that configure the api key.
+fn api_key_snippet(api_key: &str) -> String {
+    format!(
+        "# Environment variable\n\
+         API_KEY={api_key}\n\
+         \n\
+         # {}\n\
+         api_key = \"{api_key}\"\n\
+         \n\
+         # Or store the key in the secret provider and set API_KEY_SECRET_ID to its id\n",
+        file_config::CONFIG_FILE_NAME
+    )
+}
+
+/// Resolve all socket addresses to listen on, defaulting to localhost.
+///
+/// Duplicate addresses are only bound once.
+fn get_addresses(hosts: Vec<String>, port: u16) -> Vec<SocketAddr> {
+    if hosts.is_empty() {
+        return vec![get_address(None, port)];
+    }
+    let mut addresses = Vec::with_capacity(hosts.len());
+    for host in hosts {
+        let address = host
+            .parse::<SocketAddr>()
+            .unwrap_or_else(|_| get_address(Some(host), port));
+        if !addresses.contains(&address) {
+            addresses.push(address);
+        }
+    }
+    addresses
+}
+
 /// Resolve socket address (ip and port) from arguments or use default.
 fn get_address(host: Option<String>, port: u16) -> SocketAddr {
     let ip = host.map_or(IpAddr::V4(Ipv4Addr::LOCALHOST), |ip_string| {
@@ -102,6 +617,30 @@ fn get_address(host: Option<String>, port: u16) -> SocketAddr {
 mod tests {
     use super::*;
 
+    use axum_example::client_command::ClientCommand;
+    use axum_example::types::DEFAULT_API_KEY;
+
+    #[tokio::test]
+    async fn check_startup_fails_when_the_port_is_taken() {
+        let taken = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = taken.local_addr().unwrap().port();
+        let loader = |port| {
+            SettingsLoader::new(
+                PathBuf::from("missing-axum-example.toml"),
+                CliSettings {
+                    host: vec!["127.0.0.1".to_string()],
+                    port: Some(port),
+                    ..CliSettings::default()
+                },
+            )
+        };
+
+        let error = check_startup(loader(port)).await.expect_err("port is taken");
+        assert_eq!(error.to_string(), format!("Failed to bind 127.0.0.1:{port}"));
+        drop(taken);
+        check_startup(loader(port)).await.expect("port is free");
+    }
+
     #[test]
     fn get_address_defaults_to_localhost() {
         let address = get_address(None, 3000);
@@ -125,4 +664,182 @@ mod tests {
         assert_eq!(address.ip(), IpAddr::V4(Ipv4Addr::UNSPECIFIED));
         assert_eq!(address.port(), 1234);
     }
+
+    #[test]
+    fn get_addresses_defaults_to_localhost() {
+        assert_eq!(
+            get_addresses(Vec::new(), 3000),
+            vec![SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 3000)]
+        );
+    }
+
+    #[test]
+    fn get_addresses_supports_multiple_hosts_and_port_overrides() {
+        let addresses = get_addresses(
+            vec![
+                "127.0.0.1".to_string(),
+                "::1".to_string(),
+                "127.0.0.1:9000".to_string(),
+                "127.0.0.1".to_string(),
+            ],
+            3000,
+        );
+
+        assert_eq!(
+            addresses,
+            vec![
+                "127.0.0.1:3000".parse::<SocketAddr>().unwrap(),
+                "[::1]:3000".parse::<SocketAddr>().unwrap(),
+                "127.0.0.1:9000".parse::<SocketAddr>().unwrap(),
+            ]
+        );
+    }
+
+    #[test]
+    fn args_accept_repeated_and_comma_separated_hosts() {
+        let args = Args::try_parse_from(["axum-example", "--host", "127.0.0.1", "-H", "::1,0.0.0.0:9000"])
+            .expect("arguments should parse");
+
+        assert_eq!(args.serve.host, vec!["127.0.0.1", "::1", "0.0.0.0:9000"]);
+    }
+
+    #[test]
+    fn args_support_subcommands_with_global_options() {
+        let args = Args::try_parse_from(["axum-example", "serve", "--port", "8080", "--config", "other.toml"])
+            .expect("arguments should parse");
+        assert!(matches!(
+            args.command,
+            Some(Command::Serve(ServeArgs { port: Some(8080), .. }))
+        ));
+        assert_eq!(args.config, Some(PathBuf::from("other.toml")));
+
+        let args = Args::try_parse_from(["axum-example", "check-config"]).expect("arguments should parse");
+        assert!(matches!(args.command, Some(Command::CheckConfig)));
+
+        let args =
+            Args::try_parse_from(["axum-example", "export-openapi", "spec.json"]).expect("arguments should parse");
+        assert!(matches!(args.command, Some(Command::ExportOpenapi { path }) if path == Path::new("spec.json")));
+
+        let args =
+            Args::try_parse_from(["axum-example", "diff-openapi", "openapi.json"]).expect("arguments should parse");
+        assert!(matches!(args.command, Some(Command::DiffOpenapi { path }) if path == Path::new("openapi.json")));
+
+        let args = Args::try_parse_from(["axum-example", "bench", "-n", "100", "--url", "http://127.0.0.1:3000"])
+            .expect("arguments should parse");
+        assert!(matches!(
+            args.command,
+            Some(Command::Bench(BenchArgs {
+                requests: 100,
+                url: Some(_),
+                ..
+            }))
+        ));
+
+        let args = Args::try_parse_from([
+            "axum-example",
+            "client",
+            "create",
+            "--name",
+            "foo",
+            "--tag",
+            "a",
+            "--tag",
+            "b",
+        ])
+        .expect("arguments should parse");
+        assert!(matches!(
+            args.command,
+            Some(Command::Client(ClientArgs {
+                command: ClientCommand::Create { name, tags, .. },
+                ..
+            })) if name == "foo" && tags == ["a", "b"]
+        ));
+
+        let args = Args::try_parse_from(["axum-example", "client", "--url", "http://127.0.0.1:8080", "list"])
+            .expect("arguments should parse");
+        assert!(matches!(
+            args.command,
+            Some(Command::Client(ClientArgs {
+                url: Some(_),
+                command: ClientCommand::List { .. },
+                ..
+            }))
+        ));
+
+        let args = Args::try_parse_from(["axum-example", "replay", "requests.ndjson"]).expect("arguments should parse");
+        assert!(matches!(args.command, Some(Command::Replay { path }) if path == Path::new("requests.ndjson")));
+
+        assert!(Args::try_parse_from(["axum-example", "--port", "8080", "check-config"]).is_err());
+    }
+
+    #[test]
+    fn args_accept_runtime_thread_counts() {
+        let args = Args::try_parse_from(["axum-example", "--workers", "2", "--blocking-threads", "8"])
+            .expect("arguments should parse");
+        assert_eq!(args.serve.workers, NonZeroUsize::new(2));
+        assert_eq!(args.serve.blocking_threads, NonZeroUsize::new(8));
+
+        assert!(Args::try_parse_from(["axum-example", "--workers", "0"]).is_err());
+    }
+
+    #[test]
+    fn build_runtime_uses_worker_count() {
+        let runtime = build_runtime(NonZeroUsize::new(2), NonZeroUsize::new(4)).expect("runtime should build");
+
+        assert_eq!(runtime.metrics().num_workers(), 2);
+    }
+
+    #[test]
+    fn args_accept_openapi_dump_flags_with_optional_path() {
+        let args = Args::try_parse_from(["axum-example", "--openapi-json"]).expect("arguments should parse");
+        assert_eq!(args.openapi_json, Some(PathBuf::from(STDOUT_PATH)));
+
+        let args =
+            Args::try_parse_from(["axum-example", "--openapi-yaml", "spec.yaml"]).expect("arguments should parse");
+        assert_eq!(args.openapi_yaml, Some(PathBuf::from("spec.yaml")));
+
+        assert!(Args::try_parse_from(["axum-example", "--openapi-json", "--openapi-yaml"]).is_err());
+    }
+
+    #[test]
+    fn completions_include_subcommands() {
+        let script = completions(Shell::Bash);
+
+        assert!(script.contains("axum-example"));
+        assert!(script.contains("generate-config"));
+        assert!(script.contains("--print-routes"));
+    }
+
+    #[test]
+    fn api_key_snippet_sets_env_and_config() {
+        let snippet = api_key_snippet("abc-123");
+
+        assert!(snippet.contains("\nAPI_KEY=abc-123\n"));
+        assert!(snippet.contains("\napi_key = \"abc-123\"\n"));
+        assert!(!snippet.contains(DEFAULT_API_KEY));
+    }
+
+    #[test]
+    fn generate_config_does_not_overwrite() {
+        let path = std::env::temp_dir().join(format!("axum-example-generated-{}.toml", std::process::id()));
+
+        generate_config(Some(&path)).expect("config should be written");
+        let written = std::fs::read_to_string(&path).expect("config should be readable");
+        assert!(written.contains("# port = 3000"));
+        assert!(generate_config(Some(&path)).is_err());
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn dump_openapi_writes_spec() {
+        let path = std::env::temp_dir().join(format!("axum-example-openapi-{}.json", std::process::id()));
+
+        dump_openapi(SpecFormat::Json, &path, IdStrategy::Numeric).expect("spec should be written");
+
+        let spec: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&path).expect("spec should be readable"))
+                .expect("spec should be JSON");
+        assert!(spec["paths"]["/items"].is_object());
+        std::fs::remove_file(&path).ok();
+    }
 }
@@ -0,0 +1,243 @@
+//! MQTT publishing of item events.
+//!
+//! With `mqtt.host` set, every item change is published as the item event JSON to the broker with `QoS` 1.
+//! With `mqtt.topic_per_item`, events about an item go to `{mqtt.topic}/{item name}`,
+//! like `axum-example/items/esgrove`, so a device can subscribe to just the items it cares about,
+//! and clear events go to `{mqtt.topic}` itself.
+//! Otherwise every event goes to `{mqtt.topic}`.
+//! Subscribing to `axum-example/items/#` receives every event either way.
+//! Changes applied from peers are not published, so each change is published once by the instance that made it.
+//! A separate task keeps the connection up and reconnects after errors,
+//! while events wait in the client queue and are skipped when the bus overflows,
+//! so delivery is at most once like the rest of the event bus.
+//! Only plain TCP connections are supported.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use rumqttc::{AsyncClient, Event, MqttOptions, Packet, QoS};
+use tokio::task::JoinHandle;
+use ulid::Ulid;
+
+use crate::events::{Actor, EVENT_CHANNEL_CAPACITY, ItemChange, ItemEvent, next_event};
+use crate::settings::SharedSettings;
+use crate::types::{MqttConfig, SharedState};
+
+/// Largest MQTT packet sent or received, larger events are skipped.
+const MAX_PACKET_BYTES: usize = 1024 * 1024;
+
+/// Bytes of a `QoS` 1 publish packet besides the topic and payload, not counting the fixed header.
+const PUBLISH_OVERHEAD_BYTES: usize = 4;
+
+/// Delay before reconnecting after the connection to the broker fails.
+const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+
+/// Topic that the event is published to.
+#[must_use]
+pub fn topic(config: &MqttConfig, event: &ItemEvent) -> String {
+    match &event.change {
+        ItemChange::Created { item }
+        | ItemChange::Removed { item }
+        | ItemChange::Expired { item }
+        | ItemChange::Evicted { item, .. }
+            if config.topic_per_item =>
+        {
+            format!("{}/{}", config.topic, item.name)
+        }
+        _ => config.topic.clone(),
+    }
+}
+
+/// Publish the item changes made on this instance to the MQTT broker when `mqtt.host` is set.
+///
+/// Returns the publisher and connection tasks.
+/// The broker and client id are only read at startup, the topic settings are read for every event.
+pub fn spawn_publisher(state: &SharedState, settings: &SharedSettings) -> Vec<JoinHandle<()>> {
+    let config = settings.load().mqtt.clone();
+    let Some(host) = config.host else {
+        return Vec::new();
+    };
+    let client_id = config
+        .client_id
+        .unwrap_or_else(|| format!("{}-{}", env!("CARGO_PKG_NAME"), Ulid::generate()));
+    let mut options = MqttOptions::new(client_id, host, config.port);
+    options.set_max_packet_size(MAX_PACKET_BYTES, MAX_PACKET_BYTES);
+    let (client, mut event_loop) = AsyncClient::new(options, EVENT_CHANNEL_CAPACITY);
+
+    let connection = tokio::spawn(async move {
+        let mut connected = false;
+        loop {
+            match event_loop.poll().await {
+                Ok(Event::Incoming(Packet::ConnAck(_))) => {
+                    crate::log_info!("Connected to the MQTT broker");
+                    connected = true;
+                }
+                Ok(_) => {}
+                Err(error) => {
+                    // Only the first failure is a warning, so a broker that stays down does not flood the logs.
+                    if connected {
+                        crate::log_warn!("MQTT connection failed, reconnecting: {error}");
+                    } else {
+                        crate::log_debug!("MQTT connection failed, reconnecting: {error}");
+                    }
+                    connected = false;
+                    tokio::time::sleep(RECONNECT_DELAY).await;
+                }
+            }
+        }
+    });
+
+    let mut receiver = state.events().subscribe();
+    let metrics = state.telemetry().metrics();
+    let settings = Arc::clone(settings);
+    let publisher = tokio::spawn(async move {
+        while let Some(event) = next_event(&mut receiver, "mqtt", &metrics).await {
+            if event.actor == Actor::Replication {
+                continue;
+            }
+            let topic = topic(&settings.load().mqtt, &event);
+            let payload = match serde_json::to_vec(&event) {
+                Ok(payload) => payload,
+                Err(error) => {
+                    crate::log_error!("Failed to serialize item event for MQTT: {error}");
+                    continue;
+                }
+            };
+            if topic.len() + payload.len() + PUBLISH_OVERHEAD_BYTES > MAX_PACKET_BYTES {
+                crate::log_warn!(
+                    "Item event of {} bytes is over the MQTT packet limit, not publishing it to {topic}",
+                    payload.len()
+                );
+                continue;
+            }
+            if let Err(error) = client.publish(topic.clone(), QoS::AtLeastOnce, false, payload).await {
+                crate::log_warn!("Failed to publish item event to MQTT topic {topic}: {error}");
+            }
+        }
+    });
+    vec![publisher, connection]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+    use tokio::sync::mpsc;
+
+    use crate::settings::Settings;
+    use crate::types::{AppState, IdStrategy, Item, ItemId};
+
+    /// Minimal MQTT broker that accepts the connection, acknowledges publishes and forwards them.
+    async fn spawn_fake_broker() -> (u16, mpsc::UnboundedReceiver<(String, String)>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let (sender, receiver) = mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            while let Ok(header) = stream.read_u8().await {
+                let mut length = 0;
+                for shift in (0..28).step_by(7) {
+                    let byte = stream.read_u8().await.unwrap();
+                    length |= usize::from(byte & 0x7f) << shift;
+                    if byte & 0x80 == 0 {
+                        break;
+                    }
+                }
+                let mut body = vec![0; length];
+                stream.read_exact(&mut body).await.unwrap();
+                match header >> 4 {
+                    // CONNECT is answered with an accepting CONNACK.
+                    1 => stream.write_all(&[0x20, 0x02, 0x00, 0x00]).await.unwrap(),
+                    // PUBLISH with QoS 1 is acknowledged with its packet id.
+                    3 => {
+                        let topic_length = usize::from(u16::from_be_bytes([body[0], body[1]]));
+                        let topic = String::from_utf8(body[2..2 + topic_length].to_vec()).unwrap();
+                        let packet_id = &body[2 + topic_length..4 + topic_length];
+                        stream
+                            .write_all(&[0x40, 0x02, packet_id[0], packet_id[1]])
+                            .await
+                            .unwrap();
+                        let payload = String::from_utf8(body[4 + topic_length..].to_vec()).unwrap();
+                        sender.send((topic, payload)).ok();
+                    }
+                    // PINGREQ
+                    12 => stream.write_all(&[0xd0, 0x00]).await.unwrap(),
+                    _ => {}
+                }
+            }
+        });
+        (port, receiver)
+    }
+
+    fn config(topic_per_item: bool) -> MqttConfig {
+        MqttConfig {
+            topic: "test/items".to_string(),
+            topic_per_item,
+            ..MqttConfig::default()
+        }
+    }
+
+    #[test]
+    fn topics_are_per_item_or_shared() {
+        let item = Item::new("sensor-1".to_string(), ItemId::Numeric(1001), IdStrategy::Numeric).unwrap();
+        let event = |change| ItemEvent {
+            occurred_at: chrono::Utc::now(),
+            actor: Actor::Client,
+            change,
+        };
+        let created = event(ItemChange::Created { item });
+        let cleared = event(ItemChange::Cleared { count: 1 });
+
+        assert_eq!(topic(&config(true), &created), "test/items/sensor-1");
+        assert_eq!(topic(&config(true), &cleared), "test/items");
+        assert_eq!(topic(&config(false), &created), "test/items");
+    }
+
+    #[tokio::test]
+    async fn item_events_are_published_to_the_broker() {
+        let (port, mut broker_messages) = spawn_fake_broker().await;
+        let settings = Settings {
+            mqtt: MqttConfig {
+                host: Some("127.0.0.1".to_string()),
+                port,
+                ..config(true)
+            },
+            ..Settings::default()
+        }
+        .into_shared();
+        let state = AppState::new_shared_state();
+        let tasks = spawn_publisher(&state, &settings);
+
+        let item = Item::new("sensor-1".to_string(), ItemId::Numeric(1001), IdStrategy::Numeric).unwrap();
+        state.publish(Actor::Replication, ItemChange::Created { item: item.clone() });
+        state.publish(Actor::Client, ItemChange::Created { item });
+        state.publish(Actor::Admin, ItemChange::Cleared { count: 1 });
+
+        let mut messages = Vec::new();
+        while messages.len() < 2 {
+            let message = tokio::time::timeout(Duration::from_secs(5), broker_messages.recv())
+                .await
+                .expect("message should be published")
+                .unwrap();
+            messages.push(message);
+        }
+        for task in tasks {
+            task.abort();
+        }
+
+        assert_eq!(messages[0].0, "test/items/sensor-1");
+        assert_eq!(messages[1].0, "test/items");
+        let event: ItemEvent = serde_json::from_str(&messages[0].1).unwrap();
+        assert_eq!(event.actor, Actor::Client);
+        assert!(matches!(event.change, ItemChange::Created { item } if item.name == "sensor-1"));
+    }
+
+    #[test]
+    fn publisher_is_off_without_a_host() {
+        let state = AppState::new_shared_state();
+
+        assert!(spawn_publisher(&state, &Settings::default().into_shared()).is_empty());
+    }
+}
@@ -0,0 +1,158 @@
+//! NATS publishing of item events.
+//!
+//! With `nats.url` set, every item change is published as the item event JSON
+//! to the subject `{nats.subject_prefix}.{type}`, like `axum-example.items.created`,
+//! so consumers can subscribe to all events with `axum-example.items.>` or to single event types.
+//! Changes applied from peers are not published, so each change is published once by the instance that made it.
+//! The client connects and reconnects in the background, so a server that is down does not hold up startup.
+//! Delivery is at most once like the rest of the event bus:
+//! events are buffered in the client while it reconnects and skipped when the bus overflows.
+
+use std::sync::Arc;
+
+use async_nats::ConnectOptions;
+use axum::body::Bytes;
+use tokio::task::JoinHandle;
+
+use crate::events::{Actor, ItemEvent, next_event};
+use crate::settings::SharedSettings;
+use crate::types::SharedState;
+
+/// Subject that the event is published to.
+#[must_use]
+pub fn subject(prefix: &str, event: &ItemEvent) -> String {
+    format!("{prefix}.{}", event.change.event_type())
+}
+
+/// Publish the item changes made on this instance to the NATS server when `nats.url` is set.
+///
+/// The server is only read at startup, the subject prefix is read for every event.
+pub fn spawn_publisher(state: &SharedState, settings: &SharedSettings) -> Option<JoinHandle<()>> {
+    let url = settings.load().nats.url.clone()?;
+    let mut receiver = state.events().subscribe();
+    let metrics = state.telemetry().metrics();
+    let settings = Arc::clone(settings);
+    Some(tokio::spawn(async move {
+        let client = match ConnectOptions::new()
+            .name(env!("CARGO_PKG_NAME"))
+            .retry_on_initial_connect()
+            .connect(url.as_str())
+            .await
+        {
+            Ok(client) => client,
+            Err(error) => {
+                crate::log_error!("Failed to connect to NATS: {error}");
+                return;
+            }
+        };
+        while let Some(event) = next_event(&mut receiver, "nats", &metrics).await {
+            if event.actor == Actor::Replication {
+                continue;
+            }
+            let subject = subject(&settings.load().nats.subject_prefix, &event);
+            let payload = match serde_json::to_vec(&event) {
+                Ok(payload) => Bytes::from(payload),
+                Err(error) => {
+                    crate::log_error!("Failed to serialize item event for NATS: {error}");
+                    continue;
+                }
+            };
+            if let Err(error) = client.publish(subject.clone(), payload).await {
+                crate::log_warn!("Failed to publish item event to NATS subject {subject}: {error}");
+            }
+        }
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::time::Duration;
+
+    use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::TcpListener;
+    use tokio::sync::mpsc;
+
+    use crate::events::ItemChange;
+    use crate::settings::Settings;
+    use crate::types::{AppState, IdStrategy, Item, ItemId, NatsConfig};
+
+    /// Minimal NATS server that answers pings and forwards every published message.
+    async fn spawn_fake_server() -> (String, mpsc::UnboundedReceiver<(String, String)>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let (sender, receiver) = mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let (reader, mut writer) = stream.into_split();
+            let info = format!(
+                "INFO {{\"server_id\":\"test\",\"version\":\"2.10.0\",\"host\":\"127.0.0.1\",\"port\":{},\"max_payload\":1048576,\"proto\":1}}\r\n",
+                address.port()
+            );
+            writer.write_all(info.as_bytes()).await.unwrap();
+            let mut reader = BufReader::new(reader);
+            let mut line = String::new();
+            while reader.read_line(&mut line).await.unwrap_or(0) > 0 {
+                let words: Vec<&str> = line.split_whitespace().collect();
+                match words.as_slice() {
+                    ["PING"] => writer.write_all(b"PONG\r\n").await.unwrap(),
+                    ["PUB", subject, length] => {
+                        let mut payload = vec![0; length.parse::<usize>().unwrap() + 2];
+                        reader.read_exact(&mut payload).await.unwrap();
+                        payload.truncate(payload.len() - 2);
+                        sender
+                            .send(((*subject).to_string(), String::from_utf8(payload).unwrap()))
+                            .ok();
+                    }
+                    _ => {}
+                }
+                line.clear();
+            }
+        });
+        (format!("nats://{address}"), receiver)
+    }
+
+    #[tokio::test]
+    async fn item_events_are_published_to_subjects_by_type() {
+        let (url, mut server_messages) = spawn_fake_server().await;
+        let settings = Settings {
+            nats: NatsConfig {
+                url: Some(url),
+                subject_prefix: "test.items".to_string(),
+            },
+            ..Settings::default()
+        }
+        .into_shared();
+        let state = AppState::new_shared_state();
+        let publisher = spawn_publisher(&state, &settings).expect("publisher with a url");
+
+        let item = Item::new("published".to_string(), ItemId::Numeric(1001), IdStrategy::Numeric).unwrap();
+        state.publish(Actor::Replication, ItemChange::Created { item: item.clone() });
+        state.publish(Actor::Client, ItemChange::Created { item: item.clone() });
+        state.publish(Actor::Admin, ItemChange::Removed { item });
+
+        let mut messages = Vec::new();
+        while messages.len() < 2 {
+            let message = tokio::time::timeout(Duration::from_secs(5), server_messages.recv())
+                .await
+                .expect("message should be published")
+                .unwrap();
+            messages.push(message);
+        }
+        publisher.abort();
+
+        assert_eq!(messages[0].0, "test.items.created");
+        assert_eq!(messages[1].0, "test.items.removed");
+        let event: ItemEvent = serde_json::from_str(&messages[0].1).unwrap();
+        assert_eq!(event.actor, Actor::Client);
+        assert!(matches!(event.change, ItemChange::Created { item } if item.name == "published"));
+    }
+
+    #[test]
+    fn publisher_is_off_without_a_url() {
+        let state = AppState::new_shared_state();
+
+        assert!(spawn_publisher(&state, &Settings::default().into_shared()).is_none());
+    }
+}
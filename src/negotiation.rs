@@ -0,0 +1,399 @@
+//! Response content negotiation.
+//!
+//! Picks the response encoding from the `Accept` header:
+//! JSON by default, XML or `MessagePack` when the client prefers them.
+//! Response types implement [`Negotiate`] once and handlers wrap them in [`Negotiated`]
+//! together with the format from the [`AcceptFormat`] extractor.
+//! A `Result` with an `ApiError` negotiates either side, so handlers can return errors in the client's format.
+//! The XML and `MessagePack` encoders work on the `serde_json::Value` form of the response,
+//! so every `Serialize` type can be encoded without format-specific derives.
+
+use std::convert::Infallible;
+
+use axum::extract::FromRequestParts;
+use axum::http::header::{ACCEPT, CONTENT_TYPE};
+use axum::http::request::Parts;
+use axum::http::{HeaderMap, HeaderValue, StatusCode};
+use axum::response::{IntoResponse, Response};
+use serde::Serialize;
+use serde_json::{Map, Number, Value};
+use strum::{Display, IntoStaticStr};
+
+use crate::schemas::ApiError;
+
+/// Root element name used for XML responses.
+const XML_ROOT_ELEMENT: &str = "response";
+
+/// Element name used for each array entry in XML responses.
+const XML_ARRAY_ELEMENT: &str = "item";
+
+/// Extractor for the response format preferred by the client.
+///
+/// Never rejects: a missing or unsupported `Accept` header selects JSON.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AcceptFormat(pub ResponseFormat);
+
+/// Response wrapper that encodes the inner response in the negotiated format.
+pub struct Negotiated<T>(pub ResponseFormat, pub T);
+
+/// Successful response for a created resource, encoded with `201 Created`.
+pub struct Created<T>(pub T);
+
+/// Supported response encodings.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Display, IntoStaticStr)]
+pub enum ResponseFormat {
+    #[default]
+    #[strum(serialize = "application/json")]
+    Json,
+    #[strum(serialize = "application/xml")]
+    Xml,
+    #[strum(serialize = "application/msgpack")]
+    MessagePack,
+}
+
+/// Response types that can be encoded in any [`ResponseFormat`].
+pub trait Negotiate {
+    /// Build the response encoded in the given format.
+    fn negotiate(self, format: ResponseFormat) -> Response;
+}
+
+impl<T: Negotiate> Negotiate for Result<T, ApiError> {
+    fn negotiate(self, format: ResponseFormat) -> Response {
+        match self {
+            Ok(response) => response.negotiate(format),
+            Err(error) => error.negotiate(format),
+        }
+    }
+}
+
+impl<T: Serialize> Negotiate for Created<T> {
+    fn negotiate(self, format: ResponseFormat) -> Response {
+        encode(format, StatusCode::CREATED, &self.0)
+    }
+}
+
+impl<T: Negotiate> IntoResponse for Negotiated<T> {
+    fn into_response(self) -> Response {
+        let Self(format, response) = self;
+        response.negotiate(format)
+    }
+}
+
+impl ResponseFormat {
+    /// Select the most preferred supported format from the `Accept` header.
+    ///
+    /// Media ranges are ranked by their `q` parameter.
+    /// Wildcards select JSON, and ties keep the order the client listed them in.
+    pub fn from_headers(headers: &HeaderMap) -> Self {
+        let Some(accept) = headers.get(ACCEPT).and_then(|value| value.to_str().ok()) else {
+            return Self::default();
+        };
+
+        let mut best: Option<(Self, f32)> = None;
+        for media_range in accept.split(',') {
+            let mut parameters = media_range.split(';').map(str::trim);
+            let Some(format) = parameters.next().and_then(Self::from_media_type) else {
+                continue;
+            };
+            let quality = parameters
+                .find_map(|parameter| parameter.strip_prefix("q="))
+                .and_then(|value| value.parse::<f32>().ok())
+                .unwrap_or(1.0);
+            if quality > 0.0 && best.is_none_or(|(_, best_quality)| quality > best_quality) {
+                best = Some((format, quality));
+            }
+        }
+        best.map(|(format, _)| format).unwrap_or_default()
+    }
+
+    /// Map a media type to a supported format.
+    fn from_media_type(media_type: &str) -> Option<Self> {
+        match media_type.to_ascii_lowercase().as_str() {
+            "application/json" | "application/*" | "*/*" => Some(Self::Json),
+            "application/xml" | "text/xml" => Some(Self::Xml),
+            "application/msgpack" | "application/x-msgpack" | "application/vnd.msgpack" => Some(Self::MessagePack),
+            _ => None,
+        }
+    }
+}
+
+impl<S> FromRequestParts<S> for AcceptFormat
+where
+    S: Send + Sync,
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Ok(Self(ResponseFormat::from_headers(&parts.headers)))
+    }
+}
+
+/// Encode a serializable value with the given status code in the given format.
+pub fn encode<T: Serialize>(format: ResponseFormat, status: StatusCode, value: &T) -> Response {
+    let body = match format {
+        ResponseFormat::Json => serde_json::to_vec(value).map_err(anyhow::Error::from),
+        ResponseFormat::Xml => serde_json::to_value(value)
+            .map(|value| to_xml(&value))
+            .map_err(anyhow::Error::from),
+        ResponseFormat::MessagePack => serde_json::to_value(value)
+            .map(|value| to_message_pack(&value))
+            .map_err(anyhow::Error::from),
+    };
+    match body {
+        Ok(body) => {
+            let content_type = HeaderValue::from_static(format.into());
+            (status, [(CONTENT_TYPE, content_type)], body).into_response()
+        }
+        Err(error) => ApiError::from(error.context("Failed to encode response")).into_response(),
+    }
+}
+
+/// Encode a JSON value as an XML document.
+///
+/// Objects become nested elements, array entries become repeated `<item>` elements,
+/// and null becomes an empty element.
+fn to_xml(value: &Value) -> Vec<u8> {
+    let mut xml = String::from(r#"<?xml version="1.0" encoding="UTF-8"?>"#);
+    write_xml_element(&mut xml, XML_ROOT_ELEMENT, value);
+    xml.into_bytes()
+}
+
+fn write_xml_element(xml: &mut String, name: &str, value: &Value) {
+    let name = &xml_element_name(name);
+    match value {
+        Value::Null => {
+            xml.push('<');
+            xml.push_str(name);
+            xml.push_str("/>");
+        }
+        Value::Bool(boolean) => write_xml_text(xml, name, &boolean.to_string()),
+        Value::Number(number) => write_xml_text(xml, name, &number.to_string()),
+        Value::String(string) => write_xml_text(xml, name, string),
+        Value::Array(values) => {
+            push_tag(xml, name, false);
+            for value in values {
+                write_xml_element(xml, XML_ARRAY_ELEMENT, value);
+            }
+            push_tag(xml, name, true);
+        }
+        Value::Object(object) => {
+            push_tag(xml, name, false);
+            for (key, value) in object {
+                write_xml_element(xml, key, value);
+            }
+            push_tag(xml, name, true);
+        }
+    }
+}
+
+fn write_xml_text(xml: &mut String, name: &str, text: &str) {
+    push_tag(xml, name, false);
+    for character in text.chars() {
+        match character {
+            '<' => xml.push_str("&lt;"),
+            '>' => xml.push_str("&gt;"),
+            '&' => xml.push_str("&amp;"),
+            '"' => xml.push_str("&quot;"),
+            '\'' => xml.push_str("&apos;"),
+            _ => xml.push(character),
+        }
+    }
+    push_tag(xml, name, true);
+}
+
+fn push_tag(xml: &mut String, name: &str, closing: bool) {
+    xml.push_str(if closing { "</" } else { "<" });
+    xml.push_str(name);
+    xml.push('>');
+}
+
+/// Turn an arbitrary object key into a valid XML element name.
+fn xml_element_name(key: &str) -> String {
+    let mut name: String = key
+        .chars()
+        .map(|character| {
+            if character.is_alphanumeric() || matches!(character, '_' | '-' | '.') {
+                character
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    if !name.starts_with(|character: char| character.is_alphabetic() || character == '_') {
+        name.insert(0, '_');
+    }
+    name
+}
+
+/// Encode a JSON value as `MessagePack`.
+fn to_message_pack(value: &Value) -> Vec<u8> {
+    let mut buffer = Vec::new();
+    write_message_pack(&mut buffer, value);
+    buffer
+}
+
+#[allow(clippy::cast_possible_truncation)]
+fn write_message_pack(buffer: &mut Vec<u8>, value: &Value) {
+    match value {
+        Value::Null => buffer.push(0xc0),
+        Value::Bool(false) => buffer.push(0xc2),
+        Value::Bool(true) => buffer.push(0xc3),
+        Value::Number(number) => write_message_pack_number(buffer, number),
+        Value::String(string) => {
+            let length = string.len();
+            if length < 32 {
+                buffer.push(0xa0 | length as u8);
+            } else if let Ok(length) = u8::try_from(length) {
+                buffer.extend([0xd9, length]);
+            } else if let Ok(length) = u16::try_from(length) {
+                buffer.push(0xda);
+                buffer.extend(length.to_be_bytes());
+            } else {
+                buffer.push(0xdb);
+                buffer.extend((length as u32).to_be_bytes());
+            }
+            buffer.extend(string.as_bytes());
+        }
+        Value::Array(values) => {
+            write_message_pack_length(buffer, values.len(), 0x90, 0xdc);
+            for value in values {
+                write_message_pack(buffer, value);
+            }
+        }
+        Value::Object(object) => write_message_pack_map(buffer, object),
+    }
+}
+
+fn write_message_pack_map(buffer: &mut Vec<u8>, object: &Map<String, Value>) {
+    write_message_pack_length(buffer, object.len(), 0x80, 0xde);
+    for (key, value) in object {
+        write_message_pack(buffer, &Value::String(key.clone()));
+        write_message_pack(buffer, value);
+    }
+}
+
+/// Write an array or map header: fix variant, 16-bit, or 32-bit length.
+#[allow(clippy::cast_possible_truncation)]
+fn write_message_pack_length(buffer: &mut Vec<u8>, length: usize, fix_marker: u8, marker_16: u8) {
+    if length < 16 {
+        buffer.push(fix_marker | length as u8);
+    } else if let Ok(length) = u16::try_from(length) {
+        buffer.push(marker_16);
+        buffer.extend(length.to_be_bytes());
+    } else {
+        buffer.push(marker_16 + 1);
+        buffer.extend((length as u32).to_be_bytes());
+    }
+}
+
+fn write_message_pack_number(buffer: &mut Vec<u8>, number: &Number) {
+    if let Some(unsigned) = number.as_u64() {
+        if unsigned < 128 {
+            buffer.push(unsigned.to_be_bytes()[7]);
+        } else if let Ok(unsigned) = u8::try_from(unsigned) {
+            buffer.extend([0xcc, unsigned]);
+        } else if let Ok(unsigned) = u16::try_from(unsigned) {
+            buffer.push(0xcd);
+            buffer.extend(unsigned.to_be_bytes());
+        } else if let Ok(unsigned) = u32::try_from(unsigned) {
+            buffer.push(0xce);
+            buffer.extend(unsigned.to_be_bytes());
+        } else {
+            buffer.push(0xcf);
+            buffer.extend(unsigned.to_be_bytes());
+        }
+    } else if let Some(signed) = number.as_i64() {
+        if signed >= -32 {
+            buffer.push(signed.to_be_bytes()[7]);
+        } else {
+            buffer.push(0xd3);
+            buffer.extend(signed.to_be_bytes());
+        }
+    } else if let Some(float) = number.as_f64() {
+        buffer.push(0xcb);
+        buffer.extend(float.to_be_bytes());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serde_json::json;
+
+    fn headers_with_accept(accept: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(ACCEPT, HeaderValue::from_str(accept).expect("valid header value"));
+        headers
+    }
+
+    #[test]
+    fn accept_header_selects_preferred_supported_format() {
+        for (accept, expected) in [
+            ("", ResponseFormat::Json),
+            ("*/*", ResponseFormat::Json),
+            ("text/html", ResponseFormat::Json),
+            ("application/xml", ResponseFormat::Xml),
+            ("text/xml, application/json", ResponseFormat::Xml),
+            ("application/msgpack", ResponseFormat::MessagePack),
+            (
+                "application/json;q=0.5, application/x-msgpack",
+                ResponseFormat::MessagePack,
+            ),
+            ("application/xml;q=0.9, application/json", ResponseFormat::Json),
+            ("application/xml;q=0, */*;q=0.1", ResponseFormat::Json),
+        ] {
+            assert_eq!(
+                ResponseFormat::from_headers(&headers_with_accept(accept)),
+                expected,
+                "wrong format for '{accept}'"
+            );
+        }
+        assert_eq!(ResponseFormat::from_headers(&HeaderMap::new()), ResponseFormat::Json);
+    }
+
+    #[test]
+    fn xml_encodes_nested_values_and_escapes_text() {
+        let xml = to_xml(&json!({"name": "a<b>&c", "tags": ["x", "y"], "1st key": null, "ok": true}));
+        let xml = String::from_utf8(xml).expect("xml should be utf-8");
+
+        assert_eq!(
+            xml,
+            concat!(
+                r#"<?xml version="1.0" encoding="UTF-8"?>"#,
+                "<response><_1st_key/><name>a&lt;b&gt;&amp;c</name>",
+                "<ok>true</ok><tags><item>x</item><item>y</item></tags></response>"
+            )
+        );
+    }
+
+    #[test]
+    fn message_pack_encodes_known_byte_sequences() {
+        assert_eq!(
+            to_message_pack(&json!({"id": 1234, "name": "ab"})),
+            [
+                0x82, 0xa2, b'i', b'd', 0xcd, 0x04, 0xd2, 0xa4, b'n', b'a', b'm', b'e', 0xa2, b'a', b'b'
+            ]
+        );
+        assert_eq!(
+            to_message_pack(&json!([null, false, true, -1, 200])),
+            [0x95, 0xc0, 0xc2, 0xc3, 0xff, 0xcc, 0xc8]
+        );
+        assert_eq!(
+            to_message_pack(&json!(-100)),
+            [0xd3, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x9c]
+        );
+        assert_eq!(to_message_pack(&json!(1.5))[0], 0xcb);
+        assert_eq!(to_message_pack(&json!("x".repeat(40)))[..2], [0xd9, 40]);
+    }
+
+    #[test]
+    fn encode_sets_content_type_for_each_format() {
+        for format in [ResponseFormat::Json, ResponseFormat::Xml, ResponseFormat::MessagePack] {
+            let response = encode(format, StatusCode::CREATED, &json!({"name": "value"}));
+
+            assert_eq!(response.status(), StatusCode::CREATED);
+            assert_eq!(response.headers()[CONTENT_TYPE], format.to_string());
+        }
+    }
+}
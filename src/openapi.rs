@@ -3,35 +3,137 @@
 //! Keeps the documentation derive and security-scheme wiring separate from
 //! router construction, so route mounting can stay focused on runtime behavior.
 
+use std::path::Path;
+
+use anyhow::Context;
+use axum::http::Method;
+use axum::http::header::RETRY_AFTER;
+use serde_json::json;
+use utoipa::openapi::extensions::Extensions;
+use utoipa::openapi::header::{Header, HeaderBuilder};
+use utoipa::openapi::path::{Operation, PathItem};
+use utoipa::openapi::response::ResponseBuilder;
+use utoipa::openapi::schema::{ObjectBuilder, OneOfBuilder, Schema, SchemaFormat, Type};
 use utoipa::openapi::security::{ApiKey, ApiKeyValue, SecurityScheme};
-use utoipa::{Modify, OpenApi};
+use utoipa::openapi::server::{Server, ServerBuilder};
+use utoipa::openapi::{ContentBuilder, Deprecated, Object, Ref, RefOr};
+use utoipa::{Modify, OpenApi, PartialSchema, ToSchema};
 
-use crate::router;
+use crate::deprecation::{DEPRECATION_HEADER, DeprecationRegistry, SUNSET_HEADER};
+use crate::envelope::ResponseEnvelope;
+use crate::events::ItemEventType;
+use crate::problem::ProblemDetails;
+use crate::rate_limit::{RATE_LIMIT_LIMIT_HEADER, RATE_LIMIT_REMAINING_HEADER, RATE_LIMIT_RESET_HEADER};
+use crate::request_id::REQUEST_ID_HEADER;
+use crate::router::{self, API_V1_PREFIX};
 use crate::routing::admin;
 use crate::routing::routes;
+use crate::schemas::Deprecation;
+use crate::types::{IdStrategy, ItemId};
+use crate::webhooks::{SIGNATURE_HEADER, TIMESTAMP_HEADER};
+
+/// Paths served at the root instead of under the API version prefix,
+/// since probes and metric scrapers should not have to follow API versions.
+pub const UNVERSIONED_PATHS: [&str; 5] = ["/", "/health", "/readyz", "/metrics", "/{path}"];
 
 /// `OpenAPI` documentation for the example API.
 #[derive(OpenApi)]
 #[openapi(
-    modifiers(&SecurityAddon),
+    modifiers(&SecurityAddon, &ServersAddon, &ResponseHeadersAddon, &WebhooksAddon),
     paths(
         routes::root,
         routes::health,
+        routes::readiness,
         routes::metrics,
         routes::version,
         routes::query_item,
         routes::list_items,
         routes::create_item,
+        routes::batch_get_items,
+        routes::item_history,
+        routes::usage,
+        routes::list_collection_items,
+        routes::create_collection_item,
+        routes::get_collection_item,
         admin::delete_all_items,
         admin::remove_item,
+        admin::list_deprecations,
+        admin::get_config,
+        admin::backup,
+        admin::restore,
+        admin::create_backup,
+        admin::job_status,
+        admin::drain,
+        admin::reload_config,
+        admin::compact,
+        admin::inflight,
+        admin::list_items,
+        admin::list_collections,
+        admin::create_collection,
+        admin::drop_collection,
+        admin::create_webhook,
+        admin::list_webhooks,
+        admin::remove_webhook,
+        admin::webhook_deliveries,
+        admin::replicate,
         router::not_found,
     ),
+    components(schemas(ProblemDetails, ResponseEnvelope)),
 )]
 pub struct ApiDoc;
 
 /// Document api key in `OpenAPI` specs.
 struct SecurityAddon;
 
+/// Document the versioned base path, and the root for unversioned paths.
+struct ServersAddon;
+
+/// Document the headers every route sends, and the `429` response of the rate limiter.
+///
+/// The headers come from middleware shared by all routes,
+/// so they are added here instead of repeating them in every `utoipa::path`.
+struct ResponseHeadersAddon;
+
+/// Document the item event callbacks posted to registered webhooks.
+///
+/// utoipa 5 has no field for the `OpenAPI` 3.1 `webhooks` object,
+/// so it is added through the top-level extensions, which are serialized as they are.
+struct WebhooksAddon;
+
+/// Output format for the `OpenAPI` spec.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpecFormat {
+    Json,
+    Yaml,
+}
+
+impl SpecFormat {
+    /// Pick the format from the file extension, defaulting to JSON.
+    #[must_use]
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some(extension) if extension.eq_ignore_ascii_case("yaml") || extension.eq_ignore_ascii_case("yml") => {
+                Self::Yaml
+            }
+            _ => Self::Json,
+        }
+    }
+}
+
+impl PartialSchema for ItemId {
+    /// Any of the id formats, narrowed to the configured one by [`api_doc`].
+    fn schema() -> RefOr<Schema> {
+        OneOfBuilder::new()
+            .item(id_schema(IdStrategy::Numeric))
+            .item(id_schema(IdStrategy::Uuid))
+            .item(id_schema(IdStrategy::Ulid))
+            .description(Some("Item id, the format depends on the server id strategy"))
+            .into()
+    }
+}
+
+impl ToSchema for ItemId {}
+
 impl Modify for SecurityAddon {
     fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
         if let Some(components) = openapi.components.as_mut() {
@@ -42,3 +144,490 @@ impl Modify for SecurityAddon {
         }
     }
 }
+
+impl Modify for ServersAddon {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        openapi.servers = Some(vec![
+            ServerBuilder::new()
+                .url(API_V1_PREFIX)
+                .description(Some("API version 1"))
+                .build(),
+        ]);
+        for (path, item) in &mut openapi.paths.paths {
+            if UNVERSIONED_PATHS.contains(&path.as_str()) {
+                item.servers = Some(vec![Server::new("/")]);
+            }
+        }
+    }
+}
+
+impl Modify for ResponseHeadersAddon {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        for item in openapi.paths.paths.values_mut() {
+            for (_, operation) in operations_mut(item) {
+                add_response_headers(operation);
+            }
+        }
+    }
+}
+
+impl Modify for WebhooksAddon {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let webhooks: serde_json::Map<String, serde_json::Value> = [
+            ItemEventType::Created,
+            ItemEventType::Removed,
+            ItemEventType::Expired,
+            ItemEventType::Evicted,
+            ItemEventType::Cleared,
+        ]
+        .into_iter()
+        .map(|event_type| (format!("item_{event_type}"), webhook(event_type)))
+        .collect();
+        openapi
+            .extensions
+            .get_or_insert_with(Extensions::default)
+            .insert("webhooks".to_string(), webhooks.into());
+    }
+}
+
+/// `OpenAPI` spec with the item id schema for the configured id strategy,
+/// and the registered deprecations marked on the affected operations.
+#[must_use]
+pub fn api_doc(id_strategy: IdStrategy, deprecations: &DeprecationRegistry) -> utoipa::openapi::OpenApi {
+    let mut spec = ApiDoc::openapi();
+    if let Some(components) = spec.components.as_mut() {
+        components
+            .schemas
+            .insert(ItemId::name().into_owned(), id_schema(id_strategy).into());
+    }
+    apply_deprecations(&mut spec, deprecations);
+    spec
+}
+
+/// Render the `OpenAPI` spec in the given format.
+pub fn render_spec(
+    format: SpecFormat,
+    id_strategy: IdStrategy,
+    deprecations: &DeprecationRegistry,
+) -> anyhow::Result<String> {
+    let spec = api_doc(id_strategy, deprecations);
+    match format {
+        SpecFormat::Json => spec
+            .to_pretty_json()
+            .context("Failed to serialize OpenAPI spec as JSON"),
+        SpecFormat::Yaml => spec.to_yaml().context("Failed to serialize OpenAPI spec as YAML"),
+    }
+}
+
+/// Documented paths that are served under the API version prefix, relative to the prefix.
+#[must_use]
+pub fn versioned_paths() -> Vec<String> {
+    ApiDoc::openapi()
+        .paths
+        .paths
+        .into_keys()
+        .filter(|path| !UNVERSIONED_PATHS.contains(&path.as_str()))
+        .collect()
+}
+
+/// Documented operations of a path with their HTTP method.
+fn operations_mut(item: &mut PathItem) -> impl Iterator<Item = (Method, &mut Operation)> {
+    [
+        (Method::GET, &mut item.get),
+        (Method::PUT, &mut item.put),
+        (Method::POST, &mut item.post),
+        (Method::DELETE, &mut item.delete),
+        (Method::PATCH, &mut item.patch),
+    ]
+    .into_iter()
+    .filter_map(|(method, operation)| operation.as_mut().map(|operation| (method, operation)))
+}
+
+/// Mark deprecated operations and query parameters.
+///
+/// Deprecated unversioned aliases of versioned paths are not documented as separate paths,
+/// so they are noted in the description of the versioned operation instead.
+fn apply_deprecations(spec: &mut utoipa::openapi::OpenApi, deprecations: &DeprecationRegistry) {
+    if deprecations.is_empty() {
+        return;
+    }
+    for (path, item) in &mut spec.paths.paths {
+        let is_versioned = !UNVERSIONED_PATHS.contains(&path.as_str());
+        let route = if is_versioned {
+            format!("{API_V1_PREFIX}{path}")
+        } else {
+            path.clone()
+        };
+        for (method, operation) in operations_mut(item) {
+            for entry in deprecations.matching(&method, &route) {
+                deprecate(operation, entry);
+            }
+            if !is_versioned {
+                continue;
+            }
+            for entry in deprecations
+                .matching(&method, path)
+                .filter(|entry| entry.field.is_none())
+            {
+                append_description(
+                    operation,
+                    &format!(
+                        "Also served at the deprecated unversioned path `{path}` until {}.",
+                        entry.sunset.format("%Y-%m-%d")
+                    ),
+                );
+            }
+        }
+    }
+}
+
+/// Mark the whole operation, or the deprecated query parameter, and document the deprecation headers.
+fn deprecate(operation: &mut Operation, entry: &Deprecation) {
+    let sunset = entry.sunset.format("%Y-%m-%d");
+    match &entry.field {
+        None => {
+            operation.deprecated = Some(Deprecated::True);
+            append_description(operation, &format!("Deprecated, sunset on {sunset}: {}", entry.message));
+        }
+        Some(field) => {
+            for parameter in operation.parameters.iter_mut().flatten() {
+                if &parameter.name == field {
+                    parameter.deprecated = Some(Deprecated::True);
+                }
+            }
+            append_description(
+                operation,
+                &format!(
+                    "The `{field}` field is deprecated, sunset on {sunset}: {}",
+                    entry.message
+                ),
+            );
+        }
+    }
+    for response in operation.responses.responses.values_mut() {
+        let RefOr::T(response) = response else {
+            continue;
+        };
+        response.headers.insert(
+            DEPRECATION_HEADER.to_string(),
+            string_header("When the route or field was deprecated, as `@` and Unix seconds"),
+        );
+        response.headers.insert(
+            SUNSET_HEADER.to_string(),
+            string_header("HTTP date after which it may be removed"),
+        );
+    }
+}
+
+fn append_description(operation: &mut Operation, note: &str) {
+    operation.description = Some(match operation.description.take() {
+        Some(description) if !description.is_empty() => format!("{description}\n\n{note}"),
+        _ => note.to_string(),
+    });
+}
+
+/// Add the rate limit response and the standard headers to every response of the operation.
+fn add_response_headers(operation: &mut Operation) {
+    let too_many_requests = ResponseBuilder::new()
+        .description("Rate limit exceeded")
+        .content(
+            "application/json",
+            ContentBuilder::new()
+                .schema(Some(Ref::from_schema_name("ApiError")))
+                .build(),
+        )
+        .header(
+            RETRY_AFTER.as_str(),
+            integer_header("Seconds until the client can make the next request"),
+        )
+        .build();
+    operation
+        .responses
+        .responses
+        .entry("429".to_string())
+        .or_insert_with(|| too_many_requests.into());
+
+    for response in operation.responses.responses.values_mut() {
+        let RefOr::T(response) = response else {
+            continue;
+        };
+        response.headers.insert(
+            REQUEST_ID_HEADER.to_string(),
+            string_header("Request id, the one the client sent or a generated UUID"),
+        );
+        response.headers.insert(
+            RATE_LIMIT_LIMIT_HEADER.to_string(),
+            integer_header("Requests the client can make in a burst, sent when rate limiting is enabled"),
+        );
+        response.headers.insert(
+            RATE_LIMIT_REMAINING_HEADER.to_string(),
+            integer_header("Requests the client can still make right now, sent when rate limiting is enabled"),
+        );
+        response.headers.insert(
+            RATE_LIMIT_RESET_HEADER.to_string(),
+            integer_header("Seconds until the full burst is available again, sent when rate limiting is enabled"),
+        );
+    }
+}
+
+fn string_header(description: &str) -> Header {
+    HeaderBuilder::new()
+        .schema(Object::with_type(Type::String))
+        .description(Some(description))
+        .build()
+}
+
+fn integer_header(description: &str) -> Header {
+    HeaderBuilder::new()
+        .schema(Object::with_type(Type::Integer))
+        .description(Some(description))
+        .build()
+}
+
+/// Callback request a webhook receives for one event type, with the signature headers.
+fn webhook(event_type: ItemEventType) -> serde_json::Value {
+    json!({
+        "post": {
+            "summary": format!("Item {event_type} event"),
+            "description": format!(
+                "Posted to webhooks subscribed to `{event_type}` events. \
+                Verify `{SIGNATURE_HEADER}` against the HMAC-SHA256 of `{{timestamp}}.{{body}}` keyed with the webhook secret."
+            ),
+            "parameters": [
+                {
+                    "name": SIGNATURE_HEADER.as_str(),
+                    "in": "header",
+                    "required": true,
+                    "description": "`sha256=` followed by the hex HMAC-SHA256 of the timestamp, a dot and the body",
+                    "schema": { "type": "string", "pattern": "^sha256=[0-9a-f]{64}$" },
+                },
+                {
+                    "name": TIMESTAMP_HEADER.as_str(),
+                    "in": "header",
+                    "required": true,
+                    "description": "Unix time the delivery was sent, included in the signature",
+                    "schema": { "type": "integer" },
+                },
+            ],
+            "requestBody": {
+                "required": true,
+                "content": {
+                    "application/json": {
+                        "schema": {
+                            "allOf": [
+                                { "$ref": "#/components/schemas/ItemEvent" },
+                                { "properties": { "type": { "const": event_type.to_string() } } },
+                            ],
+                        },
+                    },
+                },
+            },
+            "responses": {
+                "2XX": { "description": "Delivery accepted" },
+                "default": { "description": "Delivery failed and is retried with backoff" },
+            },
+        },
+    })
+}
+
+/// Schema for item ids generated with the strategy.
+fn id_schema(id_strategy: IdStrategy) -> Schema {
+    let schema = match id_strategy {
+        IdStrategy::Numeric => ObjectBuilder::new()
+            .schema_type(Type::Integer)
+            .minimum(Some(1000))
+            .maximum(Some(9999))
+            .examples([json!(1234)]),
+        IdStrategy::Uuid => ObjectBuilder::new()
+            .schema_type(Type::String)
+            .format(Some(SchemaFormat::Custom("uuid".to_string())))
+            .examples([json!("67e55044-10b1-426f-9247-bb680e5fe0c8")]),
+        IdStrategy::Ulid => ObjectBuilder::new()
+            .schema_type(Type::String)
+            .format(Some(SchemaFormat::Custom("ulid".to_string())))
+            .pattern(Some("^[0-9A-HJKMNP-TV-Z]{26}$"))
+            .examples([json!("01ARZ3NDEKTSV4RRFFQ69G5FAV")]),
+    };
+    schema.into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use chrono::{TimeZone, Utc};
+
+    #[test]
+    fn spec_format_follows_file_extension() {
+        assert_eq!(SpecFormat::from_path(Path::new("openapi.json")), SpecFormat::Json);
+        assert_eq!(SpecFormat::from_path(Path::new("openapi.yaml")), SpecFormat::Yaml);
+        assert_eq!(SpecFormat::from_path(Path::new("openapi.YML")), SpecFormat::Yaml);
+        assert_eq!(SpecFormat::from_path(Path::new("openapi")), SpecFormat::Json);
+    }
+
+    #[test]
+    fn renders_json_and_yaml() {
+        let json =
+            render_spec(SpecFormat::Json, IdStrategy::Numeric, &DeprecationRegistry::default()).expect("JSON spec");
+        let yaml =
+            render_spec(SpecFormat::Yaml, IdStrategy::Numeric, &DeprecationRegistry::default()).expect("YAML spec");
+
+        assert!(json.trim_start().starts_with('{'));
+        assert!(json.contains("\"/items\""));
+        assert!(yaml.starts_with("openapi:"));
+        assert!(yaml.contains("/items:"));
+    }
+
+    #[test]
+    fn item_id_schema_follows_id_strategy() {
+        let spec = |strategy| {
+            serde_json::to_value(api_doc(strategy, &DeprecationRegistry::default())).expect("spec should serialize")
+        };
+
+        let numeric = spec(IdStrategy::Numeric);
+        assert_eq!(numeric["components"]["schemas"]["ItemId"]["type"], "integer");
+        assert_eq!(numeric["components"]["schemas"]["ItemId"]["maximum"], 9999);
+        assert_eq!(
+            numeric["components"]["schemas"]["Item"]["properties"]["id"]["$ref"],
+            "#/components/schemas/ItemId"
+        );
+
+        let ulid = spec(IdStrategy::Ulid);
+        assert_eq!(ulid["components"]["schemas"]["ItemId"]["format"], "ulid");
+    }
+
+    #[test]
+    fn servers_point_to_the_versioned_base_path() {
+        let spec = serde_json::to_value(api_doc(IdStrategy::Numeric, &DeprecationRegistry::default()))
+            .expect("spec should serialize");
+
+        assert_eq!(spec["servers"][0]["url"], "/api/v1");
+        assert_eq!(spec["paths"]["/health"]["servers"][0]["url"], "/");
+        assert!(spec["paths"]["/items"].get("servers").is_none());
+
+        let paths = versioned_paths();
+        assert!(paths.contains(&"/items".to_string()));
+        assert!(paths.contains(&"/admin/remove/{name}".to_string()));
+        assert!(!paths.contains(&"/health".to_string()));
+    }
+
+    #[test]
+    fn every_response_documents_standard_headers() {
+        let spec = serde_json::to_value(api_doc(IdStrategy::Numeric, &DeprecationRegistry::default()))
+            .expect("spec should serialize");
+
+        for (path, item) in spec["paths"].as_object().unwrap() {
+            for (method, operation) in item.as_object().unwrap() {
+                let Some(responses) = operation["responses"].as_object() else {
+                    continue;
+                };
+                assert!(
+                    responses["429"]["headers"].get("retry-after").is_some(),
+                    "{method} {path}"
+                );
+                for (status, response) in responses {
+                    for header in [
+                        "x-request-id",
+                        "x-ratelimit-limit",
+                        "x-ratelimit-remaining",
+                        "x-ratelimit-reset",
+                    ] {
+                        assert!(
+                            response["headers"].get(header).is_some(),
+                            "{method} {path} {status} {header}"
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn webhooks_document_every_event_payload_and_signature() {
+        let spec = serde_json::to_value(api_doc(IdStrategy::Numeric, &DeprecationRegistry::default()))
+            .expect("spec should serialize");
+
+        let webhooks = spec["webhooks"].as_object().expect("spec should have webhooks");
+        assert_eq!(
+            webhooks.keys().map(String::as_str).collect::<Vec<_>>(),
+            [
+                "item_cleared",
+                "item_created",
+                "item_evicted",
+                "item_expired",
+                "item_removed"
+            ]
+        );
+        let created = &webhooks["item_created"]["post"];
+        let schema = &created["requestBody"]["content"]["application/json"]["schema"]["allOf"];
+        assert_eq!(schema[0]["$ref"], "#/components/schemas/ItemEvent");
+        assert_eq!(schema[1]["properties"]["type"]["const"], "created");
+        assert!(spec["components"]["schemas"].get("ItemEvent").is_some());
+        let headers: Vec<&str> = created["parameters"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|parameter| parameter["name"].as_str().unwrap())
+            .collect();
+        assert_eq!(headers, ["x-webhook-signature", "x-webhook-timestamp"]);
+
+        let yaml =
+            render_spec(SpecFormat::Yaml, IdStrategy::Numeric, &DeprecationRegistry::default()).expect("YAML spec");
+        assert!(yaml.contains("webhooks:"));
+    }
+
+    #[test]
+    fn deprecations_are_marked_on_operations() {
+        let deprecated_at = Utc.with_ymd_and_hms(2029, 1, 1, 0, 0, 0).unwrap();
+        let sunset = Utc.with_ymd_and_hms(2030, 1, 1, 0, 0, 0).unwrap();
+        let deprecations = DeprecationRegistry::default()
+            .with_route(
+                Some(Method::DELETE),
+                "/api/v1/admin/clear_items",
+                deprecated_at,
+                sunset,
+                "Use remove",
+                None,
+            )
+            .with_field(
+                None,
+                "/api/v1/items",
+                "skip",
+                deprecated_at,
+                sunset,
+                "Use cursors",
+                None,
+            )
+            .with_route(None, "/version", deprecated_at, sunset, "Use /api/v1/version", None);
+        let spec = serde_json::to_value(api_doc(IdStrategy::Numeric, &deprecations)).expect("spec should serialize");
+
+        let clear = &spec["paths"]["/admin/clear_items"]["delete"];
+        assert_eq!(clear["deprecated"], true);
+        assert!(
+            clear["description"]
+                .as_str()
+                .unwrap()
+                .contains("sunset on 2030-01-01: Use remove")
+        );
+        assert!(clear["responses"]["200"]["headers"].get("sunset").is_some());
+
+        let list = &spec["paths"]["/items"]["get"];
+        assert!(list.get("deprecated").is_none());
+        let skip = list["parameters"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|parameter| parameter["name"] == "skip")
+            .unwrap();
+        assert_eq!(skip["deprecated"], true);
+
+        let version = &spec["paths"]["/version"]["get"];
+        assert!(version.get("deprecated").is_none());
+        assert!(
+            version["description"]
+                .as_str()
+                .unwrap()
+                .contains("deprecated unversioned path `/version` until 2030-01-01")
+        );
+    }
+}
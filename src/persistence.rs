@@ -0,0 +1,96 @@
+//! State file kept across restarts.
+//!
+//! With `state_file` set, the items of the default collection are written to the file
+//! after a graceful shutdown has finished the last requests,
+//! and loaded from it at the next start, so restarts keep the items without a backing store.
+//! The file is a snapshot in the same gzip JSON format as the backups,
+//! so it can also be loaded with `POST /admin/restore` or inspected with `gunzip` and `jq`.
+//! A state file that exists replaces the seed data,
+//! so items removed before the restart do not come back from the seed file.
+
+use std::path::Path;
+
+use anyhow::{Context, Result};
+
+use crate::schemas::RestoreMode;
+use crate::snapshot::Snapshot;
+use crate::types::AppState;
+
+/// Load the items from the state file and return how many there were, `None` if the file does not exist yet.
+///
+/// An unreadable or invalid file is an error, so a broken file fails startup instead of losing the items.
+pub fn load(state: &AppState, path: &Path) -> Result<Option<usize>> {
+    let bytes = match std::fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => {
+            crate::log_info!("No state file at {} yet", path.display());
+            return Ok(None);
+        }
+        Err(error) => return Err(error).with_context(|| format!("Failed to read state file {}", path.display())),
+    };
+    let snapshot = Snapshot::from_gzip(&bytes).with_context(|| format!("Invalid state file {}", path.display()))?;
+    let summary = snapshot.restore(state.items(), RestoreMode::Replace);
+    crate::log_info!(
+        "Loaded {} items from state file {} written at {}",
+        summary.restored,
+        path.display(),
+        snapshot.created_at
+    );
+    Ok(Some(summary.restored))
+}
+
+/// Write the items to the state file and return how many there were.
+///
+/// The snapshot is written to a temporary file first and renamed,
+/// so a failed write leaves the previous state file in place.
+pub fn save(state: &AppState, path: &Path) -> Result<usize> {
+    if let Some(directory) = path.parent().filter(|directory| !directory.as_os_str().is_empty()) {
+        std::fs::create_dir_all(directory)
+            .with_context(|| format!("Failed to create state directory {}", directory.display()))?;
+    }
+    let snapshot = Snapshot::capture(state.items());
+    let partial = path.with_extension("partial");
+    std::fs::write(&partial, snapshot.to_gzip()?)
+        .with_context(|| format!("Failed to write state file {}", partial.display()))?;
+    std::fs::rename(&partial, path).with_context(|| format!("Failed to write state file {}", path.display()))?;
+    crate::log_info!("Saved {} items to state file {}", snapshot.items.len(), path.display());
+    Ok(snapshot.items.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::types::{IdStrategy, Item};
+
+    #[test]
+    fn saved_items_are_loaded_after_restart() {
+        let directory = std::env::temp_dir().join(format!("axum-example-state-{}", std::process::id()));
+        let path = directory.join("state.json.gz");
+        let restarted = AppState::new();
+        assert_eq!(load(&restarted, &path).unwrap(), None);
+
+        let state = AppState::new();
+        for name in ["first", "second"] {
+            state
+                .items()
+                .insert_item(&Item::new_with_random_id(name.to_string(), IdStrategy::Uuid))
+                .expect("new item");
+        }
+        assert_eq!(save(&state, &path).unwrap(), 2);
+        assert!(!path.with_extension("partial").exists());
+
+        restarted
+            .items()
+            .insert_item(&Item::new_with_random_id("stale".to_string(), IdStrategy::Uuid))
+            .expect("new item");
+        assert_eq!(load(&restarted, &path).unwrap(), Some(2));
+        let mut names: Vec<String> = restarted.items().db.iter().map(|entry| entry.key().clone()).collect();
+        names.sort_unstable();
+        assert_eq!(names, ["first", "second"]);
+
+        std::fs::write(&path, b"not gzip").unwrap();
+        assert!(load(&restarted, &path).is_err());
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+}
@@ -0,0 +1,127 @@
+//! Pre-serialized responses.
+//!
+//! Payloads that never change while the server runs, like the version info and the `OpenAPI` spec in JSON and YAML,
+//! are serialized once and every request is served from the same bytes.
+//! The responses carry an `ETag` derived from the body,
+//! so clients revalidating with `If-None-Match` get `304 Not Modified` without a body.
+
+use std::hash::{DefaultHasher, Hasher};
+
+use axum::body::Bytes;
+use axum::http::header::{CONTENT_TYPE, ETAG, IF_NONE_MATCH};
+use axum::http::{HeaderMap, HeaderValue, StatusCode};
+use axum::response::{IntoResponse, Response};
+use serde::Serialize;
+
+/// Body serialized once, with its content type and entity tag.
+#[derive(Debug, Clone)]
+pub struct PrecomputedResponse {
+    body: Bytes,
+    content_type: HeaderValue,
+    etag: HeaderValue,
+}
+
+impl PrecomputedResponse {
+    pub fn new(body: impl Into<Bytes>, content_type: &'static str) -> Self {
+        let body = body.into();
+        let etag = entity_tag(&body);
+        Self {
+            body,
+            content_type: HeaderValue::from_static(content_type),
+            etag,
+        }
+    }
+
+    pub fn json(value: &impl Serialize) -> serde_json::Result<Self> {
+        Ok(Self::new(serde_json::to_vec(value)?, "application/json"))
+    }
+
+    /// The stored body, or `304 Not Modified` when the client already has it.
+    pub fn respond(&self, headers: &HeaderMap) -> Response {
+        if self.is_fresh(headers) {
+            return (StatusCode::NOT_MODIFIED, [(ETAG, self.etag.clone())]).into_response();
+        }
+        (
+            StatusCode::OK,
+            [(CONTENT_TYPE, self.content_type.clone()), (ETAG, self.etag.clone())],
+            self.body.clone(),
+        )
+            .into_response()
+    }
+
+    /// Check if `If-None-Match` lists this entity tag or `*`.
+    ///
+    /// Weak tags compare equal to the strong tag, as required for `If-None-Match`.
+    fn is_fresh(&self, headers: &HeaderMap) -> bool {
+        let Ok(etag) = self.etag.to_str() else {
+            return false;
+        };
+        headers
+            .get_all(IF_NONE_MATCH)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .map(str::trim)
+            .any(|tag| tag == "*" || tag.strip_prefix("W/").unwrap_or(tag) == etag)
+    }
+}
+
+/// Quoted hash of the body.
+fn entity_tag(body: &[u8]) -> HeaderValue {
+    let mut hasher = DefaultHasher::new();
+    hasher.write(body);
+    HeaderValue::from_str(&format!("\"{:016x}\"", hasher.finish())).expect("hex entity tag should be a valid header")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn if_none_match(value: &'static str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(IF_NONE_MATCH, HeaderValue::from_static(value));
+        headers
+    }
+
+    #[test]
+    fn matching_entity_tag_is_not_modified() {
+        let precomputed = PrecomputedResponse::json(&serde_json::json!({ "name": "test" })).unwrap();
+        let etag = precomputed.etag.to_str().unwrap().to_string();
+
+        let response = precomputed.respond(&HeaderMap::new());
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[ETAG], etag.as_str());
+        assert_eq!(response.headers()[CONTENT_TYPE], "application/json");
+
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            IF_NONE_MATCH,
+            HeaderValue::from_str(&format!("\"other\", W/{etag}")).unwrap(),
+        );
+        assert_eq!(precomputed.respond(&headers).status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(
+            precomputed.respond(&if_none_match("*")).status(),
+            StatusCode::NOT_MODIFIED
+        );
+        assert_eq!(
+            precomputed.respond(&if_none_match("\"other\"")).status(),
+            StatusCode::OK
+        );
+    }
+
+    #[test]
+    fn entity_tag_follows_the_body() {
+        let first = PrecomputedResponse::json(&"first").unwrap();
+        let second = PrecomputedResponse::json(&"second").unwrap();
+        assert_ne!(first.etag, second.etag);
+        assert_eq!(first.etag, PrecomputedResponse::json(&"first").unwrap().etag);
+    }
+
+    #[test]
+    fn content_type_is_kept() {
+        let yaml = PrecomputedResponse::new("name: test\n", "application/yaml");
+
+        let response = yaml.respond(&HeaderMap::new());
+        assert_eq!(response.headers()[CONTENT_TYPE], "application/yaml");
+    }
+}
@@ -0,0 +1,130 @@
+//! Optimistic concurrency preconditions.
+//!
+//! Items carry a revision number that increments on every change.
+//! Mutating item routes require `If-Match` with the revision the client last saw,
+//! so a write based on a stale read gets `412 Precondition Failed`
+//! instead of silently overwriting someone else's change.
+
+use axum::extract::FromRequestParts;
+use axum::http::HeaderMap;
+use axum::http::header::IF_MATCH;
+use axum::http::request::Parts;
+use axum::response::{IntoResponse, Response};
+
+use crate::i18n;
+use crate::schemas::{ApiError, ErrorCode};
+
+/// Revision the client expects the resource to be at, from the `If-Match` header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IfMatch {
+    /// `*` matches any existing revision.
+    Any,
+    /// A specific revision, given as `3` or as the entity tag `"3"`.
+    Revision(u64),
+}
+
+/// Rejection for a missing or malformed `If-Match` header.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IfMatchRejection {
+    Missing,
+    Invalid(String),
+}
+
+impl IfMatch {
+    /// Read the precondition from request headers.
+    pub fn from_headers(headers: &HeaderMap) -> Result<Self, IfMatchRejection> {
+        let value = headers.get(IF_MATCH).ok_or(IfMatchRejection::Missing)?;
+        let value = value
+            .to_str()
+            .map_err(|_| IfMatchRejection::Invalid(String::from_utf8_lossy(value.as_bytes()).into_owned()))?;
+        Self::parse(value).ok_or_else(|| IfMatchRejection::Invalid(value.to_string()))
+    }
+
+    /// Check if the current revision satisfies the precondition.
+    #[must_use]
+    pub const fn matches(self, revision: u64) -> bool {
+        match self {
+            Self::Any => true,
+            Self::Revision(expected) => expected == revision,
+        }
+    }
+
+    fn parse(value: &str) -> Option<Self> {
+        let value = value.trim();
+        if value == "*" {
+            return Some(Self::Any);
+        }
+        let value = value
+            .strip_prefix('"')
+            .and_then(|value| value.strip_suffix('"'))
+            .unwrap_or(value);
+        value.parse().ok().map(Self::Revision)
+    }
+}
+
+impl<S> FromRequestParts<S> for IfMatch
+where
+    S: Send + Sync,
+{
+    type Rejection = IfMatchRejection;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Self::from_headers(&parts.headers)
+    }
+}
+
+impl IntoResponse for IfMatchRejection {
+    fn into_response(self) -> Response {
+        match self {
+            Self::Missing => {
+                ApiError::new(ErrorCode::PreconditionRequired, i18n::message("if_match_missing", &[])).into_response()
+            }
+            Self::Invalid(value) => ApiError::new(
+                ErrorCode::BadRequest,
+                i18n::message("if_match_invalid", &[("value", &value)]),
+            )
+            .into_response(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use axum::http::{HeaderValue, StatusCode};
+
+    fn if_match(value: &'static str) -> Result<IfMatch, IfMatchRejection> {
+        let mut headers = HeaderMap::new();
+        headers.insert(IF_MATCH, HeaderValue::from_static(value));
+        IfMatch::from_headers(&headers)
+    }
+
+    #[test]
+    fn parses_revisions_entity_tags_and_wildcard() {
+        assert_eq!(if_match("3"), Ok(IfMatch::Revision(3)));
+        assert_eq!(if_match("\"12\""), Ok(IfMatch::Revision(12)));
+        assert_eq!(if_match(" * "), Ok(IfMatch::Any));
+        assert_eq!(if_match("latest"), Err(IfMatchRejection::Invalid("latest".to_string())));
+        assert_eq!(IfMatch::from_headers(&HeaderMap::new()), Err(IfMatchRejection::Missing));
+    }
+
+    #[test]
+    fn matches_only_the_expected_revision() {
+        assert!(IfMatch::Revision(2).matches(2));
+        assert!(!IfMatch::Revision(1).matches(2));
+        assert!(IfMatch::Any.matches(7));
+    }
+
+    #[test]
+    fn rejections_map_to_expected_statuses() {
+        assert_eq!(
+            IfMatchRejection::Missing.into_response().status(),
+            StatusCode::PRECONDITION_REQUIRED
+        );
+        assert_eq!(
+            IfMatchRejection::Invalid("x".to_string()).into_response().status(),
+            StatusCode::BAD_REQUEST
+        );
+    }
+}
@@ -0,0 +1,216 @@
+//! RFC 7807 problem details.
+//!
+//! Error responses can be sent as `application/problem+json` instead of the `ApiError` body,
+//! for API gateways that standardize on problem details.
+//! Clients ask for them with `Accept: application/problem+json`,
+//! or `error_format = "problem"` sends them to every client.
+//! The conversion wraps the whole router and rewrites the JSON `ApiError` bodies the routes produced,
+//! so handlers and the other middleware keep building `ApiError` as before.
+//! Error bodies negotiated to XML or `MessagePack` are left alone.
+
+use axum::Json;
+use axum::body::to_bytes;
+use axum::extract::{Request, State};
+use axum::http::header::{ACCEPT, CONTENT_LENGTH, CONTENT_TYPE};
+use axum::http::{HeaderMap, HeaderValue, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::schemas::{ApiError, ErrorCode, FieldError, QuotaExceeded};
+use crate::settings::SharedSettings;
+use crate::types::ErrorFormat;
+
+/// Media type of problem details responses.
+pub const PROBLEM_JSON_CONTENT_TYPE: &str = "application/problem+json";
+
+/// Error bodies larger than this are passed through unchanged.
+const MAX_ERROR_BODY_BYTES: usize = 64 * 1024;
+
+/// Error response in the RFC 7807 problem details format.
+///
+/// The `type` is always `about:blank`, so the `title` is the HTTP status reason.
+/// The `ApiError` code, request id, field errors and exceeded quota are included as extension members.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ProblemDetails {
+    #[serde(rename = "type")]
+    #[schema(example = "about:blank")]
+    pub problem_type: String,
+    #[schema(example = "Not Found")]
+    pub title: String,
+    #[schema(example = 404)]
+    pub status: u16,
+    #[schema(example = "Item does not exist: esgrove")]
+    pub detail: String,
+    /// Path of the failed request.
+    #[schema(example = "/api/v1/item")]
+    pub instance: String,
+    pub code: ErrorCode,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub fields: Vec<FieldError>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quota: Option<QuotaExceeded>,
+}
+
+impl ProblemDetails {
+    #[must_use]
+    pub fn new(error: ApiError, status: StatusCode, instance: String) -> Self {
+        Self {
+            problem_type: "about:blank".to_string(),
+            title: status.canonical_reason().unwrap_or("Error").to_string(),
+            status: status.as_u16(),
+            detail: error.message,
+            instance,
+            code: error.code,
+            request_id: error.request_id,
+            fields: error.fields,
+            quota: error.quota,
+        }
+    }
+}
+
+impl IntoResponse for ProblemDetails {
+    fn into_response(self) -> Response {
+        let status = StatusCode::from_u16(self.status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+        let mut response = (status, Json(self)).into_response();
+        response
+            .headers_mut()
+            .insert(CONTENT_TYPE, HeaderValue::from_static(PROBLEM_JSON_CONTENT_TYPE));
+        response
+    }
+}
+
+/// Send JSON error responses as problem details when configured or requested.
+pub async fn problem_middleware(State(settings): State<SharedSettings>, request: Request, next: Next) -> Response {
+    let wants_problem = settings.load().error_format == ErrorFormat::Problem || accepts_problem_json(request.headers());
+    let instance = request.uri().path().to_string();
+    let response = next.run(request).await;
+    let status = response.status();
+    let is_json = response
+        .headers()
+        .get(CONTENT_TYPE)
+        .is_some_and(|value| value.as_bytes().starts_with(b"application/json"));
+    if !wants_problem || !is_json || !(status.is_client_error() || status.is_server_error()) {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let Ok(bytes) = to_bytes(body, MAX_ERROR_BODY_BYTES).await else {
+        crate::log_error!("Failed to read error body for problem details");
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    };
+    let Ok(error) = serde_json::from_slice::<ApiError>(&bytes) else {
+        return Response::from_parts(parts, bytes.into());
+    };
+    let (problem_parts, problem_body) = ProblemDetails::new(error, status, instance)
+        .into_response()
+        .into_parts();
+    // Keep the original headers like `Allow`, `Retry-After` and the request id.
+    parts.headers.remove(CONTENT_LENGTH);
+    parts.headers.extend(problem_parts.headers);
+    Response::from_parts(parts, problem_body)
+}
+
+/// The `Accept` header lists the problem details media type without `q=0`.
+fn accepts_problem_json(headers: &HeaderMap) -> bool {
+    headers
+        .get_all(ACCEPT)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|media_range| {
+            let mut parameters = media_range.split(';').map(str::trim);
+            parameters
+                .next()
+                .is_some_and(|media_type| media_type.eq_ignore_ascii_case(PROBLEM_JSON_CONTENT_TYPE))
+                && parameters.all(|parameter| {
+                    parameter
+                        .strip_prefix("q=")
+                        .and_then(|quality| quality.parse::<f32>().ok())
+                        .is_none_or(|quality| quality > 0.0)
+                })
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use axum::body::Body;
+    use axum::http::Method;
+
+    use crate::settings::Settings;
+    use crate::test_utils::TestApp;
+
+    fn request(uri: &str, accept: Option<&str>) -> Request {
+        let mut builder = Request::builder().method(Method::GET).uri(uri);
+        if let Some(accept) = accept {
+            builder = builder.header(ACCEPT, accept);
+        }
+        builder.body(Body::empty()).unwrap()
+    }
+
+    #[test]
+    fn accept_header_selects_problem_details() {
+        let headers = |value: &'static str| HeaderMap::from_iter([(ACCEPT, HeaderValue::from_static(value))]);
+        assert!(accepts_problem_json(&headers("application/problem+json")));
+        assert!(accepts_problem_json(&headers(
+            "application/json, application/problem+json;q=0.5"
+        )));
+        assert!(!accepts_problem_json(&headers("application/problem+json;q=0")));
+        assert!(!accepts_problem_json(&headers("application/json")));
+        assert!(!accepts_problem_json(&HeaderMap::new()));
+    }
+
+    #[tokio::test]
+    async fn errors_are_problem_details_when_accepted() {
+        let app = TestApp::new();
+        let response = app
+            .send(request("/api/v1/item?name=missing", Some(PROBLEM_JSON_CONTENT_TYPE)))
+            .await;
+        assert_eq!(response.status, StatusCode::NOT_FOUND);
+        assert_eq!(response.headers[CONTENT_TYPE], PROBLEM_JSON_CONTENT_TYPE);
+        let problem: ProblemDetails = response.json();
+        assert_eq!(problem.problem_type, "about:blank");
+        assert_eq!(problem.title, "Not Found");
+        assert_eq!(problem.status, 404);
+        assert_eq!(problem.detail, "Item does not exist: missing");
+        assert_eq!(problem.instance, "/api/v1/item");
+        assert_eq!(problem.code, ErrorCode::NotFound);
+        assert_eq!(
+            problem.request_id.as_deref(),
+            response.headers["x-request-id"].to_str().ok()
+        );
+
+        let response = app.send(request("/api/v1/item?name=missing", None)).await;
+        assert_eq!(response.headers[CONTENT_TYPE], "application/json");
+        assert_eq!(response.json::<ApiError>().code, ErrorCode::NotFound);
+    }
+
+    #[tokio::test]
+    async fn error_format_setting_sends_problem_details_to_every_client() {
+        let app = TestApp::with_settings(Settings {
+            error_format: ErrorFormat::Problem,
+            ..Settings::default()
+        });
+        let response = app.send(request("/missing", None)).await;
+        assert_eq!(response.status, StatusCode::NOT_FOUND);
+        assert_eq!(response.headers[CONTENT_TYPE], PROBLEM_JSON_CONTENT_TYPE);
+        assert_eq!(response.json::<ProblemDetails>().instance, "/missing");
+
+        let mut method_not_allowed = request("/api/v1/items", None);
+        *method_not_allowed.method_mut() = Method::PUT;
+        let response = app.send(method_not_allowed).await;
+        assert!(response.headers.contains_key("allow"));
+        let problem: ProblemDetails = response.json();
+        assert_eq!(problem.title, "Method Not Allowed");
+        assert!(problem.detail.contains("GET, HEAD, POST"));
+
+        // Successful responses are not touched.
+        let response = app.send(request("/health", None)).await;
+        assert_eq!(response.headers[CONTENT_TYPE], "application/json");
+    }
+}
@@ -0,0 +1,357 @@
+//! Per-client request throttling.
+//!
+//! Every client IP gets a token bucket that refills at the configured requests per second.
+//! Requests arriving with an empty bucket get `429 Too Many Requests`
+//! with a `Retry-After` header and an `ApiError` body.
+//! While throttling is enabled, every response tells the client its quota
+//! in the `x-ratelimit-limit`, `x-ratelimit-remaining` and `x-ratelimit-reset` headers.
+//! This is independent of the api key, so it also protects the public routes.
+//! Behind the `admin_access.trusted_proxies`, clients are told apart by their `X-Forwarded-For` address.
+//! Limits are read on every request, so settings changes apply without a restart.
+
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use axum::extract::{ConnectInfo, Request, State};
+use axum::http::header::RETRY_AFTER;
+use axum::http::{HeaderMap, HeaderName, HeaderValue};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use dashmap::DashMap;
+
+use crate::i18n;
+use crate::schemas::{ApiError, ErrorCode};
+use crate::settings::SharedSettings;
+use crate::types::RateLimitConfig;
+
+/// Header with the burst size, the most requests a client can make at once.
+pub const RATE_LIMIT_LIMIT_HEADER: HeaderName = HeaderName::from_static("x-ratelimit-limit");
+
+/// Header with the number of requests the client can still make right now.
+pub const RATE_LIMIT_REMAINING_HEADER: HeaderName = HeaderName::from_static("x-ratelimit-remaining");
+
+/// Header with the seconds until the client's bucket is full again.
+pub const RATE_LIMIT_RESET_HEADER: HeaderName = HeaderName::from_static("x-ratelimit-reset");
+
+/// Drop buckets that have been idle for this long.
+const IDLE_BUCKET_TIMEOUT: Duration = Duration::from_mins(1);
+
+/// How many checks happen between idle bucket cleanups.
+const CLEANUP_INTERVAL_CHECKS: u64 = 1024;
+
+/// Token buckets for every client IP.
+#[derive(Debug)]
+pub struct RateLimiter {
+    settings: SharedSettings,
+    buckets: DashMap<IpAddr, TokenBucket>,
+    checks: AtomicU64,
+}
+
+/// Requests left for a client after a check.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Quota {
+    pub limit: u32,
+    pub remaining: u32,
+    /// Time until the bucket is full again.
+    pub reset: Duration,
+}
+
+/// Rejected check for a client with an empty bucket.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Throttled {
+    pub quota: Quota,
+    /// Time until the next token is available.
+    pub retry_after: Duration,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct TokenBucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl RateLimiter {
+    #[must_use]
+    pub fn new(settings: SharedSettings) -> Self {
+        Self {
+            settings,
+            buckets: DashMap::new(),
+            checks: AtomicU64::new(0),
+        }
+    }
+
+    /// Take one token for the client with the given limit.
+    ///
+    /// Returns the remaining quota, and also the time until the next token is available when the bucket is empty.
+    /// The limit is passed in so a request uses the same limit from start to end,
+    /// and a zero rate allows every request with a full quota.
+    pub fn check(&self, client: IpAddr, limit: RateLimitConfig, now: Instant) -> Result<Quota, Throttled> {
+        let burst = limit.burst.max(1);
+        if !limit.is_enabled() {
+            return Ok(Quota {
+                limit: burst,
+                remaining: burst,
+                reset: Duration::ZERO,
+            });
+        }
+        if self
+            .checks
+            .fetch_add(1, Ordering::Relaxed)
+            .is_multiple_of(CLEANUP_INTERVAL_CHECKS)
+        {
+            self.remove_idle_buckets(now);
+        }
+
+        let rate = f64::from(limit.requests_per_second);
+        let capacity = f64::from(burst);
+        let mut bucket = self.buckets.entry(client).or_insert(TokenBucket {
+            tokens: capacity,
+            updated: now,
+        });
+        let elapsed = now.saturating_duration_since(bucket.updated).as_secs_f64();
+        bucket.tokens = elapsed.mul_add(rate, bucket.tokens).min(capacity);
+        bucket.updated = now;
+
+        let allowed = bucket.tokens >= 1.0;
+        if allowed {
+            bucket.tokens -= 1.0;
+        }
+        let quota = Quota {
+            limit: burst,
+            // Truncation is intended, partial tokens do not allow a request.
+            #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+            remaining: bucket.tokens.floor() as u32,
+            reset: Duration::from_secs_f64((capacity - bucket.tokens) / rate),
+        };
+        if allowed {
+            Ok(quota)
+        } else {
+            Err(Throttled {
+                quota,
+                retry_after: Duration::from_secs_f64((1.0 - bucket.tokens) / rate),
+            })
+        }
+    }
+
+    /// Current limit from the settings.
+    pub fn limit(&self) -> RateLimitConfig {
+        self.settings.load().rate_limit
+    }
+
+    fn remove_idle_buckets(&self, now: Instant) {
+        self.buckets
+            .retain(|_, bucket| now.saturating_duration_since(bucket.updated) < IDLE_BUCKET_TIMEOUT);
+    }
+}
+
+/// Reject requests from clients that exceed their request rate.
+pub async fn rate_limit_middleware(State(limiter): State<Arc<RateLimiter>>, request: Request, next: Next) -> Response {
+    let settings = limiter.settings.load();
+    let limit = settings.rate_limit;
+    if !limit.is_enabled() {
+        return next.run(request).await;
+    }
+
+    // Behind trusted proxies the bucket belongs to the forwarded client, not the load balancer.
+    let peer = request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map_or(IpAddr::V4(Ipv4Addr::UNSPECIFIED), |ConnectInfo(address)| address.ip());
+    let client = settings.admin_access.client_address(peer, request.headers());
+    drop(settings);
+
+    match limiter.check(client, limit, Instant::now()) {
+        Ok(quota) => {
+            let mut response = next.run(request).await;
+            insert_quota_headers(response.headers_mut(), quota);
+            response
+        }
+        Err(Throttled { quota, retry_after }) => {
+            let seconds = whole_seconds(retry_after);
+            crate::log_warn!(
+                "Rate limit exceeded for {client}: {} {}",
+                request.method(),
+                request.uri().path()
+            );
+            let mut response = (
+                [(RETRY_AFTER, seconds.to_string())],
+                ApiError::new(
+                    ErrorCode::RateLimited,
+                    i18n::message("rate_limited", &[("seconds", &seconds)]),
+                ),
+            )
+                .into_response();
+            insert_quota_headers(response.headers_mut(), quota);
+            response
+        }
+    }
+}
+
+fn insert_quota_headers(headers: &mut HeaderMap, quota: Quota) {
+    headers.insert(RATE_LIMIT_LIMIT_HEADER, HeaderValue::from(quota.limit));
+    headers.insert(RATE_LIMIT_REMAINING_HEADER, HeaderValue::from(quota.remaining));
+    headers.insert(RATE_LIMIT_RESET_HEADER, HeaderValue::from(whole_seconds(quota.reset)));
+}
+
+/// Headers only support whole seconds, so round up.
+fn whole_seconds(duration: Duration) -> u64 {
+    duration.as_secs() + u64::from(duration.subsec_nanos() > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::settings::Settings;
+
+    fn limiter(requests_per_second: u32, burst: u32) -> RateLimiter {
+        RateLimiter::new(
+            Settings {
+                rate_limit: RateLimitConfig {
+                    requests_per_second,
+                    burst,
+                },
+                ..Settings::default()
+            }
+            .into_shared(),
+        )
+    }
+
+    #[test]
+    fn bucket_allows_burst_then_throttles() {
+        let limiter = limiter(2, 3);
+        let client = IpAddr::V4(Ipv4Addr::LOCALHOST);
+        let now = Instant::now();
+
+        for remaining in (0..3).rev() {
+            assert_eq!(
+                limiter.check(client, limiter.limit(), now).unwrap().remaining,
+                remaining
+            );
+        }
+        let throttled = limiter
+            .check(client, limiter.limit(), now)
+            .expect_err("bucket should be empty");
+        assert_eq!(throttled.retry_after, Duration::from_millis(500));
+        assert_eq!(
+            throttled.quota,
+            Quota {
+                limit: 3,
+                remaining: 0,
+                reset: Duration::from_millis(1500),
+            }
+        );
+    }
+
+    #[test]
+    fn bucket_refills_over_time() {
+        let limiter = limiter(1, 1);
+        let client = IpAddr::V4(Ipv4Addr::LOCALHOST);
+        let now = Instant::now();
+
+        assert!(limiter.check(client, limiter.limit(), now).is_ok());
+        assert!(limiter.check(client, limiter.limit(), now).is_err());
+        assert!(
+            limiter
+                .check(client, limiter.limit(), now + Duration::from_secs(1))
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn clients_have_separate_buckets() {
+        let limiter = limiter(1, 1);
+        let now = Instant::now();
+
+        assert!(
+            limiter
+                .check(IpAddr::V4(Ipv4Addr::LOCALHOST), limiter.limit(), now)
+                .is_ok()
+        );
+        assert!(
+            limiter
+                .check(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)), limiter.limit(), now)
+                .is_ok()
+        );
+        assert!(
+            limiter
+                .check(IpAddr::V4(Ipv4Addr::LOCALHOST), limiter.limit(), now)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn idle_buckets_are_removed() {
+        let limiter = limiter(1, 1);
+        let now = Instant::now();
+        limiter
+            .check(IpAddr::V4(Ipv4Addr::LOCALHOST), limiter.limit(), now)
+            .ok();
+
+        limiter.remove_idle_buckets(now + IDLE_BUCKET_TIMEOUT);
+
+        assert!(limiter.buckets.is_empty());
+    }
+
+    #[test]
+    fn settings_changes_apply_to_running_limiter() {
+        let limiter = limiter(0, 0);
+        assert!(!limiter.limit().is_enabled());
+
+        limiter.settings.store(Arc::new(Settings {
+            rate_limit: RateLimitConfig {
+                requests_per_second: 1,
+                burst: 1,
+            },
+            ..Settings::default()
+        }));
+
+        assert!(limiter.limit().is_enabled());
+        let now = Instant::now();
+        assert!(
+            limiter
+                .check(IpAddr::V4(Ipv4Addr::LOCALHOST), limiter.limit(), now)
+                .is_ok()
+        );
+        assert!(
+            limiter
+                .check(IpAddr::V4(Ipv4Addr::LOCALHOST), limiter.limit(), now)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn partial_seconds_round_up() {
+        assert_eq!(whole_seconds(Duration::ZERO), 0);
+        assert_eq!(whole_seconds(Duration::from_millis(1)), 1);
+        assert_eq!(whole_seconds(Duration::from_secs(2)), 2);
+    }
+
+    #[test]
+    fn zero_rate_disables_throttling() {
+        assert!(!limiter(0, 10).limit().is_enabled());
+        assert!(limiter(1, 10).limit().is_enabled());
+
+        // A limit loaded before a reload to zero keeps throttling the request it was loaded for,
+        // and the zero limit allows every request without dividing by the rate.
+        let limiter = limiter(1, 1);
+        let client = IpAddr::V4(Ipv4Addr::LOCALHOST);
+        let now = Instant::now();
+        let limit = limiter.limit();
+        limiter.settings.store(Arc::new(Settings {
+            rate_limit: RateLimitConfig {
+                requests_per_second: 0,
+                burst: 0,
+            },
+            ..Settings::default()
+        }));
+        assert!(limiter.check(client, limit, now).is_ok());
+        assert!(limiter.check(client, limit, now).is_err());
+        for _ in 0..3 {
+            let quota = limiter.check(client, limiter.limit(), now).expect("throttling is off");
+            assert_eq!(quota.reset, Duration::ZERO);
+        }
+    }
+}
@@ -0,0 +1,371 @@
+//! Request recording and replay.
+//!
+//! With `record_file` set, every request is appended to the file as a JSON line
+//! with its method, path, headers, body and the status it got.
+//! The `replay` subcommand sends the recorded requests in order through an in-process router
+//! and reports the ones that got a different status,
+//! so a sample of real traffic can be reproduced locally.
+//! Credential headers are never written to the file,
+//! replayed requests use the api key from the local settings instead.
+
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex, PoisonError};
+
+use anyhow::{Context, Result};
+use axum::Router;
+use axum::body::{Body, to_bytes};
+use axum::extract::{Request, State};
+use axum::http::header::{AUTHORIZATION, COOKIE};
+use axum::http::{HeaderMap, HeaderName, Method, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use chrono::{DateTime, Utc};
+use http_body_util::BodyExt;
+use serde::{Deserialize, Serialize};
+use tower::ServiceExt;
+
+use crate::i18n;
+use crate::router::build_router;
+use crate::schemas::{ApiError, ErrorCode};
+use crate::settings::Settings;
+use crate::telemetry::Telemetry;
+use crate::types::AppState;
+
+/// Header with the api key for admin routes.
+const API_KEY_HEADER: HeaderName = HeaderName::from_static("api-key");
+
+/// Headers that are left out of the recording.
+const CREDENTIAL_HEADERS: [HeaderName; 3] = [API_KEY_HEADER, AUTHORIZATION, COOKIE];
+
+/// Largest request body that is recorded, the same as the axum default body limit.
+const MAX_RECORDED_BODY_BYTES: usize = 2 * 1024 * 1024;
+
+/// One line of the recording file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecordedRequest {
+    pub recorded_at: DateTime<Utc>,
+    pub method: String,
+    /// Path with the query string.
+    pub uri: String,
+    /// Header names and values in the order they were received, without credentials.
+    #[serde(default)]
+    pub headers: Vec<(String, String)>,
+    /// Base64 encoded request body.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub body: String,
+    /// Status of the recorded response.
+    pub status: u16,
+}
+
+/// Appends requests to the recording file.
+#[derive(Debug)]
+pub struct RequestRecorder {
+    file: Mutex<BufWriter<File>>,
+}
+
+/// Result of a replay run.
+#[derive(Debug, Default)]
+pub struct ReplayReport {
+    replayed: usize,
+    mismatches: Vec<StatusMismatch>,
+}
+
+/// Replayed request that got a different status than when it was recorded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StatusMismatch {
+    /// One-based line number in the recording file.
+    pub line: usize,
+    pub method: String,
+    pub uri: String,
+    pub recorded: u16,
+    pub replayed: u16,
+}
+
+impl RecordedRequest {
+    /// Build the request to send again, with the api key from the local settings.
+    fn to_request(&self, api_key: &str) -> Result<Request> {
+        let body = BASE64.decode(&self.body).context("Invalid body encoding")?;
+        let mut builder = Request::builder()
+            .method(Method::from_bytes(self.method.as_bytes()).context("Invalid method")?)
+            .uri(&self.uri);
+        for (name, value) in &self.headers {
+            builder = builder.header(name, value);
+        }
+        Ok(builder.header(API_KEY_HEADER, api_key).body(Body::from(body))?)
+    }
+}
+
+impl RequestRecorder {
+    /// Open the recording file for appending, creating it if needed.
+    pub fn create(path: &Path) -> Result<Self> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .with_context(|| format!("Failed to open record file {}", path.display()))?;
+        Ok(Self {
+            file: Mutex::new(BufWriter::new(file)),
+        })
+    }
+
+    /// Append the request as a single line.
+    ///
+    /// Each line is flushed right away, so the file is complete up to the last finished request.
+    fn write(&self, request: &RecordedRequest) -> Result<()> {
+        let line = serde_json::to_string(request)?;
+        let mut file = self.file.lock().unwrap_or_else(PoisonError::into_inner);
+        writeln!(file, "{line}")?;
+        file.flush()?;
+        drop(file);
+        Ok(())
+    }
+}
+
+impl ReplayReport {
+    /// Total requests sent.
+    #[must_use]
+    pub const fn num_replayed(&self) -> usize {
+        self.replayed
+    }
+
+    #[must_use]
+    pub fn mismatches(&self) -> &[StatusMismatch] {
+        &self.mismatches
+    }
+}
+
+impl fmt::Display for ReplayReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Replayed {} requests, {} with a different status",
+            self.replayed,
+            self.mismatches.len()
+        )?;
+        for mismatch in &self.mismatches {
+            writeln!(
+                f,
+                "line {}: {} {} recorded {}, replayed {}",
+                mismatch.line, mismatch.method, mismatch.uri, mismatch.recorded, mismatch.replayed
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Record the request and the status it got to the recording file.
+///
+/// A failed write is logged and does not affect the response.
+pub async fn recording_middleware(
+    State(recorder): State<Arc<RequestRecorder>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let (parts, body) = request.into_parts();
+    let Ok(body) = to_bytes(body, MAX_RECORDED_BODY_BYTES).await else {
+        return ApiError::new(ErrorCode::PayloadTooLarge, i18n::message("request_body_too_large", &[])).into_response();
+    };
+    let mut line = RecordedRequest {
+        recorded_at: Utc::now(),
+        method: parts.method.to_string(),
+        uri: parts
+            .uri
+            .path_and_query()
+            .map_or_else(|| parts.uri.path().to_string(), ToString::to_string),
+        headers: recorded_headers(&parts.headers),
+        body: BASE64.encode(&body),
+        status: 0,
+    };
+    let response = next.run(Request::from_parts(parts, Body::from(body))).await;
+    line.status = response.status().as_u16();
+    if let Err(error) = recorder.write(&line) {
+        crate::log_error!("Failed to record request: {error:#}");
+    }
+    response
+}
+
+/// Read the recorded requests from a file written by [`recording_middleware`].
+pub fn read(path: &Path) -> Result<Vec<RecordedRequest>> {
+    let contents =
+        std::fs::read_to_string(path).with_context(|| format!("Failed to read record file {}", path.display()))?;
+    contents
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(index, line)| {
+            serde_json::from_str(line)
+                .with_context(|| format!("Invalid record file {} line {}", path.display(), index + 1))
+        })
+        .collect()
+}
+
+/// Send the recorded requests through a new in-process router and compare the statuses.
+///
+/// Requests are sent one at a time in the recorded order,
+/// to a state that starts from the seed file and uses the deterministic settings,
+/// so repeated replays of the same file behave the same.
+pub async fn replay(path: &Path, settings: Settings) -> Result<ReplayReport> {
+    let requests = read(path)?;
+    let state = Arc::new(
+        AppState::new_with_telemetry(Telemetry::noop(), &settings.storage).with_deterministic(&settings.deterministic),
+    );
+    crate::seed::load(&state, &settings)?;
+    let api_key = settings.api_key.clone();
+    let router = build_router(&state, &settings.into_shared());
+    let mut report = ReplayReport::default();
+    for (index, recorded) in requests.iter().enumerate() {
+        let status = send(&router, recorded, &api_key)
+            .await
+            .with_context(|| format!("Failed to replay line {}", index + 1))?;
+        report.replayed += 1;
+        if status.as_u16() != recorded.status {
+            report.mismatches.push(StatusMismatch {
+                line: index + 1,
+                method: recorded.method.clone(),
+                uri: recorded.uri.clone(),
+                recorded: recorded.status,
+                replayed: status.as_u16(),
+            });
+        }
+    }
+    Ok(report)
+}
+
+/// Send a recorded request, read the whole response body, and return the status.
+async fn send(router: &Router, recorded: &RecordedRequest, api_key: &str) -> Result<StatusCode> {
+    let response = router.clone().oneshot(recorded.to_request(api_key)?).await?;
+    let status = response.status();
+    response.into_body().collect().await?;
+    Ok(status)
+}
+
+/// Header names and values to record, leaving out credentials and values that are not text.
+fn recorded_headers(headers: &HeaderMap) -> Vec<(String, String)> {
+    headers
+        .iter()
+        .filter(|(name, _)| !CREDENTIAL_HEADERS.contains(name))
+        .filter_map(|(name, value)| value.to_str().ok().map(|value| (name.to_string(), value.to_string())))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use axum::http::HeaderValue;
+    use axum::middleware::from_fn_with_state;
+    use axum::routing::post;
+    use serde_json::json;
+
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("axum-example-{name}-{}.ndjson", std::process::id()))
+    }
+
+    #[test]
+    fn credential_headers_are_not_recorded() {
+        let mut headers = HeaderMap::new();
+        headers.insert(API_KEY_HEADER, HeaderValue::from_static("secret"));
+        headers.insert(AUTHORIZATION, HeaderValue::from_static("Basic c2VjcmV0"));
+        headers.insert("content-type", HeaderValue::from_static("application/json"));
+
+        assert_eq!(
+            recorded_headers(&headers),
+            [("content-type".to_string(), "application/json".to_string())]
+        );
+    }
+
+    #[tokio::test]
+    async fn middleware_records_request_and_status() {
+        let path = temp_path("record");
+        std::fs::remove_file(&path).ok();
+        let recorder = Arc::new(RequestRecorder::create(&path).expect("record file should open"));
+        let app = Router::new()
+            .route("/echo", post(|body: String| async move { (StatusCode::CREATED, body) }))
+            .layer(from_fn_with_state(recorder, recording_middleware));
+
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri("/echo?verbose=true")
+            .header(API_KEY_HEADER, "secret")
+            .body(Body::from("hello"))
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!(body, "hello");
+
+        let lines = read(&path).expect("record file should parse");
+        assert_eq!(lines.len(), 1);
+        assert_eq!(lines[0].method, "POST");
+        assert_eq!(lines[0].uri, "/echo?verbose=true");
+        assert_eq!(lines[0].status, 201);
+        assert_eq!(BASE64.decode(&lines[0].body).unwrap(), b"hello");
+        assert!(lines[0].headers.is_empty());
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[tokio::test]
+    async fn oversized_request_bodies_are_rejected() {
+        let path = temp_path("oversized");
+        let recorder = Arc::new(RequestRecorder::create(&path).expect("record file should open"));
+        let app = Router::new()
+            .route("/echo", post(|body: String| async move { body }))
+            .layer(from_fn_with_state(recorder, recording_middleware));
+
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri("/echo")
+            .body(Body::from(vec![b'a'; MAX_RECORDED_BODY_BYTES + 1]))
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["message"], "Request body is too large");
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[tokio::test]
+    async fn replay_reports_requests_with_a_different_status() {
+        let path = temp_path("replay");
+        let create = |status| RecordedRequest {
+            recorded_at: Utc::now(),
+            method: "POST".to_string(),
+            uri: "/api/v1/items".to_string(),
+            headers: vec![("content-type".to_string(), "application/json".to_string())],
+            body: BASE64.encode(json!({ "name": "replayed" }).to_string()),
+            status,
+        };
+        let lines = [create(201), create(201)]
+            .iter()
+            .map(|request| serde_json::to_string(request).unwrap())
+            .collect::<Vec<_>>()
+            .join("\n");
+        std::fs::write(&path, lines).expect("write record file");
+
+        let report = replay(&path, Settings::default()).await.expect("replay should run");
+
+        assert_eq!(report.num_replayed(), 2);
+        assert_eq!(
+            report.mismatches(),
+            [StatusMismatch {
+                line: 2,
+                method: "POST".to_string(),
+                uri: "/api/v1/items".to_string(),
+                recorded: 201,
+                replayed: 409,
+            }]
+        );
+        assert!(
+            report
+                .to_string()
+                .contains("line 2: POST /api/v1/items recorded 201, replayed 409")
+        );
+        std::fs::remove_file(&path).ok();
+    }
+}
@@ -0,0 +1,345 @@
+//! Replication between instances.
+//!
+//! With `replication.peers` set, every item change made through the API is posted as an item event
+//! to `POST /api/v1/admin/replicate` on each peer with the api key,
+//! and the peer applies it to its default collection,
+//! so replicas behind a load balancer converge on the same items.
+//! Each peer has its own queue and sender task, so changes arrive in the order they were made
+//! and a peer that is down does not hold up the others.
+//! Failed sends are retried with backoff until the attempts run out.
+//!
+//! Applied changes are published with the `replication` actor,
+//! which are not sent on again and not delivered to webhooks,
+//! so each change is delivered once by the instance that made it.
+//! When two replicas create an item with the same name, the one changed last wins on both.
+//! Expiry and eviction run on every instance and are not sent,
+//! and tenant stores, named collections, restores and seeding stay local.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::body::Bytes;
+use axum::http::header::CONTENT_TYPE;
+use axum::http::{Method, Request};
+use http_body_util::Full;
+use hyper_util::client::legacy::Client;
+use hyper_util::client::legacy::connect::HttpConnector;
+use hyper_util::rt::TokioExecutor;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+use crate::events::{Actor, EVENT_CHANNEL_CAPACITY, ItemChange, ItemEvent, next_event};
+use crate::router::API_V1_PREFIX;
+use crate::schemas::{FieldError, item_name_violation};
+use crate::settings::SharedSettings;
+use crate::types::{Item, SharedState};
+use crate::validation::Validate;
+use crate::webhooks::RetryPolicy;
+
+/// Admin route that peers post their item events to.
+pub const REPLICATE_PATH: &str = "/admin/replicate";
+
+/// How long one send may take before it counts as failed.
+const SEND_TIMEOUT: Duration = Duration::from_secs(10);
+
+type HttpClient = Client<HttpConnector, Full<Bytes>>;
+
+/// Serialized event and the api key to send it with.
+#[derive(Debug, Clone)]
+struct Outgoing {
+    body: Bytes,
+    api_key: String,
+}
+
+impl Validate for ItemEvent {
+    fn validate(&self) -> Vec<FieldError> {
+        match &self.change {
+            ItemChange::Created { item }
+            | ItemChange::Removed { item }
+            | ItemChange::Expired { item }
+            | ItemChange::Evicted { item, .. } => item_name_violation(&item.name)
+                .map(|message| vec![FieldError::new("item.name", &message)])
+                .unwrap_or_default(),
+            ItemChange::Cleared { .. } => Vec::new(),
+        }
+    }
+}
+
+/// Whether the event is a change made on this instance through the API.
+#[must_use]
+pub const fn is_replicated(event: &ItemEvent) -> bool {
+    matches!(event.actor, Actor::Client | Actor::Admin)
+}
+
+/// Apply a change made on a peer to the default collection and publish it with the `replication` actor.
+///
+/// A created item replaces a local item with the same name unless the local one was changed later,
+/// and is skipped if its id belongs to a local item with another name,
+/// and removals only remove the item with the same id.
+/// Returns whether the collection changed.
+pub fn apply(state: &SharedState, change: ItemChange) -> bool {
+    let items = state.items();
+    let applied = match &change {
+        ItemChange::Created { item } => {
+            let is_newer =
+                |stored: &Item| (stored.updated_at, stored.id.to_string()) < (item.updated_at, item.id.to_string());
+            let id_owner = items.get_item_by_id(&item.id).filter(|owner| owner.name != item.name);
+            if items.db.get(&item.name).is_some_and(|stored| !is_newer(&stored)) {
+                false
+            } else if let Some(owner) = id_owner {
+                crate::log_warn!(
+                    "Replicated item {} conflicts with local item {} that has the same id {}",
+                    item.name,
+                    owner.name,
+                    item.id
+                );
+                false
+            } else {
+                let replaced = items.remove_item_if(&item.name, is_newer);
+                items
+                    .insert_item(item)
+                    .inspect_err(|duplicate| {
+                        crate::log_warn!(
+                            "Replicated item {} conflicts with a local item: {duplicate:?}",
+                            item.name
+                        );
+                        // Put the replaced item back, so a failed insert leaves the collection as it was.
+                        if let Some(replaced) = &replaced {
+                            items.insert_item(replaced).ok();
+                        }
+                    })
+                    .is_ok()
+            }
+        }
+        ItemChange::Removed { item } | ItemChange::Expired { item } | ItemChange::Evicted { item, .. } => items
+            .remove_item_if(&item.name, |stored| stored.id == item.id)
+            .is_some(),
+        ItemChange::Cleared { .. } => {
+            items.clear_items();
+            true
+        }
+    };
+    if applied {
+        crate::log_debug!("Applied replicated {} event", change.event_type());
+        state.publish(Actor::Replication, change);
+    }
+    applied
+}
+
+/// Send the item changes made on this instance to the configured peers.
+///
+/// The peers and api key are read for every event, so peers can be changed without a restart.
+pub fn spawn_sender(state: &SharedState, settings: &SharedSettings, retry: RetryPolicy) -> JoinHandle<()> {
+    let mut receiver = state.events().subscribe();
+    let metrics = state.telemetry().metrics();
+    let settings = Arc::clone(settings);
+    let client: HttpClient = Client::builder(TokioExecutor::new()).build_http();
+    tokio::spawn(async move {
+        let mut queues: HashMap<String, mpsc::Sender<Outgoing>> = HashMap::new();
+        while let Some(event) = next_event(&mut receiver, "replication", &metrics).await {
+            if !is_replicated(&event) {
+                continue;
+            }
+            let (peers, api_key) = {
+                let settings = settings.load();
+                (settings.replication.peers.clone(), settings.api_key.clone())
+            };
+            if peers.is_empty() {
+                continue;
+            }
+            let body = match serde_json::to_vec(&event) {
+                Ok(body) => Bytes::from(body),
+                Err(error) => {
+                    crate::log_error!("Failed to serialize item event: {error}");
+                    continue;
+                }
+            };
+            for peer in peers {
+                let queue = queues
+                    .entry(peer.clone())
+                    .or_insert_with(|| spawn_peer(client.clone(), &peer, retry));
+                let outgoing = Outgoing {
+                    body: body.clone(),
+                    api_key: api_key.clone(),
+                };
+                if queue.try_send(outgoing).is_err() {
+                    crate::log_warn!(
+                        "Replication queue for {peer} is full, dropped {} event",
+                        event.change.event_type()
+                    );
+                }
+            }
+        }
+    })
+}
+
+/// Start the task that sends queued events to one peer in order.
+fn spawn_peer(client: HttpClient, peer: &str, retry: RetryPolicy) -> mpsc::Sender<Outgoing> {
+    let (sender, mut receiver) = mpsc::channel(EVENT_CHANNEL_CAPACITY);
+    let url = format!("{}{API_V1_PREFIX}{REPLICATE_PATH}", peer.trim_end_matches('/'));
+    tokio::spawn(async move {
+        while let Some(outgoing) = receiver.recv().await {
+            deliver(&client, &url, outgoing, retry).await;
+        }
+    });
+    sender
+}
+
+/// Post the event until the peer accepts it or the attempts run out.
+async fn deliver(client: &HttpClient, url: &str, outgoing: Outgoing, retry: RetryPolicy) {
+    let mut error = String::new();
+    for attempt in 1..=retry.max_attempts {
+        if attempt > 1 {
+            tokio::time::sleep(retry.delay_before(attempt)).await;
+        }
+        match send(client, url, &outgoing).await {
+            Ok(status) if (200..300).contains(&status) => return,
+            Ok(status) => error = format!("status {status}"),
+            Err(message) => error = message,
+        }
+    }
+    crate::log_warn!(
+        "Replication to {url} gave up after {} attempts: {error}",
+        retry.max_attempts
+    );
+}
+
+/// Send one event and return the response status.
+async fn send(client: &HttpClient, url: &str, outgoing: &Outgoing) -> Result<u16, String> {
+    let request = Request::builder()
+        .method(Method::POST)
+        .uri(url)
+        .header(CONTENT_TYPE, "application/json")
+        .header("api-key", &outgoing.api_key)
+        .body(Full::new(outgoing.body.clone()))
+        .map_err(|error| error.to_string())?;
+    match tokio::time::timeout(SEND_TIMEOUT, client.request(request)).await {
+        Ok(Ok(response)) => Ok(response.status().as_u16()),
+        Ok(Err(error)) => Err(error.to_string()),
+        Err(_) => Err(format!("No response in {} seconds", SEND_TIMEOUT.as_secs())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use axum::http::StatusCode;
+    use chrono::{TimeDelta, Utc};
+    use serde_json::json;
+
+    use crate::settings::Settings;
+    use crate::test_utils::TestApp;
+    use crate::types::{AppState, IdStrategy, ItemId, ReplicationConfig};
+
+    fn item(name: &str, id: u64, age: TimeDelta) -> Item {
+        let mut item = Item::new(name.to_string(), ItemId::Numeric(id), IdStrategy::Numeric).expect("valid item");
+        item.updated_at = Utc::now() - age;
+        item
+    }
+
+    #[test]
+    fn applying_changes_keeps_the_latest_item() {
+        let state = AppState::new_shared_state();
+        let mut events = state.events().subscribe();
+        let items = state.items();
+        items
+            .insert_item(&item("shared", 1001, TimeDelta::hours(1)))
+            .expect("new item");
+
+        let newer = item("shared", 1002, TimeDelta::zero());
+        assert!(apply(&state, ItemChange::Created { item: newer.clone() }));
+        assert!(!apply(
+            &state,
+            ItemChange::Created {
+                item: item("shared", 1003, TimeDelta::hours(2))
+            }
+        ));
+        assert!(!apply(&state, ItemChange::Created { item: newer.clone() }));
+        assert_eq!(items.db.get("shared").map(|stored| stored.id), Some(newer.id));
+
+        assert!(!apply(
+            &state,
+            ItemChange::Removed {
+                item: item("shared", 1001, TimeDelta::zero())
+            }
+        ));
+        assert!(apply(&state, ItemChange::Removed { item: newer }));
+        assert!(items.db.is_empty());
+
+        items
+            .insert_item(&item("local", 1004, TimeDelta::zero()))
+            .expect("new item");
+        assert!(apply(&state, ItemChange::Cleared { count: 0 }));
+        assert!(items.db.is_empty());
+
+        let actors: Vec<Actor> = std::iter::from_fn(|| events.try_recv().ok())
+            .map(|event| event.actor)
+            .collect();
+        assert_eq!(actors, [Actor::Replication; 3]);
+    }
+
+    #[test]
+    fn created_item_with_a_taken_id_keeps_the_local_items() {
+        let state = AppState::new_shared_state();
+        let mut events = state.events().subscribe();
+        let items = state.items();
+        let local = item("shared", 1001, TimeDelta::hours(1));
+        let other = item("other", 1002, TimeDelta::hours(1));
+        items.insert_item(&local).expect("new item");
+        items.insert_item(&other).expect("new item");
+
+        assert!(!apply(
+            &state,
+            ItemChange::Created {
+                item: item("shared", 1002, TimeDelta::zero())
+            }
+        ));
+        assert_eq!(items.db.get("shared").map(|stored| stored.id), Some(local.id));
+        assert_eq!(items.db.get("other").map(|stored| stored.id), Some(other.id));
+        assert!(events.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn changes_are_sent_to_peers() {
+        let peer = TestApp::new();
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let peer_router = peer.router.clone();
+        let peer_server = tokio::spawn(async move { axum::serve(listener, peer_router).await });
+
+        let app = TestApp::with_settings(Settings {
+            replication: ReplicationConfig {
+                peers: vec![format!("http://{address}/")],
+            },
+            ..Settings::default()
+        });
+        let sender = spawn_sender(&app.state, &app.settings, RetryPolicy::default());
+        let mut peer_events = peer.state.events().subscribe();
+        let created = app.create_item(json!({ "name": "replicated" })).await;
+        app.create_item(json!({ "name": "second" })).await;
+        assert_eq!(app.delete("/admin/clear_items").await.status, StatusCode::OK);
+
+        let mut received = Vec::new();
+        while received.len() < 3 {
+            let event = tokio::time::timeout(Duration::from_secs(5), peer_events.recv())
+                .await
+                .expect("replicated event")
+                .unwrap();
+            received.push(event);
+        }
+        sender.abort();
+        peer_server.abort();
+
+        assert!(received.iter().all(|event| event.actor == Actor::Replication));
+        assert_eq!(received[0].change, ItemChange::Created { item: created });
+        assert_eq!(received[2].change, ItemChange::Cleared { count: 2 });
+        assert!(peer.state.items().db.is_empty());
+
+        let response = peer
+            .post_json(REPLICATE_PATH, &json!({ "occurred_at": Utc::now(), "actor": "client", "type": "created", "item": { "id": 1234, "name": "", "revision": 1, "created_at": Utc::now(), "updated_at": Utc::now() } }))
+            .await;
+        assert_eq!(response.status, StatusCode::UNPROCESSABLE_ENTITY);
+    }
+}
@@ -0,0 +1,163 @@
+//! Request ids and error bodies.
+//!
+//! Every request gets an id from the `x-request-id` header, or a new UUID when the client did not send one.
+//! The id is echoed back in the response header and included in every `ApiError` body,
+//! so a failed request can be matched with the server logs.
+//! Error responses that do not come from the handlers,
+//! like extractor rejections and request timeouts, are converted to the same `ApiError` body.
+//! A panicking handler gets an `internal` error with the request id instead of a dropped connection.
+
+use std::any::Any;
+
+use axum::Json;
+use axum::body::to_bytes;
+use axum::extract::Request;
+use axum::http::header::{CONTENT_LENGTH, CONTENT_TYPE};
+use axum::http::{HeaderName, HeaderValue};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use uuid::Uuid;
+
+use crate::i18n;
+use crate::schemas::{ApiError, ErrorCode};
+
+/// Header carrying the request id in both directions.
+pub const REQUEST_ID_HEADER: HeaderName = HeaderName::from_static("x-request-id");
+
+/// Longest client provided request id that is accepted, longer ones are replaced.
+const MAX_REQUEST_ID_LENGTH: usize = 128;
+
+/// Plain text error bodies longer than this are replaced with the status reason.
+const MAX_PLAIN_ERROR_BYTES: usize = 4096;
+
+tokio::task_local! {
+    static REQUEST_ID: String;
+}
+
+/// Id of the request being handled, if called inside [`request_id_middleware`].
+pub fn current() -> Option<String> {
+    REQUEST_ID.try_with(Clone::clone).ok()
+}
+
+/// Assign the request id and make it available to everything that handles the request.
+pub async fn request_id_middleware(mut request: Request, next: Next) -> Response {
+    let id = request
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .filter(|value| is_valid(value))
+        .map_or_else(|| Uuid::new_v4().to_string(), ToString::to_string);
+    let header = HeaderValue::from_str(&id).ok();
+    if let Some(header) = &header {
+        request.headers_mut().insert(REQUEST_ID_HEADER, header.clone());
+    }
+    let mut response = REQUEST_ID.scope(id, next.run(request)).await;
+    if let Some(header) = header {
+        response.headers_mut().insert(REQUEST_ID_HEADER, header);
+    }
+    response
+}
+
+/// Give error responses with an empty or plain text body an `ApiError` body instead.
+///
+/// Axum extractor rejections, method routing and the request timeout produce those,
+/// while handler errors already are JSON or another negotiated format and are kept as is.
+pub async fn error_body_middleware(request: Request, next: Next) -> Response {
+    let response = next.run(request).await;
+    let status = response.status();
+    let is_plain = response
+        .headers()
+        .get(CONTENT_TYPE)
+        .is_none_or(|value| value.as_bytes().starts_with(b"text/plain"));
+    if !is_plain || !(status.is_client_error() || status.is_server_error()) {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let message = to_bytes(body, MAX_PLAIN_ERROR_BYTES)
+        .await
+        .ok()
+        .map(|bytes| String::from_utf8_lossy(&bytes).trim().to_string())
+        .filter(|text| !text.is_empty())
+        .unwrap_or_else(|| status.canonical_reason().unwrap_or("Error").to_string());
+    let (json_parts, json_body) = Json(ApiError::new(ErrorCode::from_status(status), message))
+        .into_response()
+        .into_parts();
+    // Keep the original status and headers like `Allow` and `Retry-After`.
+    parts.headers.remove(CONTENT_LENGTH);
+    parts.headers.extend(json_parts.headers);
+    Response::from_parts(parts, json_body)
+}
+
+/// Answer a request whose handler panicked with an `internal` error.
+///
+/// The panic message and backtrace are logged by the panic hook from [`crate::logging`],
+/// so the client only gets the request id to look them up with.
+// `CatchPanicLayer` hands over the panic payload by value.
+#[allow(clippy::needless_pass_by_value)]
+pub fn panic_response(panic: Box<dyn Any + Send + 'static>) -> Response {
+    let message = panic
+        .downcast_ref::<String>()
+        .map(String::as_str)
+        .or_else(|| panic.downcast_ref::<&str>().copied())
+        .unwrap_or("unknown panic");
+    crate::log_error!("Request handler panicked: {message}");
+    ApiError::new(ErrorCode::Internal, i18n::message("internal_error", &[])).into_response()
+}
+
+/// Accept short ids of visible ASCII characters, so clients can not inject anything into logs.
+fn is_valid(id: &str) -> bool {
+    !id.is_empty() && id.len() <= MAX_REQUEST_ID_LENGTH && id.bytes().all(|byte| byte.is_ascii_graphic())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_short_visible_ids_are_accepted() {
+        assert!(is_valid("3f8a2c10-request"));
+        assert!(!is_valid(""));
+        assert!(!is_valid("with space"));
+        assert!(!is_valid(&"x".repeat(MAX_REQUEST_ID_LENGTH + 1)));
+    }
+
+    #[tokio::test]
+    async fn panicking_handler_gets_an_internal_error_with_the_request_id() {
+        use axum::Router;
+        use axum::body::Body;
+        use axum::http::StatusCode;
+        use axum::routing::get;
+        use tower::ServiceExt;
+        use tower_http::catch_panic::CatchPanicLayer;
+
+        async fn panicking() -> &'static str {
+            panic!("handler bug")
+        }
+
+        let router: Router = Router::new()
+            .route("/panic", get(panicking))
+            .layer(CatchPanicLayer::custom(panic_response))
+            .layer(axum::middleware::from_fn(request_id_middleware));
+        let request = Request::builder()
+            .uri("/panic")
+            .header(REQUEST_ID_HEADER, "panic-1")
+            .body(Body::empty())
+            .unwrap();
+
+        let response = router.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(response.headers()[REQUEST_ID_HEADER], "panic-1");
+        let body = to_bytes(response.into_body(), MAX_PLAIN_ERROR_BYTES).await.unwrap();
+        let error: ApiError = serde_json::from_slice(&body).unwrap();
+        assert_eq!(error.code, ErrorCode::Internal);
+        assert_eq!(error.request_id.as_deref(), Some("panic-1"));
+    }
+
+    #[tokio::test]
+    async fn current_id_is_only_set_inside_the_scope() {
+        assert_eq!(current(), None);
+        let id = REQUEST_ID.scope("abc".to_string(), async { current() }).await;
+        assert_eq!(id.as_deref(), Some("abc"));
+    }
+}
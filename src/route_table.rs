@@ -0,0 +1,229 @@
+//! Route table for operators.
+//!
+//! `--print-routes` prints every route the server exposes with its method,
+//! whether it needs the api key and how long a request may run, and exits,
+//! and `log_routes` logs the same routes at startup,
+//! so operators can verify what a build exposes.
+//! The API routes come from the operations the router documents in the `OpenAPI` spec,
+//! and the documentation routes from the settings that decide if and where they are served.
+//! API routes are listed at their versioned paths,
+//! their unversioned aliases are served too until they are removed.
+
+use std::fmt;
+use std::time::Duration;
+
+use axum::http::Method;
+use strum::IntoEnumIterator;
+use utoipa::openapi::path::{Operation, PathItem};
+
+use crate::deprecation::DeprecationRegistry;
+use crate::openapi::{UNVERSIONED_PATHS, api_doc};
+use crate::router::{API_V1_PREFIX, OPENAPI_JSON_PATH, OPENAPI_YAML_PATH, REQUEST_TIMEOUT, legacy_routes_sunset};
+use crate::settings::Settings;
+use crate::types::{DocsUi, Environment};
+
+/// Path of the fallback that answers unknown paths, which is not a route of its own.
+const FALLBACK_PATH: &str = "/{path}";
+
+/// One method and path the server answers.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RouteInfo {
+    pub method: Method,
+    pub path: String,
+    /// Whether the route needs the api key.
+    pub requires_api_key: bool,
+    /// How long a request may run before it gets `503`, `None` if it is not limited.
+    pub timeout: Option<Duration>,
+}
+
+impl RouteInfo {
+    const fn auth(&self) -> &'static str {
+        if self.requires_api_key { "api key" } else { "none" }
+    }
+
+    fn timeout(&self) -> String {
+        self.timeout
+            .map_or_else(|| "none".to_string(), |timeout| format!("{}s", timeout.as_secs()))
+    }
+}
+
+impl fmt::Display for RouteInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {} (auth: {}, timeout: {})",
+            self.method,
+            self.path,
+            self.auth(),
+            self.timeout()
+        )
+    }
+}
+
+/// Routes the server exposes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RouteTable {
+    /// Routes sorted by path and method.
+    pub routes: Vec<RouteInfo>,
+}
+
+impl RouteTable {
+    /// Routes served with the given settings.
+    #[must_use]
+    pub fn new(settings: &Settings) -> Self {
+        Self {
+            routes: routes(settings),
+        }
+    }
+}
+
+/// Routes served with the given settings, sorted by path and method.
+fn routes(settings: &Settings) -> Vec<RouteInfo> {
+    let spec = api_doc(settings.id_strategy, &DeprecationRegistry::default());
+    let mut routes: Vec<RouteInfo> = spec
+        .paths
+        .paths
+        .iter()
+        .filter(|(path, _)| path.as_str() != FALLBACK_PATH)
+        .flat_map(|(path, item)| {
+            let path = if UNVERSIONED_PATHS.contains(&path.as_str()) {
+                path.clone()
+            } else {
+                format!("{API_V1_PREFIX}{path}")
+            };
+            operations(item).map(move |(method, operation)| RouteInfo {
+                method,
+                path: path.clone(),
+                requires_api_key: operation.security.as_ref().is_some_and(|security| !security.is_empty()),
+                timeout: Some(REQUEST_TIMEOUT),
+            })
+        })
+        .collect();
+    if settings.env != Environment::Production {
+        // Documentation routes are outside the timeout layer.
+        let requires_api_key = settings.docs_require_auth && settings.env != Environment::Local;
+        let docs = DocsUi::iter()
+            .filter(|ui| settings.docs_uis.contains(ui))
+            .map(DocsUi::path);
+        routes.extend(
+            [OPENAPI_JSON_PATH, OPENAPI_YAML_PATH]
+                .into_iter()
+                .chain(docs)
+                .map(|path| RouteInfo {
+                    method: Method::GET,
+                    path: path.to_string(),
+                    requires_api_key,
+                    timeout: None,
+                }),
+        );
+    }
+    routes.sort_by(|a, b| (&a.path, a.method.as_str()).cmp(&(&b.path, b.method.as_str())));
+    routes
+}
+
+impl fmt::Display for RouteTable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let method_width = self
+            .routes
+            .iter()
+            .map(|route| route.method.as_str().len())
+            .max()
+            .unwrap_or_default()
+            .max("METHOD".len());
+        let path_width = self
+            .routes
+            .iter()
+            .map(|route| route.path.len())
+            .max()
+            .unwrap_or_default()
+            .max("PATH".len());
+        writeln!(
+            f,
+            "{:method_width$}  {:path_width$}  {:7}  TIMEOUT",
+            "METHOD", "PATH", "AUTH"
+        )?;
+        for route in &self.routes {
+            writeln!(
+                f,
+                "{:method_width$}  {:path_width$}  {:7}  {}",
+                route.method.as_str(),
+                route.path,
+                route.auth(),
+                route.timeout()
+            )?;
+        }
+        writeln!(f)?;
+        writeln!(
+            f,
+            "API routes are also served without the {API_V1_PREFIX} prefix until {}.",
+            legacy_routes_sunset().date_naive()
+        )
+    }
+}
+
+/// Documented operations of a path with their HTTP method.
+fn operations(item: &PathItem) -> impl Iterator<Item = (Method, &Operation)> {
+    [
+        (Method::GET, &item.get),
+        (Method::PUT, &item.put),
+        (Method::POST, &item.post),
+        (Method::DELETE, &item.delete),
+        (Method::PATCH, &item.patch),
+    ]
+    .into_iter()
+    .filter_map(|(method, operation)| operation.as_ref().map(|operation| (method, operation)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn find<'a>(routes: &'a [RouteInfo], method: &Method, path: &str) -> &'a RouteInfo {
+        routes
+            .iter()
+            .find(|route| route.method == method && route.path == path)
+            .unwrap_or_else(|| panic!("{method} {path} should be listed"))
+    }
+
+    #[test]
+    fn routes_list_auth_and_timeouts() {
+        let settings = Settings {
+            docs_uis: vec![DocsUi::Scalar],
+            ..Settings::default()
+        };
+        let table = RouteTable::new(&settings);
+        let routes = &table.routes;
+
+        let health = find(routes, &Method::GET, "/health");
+        assert!(!health.requires_api_key);
+        assert_eq!(health.timeout, Some(REQUEST_TIMEOUT));
+        assert!(!find(routes, &Method::POST, "/api/v1/items").requires_api_key);
+        assert!(find(routes, &Method::DELETE, "/api/v1/admin/clear_items").requires_api_key);
+        assert_eq!(find(routes, &Method::GET, "/scalar").timeout, None);
+        assert!(
+            !routes
+                .iter()
+                .any(|route| route.path == "/doc" || route.path == FALLBACK_PATH)
+        );
+
+        let table = table.to_string();
+        assert!(table.starts_with("METHOD  PATH"));
+        assert!(
+            table
+                .lines()
+                .any(|line| line.starts_with("DELETE  /api/v1/admin/clear_items") && line.ends_with("api key  10s"))
+        );
+        assert_eq!(health.to_string(), "GET /health (auth: none, timeout: 10s)");
+
+        let production = Settings {
+            env: Environment::Production,
+            ..Settings::default()
+        };
+        assert!(
+            !RouteTable::new(&production)
+                .routes
+                .iter()
+                .any(|route| route.path == OPENAPI_JSON_PATH)
+        );
+    }
+}
@@ -5,69 +5,158 @@
 //! This module is the runtime wiring layer.
 
 use std::sync::Arc;
+use std::time::Duration;
 
-use axum::http::StatusCode;
-use axum::middleware::from_fn_with_state;
+use axum::Router;
+use axum::extract::{Request, State};
+use axum::http::header::{ACCESS_CONTROL_REQUEST_METHOD, CONTENT_TYPE, ETAG, IF_MATCH, RETRY_AFTER};
+use axum::http::{HeaderMap, HeaderName, Method, StatusCode};
+use axum::middleware::{Next, from_fn_with_state};
 use axum::response::{IntoResponse, Response};
 use axum::routing::{get, post};
-use axum::{Json, Router};
-use tower::ServiceBuilder;
+use chrono::{DateTime, TimeZone, Utc};
+use strum::IntoEnumIterator;
+use tower::{Layer, ServiceBuilder, ServiceExt};
+use tower_http::catch_panic::CatchPanicLayer;
+use tower_http::cors::{AllowOrigin, CorsLayer};
+use tower_http::normalize_path::NormalizePathLayer;
 use tower_http::timeout::TimeoutLayer;
 use tower_http::trace::{DefaultMakeSpan, DefaultOnResponse, TraceLayer};
 use tracing::Level;
-use utoipa::OpenApi;
 use utoipa_rapidoc::RapiDoc;
 use utoipa_redoc::{Redoc, Servable};
 use utoipa_scalar::{Scalar, Servable as ScalarServable};
-use utoipa_swagger_ui::SwaggerUi;
-
+use utoipa_swagger_ui::{Config, SwaggerUi};
+
+use crate::allowed_methods::allowed_methods_middleware;
+use crate::body_logging::body_logging_middleware;
+use crate::cache::{ResponseCache, response_cache_middleware};
+use crate::deprecation::{DeprecationRegistry, deprecation_middleware};
+use crate::docs_auth::docs_auth_middleware;
+use crate::envelope::envelope_middleware;
+use crate::i18n::{self, language_middleware};
+use crate::inflight::inflight_middleware;
+use crate::ip_filter::ip_filter_middleware;
 use crate::middleware::{RequestTelemetryState, request_telemetry_middleware};
-use crate::openapi::ApiDoc;
+use crate::openapi::{api_doc, versioned_paths};
+use crate::precomputed::PrecomputedResponse;
+use crate::problem::problem_middleware;
+use crate::rate_limit::{
+    RATE_LIMIT_LIMIT_HEADER, RATE_LIMIT_REMAINING_HEADER, RATE_LIMIT_RESET_HEADER, RateLimiter, rate_limit_middleware,
+};
+use crate::request_id::{REQUEST_ID_HEADER, error_body_middleware, panic_response, request_id_middleware};
 use crate::routing::admin;
 use crate::routing::routes;
-use crate::schemas::NotFoundResponse;
-use crate::types::{Config, Environment, SharedState};
+use crate::schemas::{ApiError, ErrorCode};
+use crate::settings::SharedSettings;
+use crate::slow_request::slow_request_middleware;
+use crate::tenant::{TENANT_HEADER, tenant_middleware};
+use crate::types::{DocsUi, Environment, SharedState};
+
+/// Requests running longer than this get `503 Service Unavailable`.
+pub const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Where the documentation UIs load the `OpenAPI` spec from.
+pub const OPENAPI_JSON_PATH: &str = "/api-docs/openapi.json";
+
+/// The same `OpenAPI` spec as YAML, for tools that only accept YAML.
+pub const OPENAPI_YAML_PATH: &str = "/api-docs/openapi.yaml";
+
+/// Prefix of the current API version.
+pub const API_V1_PREFIX: &str = "/api/v1";
 
 /// Create Router app with routes and `OpenAPI` documentation.
-pub fn build_router(shared_state: &SharedState, config: &Arc<Config>) -> Router {
+///
+/// API routes are served under `/api/v1`,
+/// and at their old unversioned paths as deprecated aliases until [`legacy_routes_sunset`].
+/// Health, readiness and metrics routes stay at the root.
+pub fn build_router(shared_state: &SharedState, settings: &SharedSettings) -> Router {
+    let api = api_routes(shared_state, settings);
     let router = Router::new()
         .route("/", get(routes::root))
         .route("/health", get(routes::health))
+        .route("/readyz", get(routes::readiness))
         .route("/metrics", get(routes::metrics))
-        .route("/version", get(routes::version))
-        .route("/item", get(routes::query_item))
-        .route("/items", get(routes::list_items))
-        .route("/items", post(routes::create_item))
-        .nest("/admin", admin::routes())
+        .nest(API_V1_PREFIX, api.clone())
+        .merge(api)
         .fallback(not_found)
         .layer(
             ServiceBuilder::new()
-                .layer(axum::Extension(Arc::clone(config)))
+                .layer(axum::middleware::from_fn(request_id_middleware))
+                .layer(axum::middleware::from_fn(error_body_middleware))
+                .layer(axum::Extension(Arc::clone(settings)))
+                .layer(from_fn_with_state(Arc::clone(shared_state), inflight_middleware))
+                .layer(from_fn_with_state(Arc::clone(settings), slow_request_middleware))
                 .layer(
                     TraceLayer::new_for_http()
                         .make_span_with(DefaultMakeSpan::new().level(Level::INFO))
                         .on_response(DefaultOnResponse::new().level(Level::INFO)),
                 )
+                .layer(from_fn_with_state(Arc::clone(settings), body_logging_middleware))
+                .layer(from_fn_with_state(Arc::clone(settings), tenant_middleware))
+                .layer(from_fn_with_state(cors_layer(settings), cors_middleware))
                 .layer(from_fn_with_state(
                     Arc::new(RequestTelemetryState::new(shared_state.telemetry().metrics())),
                     request_telemetry_middleware,
                 ))
+                .layer(from_fn_with_state(
+                    Arc::new(RateLimiter::new(Arc::clone(settings))),
+                    rate_limit_middleware,
+                ))
+                .layer(from_fn_with_state(Arc::clone(shared_state), deprecation_middleware))
                 .layer(TimeoutLayer::with_status_code(
                     StatusCode::SERVICE_UNAVAILABLE,
-                    tokio::time::Duration::from_secs(10),
-                )),
+                    REQUEST_TIMEOUT,
+                ))
+                .layer(CatchPanicLayer::custom(panic_response)),
         )
         .with_state(Arc::clone(shared_state));
 
+    // Trailing slashes have to be trimmed before routing, so wrap the routes in an outer router.
+    // Documentation routes are merged to the outer router since Swagger UI relies on `/doc/`.
+    // Axum adds the `Allow` header after the inner layers have run, so the `OPTIONS` handling is layered here.
+    // Problem details wrap it to also convert the method-not-allowed errors it writes,
+    // the envelope wraps the final bodies,
+    // and the language is picked outermost so those errors are localized too.
+    let router = Router::new()
+        .fallback_service(NormalizePathLayer::trim_trailing_slash().layer(router))
+        .layer(axum::middleware::from_fn(allowed_methods_middleware))
+        .layer(from_fn_with_state(Arc::clone(settings), problem_middleware))
+        .layer(from_fn_with_state(Arc::clone(settings), envelope_middleware))
+        .layer(axum::middleware::from_fn(language_middleware));
+
     // Add OpenAPI documentation routes only in non-production environments.
-    if config.env == Environment::Production {
+    let current = settings.load();
+    if current.env == Environment::Production {
         router
     } else {
-        router
-            .merge(SwaggerUi::new("/doc").url("/api-docs/openapi.json", ApiDoc::openapi()))
-            .merge(Redoc::with_url("/redoc", ApiDoc::openapi()))
-            .merge(RapiDoc::new("/api-docs/openapi.json").path("/rapidoc"))
-            .merge(Scalar::with_url("/scalar", ApiDoc::openapi()))
+        let spec = api_doc(current.id_strategy, shared_state.deprecations());
+        // The spec is fixed for the lifetime of the router, so serialize it once here.
+        let spec_json = Arc::new(PrecomputedResponse::json(&spec).expect("OpenAPI spec should serialize"));
+        let spec_yaml = Arc::new(PrecomputedResponse::new(
+            spec.to_yaml().expect("OpenAPI spec should serialize as YAML"),
+            "application/yaml",
+        ));
+        let spec_routes = Router::new()
+            .route(
+                OPENAPI_JSON_PATH,
+                get(move |headers: HeaderMap| async move { spec_json.respond(&headers) }),
+            )
+            .route(
+                OPENAPI_YAML_PATH,
+                get(move |headers: HeaderMap| async move { spec_yaml.respond(&headers) }),
+            );
+        // Iterate over all UIs instead of the setting so duplicates in the list are only served once.
+        let docs = DocsUi::iter()
+            .filter(|ui| current.docs_uis.contains(ui))
+            .fold(spec_routes, |docs, ui| match ui {
+                DocsUi::Swagger => docs.merge(SwaggerUi::new(ui.path()).config(Config::new([OPENAPI_JSON_PATH]))),
+                DocsUi::Redoc => docs.merge(Redoc::with_url(ui.path(), spec.clone())),
+                DocsUi::Rapidoc => docs.merge(RapiDoc::new(OPENAPI_JSON_PATH).path(ui.path())),
+                DocsUi::Scalar => docs.merge(Scalar::with_url(ui.path(), spec.clone())),
+            })
+            .layer(from_fn_with_state(Arc::clone(settings), docs_auth_middleware));
+        router.merge(docs)
     }
 }
 
@@ -76,11 +165,109 @@ pub fn build_router(shared_state: &SharedState, config: &Arc<Config>) -> Router
     get,
     path = "/{path}",
     responses(
-        (status = NOT_FOUND, body = [NotFoundResponse], description = "Path does not exist")
+        (status = NOT_FOUND, body = ApiError, description = "Path does not exist")
     )
 )]
 pub async fn not_found() -> Response {
-    (StatusCode::NOT_FOUND, Json(NotFoundResponse::new())).into_response()
+    ApiError::new(ErrorCode::NotFound, i18n::message("path_not_found", &[])).into_response()
+}
+
+/// Deprecations for the unversioned aliases of every versioned API route.
+#[must_use]
+pub fn legacy_route_deprecations() -> DeprecationRegistry {
+    let deprecated_at = legacy_routes_deprecated_at();
+    let sunset = legacy_routes_sunset();
+    versioned_paths()
+        .into_iter()
+        .fold(DeprecationRegistry::default(), |registry, path| {
+            let message = format!("Use {API_V1_PREFIX}{path} instead");
+            registry.with_route(None, &path, deprecated_at, sunset, &message, None)
+        })
+}
+
+/// When the unversioned API route aliases were deprecated, the release that added the `/api/v1` prefix.
+#[must_use]
+pub fn legacy_routes_deprecated_at() -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2026, 10, 16, 0, 0, 0)
+        .single()
+        .expect("legacy route deprecation should be a valid date")
+}
+
+/// When the unversioned API route aliases are removed, one release after versioning was added.
+#[must_use]
+pub fn legacy_routes_sunset() -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2027, 4, 30, 0, 0, 0)
+        .single()
+        .expect("legacy route sunset should be a valid date")
+}
+
+/// Item, version and admin routes that are served under the API version prefix.
+fn api_routes(shared_state: &SharedState, settings: &SharedSettings) -> Router<SharedState> {
+    let response_cache = from_fn_with_state(
+        Arc::new(ResponseCache::new(Arc::clone(settings), Arc::clone(shared_state))),
+        response_cache_middleware,
+    );
+    Router::new()
+        .route("/version", get(routes::version))
+        .route("/usage", get(routes::usage))
+        .route("/item", get(routes::query_item).layer(response_cache.clone()))
+        .route("/items", get(routes::list_items).layer(response_cache))
+        .route("/items", post(routes::create_item))
+        .route("/items/query", post(routes::batch_get_items))
+        .route("/items/{name}/history", get(routes::item_history))
+        .route(
+            "/collections/{collection}/items",
+            get(routes::list_collection_items).post(routes::create_collection_item),
+        )
+        .route(
+            "/collections/{collection}/items/{name}",
+            get(routes::get_collection_item),
+        )
+        .nest(
+            "/admin",
+            admin::routes().layer(from_fn_with_state(Arc::clone(settings), ip_filter_middleware)),
+        )
+}
+
+/// Allow cross-origin requests from the configured origins.
+///
+/// Origins are checked on every request, so settings changes apply without a restart.
+fn cors_layer(settings: &SharedSettings) -> CorsLayer {
+    let settings = Arc::clone(settings);
+    CorsLayer::new()
+        .allow_origin(AllowOrigin::predicate(move |origin, _| {
+            settings.load().allows_origin(origin.as_bytes())
+        }))
+        .allow_methods([Method::GET, Method::POST, Method::DELETE])
+        .allow_headers([
+            CONTENT_TYPE,
+            HeaderName::from_static("api-key"),
+            IF_MATCH,
+            REQUEST_ID_HEADER,
+            TENANT_HEADER,
+        ])
+        .expose_headers([
+            ETAG,
+            REQUEST_ID_HEADER,
+            RETRY_AFTER,
+            RATE_LIMIT_LIMIT_HEADER,
+            RATE_LIMIT_REMAINING_HEADER,
+            RATE_LIMIT_RESET_HEADER,
+        ])
+}
+
+/// Apply CORS to every request except plain `OPTIONS` requests.
+///
+/// The CORS layer answers every `OPTIONS` request as a preflight,
+/// so requests without `Access-Control-Request-Method` skip it and get the methods of the path instead.
+async fn cors_middleware(State(cors): State<CorsLayer>, request: Request, next: Next) -> Response {
+    if request.method() == Method::OPTIONS && !request.headers().contains_key(ACCESS_CONTROL_REQUEST_METHOD) {
+        return next.run(request).await;
+    }
+    cors.layer(next)
+        .oneshot(request)
+        .await
+        .unwrap_or_else(|never| match never {})
 }
 
 #[cfg(test)]
@@ -92,57 +279,54 @@ mod tests {
         http::{Request, StatusCode},
     };
     use http_body_util::BodyExt;
-    use serde_json::Value;
+    use serde_json::{Value, json};
     use tower::ServiceExt;
 
-    use crate::schemas::ItemListResponse;
-    use crate::types::{AppState, Config, Environment, Item};
+    use std::net::SocketAddr;
+
+    use axum::Extension;
+    use axum::extract::ConnectInfo;
+    use base64::Engine;
+    use chrono::{Duration, TimeZone, Utc};
+
+    use crate::cache::CACHE_STATUS_HEADER;
+    use crate::deprecation::DeprecationRegistry;
+    use crate::schemas::{ItemListResponse, MAX_BATCH_NAMES, QuotaExceeded, QuotaResource, UsageResponse};
+    use crate::settings::{CliSettings, Settings, SettingsLoader};
+    use crate::test_utils::TestApp;
+    use crate::types::{
+        AppState, DEFAULT_API_KEY, DeterministicConfig, Environment, IdStrategy, IpFilterConfig, Item, ItemId,
+        QuotaConfig, RateLimitConfig,
+    };
     use crate::version;
 
     fn test_router() -> Router {
         let shared_state = AppState::new_shared_state();
-        let config = Arc::new(Config::default());
-        build_router(&shared_state, &config)
+        let settings = Settings::default().into_shared();
+        build_router(&shared_state, &settings)
     }
 
-    fn test_router_with_config(config: Config) -> Router {
+    fn test_router_with_settings(settings: Settings) -> Router {
         let shared_state = AppState::new_shared_state();
-        let config = Arc::new(config);
-        build_router(&shared_state, &config)
+        build_router(&shared_state, &settings.into_shared())
     }
 
     #[tokio::test]
     async fn test_root() {
-        let app = test_router();
-
-        let response = app
-            .oneshot(Request::builder().uri("/").body(Body::empty()).unwrap())
-            .await
-            .expect("Failed to get response");
-
-        assert_eq!(response.status(), StatusCode::OK);
-
-        let body = response.into_body().collect().await.unwrap().to_bytes();
-        let body: Value = serde_json::from_slice(&body).unwrap();
+        let response = TestApp::new().get("/").await;
 
+        assert_eq!(response.status, StatusCode::OK);
+        let body: Value = response.json();
         assert!(body.get("message").is_some(), "Body does not contain 'message' key");
         assert!(body["message"].is_string(), "'message' is not a string");
     }
 
     #[tokio::test]
     async fn test_health() {
-        let app = test_router();
-
-        let response = app
-            .oneshot(Request::builder().uri("/health").body(Body::empty()).unwrap())
-            .await
-            .expect("Failed to get response");
-
-        assert_eq!(response.status(), StatusCode::OK);
-
-        let body = response.into_body().collect().await.unwrap().to_bytes();
-        let body: Value = serde_json::from_slice(&body).unwrap();
+        let response = TestApp::new().get("/health").await;
 
+        assert_eq!(response.status, StatusCode::OK);
+        let body: Value = response.json();
         assert_eq!(body["service"], version::PACKAGE_NAME);
         assert_eq!(body["version"], version::PACKAGE_VERSION);
         assert_eq!(body["environment"], "LOCAL");
@@ -152,6 +336,34 @@ mod tests {
         assert!(body["uptime_ms"].is_number());
     }
 
+    #[tokio::test]
+    async fn deterministic_mode_repeats_timestamps_and_ids() {
+        let config = DeterministicConfig {
+            seed: Some(42),
+            time: Some("2026-01-01T00:00:00Z".parse().unwrap()),
+        };
+        let deterministic_app = || {
+            TestApp::with_state(
+                Arc::new(AppState::new().with_deterministic(&config)),
+                Settings::default(),
+            )
+        };
+        let (first, second) = (deterministic_app(), deterministic_app());
+
+        let body: Value = first.get("/").await.json();
+        assert_eq!(
+            body["message"],
+            format!("{} 2026-01-01T00:00:00Z", version::PACKAGE_NAME)
+        );
+
+        for name in ["first", "second"] {
+            let item = first.create_item(json!({ "name": name })).await;
+            assert_eq!(item, second.create_item(json!({ "name": name })).await);
+            assert_eq!(Some(item.created_at), config.time);
+            assert_eq!(item.updated_at, item.created_at);
+        }
+    }
+
     #[tokio::test]
     async fn test_metrics() {
         let app = test_router();
@@ -210,14 +422,14 @@ mod tests {
 
     #[test]
     fn openapi_spec_includes_health_and_metrics_routes() {
-        let spec = ApiDoc::openapi();
+        let spec = api_doc(IdStrategy::default(), &DeprecationRegistry::default());
         let value: Value = serde_json::from_str(&serde_json::to_string(&spec).expect("spec should serialize"))
             .expect("spec should parse");
 
         assert!(value["paths"]["/health"].is_object());
         assert!(value["paths"]["/metrics"].is_object());
         assert!(value["components"]["schemas"]["HealthResponse"].is_object());
-        assert!(value["components"]["schemas"]["NotFoundResponse"].is_object());
+        assert!(value["components"]["schemas"]["ApiError"].is_object());
     }
 
     #[tokio::test]
@@ -234,11 +446,99 @@ mod tests {
         let body = response.into_body().collect().await.unwrap().to_bytes();
         let body: Value = serde_json::from_slice(&body).unwrap();
 
-        assert_eq!(body["error"], "Not Found");
+        assert_eq!(body["code"], "not_found");
         assert_eq!(body["message"], "Path does not exist");
+        assert!(body["request_id"].is_string());
         assert!(body.get("path").is_none());
     }
 
+    #[tokio::test]
+    async fn errors_use_api_error_body_with_request_id() {
+        let app = test_router();
+
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri("/api/v1/item")
+                    .header("x-request-id", "trace-123")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .expect("Failed to get response");
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(response.headers()["x-request-id"], "trace-123");
+        assert_eq!(response.headers()[CONTENT_TYPE], "application/json");
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["code"], "bad_request");
+        assert_eq!(body["request_id"], "trace-123");
+        assert!(body["message"].as_str().unwrap().contains("name"));
+
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method("PUT")
+                    .uri("/api/v1/items")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .expect("Failed to get response");
+        assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+        assert!(response.headers().contains_key("allow"));
+        let generated_id = response.headers()["x-request-id"].to_str().unwrap().to_string();
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["code"], "method_not_allowed");
+        assert_eq!(
+            body["message"],
+            "Method PUT is not allowed, use one of: GET, HEAD, POST, OPTIONS"
+        );
+        assert_eq!(body["request_id"], generated_id.as_str());
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method("DELETE")
+                    .uri("/api/v1/admin/clear_items")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .expect("Failed to get response");
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["code"], "unauthorized");
+        assert!(body["request_id"].is_string());
+    }
+
+    #[test]
+    fn openapi_error_responses_all_use_api_error() {
+        let spec = serde_json::to_value(api_doc(IdStrategy::default(), &DeprecationRegistry::default()))
+            .expect("spec should serialize");
+
+        let mut error_responses = 0;
+        for (path, item) in spec["paths"].as_object().unwrap() {
+            for (method, operation) in item.as_object().unwrap() {
+                let Some(responses) = operation["responses"].as_object() else {
+                    continue;
+                };
+                for (status, response) in responses.iter().filter(|(status, _)| status.as_str() >= "400") {
+                    error_responses += 1;
+                    assert_eq!(
+                        response["content"]["application/json"]["schema"]["$ref"], "#/components/schemas/ApiError",
+                        "{method} {path} {status}"
+                    );
+                }
+            }
+        }
+        assert!(error_responses > 20);
+    }
+
     #[tokio::test]
     async fn test_version() {
         let app = test_router();
@@ -261,14 +561,124 @@ mod tests {
         assert_eq!(body["rust_version"], version::RUST_VERSION);
     }
 
+    #[tokio::test]
+    async fn static_responses_support_etag_revalidation() {
+        let app = test_router();
+
+        for (path, content_type) in [
+            ("/version", "application/json"),
+            ("/api-docs/openapi.json", "application/json"),
+            ("/api-docs/openapi.yaml", "application/yaml"),
+        ] {
+            let response = app
+                .clone()
+                .oneshot(Request::builder().uri(path).body(Body::empty()).unwrap())
+                .await
+                .expect("Failed to get response");
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_eq!(response.headers()["content-type"], content_type);
+            let etag = response.headers()["etag"].clone();
+
+            let response = app
+                .clone()
+                .oneshot(
+                    Request::builder()
+                        .uri(path)
+                        .header("If-None-Match", etag.clone())
+                        .body(Body::empty())
+                        .unwrap(),
+                )
+                .await
+                .expect("Failed to get response");
+            assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+            assert_eq!(response.headers()["etag"], etag);
+            let body = response.into_body().collect().await.unwrap().to_bytes();
+            assert!(body.is_empty());
+        }
+    }
+
+    #[tokio::test]
+    async fn openapi_spec_is_served_as_yaml() {
+        let response = test_router()
+            .oneshot(
+                Request::builder()
+                    .uri("/api-docs/openapi.yaml")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .expect("Failed to get response");
+
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let body = std::str::from_utf8(&body).expect("YAML should be UTF-8");
+        assert!(body.starts_with("openapi:"));
+        assert!(body.contains("/items:"));
+    }
+
+    #[tokio::test]
+    async fn docs_require_api_key_outside_local_when_enabled() {
+        let get = |app: Router, path: &'static str, auth: Option<(&'static str, String)>| async move {
+            let mut request = Request::builder().uri(path);
+            if let Some((name, value)) = auth {
+                request = request.header(name, value);
+            }
+            app.oneshot(request.body(Body::empty()).unwrap())
+                .await
+                .expect("Failed to get response")
+        };
+
+        let app = test_router_with_settings(Settings {
+            docs_require_auth: true,
+            ..Settings::for_environment(Environment::Development)
+        });
+        for path in ["/redoc", "/scalar", "/api-docs/openapi.json", "/api-docs/openapi.yaml"] {
+            let response = get(app.clone(), path, None).await;
+            assert_eq!(
+                response.status(),
+                StatusCode::UNAUTHORIZED,
+                "{path} should require auth"
+            );
+            assert!(
+                response.headers()["www-authenticate"]
+                    .to_str()
+                    .unwrap()
+                    .starts_with("Basic")
+            );
+        }
+        let response = get(app.clone(), "/redoc", Some(("api-key", DEFAULT_API_KEY.to_string()))).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let basic = format!(
+            "Basic {}",
+            base64::engine::general_purpose::STANDARD.encode(format!("docs:{DEFAULT_API_KEY}"))
+        );
+        let response = get(app.clone(), "/api-docs/openapi.json", Some(("authorization", basic))).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let response = get(app, "/api/v1/version", None).await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let local = test_router_with_settings(Settings {
+            docs_require_auth: true,
+            ..Settings::default()
+        });
+        assert_eq!(get(local, "/redoc", None).await.status(), StatusCode::OK);
+    }
+
     #[tokio::test]
     async fn docs_routes_are_hidden_in_production() {
-        let app = test_router_with_config(Config {
+        let app = test_router_with_settings(Settings {
             env: Environment::Production,
-            ..Config::default()
+            ..Settings::default()
         });
 
-        for path in ["/doc", "/redoc", "/rapidoc", "/scalar"] {
+        for path in [
+            "/doc",
+            "/redoc",
+            "/rapidoc",
+            "/scalar",
+            "/api-docs/openapi.json",
+            "/api-docs/openapi.yaml",
+        ] {
             let response = app
                 .clone()
                 .oneshot(Request::builder().uri(path).body(Body::empty()).unwrap())
@@ -282,6 +692,29 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn only_configured_docs_uis_are_served() {
+        let app = test_router_with_settings(Settings {
+            docs_uis: vec![DocsUi::Scalar, DocsUi::Redoc, DocsUi::Scalar],
+            ..Settings::default()
+        });
+
+        for (path, expected) in [
+            ("/doc/", StatusCode::NOT_FOUND),
+            ("/redoc", StatusCode::OK),
+            ("/rapidoc", StatusCode::NOT_FOUND),
+            ("/scalar", StatusCode::OK),
+            ("/api-docs/openapi.json", StatusCode::OK),
+        ] {
+            let response = app
+                .clone()
+                .oneshot(Request::builder().uri(path).body(Body::empty()).unwrap())
+                .await
+                .expect("Failed to get response");
+            assert_eq!(response.status(), expected, "{path}");
+        }
+    }
+
     #[tokio::test]
     async fn query_item_returns_not_found_for_missing_item() {
         let app = test_router();
@@ -304,91 +737,142 @@ mod tests {
 
     #[tokio::test]
     async fn create_item() {
-        let item_json = r#"{"name": "test"}"#;
-        let shared_state = AppState::new_shared_state();
-        let config = Arc::new(Config::default());
-        let app = build_router(&shared_state, &config);
+        let app = TestApp::new();
 
-        let response = app
-            .oneshot(
-                Request::builder()
-                    .method("POST")
-                    .uri("/items")
-                    .header("Content-Type", "application/json")
-                    .body(Body::from(item_json))
-                    .unwrap(),
-            )
-            .await
-            .expect("Failed to get response");
+        let item = app.create_item(json!({"name": "test"})).await;
+        assert_eq!(item.name, "test");
+        assert!(matches!(item.id, ItemId::Numeric(id) if Item::is_valid_id(id)));
 
-        assert_eq!(response.status(), StatusCode::CREATED);
+        let response = app.post_json("/items", &json!({"name": "test"})).await;
+        assert_eq!(response.status, StatusCode::CONFLICT);
+    }
 
-        let body = response
-            .into_body()
-            .collect()
-            .await
-            .expect("Failed to get body bytes")
-            .to_bytes();
+    #[tokio::test]
+    async fn list_items() {
+        let app = TestApp::new();
+
+        let item_list = app.get_items("").await;
+        assert_eq!(item_list.num_items, 0);
+        assert!(item_list.names.is_empty());
 
-        assert!(!body.is_empty());
+        app.create_item(json!({"name": "test"})).await;
 
-        let item: Item = serde_json::from_slice(&body).unwrap();
-        assert_eq!(item.name, "test");
-        assert!(item.id <= 9999);
-        assert!(item.id >= 1000);
+        let item_list = app.get_items("").await;
+        assert_eq!(item_list.num_items, 1);
+        assert!(!item_list.names.is_empty());
+    }
+
+    #[tokio::test]
+    async fn list_items_supports_skip_and_limit_query_parameters() {
+        let app = TestApp::new();
+        for (name, id) in [("alpha", 1001), ("bravo", 1002), ("charlie", 1003)] {
+            app.create_item(json!({"name": name, "id": id})).await;
+        }
+
+        for (query, expected_names) in [
+            ("", vec!["alpha", "bravo", "charlie"]),
+            ("skip=1", vec!["bravo", "charlie"]),
+            ("limit=2", vec!["alpha", "bravo"]),
+            ("skip=1&limit=1", vec!["bravo"]),
+            ("skip=10", vec![]),
+        ] {
+            let item_list = app.get_items(query).await;
+            assert_eq!(
+                item_list.num_items, 3,
+                "total count should not be paginated for {query}"
+            );
+            assert_eq!(item_list.names, expected_names, "wrong names for {query}");
+        }
+    }
+
+    #[tokio::test]
+    async fn list_items_filters_by_created_after() {
+        let shared_state = AppState::new_shared_state();
+        let settings = Settings::default().into_shared();
+        let app = build_router(&shared_state, &settings);
+        let now = Utc::now();
+        for (name, age_days) in [("old", 10), ("new", 1)] {
+            let mut item = Item::new_with_random_id(name.to_string(), IdStrategy::Numeric);
+            item.created_at = now - Duration::days(age_days);
+            shared_state.items().insert_item(&item).expect("new item");
+        }
 
-        let app = build_router(&shared_state, &config);
+        let cutoff = (now - Duration::days(5)).to_rfc3339_opts(chrono::SecondsFormat::Secs, true);
         let response = app
             .oneshot(
                 Request::builder()
-                    .method("POST")
-                    .uri("/items")
-                    .header("Content-Type", "application/json")
-                    .body(Body::from(item_json))
+                    .uri(format!("/items?created_after={cutoff}"))
+                    .body(Body::empty())
                     .unwrap(),
             )
             .await
-            .expect("Failed to get response");
+            .expect("Failed to list items");
+        assert_eq!(response.status(), StatusCode::OK);
 
-        assert_eq!(response.status(), StatusCode::CONFLICT);
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let item_list: ItemListResponse = serde_json::from_slice(&body).unwrap();
+        assert_eq!(item_list.num_items, 1);
+        assert_eq!(item_list.names, ["new"]);
     }
 
     #[tokio::test]
-    async fn list_items() {
+    async fn list_items_streams_ndjson() {
         let shared_state = AppState::new_shared_state();
-        let config = Arc::new(Config::default());
-        let app = build_router(&shared_state, &config);
+        let settings = Settings::default().into_shared();
+        let app = build_router(&shared_state, &settings);
+        for name in ["c", "a", "b"] {
+            shared_state
+                .items()
+                .insert_item(&Item::new_with_random_id(name.to_string(), IdStrategy::Numeric))
+                .expect("new item");
+        }
 
         let response = app
             .oneshot(
                 Request::builder()
-                    .method("GET")
-                    .uri("/items")
+                    .uri("/items?stream=true&skip=1")
                     .body(Body::empty())
                     .unwrap(),
             )
             .await
-            .expect("Failed to get response");
-
+            .expect("Failed to list items");
         assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["content-type"], "application/x-ndjson");
+        assert!(response.headers().get(CACHE_STATUS_HEADER).is_none());
 
-        let body = response
-            .into_body()
-            .collect()
-            .await
-            .expect("Failed to get body bytes")
-            .to_bytes();
-
-        assert!(!body.is_empty());
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let items: Vec<Item> = std::str::from_utf8(&body)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        let names: Vec<&str> = items.iter().map(|item| item.name.as_str()).collect();
+        assert_eq!(names, ["b", "c"]);
+    }
 
-        let item_list: ItemListResponse = serde_json::from_slice(&body).unwrap();
-        assert_eq!(item_list.num_items, 0);
-        assert!(item_list.names.is_empty());
+    #[tokio::test]
+    async fn create_item_with_tags_and_list_by_tag() {
+        let app = TestApp::new();
+
+        let tagged = app
+            .create_item(json!({"name": "tagged", "tags": ["rust", "axum", "rust"], "metadata": {"owner": "esgrove"}}))
+            .await;
+        assert_eq!(tagged.tags, ["axum", "rust"]);
+        assert_eq!(tagged.metadata["owner"], "esgrove");
+        let untagged = app.create_item(json!({"name": "untagged"})).await;
+        assert!(untagged.tags.is_empty());
+
+        assert_eq!(app.get_items("tag=rust").await.names, ["tagged"]);
+    }
 
-        let item_json = r#"{"name": "test"}"#;
-        let app = build_router(&shared_state, &config);
-        let response = app
-            .oneshot(
+    #[tokio::test]
+    async fn create_item_stores_json_data_up_to_max_size() {
+        let app = test_router_with_settings(Settings {
+            max_item_data_bytes: 64,
+            ..Settings::default()
+        });
+        let create = |item_json: String| {
+            app.clone().oneshot(
                 Request::builder()
                     .method("POST")
                     .uri("/items")
@@ -396,93 +880,77 @@ mod tests {
                     .body(Body::from(item_json))
                     .unwrap(),
             )
-            .await
-            .expect("Failed to get response");
+        };
 
+        let response =
+            create(r#"{"name":"document","data":{"owner":{"name":"esgrove","teams":["core"]}}}"#.to_string())
+                .await
+                .expect("Failed to create item");
         assert_eq!(response.status(), StatusCode::CREATED);
 
-        let app = build_router(&shared_state, &config);
+        let response = create(format!(r#"{{"name":"large","data":{{"blob":"{}"}}}}"#, "x".repeat(64)))
+            .await
+            .expect("Failed to create item");
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["fields"][0]["field"], "data");
 
         let response = app
             .oneshot(
                 Request::builder()
-                    .method("GET")
-                    .uri("/items")
+                    .uri("/item?name=document")
                     .body(Body::empty())
                     .unwrap(),
             )
             .await
-            .expect("Failed to get response");
-
-        assert_eq!(response.status(), StatusCode::OK);
-
-        let body = response
-            .into_body()
-            .collect()
-            .await
-            .expect("Failed to get body bytes")
-            .to_bytes();
-
-        assert!(!body.is_empty());
-
-        let item_list: ItemListResponse = serde_json::from_slice(&body).unwrap();
-        assert_eq!(item_list.num_items, 1);
-        assert!(!item_list.names.is_empty());
+            .expect("Failed to query item");
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["data"]["owner"]["teams"][0], "core");
     }
 
     #[tokio::test]
-    async fn list_items_supports_skip_and_limit_query_parameters() {
-        let shared_state = AppState::new_shared_state();
-        let config = Arc::new(Config::default());
-        let app = build_router(&shared_state, &config);
+    async fn create_item_enforces_name_rules_and_configured_pattern() {
+        let app = test_router_with_settings(Settings {
+            item_name_pattern: Some("team-[a-z]+".to_string().try_into().expect("valid pattern")),
+            ..Settings::default()
+        });
+        let create = |item_json: &'static str| {
+            app.clone().oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/items")
+                    .header("Content-Type", "application/json")
+                    .body(Body::from(item_json))
+                    .unwrap(),
+            )
+        };
 
-        for item_json in [
-            r#"{"name":"alpha","id":1001}"#,
-            r#"{"name":"bravo","id":1002}"#,
-            r#"{"name":"charlie","id":1003}"#,
-        ] {
-            let response = app
-                .clone()
-                .oneshot(
-                    Request::builder()
-                        .method("POST")
-                        .uri("/items")
-                        .header("Content-Type", "application/json")
-                        .body(Body::from(item_json))
-                        .unwrap(),
-                )
-                .await
-                .expect("Failed to create item");
-            assert_eq!(response.status(), StatusCode::CREATED);
-        }
+        let response = create(r#"{"name":"team-core"}"#).await.expect("Failed to create item");
+        assert_eq!(response.status(), StatusCode::CREATED);
 
-        for (uri, expected_names) in [
-            ("/items", vec!["alpha", "bravo", "charlie"]),
-            ("/items?skip=1", vec!["bravo", "charlie"]),
-            ("/items?limit=2", vec!["alpha", "bravo"]),
-            ("/items?skip=1&limit=1", vec!["bravo"]),
-            ("/items?skip=10", vec![]),
+        for (item_json, message) in [
+            (r#"{"name":"core"}"#, "must match the pattern team-[a-z]+"),
+            (
+                r#"{"name":"team-\u0007"}"#,
+                "may only contain letters, numbers, '-', '_' and '.'",
+            ),
         ] {
-            let response = app
-                .clone()
-                .oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
-                .await
-                .expect("Failed to list items");
-            assert_eq!(response.status(), StatusCode::OK, "wrong status for {uri}");
-
+            let response = create(item_json).await.expect("Failed to create item");
+            assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
             let body = response.into_body().collect().await.unwrap().to_bytes();
-            let item_list: ItemListResponse = serde_json::from_slice(&body).unwrap();
-
-            assert_eq!(item_list.num_items, 3, "total count should not be paginated for {uri}");
-            assert_eq!(item_list.names, expected_names, "wrong names for {uri}");
+            let body: Value = serde_json::from_slice(&body).unwrap();
+            assert_eq!(body["fields"][0]["field"], "name");
+            assert_eq!(body["fields"][0]["message"], message);
         }
     }
 
     #[tokio::test]
-    async fn query_item_returns_existing_item() {
+    async fn items_with_ttl_expire_and_are_hidden() {
         let shared_state = AppState::new_shared_state();
-        let config = Arc::new(Config::default());
-        let app = build_router(&shared_state, &config);
+        let settings = Settings::default().into_shared();
+        let app = build_router(&shared_state, &settings);
 
         let response = app
             .clone()
@@ -491,27 +959,226 @@ mod tests {
                     .method("POST")
                     .uri("/items")
                     .header("Content-Type", "application/json")
-                    .body(Body::from(r#"{"name":"lookup","id":5555}"#))
+                    .body(Body::from(r#"{"name":"ephemeral","ttl_seconds":60}"#))
                     .unwrap(),
             )
             .await
             .expect("Failed to create item");
         assert_eq!(response.status(), StatusCode::CREATED);
-
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let item: Item = serde_json::from_slice(&body).unwrap();
+        assert_eq!(item.expires_at, Some(item.created_at + Duration::seconds(60)));
+
+        // Expire the item without waiting for the sweeper.
+        shared_state.items().db.alter("ephemeral", |_, item| {
+            Arc::new(Item {
+                expires_at: Some(Utc::now()),
+                ..Arc::unwrap_or_clone(item)
+            })
+        });
         let response = app
-            .oneshot(Request::builder().uri("/item?name=lookup").body(Body::empty()).unwrap())
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri("/item?name=ephemeral")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
             .await
             .expect("Failed to query item");
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let response = app
+            .oneshot(Request::builder().uri("/items").body(Body::empty()).unwrap())
+            .await
+            .expect("Failed to list items");
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let item_list: ItemListResponse = serde_json::from_slice(&body).unwrap();
+        assert_eq!(item_list.num_items, 0);
+    }
 
-        assert_eq!(response.status(), StatusCode::OK);
+    #[tokio::test]
+    async fn create_item_rejects_duplicate_id() {
+        let app = test_router();
+        let create = |item_json: &'static str| {
+            app.clone().oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/items")
+                    .header("Content-Type", "application/json")
+                    .body(Body::from(item_json))
+                    .unwrap(),
+            )
+        };
+
+        let response = create(r#"{"name":"original","id":7777}"#)
+            .await
+            .expect("Failed to create item");
+        assert_eq!(response.status(), StatusCode::CREATED);
+
+        let response = create(r#"{"name":"copy","id":7777}"#)
+            .await
+            .expect("Failed to create item");
+        assert_eq!(response.status(), StatusCode::CONFLICT);
         let body = response.into_body().collect().await.unwrap().to_bytes();
         let body: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["message"], "Item id already exists: 7777");
+
+        let response = create(r#"{"name":"original","id":8888}"#)
+            .await
+            .expect("Failed to create item");
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+
+        let response = create(r#"{"name":"copy","id":8888}"#)
+            .await
+            .expect("Failed to create item");
+        assert_eq!(response.status(), StatusCode::CREATED);
+    }
+
+    #[tokio::test]
+    async fn query_item_returns_existing_item() {
+        let app = TestApp::new();
+        app.create_item(json!({"name": "lookup", "id": 5555})).await;
+
+        let response = app.get("/item?name=lookup").await;
+
+        assert_eq!(response.status, StatusCode::OK);
+        let body: Value = response.json();
         assert_eq!(body["name"], "lookup");
         assert_eq!(body["id"], 5555);
+        assert_eq!(body["created_at"], body["updated_at"]);
+    }
+
+    #[tokio::test]
+    async fn batch_get_returns_found_items_and_missing_names() {
+        let app = TestApp::new();
+        app.create_item(json!({"name": "first"})).await;
+        app.create_item(json!({"name": "second"})).await;
+
+        let response = app
+            .post_json(
+                "/items/query",
+                &json!({"names": ["second", "missing", "first", "second"]}),
+            )
+            .await;
+
+        assert_eq!(response.status, StatusCode::OK);
+        let body: Value = response.json();
+        let names: Vec<&str> = body["items"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|item| item["name"].as_str().unwrap())
+            .collect();
+        assert_eq!(names, ["second", "first"]);
+        assert_eq!(body["missing"], json!(["missing"]));
+
+        let response = app.post_json("/items/query", &json!({"names": []})).await;
+        assert_eq!(response.status, StatusCode::UNPROCESSABLE_ENTITY);
+        let names: Vec<String> = (0..=MAX_BATCH_NAMES).map(|index| format!("item-{index}")).collect();
+        let response = app.post_json("/items/query", &json!({ "names": names })).await;
+        assert_eq!(response.status, StatusCode::UNPROCESSABLE_ENTITY);
+    }
+
+    #[tokio::test]
+    async fn named_collections_keep_items_apart() {
+        let app = TestApp::new();
+        for (name, status) in [
+            ("archive", StatusCode::CREATED),
+            ("archive", StatusCode::CONFLICT),
+            ("Not Valid", StatusCode::UNPROCESSABLE_ENTITY),
+        ] {
+            let response = app.post_json("/admin/collections", &json!({ "name": name })).await;
+            assert_eq!(response.status, status, "{name}");
+        }
+
+        app.create_item(json!({"name": "shared", "id": 1234})).await;
+        let response = app
+            .post_json("/collections/archive/items", &json!({"name": "shared", "id": 1234}))
+            .await;
+        assert_eq!(response.status, StatusCode::CREATED);
+        let response = app.get("/collections/archive/items/shared").await;
+        assert_eq!(response.status, StatusCode::OK);
+        assert_eq!(response.json::<Value>()["id"], 1234);
+        assert_eq!(
+            app.get("/collections/archive/items").await.json::<Value>()["num_items"],
+            1
+        );
+        assert_eq!(
+            app.get("/collections/default/items").await.json::<Value>()["names"],
+            json!(["shared"])
+        );
+        assert_eq!(
+            app.get("/collections/missing/items").await.status,
+            StatusCode::NOT_FOUND
+        );
+        assert_eq!(
+            app.get("/admin/collections").await.json::<Value>(),
+            json!([{"name": "default", "num_items": 1}, {"name": "archive", "num_items": 1}])
+        );
+
+        let response = app.delete("/admin/collections/archive").await;
+        assert_eq!(response.status, StatusCode::OK);
+        assert_eq!(response.json::<Value>()["num_items"], 1);
+        assert_eq!(
+            app.get("/collections/archive/items/shared").await.status,
+            StatusCode::NOT_FOUND
+        );
+        assert_eq!(
+            app.delete("/admin/collections/default").await.status,
+            StatusCode::CONFLICT
+        );
+        assert_eq!(app.get("/item?name=shared").await.status, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn quota_limits_items_and_data_size() {
+        let app = TestApp::with_settings(Settings {
+            quota: QuotaConfig {
+                max_items: Some(2),
+                max_data_bytes: Some(16),
+            },
+            ..Settings::default()
+        });
+        app.create_item(json!({"name": "first", "data": {"a": 1}})).await;
+
+        let response = app
+            .post_json(
+                "/items",
+                &json!({"name": "large", "data": {"text": "over sixteen bytes"}}),
+            )
+            .await;
+        assert_eq!(response.status, StatusCode::PAYLOAD_TOO_LARGE);
+        let error: ApiError = response.json();
+        assert_eq!(
+            error.quota,
+            Some(QuotaExceeded {
+                resource: QuotaResource::DataBytes,
+                limit: 16,
+                requested: 36,
+            })
+        );
+
+        app.create_item(json!({"name": "second"})).await;
+        let response = app.post_json("/items", &json!({"name": "third"})).await;
+        assert_eq!(response.status, StatusCode::FORBIDDEN);
+        assert_eq!(response.json::<ApiError>().message, "Item quota of 2 items is used up");
+
+        let usage: UsageResponse = app.get("/usage").await.json();
+        assert_eq!(
+            usage,
+            UsageResponse {
+                num_items: 2,
+                max_items: Some(2),
+                data_bytes: 7,
+                max_data_bytes: Some(16),
+            }
+        );
+        app.delete("/admin/clear_items").await;
+        assert_eq!(app.get("/usage").await.json::<UsageResponse>().data_bytes, 0);
     }
 
     #[tokio::test]
-    async fn create_item_with_invalid_id_returns_server_error() {
+    async fn create_item_with_invalid_fields_returns_validation_errors() {
         let app = test_router();
 
         let response = app
@@ -520,20 +1187,70 @@ mod tests {
                     .method("POST")
                     .uri("/items")
                     .header("Content-Type", "application/json")
-                    .body(Body::from(r#"{"name":"bad","id":1}"#))
+                    .body(Body::from(r#"{"name":"bad name","id":1}"#))
                     .unwrap(),
             )
             .await
             .expect("Failed to get response");
 
-        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
         let body = response.into_body().collect().await.unwrap().to_bytes();
         let body: Value = serde_json::from_slice(&body).unwrap();
-        assert!(
-            body.as_str()
-                .expect("server error body should be a string")
-                .contains("ID must be between 1000 and 9999")
-        );
+        assert_eq!(body["code"], "validation_failed");
+        assert_eq!(body["fields"][0]["field"], "name");
+        assert_eq!(body["fields"][1]["field"], "id");
+        assert_eq!(body["fields"][1]["message"], "must be between 1000 and 9999");
+    }
+
+    #[tokio::test]
+    async fn uuid_id_strategy_generates_and_requires_uuids() {
+        let app = test_router_with_settings(Settings {
+            id_strategy: IdStrategy::Uuid,
+            ..Settings::default()
+        });
+        let create = |item_json: &'static str| {
+            app.clone().oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/items")
+                    .header("Content-Type", "application/json")
+                    .body(Body::from(item_json))
+                    .unwrap(),
+            )
+        };
+
+        let response = create(r#"{"name":"generated"}"#).await.expect("Failed to create item");
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let item: Item = serde_json::from_slice(&body).unwrap();
+        assert!(matches!(item.id, ItemId::Uuid(_)));
+
+        let response = create(r#"{"name":"provided","id":"67e55044-10b1-426f-9247-bb680e5fe0c8"}"#)
+            .await
+            .expect("Failed to create item");
+        assert_eq!(response.status(), StatusCode::CREATED);
+
+        let response = create(r#"{"name":"numeric","id":4444}"#)
+            .await
+            .expect("Failed to create item");
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["fields"][0]["field"], "id");
+        assert_eq!(body["fields"][0]["message"], "must be a UUID");
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/api-docs/openapi.json")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .expect("Failed to get spec");
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let spec: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(spec["components"]["schemas"]["ItemId"]["format"], "uuid");
     }
 
     #[tokio::test]
@@ -615,6 +1332,7 @@ mod tests {
                     .method("DELETE")
                     .uri("/admin/clear_items")
                     .header("api-key", "wrong_api_key")
+                    .header("accept-language", "fi")
                     .body(Body::empty())
                     .expect("Oneshot failed for /admin/clear_items"),
             )
@@ -622,52 +1340,125 @@ mod tests {
             .expect("Failed to get response");
 
         assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["message"], "Virheellinen API-avain");
+        assert!(!body.to_string().contains("wrong_api_key"));
     }
 
     #[tokio::test]
     async fn admin_clear_items_removes_existing_items() {
-        let shared_state = AppState::new_shared_state();
-        let config = Arc::new(Config::default());
-        let app = build_router(&shared_state, &config);
+        let app = TestApp::new();
+        app.create_item(json!({"name": "temporary"})).await;
 
-        let response = app
-            .clone()
+        let response = app.delete("/admin/clear_items").await;
+
+        assert_eq!(response.status, StatusCode::OK);
+        let body: Value = response.json();
+        assert_eq!(body["message"], "Removed 1 items");
+        assert!(app.state.items().db.is_empty());
+    }
+
+    #[tokio::test]
+    async fn admin_backup_restores_into_another_instance() {
+        let source_state = AppState::new_shared_state();
+        source_state
+            .items()
+            .insert_item(&Item::new("backed-up".to_string(), ItemId::Numeric(4321), IdStrategy::Numeric).unwrap())
+            .unwrap();
+        let source = build_router(&source_state, &Settings::default().into_shared());
+
+        let response = source
             .oneshot(
                 Request::builder()
-                    .method("POST")
-                    .uri("/items")
-                    .header("Content-Type", "application/json")
-                    .body(Body::from(r#"{"name":"temporary"}"#))
+                    .uri("/admin/backup")
+                    .header("api-key", DEFAULT_API_KEY)
+                    .body(Body::empty())
                     .unwrap(),
             )
             .await
-            .expect("Failed to create item");
-        assert_eq!(response.status(), StatusCode::CREATED);
+            .expect("Failed to get backup");
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["content-type"], "application/gzip");
+        assert!(
+            response.headers()["content-disposition"]
+                .to_str()
+                .unwrap()
+                .starts_with("attachment; filename=")
+        );
+        let backup = response.into_body().collect().await.unwrap().to_bytes();
 
-        let response = app
-            .oneshot(
+        let target_state = AppState::new_shared_state();
+        let target = build_router(&target_state, &Settings::default().into_shared());
+        let restore = |body: Body| {
+            target.clone().oneshot(
                 Request::builder()
-                    .method("DELETE")
-                    .uri("/admin/clear_items")
-                    .header("api-key", &config.api_key)
-                    .body(Body::empty())
+                    .method("POST")
+                    .uri("/admin/restore?mode=merge")
+                    .header("api-key", DEFAULT_API_KEY)
+                    .header("Content-Type", "application/gzip")
+                    .body(body)
                     .unwrap(),
             )
-            .await
-            .expect("Failed to clear items");
+        };
 
+        let response = restore(Body::from(backup)).await.expect("Failed to restore");
         assert_eq!(response.status(), StatusCode::OK);
         let body = response.into_body().collect().await.unwrap().to_bytes();
         let body: Value = serde_json::from_slice(&body).unwrap();
-        assert_eq!(body["message"], "Removed 1 items");
-        assert!(shared_state.db.is_empty());
+        assert_eq!(body["mode"], "merge");
+        assert_eq!(body["restored"], 1);
+        assert_eq!(
+            target_state.items().db.get("backed-up").unwrap().id,
+            ItemId::Numeric(4321)
+        );
+
+        let response = restore(Body::from("not a snapshot")).await.expect("Failed to restore");
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn admin_drain_fails_readiness_and_shuts_down() {
+        let shared_state = AppState::new_shared_state();
+        let settings = Settings {
+            drain_delay_seconds: 0,
+            ..Settings::default()
+        }
+        .into_shared();
+        let app = build_router(&shared_state, &settings);
+        let readiness = || {
+            app.clone()
+                .oneshot(Request::builder().uri("/readyz").body(Body::empty()).unwrap())
+        };
+        let drain = || {
+            app.clone().oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/admin/drain")
+                    .header("api-key", DEFAULT_API_KEY)
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+        };
+
+        assert_eq!(readiness().await.unwrap().status(), StatusCode::OK);
+        assert_eq!(drain().await.unwrap().status(), StatusCode::ACCEPTED);
+        assert_eq!(readiness().await.unwrap().status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(drain().await.unwrap().status(), StatusCode::CONFLICT);
+
+        tokio::time::timeout(
+            std::time::Duration::from_secs(1),
+            shared_state.lifecycle().shutdown_token().cancelled_owned(),
+        )
+        .await
+        .expect("drain should trigger shutdown");
     }
 
     #[tokio::test]
     async fn admin_remove_item_handles_found_and_missing_item() {
         let shared_state = AppState::new_shared_state();
-        let config = Arc::new(Config::default());
-        let app = build_router(&shared_state, &config);
+        let settings = Settings::default().into_shared();
+        let app = build_router(&shared_state, &settings);
 
         let response = app
             .clone()
@@ -689,7 +1480,8 @@ mod tests {
                 Request::builder()
                     .method("DELETE")
                     .uri("/admin/remove/removable")
-                    .header("api-key", &config.api_key)
+                    .header("api-key", DEFAULT_API_KEY)
+                    .header("If-Match", "1")
                     .body(Body::empty())
                     .unwrap(),
             )
@@ -707,7 +1499,8 @@ mod tests {
                 Request::builder()
                     .method("DELETE")
                     .uri("/admin/remove/removable")
-                    .header("api-key", &config.api_key)
+                    .header("api-key", DEFAULT_API_KEY)
+                    .header("If-Match", "1")
                     .body(Body::empty())
                     .unwrap(),
             )
@@ -719,4 +1512,700 @@ mod tests {
         let body: Value = serde_json::from_slice(&body).unwrap();
         assert_eq!(body["message"], "Item does not exist: removable");
     }
+
+    #[tokio::test]
+    async fn admin_remove_item_requires_current_revision() {
+        let shared_state = AppState::new_shared_state();
+        let settings = Settings::default().into_shared();
+        let app = build_router(&shared_state, &settings);
+        let mut item =
+            Item::new("guarded".to_string(), ItemId::Numeric(4321), IdStrategy::Numeric).expect("valid item");
+        item.revision = 2;
+        shared_state.items().insert_item(&item).expect("new item");
+
+        for (if_match, expected_status) in [
+            (None, StatusCode::PRECONDITION_REQUIRED),
+            (Some("latest"), StatusCode::BAD_REQUEST),
+            (Some("1"), StatusCode::PRECONDITION_FAILED),
+            (Some("\"2\""), StatusCode::OK),
+        ] {
+            let mut request = Request::builder()
+                .method("DELETE")
+                .uri("/admin/remove/guarded")
+                .header("api-key", DEFAULT_API_KEY);
+            if let Some(if_match) = if_match {
+                request = request.header("If-Match", if_match);
+            }
+            let response = app
+                .clone()
+                .oneshot(request.body(Body::empty()).unwrap())
+                .await
+                .expect("Failed to remove item");
+            assert_eq!(response.status(), expected_status, "If-Match: {if_match:?}");
+        }
+        assert!(shared_state.items().db.is_empty());
+    }
+
+    #[tokio::test]
+    async fn api_routes_are_versioned_with_deprecated_legacy_aliases() {
+        let shared_state = Arc::new(AppState::new().with_deprecations(legacy_route_deprecations()));
+        let settings = Settings::default().into_shared();
+        let app = build_router(&shared_state, &settings);
+        let get = |uri: &str| Request::builder().uri(uri).body(Body::empty()).unwrap();
+
+        let response = app
+            .clone()
+            .oneshot(get("/api/v1/items"))
+            .await
+            .expect("Failed to list items");
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(!response.headers().contains_key("deprecation"));
+
+        let response = app.clone().oneshot(get("/items")).await.expect("Failed to list items");
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["deprecation"], "@1792108800");
+        assert_eq!(response.headers()["sunset"], "Fri, 30 Apr 2027 00:00:00 GMT");
+
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri("/api/v1/admin/config")
+                    .header("api-key", DEFAULT_API_KEY)
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .expect("Failed to get config");
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = app.clone().oneshot(get("/health")).await.expect("Failed to get health");
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(!response.headers().contains_key("deprecation"));
+
+        let response = app
+            .clone()
+            .oneshot(get("/api/v1/health"))
+            .await
+            .expect("Failed to get response");
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let response = app.oneshot(get(OPENAPI_JSON_PATH)).await.expect("Failed to get spec");
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let spec: Value = serde_json::from_slice(&body).unwrap();
+        assert!(
+            spec["paths"]["/items"]["get"]["description"]
+                .as_str()
+                .unwrap()
+                .contains("deprecated unversioned path `/items` until 2027-04-30")
+        );
+        assert!(
+            spec["paths"]["/health"]["get"]
+                .get("description")
+                .is_none_or(|description| { !description.as_str().unwrap_or_default().contains("deprecated") })
+        );
+    }
+
+    #[tokio::test]
+    async fn deprecated_route_and_field_usage_adds_headers() {
+        let deprecated_at = Utc.with_ymd_and_hms(2026, 1, 1, 0, 0, 0).unwrap();
+        let deprecations = DeprecationRegistry::default()
+            .with_route(
+                Some(axum::http::Method::GET),
+                "/item",
+                deprecated_at,
+                Utc::now() + Duration::days(90),
+                "Use /items instead",
+                Some("https://example.com/migration"),
+            )
+            .with_field(
+                None,
+                "/items",
+                "id",
+                deprecated_at,
+                Utc::now() + Duration::days(30),
+                "Ids are server generated",
+                None,
+            );
+        let shared_state = Arc::new(AppState::new().with_deprecations(deprecations));
+        let settings = Settings::default().into_shared();
+        let app = build_router(&shared_state, &settings);
+
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri("/item?name=missing")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .expect("Failed to get response");
+        assert_eq!(response.headers()["deprecation"], "@1767225600");
+        assert!(response.headers().contains_key("sunset"));
+        assert_eq!(
+            response.headers()["link"],
+            "<https://example.com/migration>; rel=\"deprecation\""
+        );
+
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/items")
+                    .header("Content-Type", "application/json")
+                    .body(Body::from(r#"{"name":"without-id"}"#))
+                    .unwrap(),
+            )
+            .await
+            .expect("Failed to create item");
+        assert_eq!(response.status(), StatusCode::CREATED);
+        assert!(!response.headers().contains_key("deprecation"));
+
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/items")
+                    .header("Content-Type", "application/json")
+                    .body(Body::from(r#"{"name":"with-id","id":1234}"#))
+                    .unwrap(),
+            )
+            .await
+            .expect("Failed to create item");
+        assert_eq!(response.status(), StatusCode::CREATED);
+        assert!(response.headers().contains_key("deprecation"));
+
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri("/admin/deprecations")
+                    .header("api-key", DEFAULT_API_KEY)
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .expect("Failed to list deprecations");
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body.as_array().map(Vec::len), Some(2));
+        assert_eq!(body[1]["field"], "id");
+
+        let response = app
+            .oneshot(Request::builder().uri("/metrics").body(Body::empty()).unwrap())
+            .await
+            .expect("Failed to get metrics");
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let body = String::from_utf8(body.to_vec()).expect("metrics should be utf-8");
+        assert!(body.contains("axum_example_deprecated_usage_total"));
+    }
+
+    #[tokio::test]
+    async fn item_routes_honor_accept_header() {
+        let app = test_router();
+
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/items")
+                    .header("Content-Type", "application/json")
+                    .header("Accept", "application/xml")
+                    .body(Body::from(r#"{"name":"encoded","id":1234}"#))
+                    .unwrap(),
+            )
+            .await
+            .expect("Failed to create item");
+        assert_eq!(response.status(), StatusCode::CREATED);
+        assert_eq!(response.headers()["content-type"], "application/xml");
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let body = String::from_utf8(body.to_vec()).expect("xml should be utf-8");
+        assert!(body.contains("<id>1234</id>"));
+        assert!(body.contains("<name>encoded</name>"));
+        assert!(body.contains("<created_at>"));
+
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri("/items")
+                    .header("Accept", "application/msgpack")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .expect("Failed to list items");
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["content-type"], "application/msgpack");
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!(body[0], 0x82, "list response should be a two-entry map");
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/item?name=encoded")
+                    .header("Accept", "text/html, */*;q=0.8")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .expect("Failed to query item");
+        assert_eq!(response.headers()["content-type"], "application/json");
+    }
+
+    #[tokio::test]
+    async fn requests_over_rate_limit_get_429_with_retry_after() {
+        let app = test_router_with_settings(Settings {
+            rate_limit: RateLimitConfig {
+                requests_per_second: 1,
+                burst: 2,
+            },
+            ..Settings::default()
+        });
+
+        for _ in 0..2 {
+            let response = app
+                .clone()
+                .oneshot(Request::builder().uri("/").body(Body::empty()).unwrap())
+                .await
+                .expect("Failed to get response");
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_eq!(response.headers()["x-ratelimit-limit"], "2");
+        }
+
+        let response = app
+            .oneshot(Request::builder().uri("/").body(Body::empty()).unwrap())
+            .await
+            .expect("Failed to get response");
+
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(response.headers()["retry-after"], "1");
+        assert_eq!(response.headers()["x-ratelimit-remaining"], "0");
+        assert_eq!(response.headers()["x-ratelimit-reset"], "2");
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["message"], "Too many requests, retry after 1 seconds");
+    }
+
+    #[tokio::test]
+    async fn clients_behind_trusted_proxies_have_separate_rate_limits() {
+        let app = test_router_with_settings(Settings {
+            rate_limit: RateLimitConfig {
+                requests_per_second: 1,
+                burst: 1,
+            },
+            admin_access: IpFilterConfig {
+                trusted_proxies: vec!["172.16.0.0/12".parse().unwrap()],
+                ..IpFilterConfig::default()
+            },
+            ..Settings::default()
+        });
+        let status = |forwarded_for: &str| {
+            let request = Request::builder()
+                .uri("/")
+                .header("x-forwarded-for", forwarded_for)
+                .extension(ConnectInfo("172.16.0.1:5000".parse::<SocketAddr>().unwrap()))
+                .body(Body::empty())
+                .unwrap();
+            let app = app.clone();
+            async move { app.oneshot(request).await.expect("Failed to get response").status() }
+        };
+
+        assert_eq!(status("203.0.113.5").await, StatusCode::OK);
+        assert_eq!(status("203.0.113.6").await, StatusCode::OK);
+        assert_eq!(status("203.0.113.5").await, StatusCode::TOO_MANY_REQUESTS);
+    }
+
+    #[tokio::test]
+    async fn admin_config_shows_settings_without_api_key() {
+        let app = test_router_with_settings(Settings {
+            cors_origins: vec!["https://app.example".to_string()],
+            ..Settings::default()
+        });
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/admin/config")
+                    .header("api-key", DEFAULT_API_KEY)
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .expect("Failed to get config");
+
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let text = String::from_utf8(body.to_vec()).expect("config should be utf-8");
+        assert!(!text.contains(DEFAULT_API_KEY));
+        let body: Value = serde_json::from_str(&text).unwrap();
+        assert_eq!(body["environment"], "LOCAL");
+        assert_eq!(body["request_timeout_seconds"], 10);
+        assert_eq!(body["settings"]["api_key"], "<redacted>");
+        assert_eq!(body["settings"]["cors_origins"][0], "https://app.example");
+    }
+
+    #[tokio::test]
+    async fn admin_reload_config_applies_and_reports_changes() {
+        let path = std::env::temp_dir().join(format!("axum-example-reload-route-{}.toml", std::process::id()));
+        std::fs::write(&path, "drain_delay_seconds = 10").expect("write config");
+        let loader = SettingsLoader::new(path.clone(), CliSettings::default());
+        let settings = loader.load().expect("valid settings").into_shared();
+        let app = build_router(&AppState::new_shared_state(), &settings).layer(Extension(loader));
+        let reload = || {
+            Request::builder()
+                .method("POST")
+                .uri("/admin/reload_config")
+                .header("api-key", DEFAULT_API_KEY)
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        std::fs::write(
+            &path,
+            "drain_delay_seconds = 30\nport = 4000\n\n[backend]\ntable = \"items\"",
+        )
+        .expect("write config");
+        let response = app.clone().oneshot(reload()).await.expect("Failed to reload config");
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["changes"][0]["key"], "backend.table");
+        assert_eq!(body["changes"][0]["restart_required"], true);
+        assert_eq!(body["changes"][1]["key"], "drain_delay_seconds");
+        assert_eq!(body["changes"][1]["old"], 10);
+        assert_eq!(body["changes"][1]["new"], 30);
+        assert_eq!(body["changes"][1]["restart_required"], false);
+        assert_eq!(body["changes"][2]["key"], "port");
+        assert_eq!(body["changes"][2]["restart_required"], true);
+        assert_eq!(settings.load().drain_delay_seconds, 30);
+
+        std::fs::write(&path, "drain_delay_seconds = \"soon\"").expect("write config");
+        let response = app.oneshot(reload()).await.expect("Failed to reload config");
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(settings.load().drain_delay_seconds, 30);
+
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[tokio::test]
+    async fn admin_compact_reports_capacity() {
+        let shared_state = AppState::new_shared_state();
+        let app = build_router(&shared_state, &Settings::default().into_shared());
+        shared_state
+            .items()
+            .insert_item(&Item::new_with_random_id("kept".to_string(), IdStrategy::Numeric))
+            .unwrap();
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/admin/compact")
+                    .header("api-key", DEFAULT_API_KEY)
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .expect("Failed to compact");
+
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["num_items"], 1);
+        assert!(body["capacity_after"].as_u64().unwrap() <= body["capacity_before"].as_u64().unwrap());
+        assert!(shared_state.items().db.contains_key("kept"));
+    }
+
+    #[tokio::test]
+    async fn admin_inflight_lists_running_requests() {
+        let app = test_router();
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/admin/inflight")
+                    .header("api-key", DEFAULT_API_KEY)
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .expect("Failed to list in-flight requests");
+
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["num_requests"], 1);
+        assert_eq!(body["requests"][0]["method"], "GET");
+        assert_eq!(body["requests"][0]["path"], "/admin/inflight");
+    }
+
+    #[tokio::test]
+    async fn admin_items_lists_full_items_sorted_and_paginated() {
+        let shared_state = AppState::new_shared_state();
+        let app = build_router(&shared_state, &Settings::default().into_shared());
+        for (name, revision) in [("bravo", 3), ("alpha", 1), ("charlie", 2)] {
+            let mut item =
+                Item::new_with_random_id(name.to_string(), IdStrategy::Numeric).with_tags(vec!["ops".to_string()]);
+            item.revision = revision;
+            shared_state.items().insert_item(&item).unwrap();
+        }
+        let list = |uri: &str| {
+            Request::builder()
+                .uri(uri)
+                .header("api-key", DEFAULT_API_KEY)
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        let response = app
+            .clone()
+            .oneshot(list("/admin/items"))
+            .await
+            .expect("Failed to list items");
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["num_items"], 3);
+        assert_eq!(body["limit"], 100);
+        assert_eq!(body["items"][0]["name"], "alpha");
+        assert_eq!(body["items"][0]["tags"][0], "ops");
+
+        let response = app
+            .clone()
+            .oneshot(list("/admin/items?sort=revision&order=desc&skip=1&limit=1"))
+            .await
+            .expect("Failed to list items");
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["num_items"], 3);
+        assert_eq!(body["items"].as_array().unwrap().len(), 1);
+        assert_eq!(body["items"][0]["name"], "charlie");
+
+        let response = app
+            .oneshot(list("/admin/items?sort=color"))
+            .await
+            .expect("Failed to list items");
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn item_listing_is_cached_until_an_item_changes() {
+        let shared_state = AppState::new_shared_state();
+        let app = build_router(&shared_state, &Settings::default().into_shared());
+        let list = || Request::builder().uri("/items").body(Body::empty()).unwrap();
+
+        let response = app.clone().oneshot(list()).await.expect("Failed to list items");
+        assert_eq!(response.headers()[CACHE_STATUS_HEADER], "MISS");
+        let response = app.clone().oneshot(list()).await.expect("Failed to list items");
+        assert_eq!(response.headers()[CACHE_STATUS_HEADER], "HIT");
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["num_items"], 0);
+
+        shared_state
+            .items()
+            .insert_item(&Item::new_with_random_id("new".to_string(), IdStrategy::Numeric))
+            .unwrap();
+        let response = app.clone().oneshot(list()).await.expect("Failed to list items");
+        assert_eq!(response.headers()[CACHE_STATUS_HEADER], "MISS");
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["num_items"], 1);
+
+        let app = test_router_with_settings(Settings {
+            response_cache_ttl_ms: 0,
+            ..Settings::default()
+        });
+        let response = app.oneshot(list()).await.expect("Failed to list items");
+        assert!(!response.headers().contains_key(CACHE_STATUS_HEADER));
+    }
+
+    #[tokio::test]
+    async fn cached_responses_are_per_language_and_record_item_access() {
+        let shared_state = AppState::new_shared_state();
+        let app = build_router(&shared_state, &Settings::default().into_shared());
+        shared_state
+            .items()
+            .insert_item(&Item::new("cached".to_string(), ItemId::Numeric(4321), IdStrategy::Numeric).unwrap())
+            .unwrap();
+        let query = |language: &'static str| {
+            Request::builder()
+                .uri("/item?name=cached")
+                .header("accept-language", language)
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        let response = app.clone().oneshot(query("fi")).await.expect("Failed to query item");
+        assert_eq!(response.headers()[CACHE_STATUS_HEADER], "MISS");
+        let response = app.clone().oneshot(query("en")).await.expect("Failed to query item");
+        assert_eq!(response.headers()[CACHE_STATUS_HEADER], "MISS");
+
+        let long_ago = Utc.with_ymd_and_hms(2020, 1, 1, 0, 0, 0).unwrap();
+        shared_state.items().record_access("cached", long_ago);
+        let response = app.oneshot(query("fi")).await.expect("Failed to query item");
+        assert_eq!(response.headers()[CACHE_STATUS_HEADER], "HIT");
+        assert!(shared_state.items().last_accessed("cached").unwrap() > long_ago);
+    }
+
+    #[tokio::test]
+    async fn admin_routes_reject_clients_outside_allowed_networks() {
+        let app = test_router_with_settings(Settings {
+            admin_access: IpFilterConfig {
+                allowed: vec!["10.0.0.0/8".parse().unwrap()],
+                denied: Vec::new(),
+                trusted_proxies: vec!["172.16.0.0/12".parse().unwrap()],
+            },
+            ..Settings::default()
+        });
+        let admin_request = |peer: &str, forwarded_for: Option<&str>| {
+            let mut builder = Request::builder()
+                .method("DELETE")
+                .uri("/admin/clear_items")
+                .header("api-key", DEFAULT_API_KEY)
+                .extension(ConnectInfo(peer.parse::<SocketAddr>().unwrap()));
+            if let Some(forwarded_for) = forwarded_for {
+                builder = builder.header("x-forwarded-for", forwarded_for);
+            }
+            builder.body(Body::empty()).unwrap()
+        };
+
+        let response = app
+            .clone()
+            .oneshot(admin_request("10.1.2.3:5000", None))
+            .await
+            .expect("Failed to get response");
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = app
+            .clone()
+            .oneshot(admin_request("203.0.113.5:5000", Some("10.1.2.3")))
+            .await
+            .expect("Failed to get response");
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        let response = app
+            .clone()
+            .oneshot(admin_request("172.16.0.1:5000", Some("10.1.2.3")))
+            .await
+            .expect("Failed to get response");
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = app
+            .oneshot(Request::builder().uri("/health").body(Body::empty()).unwrap())
+            .await
+            .expect("Failed to get response");
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn trailing_slash_matches_route_without_slash() {
+        let app = test_router();
+
+        for uri in ["/items/", "/health/", "/admin/deprecations/"] {
+            let response = app
+                .clone()
+                .oneshot(
+                    Request::builder()
+                        .uri(uri)
+                        .header("api-key", DEFAULT_API_KEY)
+                        .body(Body::empty())
+                        .unwrap(),
+                )
+                .await
+                .expect("Failed to get response");
+            assert_eq!(response.status(), StatusCode::OK, "{uri}");
+        }
+
+        let response = app
+            .oneshot(Request::builder().uri("/doc/").body(Body::empty()).unwrap())
+            .await
+            .expect("Failed to get response");
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn cors_origins_follow_settings_changes() {
+        let settings = Settings::default().into_shared();
+        let shared_state = AppState::new_shared_state();
+        let app = build_router(&shared_state, &settings);
+        let preflight = || {
+            Request::builder()
+                .method("OPTIONS")
+                .uri("/items")
+                .header("origin", "https://app.example")
+                .header("access-control-request-method", "POST")
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        let response = app.clone().oneshot(preflight()).await.expect("Failed to get response");
+        assert!(response.headers().get("access-control-allow-origin").is_none());
+
+        settings.store(Arc::new(Settings {
+            cors_origins: vec!["https://app.example".to_string()],
+            ..Settings::default()
+        }));
+
+        let response = app.oneshot(preflight()).await.expect("Failed to get response");
+        assert_eq!(response.headers()["access-control-allow-origin"], "https://app.example");
+    }
+
+    #[tokio::test]
+    async fn cors_preflight_allows_the_tenant_and_if_match_headers() {
+        let settings = Settings {
+            cors_origins: vec!["https://app.example".to_string()],
+            ..Settings::default()
+        }
+        .into_shared();
+        let app = build_router(&AppState::new_shared_state(), &settings);
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method("OPTIONS")
+                    .uri("/items")
+                    .header("origin", "https://app.example")
+                    .header("access-control-request-method", "DELETE")
+                    .header("access-control-request-headers", "x-tenant-id, if-match")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .expect("Failed to get response");
+
+        let allowed = response.headers()["access-control-allow-headers"].to_str().unwrap();
+        assert!(allowed.contains("x-tenant-id"), "{allowed}");
+        assert!(allowed.contains("if-match"), "{allowed}");
+    }
+
+    #[tokio::test]
+    async fn cors_responses_expose_the_etag() {
+        let settings = Settings {
+            cors_origins: vec!["https://app.example".to_string()],
+            ..Settings::default()
+        }
+        .into_shared();
+        let app = build_router(&AppState::new_shared_state(), &settings);
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/version")
+                    .header("origin", "https://app.example")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .expect("Failed to get response");
+
+        let exposed = response.headers()["access-control-expose-headers"].to_str().unwrap();
+        assert!(exposed.contains("etag"), "{exposed}");
+    }
 }
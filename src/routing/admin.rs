@@ -3,17 +3,43 @@
 //! Contains endpoints nested under `/admin`.
 //! Every route in this module requires the custom API-key extractor.
 
+use std::cmp::Ordering;
 use std::sync::Arc;
+use std::time::Duration;
 
+use anyhow::Context;
 use axum::Router;
-use axum::extract::{Extension, Json};
+use axum::body::Bytes;
+use axum::extract::{DefaultBodyLimit, Extension, Json, Query};
 use axum::extract::{Path, State};
 use axum::http::StatusCode;
-use axum::response::IntoResponse;
-use axum::routing::delete;
+use axum::http::header::{CONTENT_DISPOSITION, CONTENT_TYPE, LOCATION};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{delete, get, post};
 
-use crate::schemas::{AuthErrorResponse, MessageResponse, RemoveItemResponse};
-use crate::types::{ApiKeyExtractor, Config, Item, SharedState};
+use crate::backend;
+use crate::backup;
+use crate::events::{Actor, ItemChange, ItemEvent};
+use crate::i18n;
+use crate::jobs::{JobWork, QueueFull};
+use crate::lock;
+use crate::negotiation::{AcceptFormat, Negotiated};
+use crate::precondition::IfMatch;
+use crate::replication;
+use crate::router::{API_V1_PREFIX, REQUEST_TIMEOUT};
+use crate::schemas::{
+    AdminItemListQuery, AdminItemListResponse, ApiError, CollectionInfo, CompactResponse, ConfigResponse,
+    CreateCollection, CreateWebhook, DEFAULT_ADMIN_PAGE_SIZE, Deprecation, ErrorCode, InflightResponse, ItemSortField,
+    JobKind, JobStatus, MAX_ADMIN_PAGE_SIZE, MessageResponse, ReloadConfigResponse, RestoreMode, RestoreQuery,
+    RestoreResponse, SortOrder, Webhook, WebhookDeliveriesResponse,
+};
+use crate::settings::{self, SettingsLoader, SharedSettings};
+use crate::snapshot::{MAX_SNAPSHOT_BYTES, Snapshot};
+use crate::types::{
+    ApiKeyExtractor, Collection, CollectionError, DEFAULT_COLLECTION, Item, MAX_COLLECTIONS, QuotaConfig, SharedState,
+};
+use crate::validation::ValidatedJson;
+use crate::version;
 
 /// Create admin routes.
 ///
@@ -22,6 +48,26 @@ pub fn routes() -> Router<SharedState> {
     Router::new()
         .route("/clear_items", delete(delete_all_items))
         .route("/remove/{name}", delete(remove_item))
+        .route("/deprecations", get(list_deprecations))
+        .route("/config", get(get_config))
+        .route("/backup", get(backup))
+        .route(
+            "/restore",
+            post(restore).layer(DefaultBodyLimit::max(MAX_SNAPSHOT_BYTES)),
+        )
+        .route("/backups", post(create_backup))
+        .route("/jobs/{id}", get(job_status))
+        .route("/drain", post(drain))
+        .route("/reload_config", post(reload_config))
+        .route("/compact", post(compact))
+        .route("/inflight", get(inflight))
+        .route("/items", get(list_items))
+        .route("/collections", get(list_collections).post(create_collection))
+        .route("/collections/{name}", delete(drop_collection))
+        .route("/webhooks", get(list_webhooks).post(create_webhook))
+        .route("/webhooks/{id}", delete(remove_webhook))
+        .route("/webhooks/{id}/deliveries", get(webhook_deliveries))
+        .route("/replicate", post(replicate))
 }
 
 /// Remove all items.
@@ -34,21 +80,38 @@ pub fn routes() -> Router<SharedState> {
     ),
     responses(
         (status = 200, body = [MessageResponse], description = "Report number of items deleted"),
-        (status = UNAUTHORIZED, body = [AuthErrorResponse], description = "Unauthorized"),
+        (status = UNAUTHORIZED, body = ApiError, description = "Unauthorized"),
+        (status = FORBIDDEN, body = ApiError, description = "Client network is not allowed"),
+        (status = CONFLICT, body = ApiError, description = "Another instance holds the items lock"),
+        (status = INTERNAL_SERVER_ERROR, body = ApiError, description = "Clearing the backend failed"),
     )
 )]
 pub async fn delete_all_items(
     _api_key: ApiKeyExtractor,
     State(state): State<SharedState>,
-    Extension(_config): Extension<Arc<Config>>,
-) -> impl IntoResponse {
-    let number_of_items = state.db.len();
-    state.db.clear();
+    Extension(_settings): Extension<SharedSettings>,
+) -> Result<(StatusCode, Json<MessageResponse>), ApiError> {
+    let items = state.tenant_items();
+    let number_of_items = match backend::for_collection(&state, &items) {
+        Some(backend) => {
+            lock::with_lock(&state, lock::ITEMS_LOCK, async {
+                // Clear the backend first, so a failure leaves the cache matching it.
+                let backend_items = backend.clear().await?;
+                Ok(items.clear_items().max(backend_items))
+            })
+            .await?
+        }
+        None => items.clear_items(),
+    };
     crate::log_debug!("Delete all {number_of_items} items");
-    (
+    state.publish(Actor::Admin, ItemChange::Cleared { count: number_of_items });
+    Ok((
         StatusCode::OK,
-        Json(MessageResponse::new(format!("Removed {number_of_items} items"))),
-    )
+        Json(MessageResponse::new(i18n::message(
+            "items_removed",
+            &[("count", &number_of_items)],
+        ))),
+    ))
 }
 
 /// Remove item with given name.
@@ -56,29 +119,776 @@ pub async fn delete_all_items(
 #[utoipa::path(
     delete,
     path = "/admin/remove/{name}",
+    params(
+        ("If-Match" = String, Header, description = "Current item revision, or * for any"),
+    ),
     security(
         ("api_key" = [])
     ),
     responses(
         (status = OK, body = [Item], description = "Item removed"),
-        (status = NOT_FOUND, body = [MessageResponse], description = "Item does not exist"),
-        (status = UNAUTHORIZED, body = [AuthErrorResponse], description = "Unauthorized"),
+        (status = NOT_FOUND, body = ApiError, description = "Item does not exist"),
+        (status = PRECONDITION_FAILED, body = ApiError, description = "Item revision does not match If-Match"),
+        (status = PRECONDITION_REQUIRED, body = ApiError, description = "Missing If-Match header"),
+        (status = UNAUTHORIZED, body = ApiError, description = "Unauthorized"),
+        (status = FORBIDDEN, body = ApiError, description = "Client network is not allowed"),
+        (status = INTERNAL_SERVER_ERROR, body = ApiError, description = "Removing the item from the backend failed"),
     )
 )]
 pub async fn remove_item(
+    _api_key: ApiKeyExtractor,
+    if_match: IfMatch,
+    AcceptFormat(format): AcceptFormat,
+    State(state): State<SharedState>,
+    Extension(_settings): Extension<SharedSettings>,
+    Path(name): Path<String>,
+) -> Negotiated<Result<Item, ApiError>> {
+    let items = state.tenant_items();
+    match remove_stored_item(&state, &items, &name, |item| if_match.matches(item.revision)).await {
+        Ok(Some(removed_item)) => {
+            state.publish(
+                Actor::Admin,
+                ItemChange::Removed {
+                    item: removed_item.clone(),
+                },
+            );
+            return Negotiated(format, Ok(removed_item));
+        }
+        Ok(None) => {}
+        Err(error) => return Negotiated(format, Err(error)),
+    }
+    let error = if let Some(existing_item) = items.db.get(&name) {
+        crate::log_warn!("Remove item rejected for stale revision: {}", name);
+        ApiError::new(
+            ErrorCode::PreconditionFailed,
+            i18n::message(
+                "item_revision_mismatch",
+                &[("name", &name), ("revision", &existing_item.revision)],
+            ),
+        )
+    } else {
+        crate::log_error!("Remove item failed for non-existing name: {}", name);
+        ApiError::new(ErrorCode::NotFound, i18n::message("item_not_found", &[("name", &name)]))
+    };
+    Negotiated(format, Err(error))
+}
+
+/// Remove the item from the collection and its backend if the predicate accepts it.
+///
+/// Returns `None` when the item does not exist or the predicate rejects it.
+pub(crate) async fn remove_stored_item(
+    state: &SharedState,
+    items: &Collection,
+    name: &str,
+    predicate: impl FnOnce(&Item) -> bool,
+) -> Result<Option<Item>, ApiError> {
+    backend::read_through(state, items, name).await;
+    let Some(removed_item) = items.remove_item_if(name, predicate) else {
+        return Ok(None);
+    };
+    if let Some(backend) = backend::for_collection(state, items)
+        && let Err(error) = backend.delete(name).await
+    {
+        // Put the item back so the cache keeps matching the backend.
+        items.insert_item(&removed_item).ok();
+        return Err(error.into());
+    }
+    crate::log_debug!("Remove item: {}", name);
+    Ok(Some(removed_item))
+}
+
+/// List deprecated routes and fields with their sunset dates.
+#[axum::debug_handler]
+#[utoipa::path(
+    get,
+    path = "/admin/deprecations",
+    security(
+        ("api_key" = [])
+    ),
+    responses(
+        (status = OK, body = [Deprecation], description = "Registered deprecations"),
+        (status = UNAUTHORIZED, body = ApiError, description = "Unauthorized"),
+        (status = FORBIDDEN, body = ApiError, description = "Client network is not allowed"),
+    )
+)]
+pub async fn list_deprecations(
+    _api_key: ApiKeyExtractor,
+    State(state): State<SharedState>,
+    Extension(_settings): Extension<SharedSettings>,
+) -> (StatusCode, Json<Vec<Deprecation>>) {
+    (StatusCode::OK, Json(state.deprecations().entries().to_vec()))
+}
+
+/// Show the configuration the running instance is using, with secrets redacted.
+#[axum::debug_handler]
+#[utoipa::path(
+    get,
+    path = "/admin/config",
+    security(
+        ("api_key" = [])
+    ),
+    responses(
+        (status = OK, body = ConfigResponse, description = "Effective configuration"),
+        (status = UNAUTHORIZED, body = ApiError, description = "Unauthorized"),
+        (status = FORBIDDEN, body = ApiError, description = "Client network is not allowed"),
+    )
+)]
+pub async fn get_config(
+    _api_key: ApiKeyExtractor,
+    Extension(settings): Extension<SharedSettings>,
+) -> (StatusCode, Json<ConfigResponse>) {
+    let settings = settings.load().redacted();
+    (
+        StatusCode::OK,
+        Json(ConfigResponse {
+            environment: settings.env.to_string(),
+            version: version::PACKAGE_VERSION.to_string(),
+            request_timeout_seconds: REQUEST_TIMEOUT.as_secs(),
+            settings,
+        }),
+    )
+}
+
+/// Download a gzip-compressed JSON snapshot of all items of the tenant.
+#[axum::debug_handler]
+#[utoipa::path(
+    get,
+    path = "/admin/backup",
+    security(
+        ("api_key" = [])
+    ),
+    responses(
+        (status = OK, body = Vec<u8>, content_type = "application/gzip", description = "Snapshot file download"),
+        (status = UNAUTHORIZED, body = ApiError, description = "Unauthorized"),
+        (status = FORBIDDEN, body = ApiError, description = "Client network is not allowed"),
+        (status = INTERNAL_SERVER_ERROR, body = ApiError, description = "Snapshot could not be written"),
+    )
+)]
+pub async fn backup(
+    _api_key: ApiKeyExtractor,
+    State(state): State<SharedState>,
+    Extension(_settings): Extension<SharedSettings>,
+) -> Result<Response, ApiError> {
+    let snapshot = Snapshot::capture(&state.tenant_items());
+    let file_name = snapshot.file_name();
+    let num_items = snapshot.items.len();
+    // Compressing a large state is CPU heavy, so keep it off the async workers.
+    let body = tokio::task::spawn_blocking(move || snapshot.to_gzip())
+        .await
+        .context("Backup task failed")??;
+    crate::log_info!("Backup of {num_items} items: {file_name}");
+    Ok((
+        [
+            (CONTENT_TYPE, "application/gzip".to_string()),
+            (CONTENT_DISPOSITION, format!("attachment; filename=\"{file_name}\"")),
+        ],
+        body,
+    )
+        .into_response())
+}
+
+/// Restore items of the tenant from a snapshot made with `/admin/backup`.
+///
+/// With `background=true` the restore runs as a job,
+/// and the response is the job status to poll at `/admin/jobs/{id}`.
+#[axum::debug_handler]
+#[utoipa::path(
+    post,
+    path = "/admin/restore",
+    params(RestoreQuery),
+    request_body(content = Vec<u8>, content_type = "application/gzip", description = "Snapshot file"),
+    security(
+        ("api_key" = [])
+    ),
+    responses(
+        (status = OK, body = RestoreResponse, description = "Snapshot restored"),
+        (status = ACCEPTED, body = JobStatus, description = "Restore job queued with `background=true`"),
+        (status = SERVICE_UNAVAILABLE, body = ApiError, description = "Job queue is full"),
+        (status = BAD_REQUEST, body = ApiError, description = "Invalid snapshot file"),
+        (status = CONFLICT, body = ApiError, description = "Another instance holds the items lock"),
+        (status = FORBIDDEN, body = ApiError, description = "Client network is not allowed, or the items would exceed the item quota"),
+        (status = PAYLOAD_TOO_LARGE, body = ApiError, description = "Snapshot file is too large, or the items would exceed the data quota"),
+        (status = UNAUTHORIZED, body = ApiError, description = "Unauthorized"),
+    )
+)]
+pub async fn restore(
+    _api_key: ApiKeyExtractor,
+    State(state): State<SharedState>,
+    Extension(settings): Extension<SharedSettings>,
+    Query(query): Query<RestoreQuery>,
+    body: Bytes,
+) -> Result<Response, ApiError> {
+    let quota = settings.load().quota.clone();
+    // Resolve the tenant here, since the background job runs outside the request.
+    let collection = state.tenant_items();
+    if query.background {
+        let job_state = Arc::clone(&state);
+        return Ok(submit_job(
+            &state,
+            JobKind::Restore,
+            Box::pin(async move {
+                let response = restore_snapshot(&job_state, &collection, body, query.mode, &quota)
+                    .await
+                    .map_err(|error| anyhow::anyhow!(error.message))?;
+                Ok(serde_json::to_value(response)?)
+            }),
+        ));
+    }
+    restore_snapshot(&state, &collection, body, query.mode, &quota)
+        .await
+        .map(|response| (StatusCode::OK, Json(response)).into_response())
+}
+
+/// Write a backup to the configured backup directory in a background job.
+///
+/// The job result has the path of the written file.
+#[axum::debug_handler]
+#[utoipa::path(
+    post,
+    path = "/admin/backups",
+    security(
+        ("api_key" = [])
+    ),
+    responses(
+        (status = ACCEPTED, body = JobStatus, description = "Backup job queued"),
+        (status = CONFLICT, body = ApiError, description = "No backup directory is configured"),
+        (status = SERVICE_UNAVAILABLE, body = ApiError, description = "Job queue is full"),
+        (status = UNAUTHORIZED, body = ApiError, description = "Unauthorized"),
+        (status = FORBIDDEN, body = ApiError, description = "Client network is not allowed"),
+    )
+)]
+pub async fn create_backup(
+    _api_key: ApiKeyExtractor,
+    State(state): State<SharedState>,
+    Extension(settings): Extension<SharedSettings>,
+) -> Response {
+    let config = settings.load().backup.clone();
+    let Some(directory) = config.directory else {
+        return ApiError::new(ErrorCode::Conflict, i18n::message("no_backup_directory", &[])).into_response();
+    };
+    let job_state = Arc::clone(&state);
+    submit_job(
+        &state,
+        JobKind::Backup,
+        Box::pin(async move {
+            // Compressing and writing a large state is blocking work, so keep it off the async workers.
+            let path =
+                tokio::task::spawn_blocking(move || backup::write_backup(&job_state, &directory, config.retention))
+                    .await??;
+            crate::log_info!("Wrote backup {}", path.display());
+            Ok(serde_json::json!({ "path": path }))
+        }),
+    )
+}
+
+/// Get the status of a background job, and its result once it has finished.
+#[axum::debug_handler]
+#[utoipa::path(
+    get,
+    path = "/admin/jobs/{id}",
+    security(
+        ("api_key" = [])
+    ),
+    responses(
+        (status = OK, body = JobStatus, description = "Job status"),
+        (status = NOT_FOUND, body = ApiError, description = "Job does not exist or finished long ago"),
+        (status = UNAUTHORIZED, body = ApiError, description = "Unauthorized"),
+        (status = FORBIDDEN, body = ApiError, description = "Client network is not allowed"),
+    )
+)]
+pub async fn job_status(
+    _api_key: ApiKeyExtractor,
+    State(state): State<SharedState>,
+    Path(id): Path<String>,
+) -> Result<Json<JobStatus>, ApiError> {
+    state
+        .jobs()
+        .status(&id)
+        .map(Json)
+        .ok_or_else(|| ApiError::new(ErrorCode::NotFound, i18n::message("job_not_found", &[("id", &id)])))
+}
+
+/// Decompress the snapshot and restore it into the collection if it fits the storage quota.
+async fn restore_snapshot(
+    state: &SharedState,
+    collection: &Collection,
+    body: Bytes,
+    mode: RestoreMode,
+    quota: &QuotaConfig,
+) -> Result<RestoreResponse, ApiError> {
+    let snapshot = tokio::task::spawn_blocking(move || Snapshot::from_gzip(&body))
+        .await
+        .map_err(anyhow::Error::from)
+        .flatten()
+        .map_err(|error| {
+            crate::log_error!("Restore failed: {error:#}");
+            ApiError::new(ErrorCode::BadRequest, format!("Invalid snapshot: {error:#}"))
+        })?;
+    let summary = lock::with_lock(state, lock::ITEMS_LOCK, async {
+        quota
+            .check(snapshot.usage_after_restore(collection, mode))
+            .map_err(|exceeded| {
+                crate::log_warn!("Restore rejected by quota: {exceeded:?}");
+                ApiError::quota(exceeded)
+            })?;
+        Ok(snapshot.restore(collection, mode))
+    })
+    .await?;
+    crate::log_info!(
+        "Restored {} items from snapshot ({mode}), skipped {}",
+        summary.restored,
+        summary.skipped
+    );
+    Ok(RestoreResponse {
+        mode,
+        restored: summary.restored,
+        skipped: summary.skipped,
+        num_items: collection.db.len(),
+    })
+}
+
+/// Queue the job and respond with its status and location.
+fn submit_job(state: &SharedState, kind: JobKind, work: JobWork) -> Response {
+    match state.jobs().submit(kind, work) {
+        Ok(status) => {
+            crate::log_info!("Queued {kind} job {}", status.id);
+            let location = format!("{API_V1_PREFIX}/admin/jobs/{}", status.id);
+            (StatusCode::ACCEPTED, [(LOCATION, location)], Json(status)).into_response()
+        }
+        Err(QueueFull) => ApiError::new(ErrorCode::Unavailable, i18n::message("job_queue_full", &[])).into_response(),
+    }
+}
+
+/// Stop receiving traffic and shut down gracefully for a zero-downtime deploy.
+///
+/// `/readyz` starts failing right away,
+/// and the server shuts down after the drain delay once in-flight requests finish.
+#[axum::debug_handler]
+#[utoipa::path(
+    post,
+    path = "/admin/drain",
+    security(
+        ("api_key" = [])
+    ),
+    responses(
+        (status = ACCEPTED, body = [MessageResponse], description = "Drain started"),
+        (status = CONFLICT, body = ApiError, description = "Drain already in progress"),
+        (status = UNAUTHORIZED, body = ApiError, description = "Unauthorized"),
+        (status = FORBIDDEN, body = ApiError, description = "Client network is not allowed"),
+    )
+)]
+pub async fn drain(
+    _api_key: ApiKeyExtractor,
+    State(state): State<SharedState>,
+    Extension(settings): Extension<SharedSettings>,
+) -> Response {
+    let delay_seconds = settings.load().drain_delay_seconds;
+    if state.lifecycle().start_drain(Duration::from_secs(delay_seconds)) {
+        crate::log_warn!("Draining, shutting down in {delay_seconds} seconds");
+        (
+            StatusCode::ACCEPTED,
+            Json(MessageResponse::new(i18n::message(
+                "drain_started",
+                &[("seconds", &delay_seconds)],
+            ))),
+        )
+            .into_response()
+    } else {
+        ApiError::new(ErrorCode::Conflict, i18n::message("already_draining", &[])).into_response()
+    }
+}
+
+/// Re-read the config file and apply the changed settings immediately.
+///
+/// Listen addresses and protocol are only read at startup,
+/// so changes to them are reported with `restart_required`.
+#[axum::debug_handler]
+#[utoipa::path(
+    post,
+    path = "/admin/reload_config",
+    security(
+        ("api_key" = [])
+    ),
+    responses(
+        (status = OK, body = ReloadConfigResponse, description = "Settings reloaded"),
+        (status = UNPROCESSABLE_ENTITY, body = ApiError, description = "Invalid settings, previous settings kept"),
+        (status = UNAUTHORIZED, body = ApiError, description = "Unauthorized"),
+        (status = FORBIDDEN, body = ApiError, description = "Client network is not allowed"),
+    )
+)]
+pub async fn reload_config(
+    _api_key: ApiKeyExtractor,
+    Extension(settings): Extension<SharedSettings>,
+    Extension(loader): Extension<SettingsLoader>,
+) -> Response {
+    match settings::reload(&loader, &settings) {
+        Ok(changes) => (StatusCode::OK, Json(ReloadConfigResponse { changes })).into_response(),
+        Err(error) => ApiError::new(ErrorCode::ValidationFailed, format!("{error:#}")).into_response(),
+    }
+}
+
+/// Release unused item map capacity of the tenant.
+///
+/// The maps keep their capacity after items are removed,
+/// this returns the memory without waiting for occupancy to drop below the automatic threshold.
+#[axum::debug_handler]
+#[utoipa::path(
+    post,
+    path = "/admin/compact",
+    security(
+        ("api_key" = [])
+    ),
+    responses(
+        (status = OK, body = CompactResponse, description = "Capacity before and after compacting"),
+        (status = UNAUTHORIZED, body = ApiError, description = "Unauthorized"),
+        (status = FORBIDDEN, body = ApiError, description = "Client network is not allowed"),
+    )
+)]
+pub async fn compact(_api_key: ApiKeyExtractor, State(state): State<SharedState>) -> Json<CompactResponse> {
+    let items = state.tenant_items();
+    let compaction = items.compact();
+    crate::log_info!(
+        "Compacted item maps from capacity {} to {}",
+        compaction.capacity_before,
+        compaction.capacity_after
+    );
+    Json(CompactResponse {
+        num_items: items.db.len(),
+        capacity_before: compaction.capacity_before,
+        capacity_after: compaction.capacity_after,
+    })
+}
+
+/// List requests that are currently being handled, oldest first.
+///
+/// Long running entries point to handlers that hang or are about to time out.
+#[axum::debug_handler]
+#[utoipa::path(
+    get,
+    path = "/admin/inflight",
+    security(
+        ("api_key" = [])
+    ),
+    responses(
+        (status = OK, body = InflightResponse, description = "Running requests"),
+        (status = UNAUTHORIZED, body = ApiError, description = "Unauthorized"),
+        (status = FORBIDDEN, body = ApiError, description = "Client network is not allowed"),
+    )
+)]
+pub async fn inflight(_api_key: ApiKeyExtractor, State(state): State<SharedState>) -> Json<InflightResponse> {
+    let requests = state.inflight().snapshot();
+    Json(InflightResponse {
+        num_requests: requests.len(),
+        requests,
+    })
+}
+
+/// List items with every field, for operational inspection.
+///
+/// Sorted by name by default, other sort fields fall back to the name for equal values.
+/// Expired items that the sweeper has not removed yet are included.
+#[axum::debug_handler]
+#[utoipa::path(
+    get,
+    path = "/admin/items",
+    params(AdminItemListQuery),
+    security(
+        ("api_key" = [])
+    ),
+    responses(
+        (status = OK, body = AdminItemListResponse, description = "One page of items"),
+        (status = BAD_REQUEST, body = ApiError, description = "Invalid query parameters"),
+        (status = UNAUTHORIZED, body = ApiError, description = "Unauthorized"),
+        (status = FORBIDDEN, body = ApiError, description = "Client network is not allowed"),
+    )
+)]
+pub async fn list_items(
+    _api_key: ApiKeyExtractor,
+    State(state): State<SharedState>,
+    Query(query): Query<AdminItemListQuery>,
+) -> Json<AdminItemListResponse> {
+    let mut items: Vec<Arc<Item>> = state
+        .tenant_items()
+        .db
+        .iter()
+        .map(|entry| Arc::clone(entry.value()))
+        .collect();
+    items.sort_by(|a, b| {
+        let ordering = match query.sort {
+            ItemSortField::Name => Ordering::Equal,
+            ItemSortField::CreatedAt => a.created_at.cmp(&b.created_at),
+            ItemSortField::UpdatedAt => a.updated_at.cmp(&b.updated_at),
+            ItemSortField::Revision => a.revision.cmp(&b.revision),
+        }
+        .then_with(|| a.name.cmp(&b.name));
+        match query.order {
+            SortOrder::Asc => ordering,
+            SortOrder::Desc => ordering.reverse(),
+        }
+    });
+    let num_items = items.len();
+    let skip = query.skip.unwrap_or_default();
+    let limit = query.limit.unwrap_or(DEFAULT_ADMIN_PAGE_SIZE).min(MAX_ADMIN_PAGE_SIZE);
+    let items = items
+        .into_iter()
+        .skip(skip)
+        .take(limit)
+        .map(Arc::unwrap_or_clone)
+        .collect();
+    Json(AdminItemListResponse {
+        num_items,
+        skip,
+        limit,
+        items,
+    })
+}
+
+/// List item collections, the `default` collection first.
+#[axum::debug_handler]
+#[utoipa::path(
+    get,
+    path = "/admin/collections",
+    security(
+        ("api_key" = [])
+    ),
+    responses(
+        (status = OK, body = [CollectionInfo], description = "Item collections"),
+        (status = UNAUTHORIZED, body = ApiError, description = "Unauthorized"),
+        (status = FORBIDDEN, body = ApiError, description = "Client network is not allowed"),
+    )
+)]
+pub async fn list_collections(
+    _api_key: ApiKeyExtractor,
+    State(state): State<SharedState>,
+) -> Json<Vec<CollectionInfo>> {
+    Json(
+        state
+            .collections()
+            .into_iter()
+            .map(|(name, collection)| CollectionInfo {
+                name,
+                num_items: collection.db.len(),
+            })
+            .collect(),
+    )
+}
+
+/// Create an empty named item collection.
+///
+/// Its items are served under `/collections/{name}/items`.
+#[axum::debug_handler]
+#[utoipa::path(
+    post,
+    path = "/admin/collections",
+    request_body = CreateCollection,
+    security(
+        ("api_key" = [])
+    ),
+    responses(
+        (status = CREATED, body = CollectionInfo, description = "Collection created"),
+        (status = CONFLICT, body = ApiError, description = "Collection already exists, or the collection limit is reached"),
+        (status = UNAUTHORIZED, body = ApiError, description = "Unauthorized"),
+        (status = FORBIDDEN, body = ApiError, description = "Client network is not allowed"),
+        (status = UNPROCESSABLE_ENTITY, body = ApiError, description = "Invalid collection name"),
+    )
+)]
+pub async fn create_collection(
+    _api_key: ApiKeyExtractor,
+    State(state): State<SharedState>,
+    ValidatedJson(payload): ValidatedJson<CreateCollection>,
+) -> Result<(StatusCode, Json<CollectionInfo>), ApiError> {
+    state.create_collection(&payload.name).map_err(|error| match error {
+        CollectionError::Exists(name) => ApiError::new(
+            ErrorCode::Conflict,
+            i18n::message("collection_exists", &[("name", &name)]),
+        ),
+        CollectionError::TooMany => ApiError::new(
+            ErrorCode::Conflict,
+            i18n::message("too_many_collections", &[("max", &MAX_COLLECTIONS)]),
+        ),
+    })?;
+    crate::log_info!("Created collection {}", payload.name);
+    Ok((
+        StatusCode::CREATED,
+        Json(CollectionInfo {
+            name: payload.name,
+            num_items: 0,
+        }),
+    ))
+}
+
+/// Drop a named item collection with all of its items.
+///
+/// The `default` collection can not be dropped.
+#[axum::debug_handler]
+#[utoipa::path(
+    delete,
+    path = "/admin/collections/{name}",
+    security(
+        ("api_key" = [])
+    ),
+    responses(
+        (status = OK, body = CollectionInfo, description = "Collection dropped, with the number of items it held"),
+        (status = NOT_FOUND, body = ApiError, description = "Collection does not exist"),
+        (status = CONFLICT, body = ApiError, description = "The default collection can not be dropped"),
+        (status = UNAUTHORIZED, body = ApiError, description = "Unauthorized"),
+        (status = FORBIDDEN, body = ApiError, description = "Client network is not allowed"),
+    )
+)]
+pub async fn drop_collection(
     _api_key: ApiKeyExtractor,
     State(state): State<SharedState>,
-    Extension(_config): Extension<Arc<Config>>,
     Path(name): Path<String>,
-) -> impl IntoResponse {
-    state.db.remove(&name).map_or_else(
-        || {
-            crate::log_error!("Remove item failed for non-existing name: {}", name);
-            RemoveItemResponse::new_error(format!("Item does not exist: {name}"))
-        },
-        |existing_item| {
-            crate::log_debug!("Remove item: {}", name);
-            RemoveItemResponse::Removed(existing_item.1)
-        },
+) -> Result<Json<CollectionInfo>, ApiError> {
+    if name == DEFAULT_COLLECTION {
+        return Err(ApiError::new(
+            ErrorCode::Conflict,
+            i18n::message("default_collection_drop", &[]),
+        ));
+    }
+    let collection = state.drop_collection(&name).ok_or_else(|| {
+        ApiError::new(
+            ErrorCode::NotFound,
+            i18n::message("collection_not_found", &[("name", &name)]),
+        )
+    })?;
+    crate::log_info!("Dropped collection {name} with {} items", collection.db.len());
+    Ok(Json(CollectionInfo {
+        num_items: collection.db.len(),
+        name,
+    }))
+}
+
+/// Register a callback URL for item events.
+///
+/// Every matching event is posted to the URL as JSON,
+/// signed with the secret in the `X-Webhook-Signature` header.
+/// The secret is never returned.
+#[axum::debug_handler]
+#[utoipa::path(
+    post,
+    path = "/admin/webhooks",
+    request_body = CreateWebhook,
+    security(
+        ("api_key" = [])
+    ),
+    responses(
+        (status = CREATED, body = Webhook, description = "Webhook registered"),
+        (status = UNAUTHORIZED, body = ApiError, description = "Unauthorized"),
+        (status = FORBIDDEN, body = ApiError, description = "Client network is not allowed"),
+        (status = UNPROCESSABLE_ENTITY, body = ApiError, description = "Invalid URL or secret"),
+    )
+)]
+pub async fn create_webhook(
+    _api_key: ApiKeyExtractor,
+    State(state): State<SharedState>,
+    ValidatedJson(payload): ValidatedJson<CreateWebhook>,
+) -> (StatusCode, Json<Webhook>) {
+    let webhook = state.webhooks().register(payload);
+    crate::log_info!("Registered webhook {} for {}", webhook.id, webhook.url);
+    (StatusCode::CREATED, Json(webhook))
+}
+
+/// List registered webhooks, oldest first.
+#[axum::debug_handler]
+#[utoipa::path(
+    get,
+    path = "/admin/webhooks",
+    security(
+        ("api_key" = [])
+    ),
+    responses(
+        (status = OK, body = [Webhook], description = "Registered webhooks"),
+        (status = UNAUTHORIZED, body = ApiError, description = "Unauthorized"),
+        (status = FORBIDDEN, body = ApiError, description = "Client network is not allowed"),
+    )
+)]
+pub async fn list_webhooks(_api_key: ApiKeyExtractor, State(state): State<SharedState>) -> Json<Vec<Webhook>> {
+    Json(state.webhooks().list())
+}
+
+/// Remove a webhook.
+#[axum::debug_handler]
+#[utoipa::path(
+    delete,
+    path = "/admin/webhooks/{id}",
+    security(
+        ("api_key" = [])
+    ),
+    responses(
+        (status = OK, body = Webhook, description = "Webhook removed"),
+        (status = NOT_FOUND, body = ApiError, description = "Webhook does not exist"),
+        (status = UNAUTHORIZED, body = ApiError, description = "Unauthorized"),
+        (status = FORBIDDEN, body = ApiError, description = "Client network is not allowed"),
+    )
+)]
+pub async fn remove_webhook(
+    _api_key: ApiKeyExtractor,
+    State(state): State<SharedState>,
+    Path(id): Path<String>,
+) -> Result<Json<Webhook>, ApiError> {
+    let webhook = state
+        .webhooks()
+        .remove(&id)
+        .ok_or_else(|| ApiError::new(ErrorCode::NotFound, i18n::message("webhook_not_found", &[("id", &id)])))?;
+    crate::log_info!("Removed webhook {} for {}", webhook.id, webhook.url);
+    Ok(Json(webhook))
+}
+
+/// List recent delivery attempts of a webhook, newest first.
+///
+/// Failed attempts are retried with exponential backoff,
+/// so one event can have several attempts.
+#[axum::debug_handler]
+#[utoipa::path(
+    get,
+    path = "/admin/webhooks/{id}/deliveries",
+    security(
+        ("api_key" = [])
+    ),
+    responses(
+        (status = OK, body = WebhookDeliveriesResponse, description = "Recent delivery attempts"),
+        (status = NOT_FOUND, body = ApiError, description = "Webhook does not exist"),
+        (status = UNAUTHORIZED, body = ApiError, description = "Unauthorized"),
+        (status = FORBIDDEN, body = ApiError, description = "Client network is not allowed"),
     )
+)]
+pub async fn webhook_deliveries(
+    _api_key: ApiKeyExtractor,
+    State(state): State<SharedState>,
+    Path(id): Path<String>,
+) -> Result<Json<WebhookDeliveriesResponse>, ApiError> {
+    let (webhook, deliveries) = state
+        .webhooks()
+        .deliveries(&id)
+        .ok_or_else(|| ApiError::new(ErrorCode::NotFound, i18n::message("webhook_not_found", &[("id", &id)])))?;
+    Ok(Json(WebhookDeliveriesResponse { webhook, deliveries }))
+}
+
+/// Apply an item change that a peer instance made to the default collection.
+///
+/// Peers send every item change made through their API here when replication is configured.
+/// Changes that do not apply, like removing an item that was already replaced, are ignored.
+#[axum::debug_handler]
+#[utoipa::path(
+    post,
+    path = "/admin/replicate",
+    request_body = ItemEvent,
+    security(
+        ("api_key" = [])
+    ),
+    responses(
+        (status = NO_CONTENT, description = "Change received"),
+        (status = UNAUTHORIZED, body = ApiError, description = "Unauthorized"),
+        (status = FORBIDDEN, body = ApiError, description = "Client network is not allowed"),
+        (status = UNPROCESSABLE_ENTITY, body = ApiError, description = "Invalid item event"),
+    )
+)]
+pub async fn replicate(
+    _api_key: ApiKeyExtractor,
+    State(state): State<SharedState>,
+    ValidatedJson(event): ValidatedJson<ItemEvent>,
+) -> StatusCode {
+    replication::apply(&state, event.change);
+    StatusCode::NO_CONTENT
 }
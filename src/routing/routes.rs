@@ -3,23 +3,41 @@
 //! Contains unauthenticated service endpoints, item CRUD examples,
 //! the health response, version information, and Prometheus metrics output.
 
-use axum::Json;
-use std::sync::Arc;
+use std::collections::HashSet;
+use std::sync::{Arc, OnceLock};
 
-use axum::extract::{Extension, Query, State};
-use axum::http::StatusCode;
+use anyhow::anyhow;
+use axum::Json;
+use axum::body::{Body, Bytes};
+use axum::extract::{Extension, Path, Query, State};
 use axum::http::header::CONTENT_TYPE;
+use axum::http::{HeaderMap, StatusCode};
 use axum::response::{IntoResponse, Response};
-use axum_extra::extract::WithRejection;
-use chrono::{SecondsFormat, Utc};
+use chrono::{DateTime, SecondsFormat, Utc};
+use futures_util::StreamExt;
 
+use crate::backend;
+use crate::cache::AccessedItem;
+use crate::events::{Actor, ItemChange};
+use crate::eviction;
+use crate::i18n;
+use crate::negotiation::{AcceptFormat, Created, Negotiate, Negotiated, ResponseFormat};
+use crate::precomputed::PrecomputedResponse;
 use crate::schemas::{
-    CreateItem, CreateItemResponse, HealthResponse, ItemListQuery, ItemListResponse, ItemQuery, ItemResponse,
-    MessageResponse, RejectionError, RejectionErrorResponse, ServerError, VERSION_INFO, VersionInfo,
+    ApiError, CreateItem, ErrorCode, HealthResponse, ItemBatchQuery, ItemBatchResponse, ItemHistoryResponse,
+    ItemListQuery, ItemListResponse, ItemQuery, MessageResponse, UsageResponse, VERSION_INFO, VersionInfo,
 };
-use crate::types::{Config, Item, SharedState};
+use crate::settings::SharedSettings;
+use crate::tenant;
+use crate::types::{
+    Collection, DEFAULT_COLLECTION, DuplicateItem, Item, MAX_RANDOM_ID_ATTEMPTS, SharedState, StorageUsage,
+};
+use crate::validation::ValidatedJson;
 use crate::version;
 
+/// Content type of streamed item listings.
+const NDJSON_CONTENT_TYPE: &str = "application/x-ndjson";
+
 // Debug handler macro generates better error messages during compile
 // https://docs.rs/axum-macros/latest/axum_macros/attr.debug_handler.html
 
@@ -34,8 +52,8 @@ use crate::version;
         (status = OK, body = [MessageResponse], description = "Return API name with current datetime")
     )
 )]
-pub async fn root() -> (StatusCode, Json<MessageResponse>) {
-    let datetime = Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true);
+pub async fn root(State(state): State<SharedState>) -> (StatusCode, Json<MessageResponse>) {
+    let datetime = state.now().to_rfc3339_opts(SecondsFormat::Secs, true);
     crate::log_debug!("Root: {}", datetime);
     (
         StatusCode::OK,
@@ -54,7 +72,7 @@ pub async fn root() -> (StatusCode, Json<MessageResponse>) {
 )]
 pub async fn health(
     State(state): State<SharedState>,
-    Extension(config): Extension<Arc<Config>>,
+    Extension(settings): Extension<SharedSettings>,
 ) -> (StatusCode, Json<HealthResponse>) {
     let uptime_ms = u64::try_from(state.uptime().as_millis()).unwrap_or(u64::MAX);
     (
@@ -62,15 +80,35 @@ pub async fn health(
         Json(HealthResponse {
             service: version::PACKAGE_NAME.to_string(),
             version: version::PACKAGE_VERSION.to_string(),
-            environment: config.env.to_string(),
+            environment: settings.load().env.to_string(),
             status: "ok".to_string(),
-            timestamp: Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true),
+            timestamp: state.now().to_rfc3339_opts(SecondsFormat::Millis, true),
             start_time: state.start_time_utc().to_rfc3339_opts(SecondsFormat::Millis, true),
             uptime_ms,
         }),
     )
 }
 
+/// Report if the instance should receive traffic.
+///
+/// Fails with `503 Service Unavailable` once a drain has started, so load balancers stop routing here.
+#[axum::debug_handler]
+#[utoipa::path(
+    get,
+    path = "/readyz",
+    responses(
+        (status = OK, body = [MessageResponse], description = "Ready for traffic"),
+        (status = SERVICE_UNAVAILABLE, body = ApiError, description = "Draining before shutdown")
+    )
+)]
+pub async fn readiness(State(state): State<SharedState>) -> Response {
+    if state.lifecycle().is_ready() {
+        (StatusCode::OK, Json(MessageResponse::new(i18n::message("ready", &[])))).into_response()
+    } else {
+        ApiError::new(ErrorCode::Unavailable, i18n::message("draining", &[])).into_response()
+    }
+}
+
 /// Return OpenTelemetry metrics in Prometheus text format.
 #[axum::debug_handler]
 #[utoipa::path(
@@ -78,32 +116,34 @@ pub async fn health(
     path = "/metrics",
     responses(
         (status = OK, description = "Prometheus metrics in text format", content_type = "text/plain"),
-        (status = INTERNAL_SERVER_ERROR, body = [MessageResponse], description = "Metrics encoding failed")
+        (status = INTERNAL_SERVER_ERROR, body = ApiError, description = "Metrics encoding failed")
     )
 )]
 pub async fn metrics(State(state): State<SharedState>) -> Response {
     match state.telemetry().render_prometheus() {
         Ok((body, content_type)) => ([(CONTENT_TYPE, content_type)], body).into_response(),
-        Err(err) => (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(MessageResponse::new(format!("Failed to render metrics: {err}"))),
-        )
-            .into_response(),
+        Err(err) => ApiError::new(ErrorCode::Internal, format!("Failed to render metrics: {err}")).into_response(),
     }
 }
 
 /// Return version and build information.
+///
+/// The body is serialized on first use and served with an `ETag`.
 #[axum::debug_handler]
 #[utoipa::path(
     get,
     path = "/version",
     responses(
-        (status = OK, body = [VersionInfo], description = "Version information")
+        (status = OK, body = [VersionInfo], description = "Version information"),
+        (status = NOT_MODIFIED, description = "Version information matches `If-None-Match`")
     )
 )]
-pub async fn version() -> (StatusCode, Json<&'static VersionInfo>) {
+pub async fn version(headers: HeaderMap) -> Response {
+    static VERSION_JSON: OnceLock<PrecomputedResponse> = OnceLock::new();
     crate::log_debug!("Version: {}", version::PACKAGE_VERSION);
-    (StatusCode::OK, Json(&VERSION_INFO))
+    VERSION_JSON
+        .get_or_init(|| PrecomputedResponse::json(&VERSION_INFO).expect("version info should serialize"))
+        .respond(&headers)
 }
 
 /// Get item info.
@@ -116,20 +156,96 @@ pub async fn version() -> (StatusCode, Json<&'static VersionInfo>) {
     params(ItemQuery),
     responses(
         (status = 200, body = [Item], description = "Found existing item"),
-        (status = 400, body = [MessageResponse], description = "Item does not exist")
+        (status = NOT_FOUND, body = ApiError, description = "Item does not exist"),
+        (status = BAD_REQUEST, body = ApiError, description = "Invalid query parameters")
     )
 )]
-pub async fn query_item(Query(item): Query<ItemQuery>, State(state): State<SharedState>) -> impl IntoResponse {
+pub async fn query_item(
+    AcceptFormat(format): AcceptFormat,
+    Query(item): Query<ItemQuery>,
+    State(state): State<SharedState>,
+) -> (Option<Extension<AccessedItem>>, Negotiated<Result<Arc<Item>, ApiError>>) {
     crate::log_debug!("Query item: {}", item.name);
-    if let Some(existing_item) = state.db.get(&item.name) {
-        crate::log_info!("{:?}", existing_item);
-        ItemResponse::Found(existing_item.clone())
-    } else {
-        crate::log_error!("Item not found: {}", item.name);
-        ItemResponse::Error(MessageResponse {
-            message: format!("Item does not exist: {}", item.name),
+    let result = find_item(&state, &state.tenant_items(), &item.name).await;
+    let accessed = result
+        .as_ref()
+        .ok()
+        .map(|found| Extension(AccessedItem(found.name.clone())));
+    (accessed, Negotiated(format, result))
+}
+
+/// Unexpired item from the collection, or a `not_found` error.
+///
+/// Missing or stale items are read through from the backend first,
+/// and found items are recorded as accessed for idle eviction.
+async fn find_item(state: &SharedState, collection: &Collection, name: &str) -> Result<Arc<Item>, ApiError> {
+    backend::read_through(state, collection, name).await;
+    let now = state.now();
+    let existing_item = collection
+        .db
+        .get(name)
+        .map(|existing_item| Arc::clone(&existing_item))
+        .filter(|existing_item| !existing_item.is_expired(now));
+    existing_item
+        .inspect(|existing_item| {
+            collection.record_access(name, now);
+            crate::log_info!("{:?}", existing_item);
         })
+        .ok_or_else(|| {
+            crate::log_error!("Item not found: {name}");
+            ApiError::new(ErrorCode::NotFound, i18n::message("item_not_found", &[("name", &name)]))
+        })
+}
+
+/// Get several items by name.
+///
+/// Returns the found items in the requested order and lists the names without an item,
+/// so clients needing many specific items do not have to query them one by one.
+/// Repeated names are only looked up once.
+#[axum::debug_handler]
+#[utoipa::path(
+    post,
+    path = "/items/query",
+    request_body = ItemBatchQuery,
+    responses(
+        (status = OK, body = ItemBatchResponse, description = "Found items and missing names"),
+        (status = BAD_REQUEST, body = ApiError, description = "Malformed JSON data"),
+        (status = UNPROCESSABLE_ENTITY, body = ApiError, description = "No names or too many names"),
+        (status = UNSUPPORTED_MEDIA_TYPE, body = ApiError, description = "Missing JSON content type header"),
+    )
+)]
+pub async fn batch_get_items(
+    AcceptFormat(format): AcceptFormat,
+    State(state): State<SharedState>,
+    ValidatedJson(query): ValidatedJson<ItemBatchQuery>,
+) -> Negotiated<ItemBatchResponse> {
+    crate::log_debug!("Batch get {} items", query.names.len());
+    let now = state.now();
+    let items = state.tenant_items();
+    let mut seen = HashSet::new();
+    let mut response = ItemBatchResponse {
+        items: Vec::new(),
+        missing: Vec::new(),
+    };
+    for name in query.names {
+        if !seen.insert(name.clone()) {
+            continue;
+        }
+        backend::read_through(&state, &items, &name).await;
+        let existing_item = items
+            .db
+            .get(&name)
+            .map(|existing_item| Arc::clone(&existing_item))
+            .filter(|existing_item| !existing_item.is_expired(now));
+        match existing_item {
+            Some(item) => {
+                items.record_access(&name, now);
+                response.items.push(item);
+            }
+            None => response.missing.push(name),
+        }
     }
+    Negotiated(format, response)
 }
 
 /// Create new item.
@@ -142,63 +258,332 @@ pub async fn query_item(Query(item): Query<ItemQuery>, State(state): State<Share
     request_body = CreateItem,
     responses(
         (status = CREATED, body = [Item], description = "New item created"),
-        (status = CONFLICT, body = [MessageResponse], description = "Item already exists"),
-        (status = BAD_REQUEST, body = [RejectionErrorResponse], description = "Malformed JSON data"),
-        (status = UNPROCESSABLE_ENTITY, body = [RejectionErrorResponse], description = "JSON deserialization error"),
-        (status = UNSUPPORTED_MEDIA_TYPE, body = [RejectionErrorResponse], description = "Missing JSON content type header"),
-        (status = PAYLOAD_TOO_LARGE, body = [RejectionErrorResponse], description = "Too many bytes"),
+        (status = CONFLICT, body = ApiError, description = "Item name or id already exists"),
+        (status = BAD_REQUEST, body = ApiError, description = "Malformed JSON data"),
+        (status = UNPROCESSABLE_ENTITY, body = ApiError, description = "JSON deserialization or validation error"),
+        (status = UNSUPPORTED_MEDIA_TYPE, body = ApiError, description = "Missing JSON content type header"),
+        (status = FORBIDDEN, body = ApiError, description = "Item quota is used up"),
+        (status = PAYLOAD_TOO_LARGE, body = ApiError, description = "Too many bytes, or the data quota is used up"),
     )
 )]
 pub async fn create_item(
+    AcceptFormat(format): AcceptFormat,
     State(state): State<SharedState>,
-    WithRejection(Json(payload), _): WithRejection<Json<CreateItem>, RejectionError>,
-) -> Result<CreateItemResponse, ServerError> {
-    if state.db.contains_key(&payload.name) {
-        crate::log_error!("Item already exists: {}", payload.name);
-        return Ok(CreateItemResponse::Error(MessageResponse::new(format!(
-            "Item already exists: {}",
-            payload.name
-        ))));
+    Extension(settings): Extension<SharedSettings>,
+    ValidatedJson(payload): ValidatedJson<CreateItem>,
+) -> Negotiated<Result<Created<Item>, ApiError>> {
+    let created = insert_new_item(&state, &state.tenant_items(), &settings, payload)
+        .await
+        .inspect(|item| state.publish(Actor::Client, ItemChange::Created { item: item.clone() }));
+    Negotiated(format, created.map(Created))
+}
+
+/// Insert the item from a validated create payload into the collection,
+/// and write it to the backend when the collection has one.
+pub(crate) async fn insert_new_item(
+    state: &SharedState,
+    collection: &Collection,
+    settings: &SharedSettings,
+    payload: CreateItem,
+) -> Result<Item, ApiError> {
+    // The id strategy, name pattern and data size limit come from settings, so they are checked here instead of in `validate`.
+    let settings = settings.load_full();
+    let errors = payload.settings_violations(&settings);
+    if !errors.is_empty() {
+        return Err(ApiError::validation(errors));
     }
-    // Check if id was provided by client
-    let item = match payload.id {
-        // Creating item with client provided id can fail if id is not valid,
-        // which will cause this method to exit with `ServerError` due to the `?` operator.
-        Some(id) => Item::new(payload.name, id)?,
-        _ => Item::new_with_random_id(payload.name),
+    // The id has already been checked against the id strategy, so this only fails if the rules drift apart,
+    // which will cause this method to exit with an `internal` error due to the `?` operator.
+    let random_id = payload.id.is_none();
+    let mut item = payload.into_item(settings.id_strategy, state)?;
+    // An item that is only in the backend still takes the name.
+    backend::read_through(state, collection, &item.name).await;
+    // Concurrent creates can both pass the check, so a store can go over its quota by a few items.
+    let usage = collection.usage();
+    settings
+        .quota
+        .check(StorageUsage {
+            num_items: usage.num_items + 1,
+            data_bytes: usage.data_bytes + item.data_size(),
+        })
+        .map_err(|exceeded| {
+            crate::log_warn!("Create item rejected by quota: {exceeded:?}");
+            ApiError::quota(exceeded)
+        })?;
+    let inserted = if random_id {
+        collection.insert_item_with_random_id(&mut item, || state.generate_id(settings.id_strategy))
+    } else {
+        collection.insert_item(&item)
     };
-    // TODO: should probably ensure ids are unique too
-    state.db.insert(item.name.clone(), item.clone());
+    if let Err(duplicate) = inserted {
+        let message = match duplicate {
+            DuplicateItem::Id(_) if random_id => {
+                return Err(anyhow!("No free item id found in {MAX_RANDOM_ID_ATTEMPTS} attempts").into());
+            }
+            DuplicateItem::Id(id) => i18n::message("item_id_exists", &[("id", &id)]),
+            DuplicateItem::Name(name) => i18n::message("item_exists", &[("name", &name)]),
+        };
+        crate::log_error!("{message}");
+        return Err(ApiError::new(ErrorCode::Conflict, message));
+    }
+    if let Some(backend) = backend::for_collection(state, collection)
+        && let Err(error) = backend.put(&item).await
+    {
+        collection.remove_item_if(&item.name, |stored| stored.id == item.id);
+        return Err(error.into());
+    }
     crate::log_debug!("Create item: {}", item.name);
-    Ok(CreateItemResponse::Created(item))
+    eviction::evict_least_recently_used(state, collection, &settings.eviction, Some(&item.name));
+    Ok(item)
+}
+
+/// Get the recent changes of an item.
+///
+/// Lists when the item was created and removed and by whom, oldest first.
+/// Histories of removed items stay available for a while after the removal.
+#[axum::debug_handler]
+#[utoipa::path(
+    get,
+    path = "/items/{name}/history",
+    params(
+        ("name" = String, Path, description = "Item name"),
+    ),
+    responses(
+        (status = OK, body = ItemHistoryResponse, description = "Recorded item changes"),
+        (status = NOT_FOUND, body = ApiError, description = "No changes recorded for the item"),
+    )
+)]
+pub async fn item_history(
+    AcceptFormat(format): AcceptFormat,
+    State(state): State<SharedState>,
+    Path(name): Path<String>,
+) -> Response {
+    crate::log_debug!("Item history: {name}");
+    // Tenant changes are not recorded, and the default collection history is not theirs to see.
+    let history = if tenant::current().is_some() {
+        None
+    } else {
+        state.history().get(&name)
+    };
+    match history {
+        Some(entries) => Negotiated(format, ItemHistoryResponse { name, entries }).into_response(),
+        None => ApiError::new(
+            ErrorCode::NotFound,
+            i18n::message("item_history_not_found", &[("name", &name)]),
+        )
+        .into_response(),
+    }
+}
+
+/// Get the item count and data size of the item store, with their quotas.
+///
+/// Reports the store of the tenant for requests with a tenant header.
+#[axum::debug_handler]
+#[utoipa::path(
+    get,
+    path = "/usage",
+    responses(
+        (status = OK, body = UsageResponse, description = "Current consumption and limits"),
+    )
+)]
+pub async fn usage(
+    AcceptFormat(format): AcceptFormat,
+    State(state): State<SharedState>,
+    Extension(settings): Extension<SharedSettings>,
+) -> Negotiated<UsageResponse> {
+    let usage = state.tenant_items().usage();
+    let quota = &settings.load().quota;
+    Negotiated(
+        format,
+        UsageResponse {
+            num_items: usage.num_items,
+            max_items: quota.max_items,
+            data_bytes: usage.data_bytes,
+            max_data_bytes: quota.max_data_bytes,
+        },
+    )
 }
 
 /// List all items.
 ///
-/// Supports optional `skip` and `limit` query parameters for simple pagination.
+/// Supports optional `skip` and `limit` query parameters for simple pagination,
+/// and `created_after` and `tag` filters.
+/// With `stream=true` the matching items are written one JSON object per line as they are serialized,
+/// so very large listings never build the whole response body in memory.
+/// The matching items are still gathered and sorted by name first, which holds one shared handle per item,
+/// so pagination stays stable and no item map lock is held while the client reads.
 #[axum::debug_handler]
 #[utoipa::path(
     get,
     path = "/items",
     params(ItemListQuery),
     responses(
-        (status = 200, body = [ItemListResponse])
+        (status = 200, body = [ItemListResponse]),
+        (status = 200, body = Item, content_type = "application/x-ndjson", description = "One item per line with `stream=true`"),
+        (status = BAD_REQUEST, body = ApiError, description = "Invalid query parameters"),
     )
 )]
 pub async fn list_items(
+    AcceptFormat(format): AcceptFormat,
     Query(query): Query<ItemListQuery>,
     State(state): State<SharedState>,
-) -> (StatusCode, Json<ItemListResponse>) {
+) -> Response {
     crate::log_debug!("List items");
-    let mut names: Vec<String> = state.db.iter().map(|entry| entry.key().clone()).collect();
-    names.sort();
-    let num_items = names.len();
-    let skip = query.skip.unwrap_or_default();
-    let names = names
+    list_collection(&state.tenant_items(), &query, state.now(), format)
+}
+
+/// Filtered page of item names, or the streamed items, from the collection.
+fn list_collection(
+    collection: &Collection,
+    query: &ItemListQuery,
+    now: DateTime<Utc>,
+    format: ResponseFormat,
+) -> Response {
+    // Collect shared handles and only copy the names on the returned page.
+    // A tag filter starts from the tag index instead of scanning every item.
+    let candidates = query.tag.as_deref().map_or_else(
+        || collection.db.iter().map(|entry| Arc::clone(entry.value())).collect(),
+        |tag| collection.items_with_tag(tag),
+    );
+    let mut items: Vec<Arc<Item>> = candidates
         .into_iter()
-        .skip(skip)
-        .take(query.limit.unwrap_or(usize::MAX))
+        .filter(|item| !item.is_expired(now))
+        .filter(|item| query.created_after.is_none_or(|after| item.created_at > after))
         .collect();
+    items.sort_unstable_by(|a, b| a.name.cmp(&b.name));
+    let num_items = items.len();
+    let skip = query.skip.unwrap_or_default();
+    let limit = query.limit.unwrap_or(usize::MAX);
     crate::log_debug!("List items: found {num_items} items");
-    (StatusCode::OK, Json(ItemListResponse { num_items, names }))
+    if query.stream {
+        return stream_items(items.into_iter().skip(skip).take(limit));
+    }
+    let names = items
+        .iter()
+        .skip(skip)
+        .take(limit)
+        .map(|item| item.name.clone())
+        .collect();
+    Negotiated(format, ItemListResponse { num_items, names }).into_response()
+}
+
+/// List the items of a named collection.
+///
+/// Takes the same filters and pagination as `GET /items`.
+#[axum::debug_handler]
+#[utoipa::path(
+    get,
+    path = "/collections/{collection}/items",
+    params(
+        ("collection" = String, Path, description = "Collection name, `default` for the `/items` collection"),
+        ItemListQuery,
+    ),
+    responses(
+        (status = 200, body = [ItemListResponse]),
+        (status = 200, body = Item, content_type = "application/x-ndjson", description = "One item per line with `stream=true`"),
+        (status = NOT_FOUND, body = ApiError, description = "Collection does not exist"),
+        (status = BAD_REQUEST, body = ApiError, description = "Invalid query parameters"),
+    )
+)]
+pub async fn list_collection_items(
+    AcceptFormat(format): AcceptFormat,
+    Path(collection): Path<String>,
+    Query(query): Query<ItemListQuery>,
+    State(state): State<SharedState>,
+) -> Response {
+    crate::log_debug!("List items in collection {collection}");
+    match find_collection(&state, &collection) {
+        Ok(collection) => list_collection(&collection, &query, state.now(), format),
+        Err(error) => error.negotiate(format),
+    }
+}
+
+/// Create a new item in a named collection.
+///
+/// Names and ids only have to be unique within the collection.
+/// Item events, history and webhooks only cover the `default` collection.
+#[axum::debug_handler]
+#[utoipa::path(
+    post,
+    path = "/collections/{collection}/items",
+    params(
+        ("collection" = String, Path, description = "Collection name, `default` for the `/items` collection"),
+    ),
+    request_body = CreateItem,
+    responses(
+        (status = CREATED, body = [Item], description = "New item created"),
+        (status = NOT_FOUND, body = ApiError, description = "Collection does not exist"),
+        (status = CONFLICT, body = ApiError, description = "Item name or id already exists"),
+        (status = BAD_REQUEST, body = ApiError, description = "Malformed JSON data"),
+        (status = UNPROCESSABLE_ENTITY, body = ApiError, description = "JSON deserialization or validation error"),
+        (status = UNSUPPORTED_MEDIA_TYPE, body = ApiError, description = "Missing JSON content type header"),
+        (status = FORBIDDEN, body = ApiError, description = "Item quota is used up"),
+        (status = PAYLOAD_TOO_LARGE, body = ApiError, description = "Too many bytes, or the data quota is used up"),
+    )
+)]
+pub async fn create_collection_item(
+    AcceptFormat(format): AcceptFormat,
+    State(state): State<SharedState>,
+    Extension(settings): Extension<SharedSettings>,
+    Path(collection): Path<String>,
+    ValidatedJson(payload): ValidatedJson<CreateItem>,
+) -> Negotiated<Result<Created<Item>, ApiError>> {
+    let created = match find_collection(&state, &collection) {
+        Ok(target) => insert_new_item(&state, &target, &settings, payload).await,
+        Err(error) => Err(error),
+    };
+    if collection == DEFAULT_COLLECTION
+        && let Ok(item) = &created
+    {
+        state.publish(Actor::Client, ItemChange::Created { item: item.clone() });
+    }
+    Negotiated(format, created.map(Created))
+}
+
+/// Get an item from a named collection.
+#[axum::debug_handler]
+#[utoipa::path(
+    get,
+    path = "/collections/{collection}/items/{name}",
+    params(
+        ("collection" = String, Path, description = "Collection name, `default` for the `/items` collection"),
+        ("name" = String, Path, description = "Item name"),
+    ),
+    responses(
+        (status = 200, body = [Item], description = "Found existing item"),
+        (status = NOT_FOUND, body = ApiError, description = "Collection or item does not exist"),
+    )
+)]
+pub async fn get_collection_item(
+    AcceptFormat(format): AcceptFormat,
+    State(state): State<SharedState>,
+    Path((collection, name)): Path<(String, String)>,
+) -> Negotiated<Result<Arc<Item>, ApiError>> {
+    crate::log_debug!("Query item {name} in collection {collection}");
+    let item = match find_collection(&state, &collection) {
+        Ok(collection) => find_item(&state, &collection, &name).await,
+        Err(error) => Err(error),
+    };
+    Negotiated(format, item)
+}
+
+/// Collection with the given name, or a `not_found` error.
+pub(crate) fn find_collection(state: &SharedState, name: &str) -> Result<Arc<Collection>, ApiError> {
+    state.collection(name).ok_or_else(|| {
+        ApiError::new(
+            ErrorCode::NotFound,
+            i18n::message("collection_not_found", &[("name", &name)]),
+        )
+    })
+}
+
+/// Newline-delimited JSON body that serializes each item only when the client is ready for it.
+fn stream_items(items: impl Iterator<Item = Arc<Item>> + Send + 'static) -> Response {
+    let lines = futures_util::stream::iter(items).map(|item| {
+        serde_json::to_vec(&item).map(|mut line| {
+            line.push(b'\n');
+            Bytes::from(line)
+        })
+    });
+    ([(CONTENT_TYPE, NDJSON_CONTENT_TYPE)], Body::from_stream(lines)).into_response()
 }
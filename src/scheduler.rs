@@ -0,0 +1,434 @@
+//! Scheduled tasks.
+//!
+//! Runs the named background tasks from the `[tasks]` settings table:
+//! scheduled backups, the expiry sweep and a periodic state log.
+//! Each task has an `enabled` flag, a schedule and a random jitter added to every wait,
+//! so instances started together do not all write backups at the same moment.
+//! A schedule is either an interval like `30s`, `15m`, `6h` or `1d`,
+//! or a five-field cron expression like `0 3 * * *` evaluated in UTC.
+//! Task settings are read again before every run, so a config reload applies to the next one.
+
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{Context, Result, anyhow, bail};
+use chrono::{DateTime, Datelike, TimeDelta, Timelike, Utc};
+use rand::RngExt;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use strum::{Display, EnumIter, IntoEnumIterator};
+use tokio::task::JoinHandle;
+
+use crate::backup::write_backup;
+use crate::eviction;
+use crate::expiry;
+use crate::settings::SharedSettings;
+use crate::types::{SharedState, TaskConfig};
+
+/// Minutes searched for the next cron match, a bit over four years so `29 2 *` always matches.
+const MAX_CRON_SEARCH_MINUTES: i64 = 4 * 366 * 24 * 60 + 1;
+
+/// Named task that the scheduler runs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Display, EnumIter)]
+#[strum(serialize_all = "snake_case")]
+pub enum TaskName {
+    /// Write a backup when a backup directory is configured.
+    Backup,
+    /// Remove expired items.
+    ExpirySweep,
+    /// Evict items by the global eviction policy.
+    EvictionSweep,
+    /// Log item count and map capacity.
+    StateLog,
+}
+
+/// When a task runs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Schedule {
+    /// Fixed time between runs, at least one second.
+    Every(Duration),
+    Cron(CronExpression),
+}
+
+/// Five-field cron expression: minute, hour, day of month, month and day of week.
+///
+/// Fields accept `*`, single values, ranges like `1-5`, steps like `*/15` or `0-30/10`,
+/// and comma-separated lists of those.
+/// Day of week is 0-7 with both 0 and 7 meaning Sunday.
+/// As in cron, when both day fields are restricted a day matching either one runs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CronExpression {
+    source: String,
+    minutes: u64,
+    hours: u64,
+    days_of_month: u64,
+    months: u64,
+    days_of_week: u64,
+    any_day_of_month: bool,
+    any_day_of_week: bool,
+}
+
+impl Schedule {
+    /// Time to wait from `now` until the next run.
+    #[must_use]
+    pub fn delay_from(&self, now: DateTime<Utc>) -> Duration {
+        match self {
+            Self::Every(interval) => *interval,
+            Self::Cron(cron) => cron
+                .next_after(now)
+                .and_then(|next| (next - now).to_std().ok())
+                .unwrap_or(Duration::MAX),
+        }
+    }
+}
+
+impl CronExpression {
+    /// First matching minute strictly after `time`, or `None` if the expression can never match.
+    #[must_use]
+    pub fn next_after(&self, time: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let start = time.with_second(0)?.with_nanosecond(0)? + TimeDelta::minutes(1);
+        (0..MAX_CRON_SEARCH_MINUTES)
+            .map(|minute| start + TimeDelta::minutes(minute))
+            .find(|candidate| self.matches(*candidate))
+    }
+
+    fn matches(&self, time: DateTime<Utc>) -> bool {
+        let day_of_month = has(self.days_of_month, time.day());
+        let day_of_week = has(self.days_of_week, time.weekday().num_days_from_sunday());
+        let day = match (self.any_day_of_month, self.any_day_of_week) {
+            (false, false) => day_of_month || day_of_week,
+            _ => day_of_month && day_of_week,
+        };
+        day && has(self.minutes, time.minute()) && has(self.hours, time.hour()) && has(self.months, time.month())
+    }
+}
+
+impl FromStr for Schedule {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        let value = value.trim();
+        if value.split_whitespace().count() == 5 {
+            return value.parse().map(Self::Cron);
+        }
+        let split = value
+            .find(|c: char| !c.is_ascii_digit())
+            .ok_or_else(|| anyhow!("Missing unit in interval {value}, use s, m, h or d"))?;
+        let (amount, unit) = value.split_at(split);
+        let amount: u64 = amount
+            .parse()
+            .with_context(|| format!("Invalid schedule {value}, use an interval like 30s or a cron expression"))?;
+        let seconds = match unit {
+            "s" => amount,
+            "m" => amount.saturating_mul(60),
+            "h" => amount.saturating_mul(3600),
+            "d" => amount.saturating_mul(86400),
+            _ => bail!("Invalid interval unit {unit}, use s, m, h or d"),
+        };
+        anyhow::ensure!(seconds > 0, "Interval must be at least one second");
+        Ok(Self::Every(Duration::from_secs(seconds)))
+    }
+}
+
+impl FromStr for CronExpression {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        let fields: Vec<&str> = value.split_whitespace().collect();
+        let [minutes, hours, days_of_month, months, days_of_week] = fields[..] else {
+            bail!("Cron expression {value} must have five fields");
+        };
+        let days_of_week = parse_field(days_of_week, 0, 7).context("Invalid day of week")?;
+        let cron = Self {
+            source: fields.join(" "),
+            minutes: parse_field(minutes, 0, 59).context("Invalid minute")?,
+            hours: parse_field(hours, 0, 23).context("Invalid hour")?,
+            days_of_month: parse_field(days_of_month, 1, 31).context("Invalid day of month")?,
+            months: parse_field(months, 1, 12).context("Invalid month")?,
+            // Sunday is both 0 and 7.
+            days_of_week: (days_of_week | (days_of_week >> 7)) & 0x7f,
+            any_day_of_month: days_of_month == "*",
+            any_day_of_week: fields[4] == "*",
+        };
+        anyhow::ensure!(
+            cron.next_after(Utc::now()).is_some(),
+            "Cron expression {value} never matches"
+        );
+        Ok(cron)
+    }
+}
+
+impl fmt::Display for Schedule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Every(interval) => {
+                let seconds = interval.as_secs();
+                match seconds {
+                    _ if seconds % 86400 == 0 => write!(f, "{}d", seconds / 86400),
+                    _ if seconds % 3600 == 0 => write!(f, "{}h", seconds / 3600),
+                    _ if seconds % 60 == 0 => write!(f, "{}m", seconds / 60),
+                    _ => write!(f, "{seconds}s"),
+                }
+            }
+            Self::Cron(cron) => write!(f, "{}", cron.source),
+        }
+    }
+}
+
+impl Serialize for Schedule {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// Accepts integer seconds as well, so `BACKUP_INTERVAL_SECONDS` keeps working.
+impl<'de> Deserialize<'de> for Schedule {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Raw {
+            Seconds(u64),
+            Text(String),
+        }
+        match Raw::deserialize(deserializer)? {
+            Raw::Seconds(seconds) => format!("{seconds}s").parse(),
+            Raw::Text(text) => text.parse(),
+        }
+        .map_err(|error| serde::de::Error::custom(format!("{error:#}")))
+    }
+}
+
+/// Start every scheduled task.
+///
+/// Disabled tasks keep their loop and check the flag again on every scheduled run,
+/// so enabling one in the config file takes effect without a restart.
+/// The tasks end at shutdown, after finishing a run that is in progress.
+pub fn spawn(state: &SharedState, settings: &SharedSettings) -> Vec<JoinHandle<()>> {
+    TaskName::iter()
+        .map(|name| {
+            let state = Arc::clone(state);
+            let settings = Arc::clone(settings);
+            let shutdown = state.lifecycle().shutdown_token();
+            tokio::spawn(async move {
+                loop {
+                    let config = task_config(&settings, name);
+                    tokio::select! {
+                        () = shutdown.cancelled() => break,
+                        () = tokio::time::sleep(next_delay(&config, Utc::now())) => {}
+                    }
+                    if task_config(&settings, name).enabled {
+                        run(name, &state, &settings).await;
+                    }
+                }
+            })
+        })
+        .collect()
+}
+
+/// Run a task once.
+pub async fn run(name: TaskName, state: &SharedState, settings: &SharedSettings) {
+    match name {
+        TaskName::Backup => {
+            let config = settings.load().backup.clone();
+            let Some(directory) = config.directory else {
+                return;
+            };
+            let state = Arc::clone(state);
+            // Compressing and writing a large state is blocking work, so keep it off the async workers.
+            let result = tokio::task::spawn_blocking(move || write_backup(&state, &directory, config.retention)).await;
+            match result {
+                Ok(Ok(path)) => crate::log_info!("Wrote backup {}", path.display()),
+                Ok(Err(error)) => crate::log_error!("Scheduled backup failed: {error:#}"),
+                Err(error) => crate::log_error!("Scheduled backup task failed: {error}"),
+            }
+        }
+        TaskName::ExpirySweep => {
+            expiry::sweep(state);
+        }
+        TaskName::EvictionSweep => {
+            let policy = settings.load().eviction;
+            eviction::sweep(state, &policy);
+        }
+        TaskName::StateLog => {
+            crate::log_info!(
+                "State: {} items, capacity {}, {} webhooks, up {} seconds",
+                state.items().db.len(),
+                state.items().db.capacity(),
+                state.webhooks().len(),
+                state.uptime().as_secs()
+            );
+        }
+    }
+}
+
+fn task_config(settings: &SharedSettings, name: TaskName) -> TaskConfig {
+    let tasks = &settings.load().tasks;
+    match name {
+        TaskName::Backup => tasks.backup.clone(),
+        TaskName::ExpirySweep => tasks.expiry_sweep.clone(),
+        TaskName::EvictionSweep => tasks.eviction_sweep.clone(),
+        TaskName::StateLog => tasks.state_log.clone(),
+    }
+}
+
+/// Wait until the next run, with a random jitter of up to `jitter_seconds`.
+fn next_delay(config: &TaskConfig, now: DateTime<Utc>) -> Duration {
+    let jitter_ms = config.jitter_seconds.saturating_mul(1000);
+    let jitter = Duration::from_millis(rand::rng().random_range(0..=jitter_ms));
+    config.schedule.delay_from(now).saturating_add(jitter)
+}
+
+/// Parse one cron field into a bit set of the allowed values.
+fn parse_field(field: &str, min: u32, max: u32) -> Result<u64> {
+    let mut bits = 0;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, step.parse::<u32>().context("Invalid step")?),
+            None => (part, 1),
+        };
+        anyhow::ensure!(step > 0, "Step must be at least 1");
+        let (start, end) = match range {
+            "*" => (min, max),
+            _ => match range.split_once('-') {
+                Some((start, end)) => (start.parse()?, end.parse()?),
+                // A single value with a step runs from that value to the end, like `5/15`.
+                None if part.contains('/') => (range.parse()?, max),
+                None => {
+                    let value = range.parse()?;
+                    (value, value)
+                }
+            },
+        };
+        anyhow::ensure!(
+            min <= start && start <= end && end <= max,
+            "{part} is not within {min}-{max}"
+        );
+        for value in (start..=end).step_by(step as usize) {
+            bits |= 1 << value;
+        }
+    }
+    Ok(bits)
+}
+
+const fn has(bits: u64, value: u32) -> bool {
+    bits & (1 << value) != 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use chrono::TimeZone;
+
+    use crate::settings::Settings;
+    use crate::types::{AppState, IdStrategy, Item};
+
+    fn time(year: i32, month: u32, day: u32, hour: u32, minute: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(year, month, day, hour, minute, 0).unwrap()
+    }
+
+    fn cron(expression: &str) -> CronExpression {
+        expression.parse().unwrap()
+    }
+
+    #[test]
+    fn parses_intervals_and_round_trips_them() {
+        for (text, seconds) in [("30s", 30), ("15m", 900), ("6h", 21600), ("1d", 86400), ("90s", 90)] {
+            let schedule: Schedule = text.parse().unwrap();
+            assert_eq!(schedule, Schedule::Every(Duration::from_secs(seconds)));
+            assert_eq!(schedule.to_string(), text);
+        }
+        assert_eq!(
+            serde_json::from_value::<Schedule>(serde_json::json!(120)).unwrap(),
+            Schedule::Every(Duration::from_mins(2))
+        );
+        for invalid in ["", "0s", "30", "5w", "1.5h", "* * * *"] {
+            assert!(invalid.parse::<Schedule>().is_err(), "{invalid} should be invalid");
+        }
+    }
+
+    #[test]
+    fn cron_finds_next_matching_minute() {
+        let now = time(2026, 6, 10, 9, 30);
+        assert_eq!(cron("* * * * *").next_after(now), Some(time(2026, 6, 10, 9, 31)));
+        assert_eq!(cron("*/15 * * * *").next_after(now), Some(time(2026, 6, 10, 9, 45)));
+        assert_eq!(cron("0 3 * * *").next_after(now), Some(time(2026, 6, 11, 3, 0)));
+        assert_eq!(cron("0 9-17/4 * * 1-5").next_after(now), Some(time(2026, 6, 10, 13, 0)));
+        // 2026-06-14 is a Sunday, written as 7.
+        assert_eq!(cron("0 0 * * 7").next_after(now), Some(time(2026, 6, 14, 0, 0)));
+        assert_eq!(cron("0 0 29 2 *").next_after(now), Some(time(2028, 2, 29, 0, 0)));
+        // Either restricted day field matches: the 1st of the month or a Friday.
+        assert_eq!(cron("0 0 1 * 5").next_after(now), Some(time(2026, 6, 12, 0, 0)));
+    }
+
+    #[test]
+    fn cron_rejects_invalid_fields() {
+        for invalid in [
+            "60 * * * *",
+            "* 24 * * *",
+            "* * 0 * *",
+            "* * * 13 *",
+            "* * * * 8",
+            "*/0 * * * *",
+            "5-1 * * * *",
+            "a * * * *",
+            "0 0 31 2 *",
+        ] {
+            assert!(
+                invalid.parse::<CronExpression>().is_err(),
+                "{invalid} should be invalid"
+            );
+        }
+    }
+
+    #[test]
+    fn delay_includes_jitter() {
+        let config = TaskConfig {
+            enabled: true,
+            schedule: Schedule::Every(Duration::from_secs(10)),
+            jitter_seconds: 5,
+        };
+        for _ in 0..20 {
+            let delay = next_delay(&config, Utc::now());
+            assert!(delay >= Duration::from_secs(10) && delay <= Duration::from_secs(15));
+        }
+        let cron_config = TaskConfig {
+            schedule: "0 3 * * *".parse().unwrap(),
+            jitter_seconds: 0,
+            ..config
+        };
+        assert_eq!(
+            next_delay(&cron_config, time(2026, 6, 10, 2, 59)),
+            Duration::from_mins(1)
+        );
+    }
+
+    #[tokio::test]
+    async fn enabled_tasks_run_on_schedule() {
+        let mut settings = Settings::default();
+        settings.tasks.expiry_sweep.schedule = Schedule::Every(Duration::from_secs(1));
+        settings.tasks.backup.enabled = false;
+        let settings = settings.into_shared();
+        let state = AppState::new_shared_state();
+        let mut item = Item::new_with_random_id("ephemeral".to_string(), IdStrategy::Numeric).with_ttl(Some(60));
+        item.expires_at = Some(Utc::now());
+        state.items().insert_item(&item).unwrap();
+
+        let tasks = spawn(&state, &settings);
+        for _ in 0..30 {
+            if state.items().db.is_empty() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        state.lifecycle().shutdown_token().cancel();
+        for task in tasks {
+            tokio::time::timeout(Duration::from_secs(1), task)
+                .await
+                .expect("tasks end at shutdown")
+                .unwrap();
+        }
+        assert!(state.items().db.is_empty(), "expiry sweep task did not run");
+    }
+}
@@ -4,18 +4,67 @@
 //! meaning everything that shows up in the `OpenAPI` documentation.
 //!
 
+use std::collections::HashMap;
 use std::fmt;
+use std::sync::Arc;
 
 use axum::Json;
 use axum::extract::rejection::JsonRejection;
-use axum::http::StatusCode;
+use axum::http::{StatusCode, Uri};
 use axum::response::{IntoResponse, Response};
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use strum::Display;
 use utoipa::{IntoParams, ToSchema};
 
-use crate::types::Item;
+use crate::events::{Actor, ItemEventType};
+use crate::i18n;
+use crate::negotiation::{Negotiate, ResponseFormat, encode};
+use crate::request_id;
+use crate::settings::Settings;
+use crate::types::{AppState, IdStrategy, Item, ItemId};
+use crate::validation::Validate;
 use crate::version;
 
+/// Longest accepted item name in characters.
+pub const MAX_ITEM_NAME_LENGTH: usize = 64;
+
+/// Shortest accepted webhook signing secret in characters.
+pub const MIN_WEBHOOK_SECRET_LENGTH: usize = 16;
+
+/// Longest accepted webhook signing secret in characters.
+pub const MAX_WEBHOOK_SECRET_LENGTH: usize = 256;
+
+/// Most tags one item can have.
+pub const MAX_ITEM_TAGS: usize = 16;
+
+/// Longest accepted tag in characters.
+pub const MAX_TAG_LENGTH: usize = 32;
+
+/// Most metadata entries one item can have.
+pub const MAX_METADATA_ENTRIES: usize = 16;
+
+/// Longest accepted metadata key or value in characters.
+pub const MAX_METADATA_LENGTH: usize = 256;
+
+/// Longest accepted item TTL in seconds, 30 days.
+pub const MAX_ITEM_TTL_SECONDS: u64 = 30 * 24 * 60 * 60;
+
+/// Default for the largest accepted item `data` document in bytes of JSON.
+pub const DEFAULT_MAX_ITEM_DATA_BYTES: usize = 16 * 1024;
+
+/// Default page size for the admin item listing.
+pub const DEFAULT_ADMIN_PAGE_SIZE: usize = 100;
+
+/// Largest page size for the admin item listing.
+pub const MAX_ADMIN_PAGE_SIZE: usize = 1000;
+
+/// Most item names in one batch get.
+pub const MAX_BATCH_NAMES: usize = 100;
+
+/// Longest allowed collection name.
+pub const MAX_COLLECTION_NAME_LENGTH: usize = 64;
+
 pub static VERSION_INFO: VersionInfo = VersionInfo {
     name: version::PACKAGE_NAME,
     version: version::PACKAGE_VERSION,
@@ -27,13 +76,29 @@ pub static VERSION_INFO: VersionInfo = VersionInfo {
 };
 
 /// Post payload for creating a new item
-#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema)]
 pub struct CreateItem {
     #[schema(example = "esgrove")]
     pub name: String,
     /// Optional id field, allowing clients to specify an id or have the server generate one
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub id: Option<u64>,
+    pub id: Option<ItemId>,
+    /// Optional tags for grouping and filtering items
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    #[schema(example = json!(["rust", "example"]))]
+    pub tags: Vec<String>,
+    /// Optional free-form key-value metadata
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    #[schema(example = json!({"owner": "esgrove"}))]
+    pub metadata: HashMap<String, String>,
+    /// Optional nested JSON document stored as is
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schema(example = json!({"owner": {"name": "esgrove", "teams": ["core"]}}))]
+    pub data: Option<serde_json::Value>,
+    /// Optional time to live, the item is removed this many seconds after creation
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(example = 3600)]
+    pub ttl_seconds: Option<u64>,
 }
 
 /// Query item information with name
@@ -43,23 +108,107 @@ pub struct ItemQuery {
     pub name: String,
 }
 
-/// Optional pagination parameters for listing items.
+/// Optional pagination and filter parameters for listing items.
 #[derive(Debug, Clone, Default, Deserialize, ToSchema, IntoParams)]
 pub struct ItemListQuery {
     #[param(example = 0)]
     pub skip: Option<usize>,
     #[param(example = 10)]
     pub limit: Option<usize>,
+    /// Only list items created after this time.
+    #[param(example = "2026-06-10T09:00:00Z")]
+    pub created_after: Option<DateTime<Utc>>,
+    /// Only list items with this tag.
+    #[param(example = "rust")]
+    pub tag: Option<String>,
+    /// Stream full items as newline-delimited JSON instead of returning a page of names.
+    #[serde(default)]
+    #[param(example = false)]
+    pub stream: bool,
 }
 
-/// Simple response with a message
+/// Pagination and sorting for the admin item listing.
+#[derive(Debug, Clone, Default, Deserialize, ToSchema, IntoParams)]
+pub struct AdminItemListQuery {
+    #[param(example = 0)]
+    pub skip: Option<usize>,
+    /// Page size, 100 by default and at most 1000.
+    #[param(example = 100)]
+    pub limit: Option<usize>,
+    #[serde(default)]
+    #[param(inline)]
+    pub sort: ItemSortField,
+    #[serde(default)]
+    #[param(inline)]
+    pub order: SortOrder,
+}
+
+/// Simple response with an informational message
 #[derive(Debug, Clone, Serialize, ToSchema)]
 pub struct MessageResponse {
-    /// Message can be either information or an error message
-    #[schema(example = "Item already exists: esgrove")]
+    #[schema(example = "Removed 3 items")]
     pub message: String,
 }
 
+/// Error body returned by every route for every error status.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ApiError {
+    pub code: ErrorCode,
+    /// Human readable description of the error
+    #[schema(example = "Item does not exist: esgrove")]
+    pub message: String,
+    /// Id of the failed request, also returned in the `x-request-id` header
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schema(example = "67e55044-10b1-426f-9247-bb680e5fe0c8")]
+    pub request_id: Option<String>,
+    /// Every violated rule, only present for validation errors
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub fields: Vec<FieldError>,
+    /// The exceeded limit, only present for quota errors
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quota: Option<QuotaExceeded>,
+}
+
+/// Storage quota that a write would have exceeded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct QuotaExceeded {
+    pub resource: QuotaResource,
+    /// Configured limit of the resource.
+    #[schema(example = 1000)]
+    pub limit: usize,
+    /// Usage the write would have led to.
+    #[schema(example = 1001)]
+    pub requested: usize,
+}
+
+/// Resource limited by a storage quota.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum QuotaResource {
+    /// Number of items, exceeding it is `403 Forbidden`
+    Items,
+    /// Total size of the item `data` documents, exceeding it is `413 Payload Too Large`
+    DataBytes,
+}
+
+/// Current consumption of the item store and its quota.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct UsageResponse {
+    #[schema(example = 12)]
+    pub num_items: usize,
+    /// Unset when the item count is not limited.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schema(example = 1000)]
+    pub max_items: Option<usize>,
+    /// Total size of the item `data` documents as compact JSON.
+    #[schema(example = 2048)]
+    pub data_bytes: usize,
+    /// Unset when the data size is not limited.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schema(example = 1_048_576)]
+    pub max_data_bytes: Option<usize>,
+}
+
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct ItemListResponse {
     /// The total number of items
@@ -69,6 +218,58 @@ pub struct ItemListResponse {
     pub names: Vec<String>,
 }
 
+/// Names of the items to get in one request.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ItemBatchQuery {
+    #[schema(example = json!(["esgrove", "missing"]))]
+    pub names: Vec<String>,
+}
+
+/// Items found for a batch get, and the requested names that did not match an item.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ItemBatchResponse {
+    /// Found items in the requested order
+    #[schema(value_type = Vec<Item>)]
+    pub items: Vec<Arc<Item>>,
+    /// Requested names without an item
+    #[schema(example = json!(["missing"]))]
+    pub missing: Vec<String>,
+}
+
+/// Recorded changes of an item, oldest first.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ItemHistoryResponse {
+    #[schema(example = "esgrove")]
+    pub name: String,
+    pub entries: Vec<ItemHistoryEntry>,
+}
+
+/// Single change in an item history.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct ItemHistoryEntry {
+    pub occurred_at: DateTime<Utc>,
+    #[serde(rename = "type")]
+    pub event_type: ItemEventType,
+    pub actor: Actor,
+    /// Item revision after a create, or at removal.
+    /// Missing when all items were cleared at once.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub revision: Option<u64>,
+}
+
+/// One page of items with every field.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct AdminItemListResponse {
+    /// The total number of items
+    #[schema(example = 5)]
+    pub num_items: usize,
+    #[schema(example = 0)]
+    pub skip: usize,
+    #[schema(example = 100)]
+    pub limit: usize,
+    pub items: Vec<Item>,
+}
+
 /// API version information.
 #[derive(Debug, Clone, Serialize, ToSchema)]
 pub struct VersionInfo {
@@ -107,60 +308,312 @@ pub struct HealthResponse {
     pub uptime_ms: u64,
 }
 
-/// Not found response.
+/// Effective runtime configuration with secrets redacted.
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
-pub struct NotFoundResponse {
-    #[schema(example = "Not Found")]
-    pub error: String,
-    #[schema(example = "Path does not exist")]
+pub struct ConfigResponse {
+    #[schema(example = "LOCAL")]
+    pub environment: String,
+    #[schema(example = "0.13.0")]
+    pub version: String,
+    #[schema(example = 10)]
+    pub request_timeout_seconds: u64,
+    /// Merged settings from defaults, config file, env variables, CLI arguments and secrets.
+    #[schema(value_type = Object)]
+    pub settings: Settings,
+}
+
+/// One setting that changed on reload.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct SettingChange {
+    /// Dotted settings key, for example `rate_limit.burst`.
+    #[schema(example = "rate_limit.burst")]
+    pub key: String,
+    #[schema(example = 20)]
+    pub old: serde_json::Value,
+    #[schema(example = 50)]
+    pub new: serde_json::Value,
+    /// The new value is stored but only takes effect after a restart.
+    pub restart_required: bool,
+}
+
+/// Settings changes applied by a config reload.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ReloadConfigResponse {
+    /// Changed settings, empty if the config was already up to date.
+    pub changes: Vec<SettingChange>,
+}
+
+/// Query parameters for restoring a snapshot.
+#[derive(Debug, Clone, Default, Deserialize, ToSchema, IntoParams)]
+pub struct RestoreQuery {
+    /// Replace all current items, or merge the snapshot into them.
+    #[serde(default)]
+    #[param(example = "merge")]
+    pub mode: RestoreMode,
+    /// Restore in a background job and return its status right away.
+    #[serde(default)]
+    pub background: bool,
+}
+
+/// Result of restoring a snapshot.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct RestoreResponse {
+    pub mode: RestoreMode,
+    /// Snapshot items added to the state.
+    #[schema(example = 12)]
+    pub restored: usize,
+    /// Snapshot items skipped because their name or id was already taken.
+    #[schema(example = 0)]
+    pub skipped: usize,
+    /// Items in the state after the restore.
+    #[schema(example = 12)]
+    pub num_items: usize,
+}
+
+/// Post payload for creating a named item collection.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct CreateCollection {
+    /// Lowercase letters, numbers, '-' and '_'
+    #[schema(example = "archive")]
+    pub name: String,
+}
+
+/// Item collection and its size.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct CollectionInfo {
+    #[schema(example = "archive")]
+    pub name: String,
+    /// Items stored in the collection, including expired items not yet swept.
+    #[schema(example = 12)]
+    pub num_items: usize,
+}
+
+/// Item map capacity before and after compacting.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct CompactResponse {
+    #[schema(example = 8192)]
+    pub num_items: usize,
+    #[schema(example = 229_376)]
+    pub capacity_before: usize,
+    #[schema(example = 14336)]
+    pub capacity_after: usize,
+}
+
+/// A request that is currently being handled.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct InflightRequest {
+    /// Sequence number in arrival order.
+    #[schema(example = 1042)]
+    pub id: u64,
+    #[schema(example = "GET")]
+    pub method: String,
+    #[schema(example = "/items")]
+    pub path: String,
+    #[schema(example = "2026-06-10T09:00:00Z")]
+    pub started_at: DateTime<Utc>,
+    /// Milliseconds since the request started.
+    #[schema(example = 2500)]
+    pub elapsed_ms: u64,
+}
+
+/// Requests that are currently being handled, oldest first.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct InflightResponse {
+    /// Number of running requests, including this one.
+    #[schema(example = 3)]
+    pub num_requests: usize,
+    pub requests: Vec<InflightRequest>,
+}
+
+/// Background job and its result once finished.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct JobStatus {
+    #[schema(example = "01J0ZQ3V6M8J2W4Y6A8C0E2G4J")]
+    pub id: String,
+    pub kind: JobKind,
+    pub state: JobState,
+    #[schema(example = "2026-06-10T09:00:00Z")]
+    pub queued_at: DateTime<Utc>,
+    pub started_at: Option<DateTime<Utc>>,
+    pub finished_at: Option<DateTime<Utc>>,
+    /// Job output when it succeeded, the same as the synchronous route would return
+    #[schema(value_type = Option<Object>)]
+    pub result: Option<serde_json::Value>,
+    /// Failure reason when it failed
+    pub error: Option<String>,
+}
+
+/// Callback registration for item events.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct CreateWebhook {
+    /// URL that receives the events with `POST`, only plain `http` is supported
+    #[schema(example = "http://hooks.internal:8080/items")]
+    pub url: String,
+    /// Key for the `X-Webhook-Signature` HMAC, never returned by the API
+    #[schema(example = "2f6c1e0a9d8b4c7e")]
+    pub secret: String,
+    /// Event types to deliver, all types when empty
+    #[serde(default)]
+    pub events: Vec<ItemEventType>,
+}
+
+/// Registered webhook.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct Webhook {
+    #[schema(example = "01J0ZQ3V6M8J2W4Y6A8C0E2G4J")]
+    pub id: String,
+    #[schema(example = "http://hooks.internal:8080/items")]
+    pub url: String,
+    /// Delivered event types, all types when empty
+    pub events: Vec<ItemEventType>,
+    #[schema(example = "2026-06-10T09:00:00Z")]
+    pub created_at: DateTime<Utc>,
+}
+
+/// Single delivery attempt of an event to a webhook.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct WebhookDelivery {
+    pub event_type: ItemEventType,
+    /// When the delivered event happened
+    #[schema(example = "2026-06-10T09:00:00Z")]
+    pub occurred_at: DateTime<Utc>,
+    /// Attempt number, starting from 1
+    #[schema(example = 1)]
+    pub attempt: u32,
+    #[schema(example = "2026-06-10T09:00:01Z")]
+    pub attempted_at: DateTime<Utc>,
+    /// Response status, or null when no response was received
+    #[schema(example = 200)]
+    pub status: Option<u16>,
+    /// Connection or timeout error when no response was received
+    pub error: Option<String>,
+    /// The receiver answered with a 2xx status
+    pub succeeded: bool,
+}
+
+/// Recent delivery attempts of a webhook, newest first.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct WebhookDeliveriesResponse {
+    pub webhook: Webhook,
+    pub deliveries: Vec<WebhookDelivery>,
+}
+
+/// Deprecated route or request field with its sunset date.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct Deprecation {
+    /// HTTP method, or null when every method of the route is deprecated
+    #[schema(example = "GET")]
+    pub method: Option<String>,
+    /// Route pattern
+    #[schema(example = "/item")]
+    pub route: String,
+    /// Deprecated query parameter or JSON body field, or null for the whole route
+    #[schema(example = "id")]
+    pub field: Option<String>,
+    pub deprecated_at: DateTime<Utc>,
+    /// Date after which the route or field may be removed
+    pub sunset: DateTime<Utc>,
+    #[schema(example = "Use GET /items/{name} instead")]
     pub message: String,
+    /// Link to migration documentation
+    pub link: Option<String>,
 }
 
-/// Authentication failed response.
-#[derive(Debug, Serialize, Deserialize, ToSchema)]
-pub struct AuthErrorResponse {
-    message: String,
+/// Single violated validation rule.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct FieldError {
+    #[schema(example = "name")]
+    pub field: String,
+    #[schema(example = "must not be empty")]
+    pub message: String,
 }
 
-/// Combined response for JSON deserialization errors.
-#[derive(Debug, Serialize, Deserialize, ToSchema)]
-pub struct RejectionErrorResponse {
-    error: String,
-    message: String,
+/// How a restored snapshot is combined with the current items.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema, Display)]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase")]
+pub enum RestoreMode {
+    /// Remove current items before restoring.
+    #[default]
+    Replace,
+    /// Keep current items and skip snapshot items that conflict with them.
+    Merge,
 }
 
-#[derive(Debug)]
-pub struct RejectionError {
-    status: StatusCode,
-    message: String,
-    rejection: String,
+/// Work that runs as a background job.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema, Display)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum JobKind {
+    /// Write a backup to the configured backup directory.
+    Backup,
+    /// Restore an uploaded snapshot.
+    Restore,
 }
 
-/// Custom error type that enables using anyhow error handling in routes.
-/// This is used for server-side errors and returns status code 500 with the error message.
-pub struct ServerError(pub anyhow::Error);
+/// Progress of a background job.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema, Display)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum JobState {
+    Queued,
+    Running,
+    Succeeded,
+    Failed,
+}
 
-pub enum ItemResponse {
-    Found(Item),
-    Error(MessageResponse),
+/// Item field to sort a listing by.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema, Display)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum ItemSortField {
+    #[default]
+    Name,
+    CreatedAt,
+    UpdatedAt,
+    Revision,
 }
 
-pub enum CreateItemResponse {
-    Created(Item),
-    Error(MessageResponse),
+/// Sort direction.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema, Display)]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase")]
+pub enum SortOrder {
+    #[default]
+    Asc,
+    Desc,
 }
 
-pub enum RemoveItemResponse {
-    Removed(Item),
-    Error(MessageResponse),
+/// Machine readable error category, each maps to one HTTP status code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema, Display)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum ErrorCode {
+    BadRequest,
+    Unauthorized,
+    Forbidden,
+    NotFound,
+    MethodNotAllowed,
+    Conflict,
+    PreconditionFailed,
+    PayloadTooLarge,
+    UnsupportedMediaType,
+    /// The request was well-formed but broke validation rules, see `fields`
+    ValidationFailed,
+    PreconditionRequired,
+    RateLimited,
+    Internal,
+    Unavailable,
 }
 
 impl MessageResponse {
+    #[must_use]
     pub const fn new(message: String) -> Self {
         Self { message }
     }
 
     #[allow(unused)]
+    #[must_use]
     pub fn new_from_str(message: &str) -> Self {
         Self {
             message: message.to_string(),
@@ -168,115 +621,319 @@ impl MessageResponse {
     }
 }
 
-impl AuthErrorResponse {
-    pub const fn new(message: String) -> Self {
-        Self { message }
+impl ApiError {
+    /// Error for the request being handled, tagged with its request id.
+    pub fn new(code: ErrorCode, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            request_id: request_id::current(),
+            fields: Vec::new(),
+            quota: None,
+        }
     }
 
-    pub fn new_from_str(message: &str) -> Self {
+    /// Error for a write that would exceed a storage quota.
+    #[must_use]
+    pub fn quota(exceeded: QuotaExceeded) -> Self {
+        let (code, key) = match exceeded.resource {
+            QuotaResource::Items => (ErrorCode::Forbidden, "quota_items_exceeded"),
+            QuotaResource::DataBytes => (ErrorCode::PayloadTooLarge, "quota_data_bytes_exceeded"),
+        };
         Self {
-            message: message.to_string(),
+            quota: Some(exceeded),
+            ..Self::new(code, i18n::message(key, &[("limit", &exceeded.limit)]))
         }
     }
-}
 
-impl NotFoundResponse {
-    pub fn new() -> Self {
+    /// Validation error listing every violated rule.
+    #[must_use]
+    pub fn validation(fields: Vec<FieldError>) -> Self {
+        let names: Vec<&str> = fields.iter().map(|field| field.field.as_str()).collect();
+        let message = i18n::message("invalid_fields", &[("fields", &names.join(", "))]);
         Self {
-            error: "Not Found".to_string(),
-            message: "Path does not exist".to_string(),
+            fields,
+            ..Self::new(ErrorCode::ValidationFailed, message)
         }
     }
-}
 
-impl RemoveItemResponse {
-    // Accept any type that implements std::fmt::Display, not just strings.
-    pub fn new_error<T: std::fmt::Display>(message: T) -> Self {
-        Self::Error(MessageResponse::new(format!("{message}")))
+    #[must_use]
+    pub const fn status(&self) -> StatusCode {
+        self.code.status()
     }
 }
 
-impl IntoResponse for CreateItemResponse {
-    fn into_response(self) -> Response {
+impl ErrorCode {
+    #[must_use]
+    pub const fn status(self) -> StatusCode {
         match self {
-            Self::Created(item) => (StatusCode::CREATED, Json(item)).into_response(),
-            Self::Error(message) => (StatusCode::CONFLICT, Json(message)).into_response(),
+            Self::BadRequest => StatusCode::BAD_REQUEST,
+            Self::Unauthorized => StatusCode::UNAUTHORIZED,
+            Self::Forbidden => StatusCode::FORBIDDEN,
+            Self::NotFound => StatusCode::NOT_FOUND,
+            Self::MethodNotAllowed => StatusCode::METHOD_NOT_ALLOWED,
+            Self::Conflict => StatusCode::CONFLICT,
+            Self::PreconditionFailed => StatusCode::PRECONDITION_FAILED,
+            Self::PayloadTooLarge => StatusCode::PAYLOAD_TOO_LARGE,
+            Self::UnsupportedMediaType => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            Self::ValidationFailed => StatusCode::UNPROCESSABLE_ENTITY,
+            Self::PreconditionRequired => StatusCode::PRECONDITION_REQUIRED,
+            Self::RateLimited => StatusCode::TOO_MANY_REQUESTS,
+            Self::Internal => StatusCode::INTERNAL_SERVER_ERROR,
+            Self::Unavailable => StatusCode::SERVICE_UNAVAILABLE,
+        }
+    }
+
+    /// Code for an error status produced outside the handlers, like extractor rejections.
+    ///
+    /// Statuses without their own code fall back to `bad_request` or `internal`.
+    #[must_use]
+    pub fn from_status(status: StatusCode) -> Self {
+        match status {
+            StatusCode::UNAUTHORIZED => Self::Unauthorized,
+            StatusCode::FORBIDDEN => Self::Forbidden,
+            StatusCode::NOT_FOUND => Self::NotFound,
+            StatusCode::METHOD_NOT_ALLOWED => Self::MethodNotAllowed,
+            StatusCode::CONFLICT => Self::Conflict,
+            StatusCode::PRECONDITION_FAILED => Self::PreconditionFailed,
+            StatusCode::PAYLOAD_TOO_LARGE => Self::PayloadTooLarge,
+            StatusCode::UNSUPPORTED_MEDIA_TYPE => Self::UnsupportedMediaType,
+            StatusCode::UNPROCESSABLE_ENTITY => Self::ValidationFailed,
+            StatusCode::PRECONDITION_REQUIRED => Self::PreconditionRequired,
+            StatusCode::TOO_MANY_REQUESTS => Self::RateLimited,
+            StatusCode::SERVICE_UNAVAILABLE | StatusCode::GATEWAY_TIMEOUT => Self::Unavailable,
+            status if status.is_server_error() => Self::Internal,
+            _ => Self::BadRequest,
         }
     }
 }
 
-impl IntoResponse for ItemResponse {
-    fn into_response(self) -> Response {
-        match self {
-            Self::Found(item) => (StatusCode::OK, Json(item)).into_response(),
-            Self::Error(message) => (StatusCode::NOT_FOUND, Json(message)).into_response(),
+impl FieldError {
+    #[must_use]
+    pub fn new(field: &str, message: &str) -> Self {
+        Self {
+            field: field.to_string(),
+            message: message.to_string(),
         }
     }
 }
 
-impl IntoResponse for RemoveItemResponse {
-    fn into_response(self) -> Response {
-        match self {
-            Self::Removed(item) => (StatusCode::OK, Json(item)).into_response(),
-            Self::Error(message) => (StatusCode::NOT_FOUND, Json(message)).into_response(),
+impl CreateItem {
+    /// Size of the `data` document serialized as compact JSON, or zero without one.
+    #[must_use]
+    pub fn data_size(&self) -> usize {
+        self.data
+            .as_ref()
+            .and_then(|data| serde_json::to_vec(data).ok())
+            .map_or(0, |bytes| bytes.len())
+    }
+
+    /// Check the rules that come from settings: the id strategy, the name pattern and the data size limit.
+    #[must_use]
+    pub fn settings_violations(&self, settings: &Settings) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        if let Some(id) = self.id
+            && let Err(message) = settings.id_strategy.check(id)
+        {
+            errors.push(FieldError::new("id", message));
         }
+        if let Some(pattern) = &settings.item_name_pattern
+            && !pattern.is_match(&self.name)
+        {
+            errors.push(FieldError::new("name", &format!("must match the pattern {pattern}")));
+        }
+        if self.data_size() > settings.max_item_data_bytes {
+            errors.push(FieldError::new(
+                "data",
+                &format!("must be at most {} bytes as JSON", settings.max_item_data_bytes),
+            ));
+        }
+        errors
+    }
+
+    /// Build the item with the state's clock, and a new id from the state if none was given.
+    pub fn into_item(self, strategy: IdStrategy, state: &AppState) -> anyhow::Result<Item> {
+        let id = self.id.unwrap_or_else(|| state.generate_id(strategy));
+        Ok(Item::new(self.name, id, strategy)?
+            .with_created_at(state.now())
+            .with_tags(self.tags)
+            .with_metadata(self.metadata)
+            .with_data(self.data)
+            .with_ttl(self.ttl_seconds))
     }
 }
 
-impl IntoResponse for AuthErrorResponse {
-    fn into_response(self) -> Response {
-        let body = Json(self);
-        (StatusCode::UNAUTHORIZED, body).into_response()
+impl Validate for CreateWebhook {
+    fn validate(&self) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        match self.url.parse::<Uri>() {
+            Ok(uri) if uri.scheme_str() == Some("http") && uri.host().is_some() => {}
+            _ => errors.push(FieldError::new("url", "must be an absolute http URL")),
+        }
+        if !(MIN_WEBHOOK_SECRET_LENGTH..=MAX_WEBHOOK_SECRET_LENGTH).contains(&self.secret.chars().count()) {
+            errors.push(FieldError::new(
+                "secret",
+                &format!("must be between {MIN_WEBHOOK_SECRET_LENGTH} and {MAX_WEBHOOK_SECRET_LENGTH} characters"),
+            ));
+        }
+        errors
     }
 }
 
-// Tell axum how to convert `ServerError` into a response.
-impl IntoResponse for ServerError {
-    fn into_response(self) -> Response {
-        (StatusCode::INTERNAL_SERVER_ERROR, Json(format!("Error: {}", self.0))).into_response()
+impl Validate for ItemBatchQuery {
+    fn validate(&self) -> Vec<FieldError> {
+        if self.names.is_empty() {
+            vec![FieldError::new("names", "must list at least one name")]
+        } else if self.names.len() > MAX_BATCH_NAMES {
+            vec![FieldError::new(
+                "names",
+                &format!("must list at most {MAX_BATCH_NAMES} names"),
+            )]
+        } else {
+            Vec::new()
+        }
     }
 }
 
-// This enables using `?` on functions that return `Result<_, anyhow::Error>`
-// to turn them into `Result<_, ServerError>`.
-// This way we don't need to do that manually.
-impl<E> From<E> for ServerError
-where
-    E: Into<anyhow::Error>,
-{
-    fn from(err: E) -> Self {
-        Self(err.into())
+impl Validate for CreateCollection {
+    fn validate(&self) -> Vec<FieldError> {
+        if self.name.is_empty() || self.name.len() > MAX_COLLECTION_NAME_LENGTH {
+            vec![FieldError::new(
+                "name",
+                &format!("must be between 1 and {MAX_COLLECTION_NAME_LENGTH} characters"),
+            )]
+        } else if !self
+            .name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || matches!(c, '-' | '_'))
+        {
+            vec![FieldError::new(
+                "name",
+                "may only contain lowercase letters, numbers, '-' and '_'",
+            )]
+        } else {
+            Vec::new()
+        }
     }
 }
 
-impl From<JsonRejection> for RejectionError {
-    fn from(error: JsonRejection) -> Self {
-        Self {
-            status: error.status(),
-            message: error.body_text(),
-            rejection: match error {
-                JsonRejection::JsonDataError(_) => "JsonDataError".to_string(),
-                JsonRejection::JsonSyntaxError(_) => "JsonSyntaxError".to_string(),
-                JsonRejection::MissingJsonContentType(_) => "MissingJsonContentType".to_string(),
-                JsonRejection::BytesRejection(_) => "BytesRejection".to_string(),
-                _ => "Unknown rejection".to_string(),
-            },
+impl Validate for CreateItem {
+    fn validate(&self) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        if let Some(message) = item_name_violation(&self.name) {
+            errors.push(FieldError::new("name", &message));
+        }
+        if matches!(self.id, Some(ItemId::Numeric(id)) if !Item::is_valid_id(id)) {
+            errors.push(FieldError::new("id", "must be between 1000 and 9999"));
+        }
+        if self
+            .ttl_seconds
+            .is_some_and(|ttl| ttl == 0 || ttl > MAX_ITEM_TTL_SECONDS)
+        {
+            errors.push(FieldError::new(
+                "ttl_seconds",
+                &format!("must be between 1 and {MAX_ITEM_TTL_SECONDS}"),
+            ));
+        }
+        if self.tags.len() > MAX_ITEM_TAGS {
+            errors.push(FieldError::new(
+                "tags",
+                &format!("must have at most {MAX_ITEM_TAGS} tags"),
+            ));
+        }
+        if self
+            .tags
+            .iter()
+            .any(|tag| tag.is_empty() || tag.chars().count() > MAX_TAG_LENGTH)
+        {
+            errors.push(FieldError::new(
+                "tags",
+                &format!("tags must be between 1 and {MAX_TAG_LENGTH} characters"),
+            ));
+        }
+        if self.metadata.len() > MAX_METADATA_ENTRIES {
+            errors.push(FieldError::new(
+                "metadata",
+                &format!("must have at most {MAX_METADATA_ENTRIES} entries"),
+            ));
         }
+        if self.metadata.iter().any(|(key, value)| {
+            key.is_empty() || key.chars().count() > MAX_METADATA_LENGTH || value.chars().count() > MAX_METADATA_LENGTH
+        }) {
+            errors.push(FieldError::new(
+                "metadata",
+                &format!("keys must be between 1 and {MAX_METADATA_LENGTH} characters and values at most {MAX_METADATA_LENGTH}"),
+            ));
+        }
+        errors
+    }
+}
+
+impl Negotiate for ApiError {
+    fn negotiate(self, format: ResponseFormat) -> Response {
+        encode(format, self.status(), &self)
     }
 }
 
-impl IntoResponse for RejectionError {
+impl Negotiate for Item {
+    fn negotiate(self, format: ResponseFormat) -> Response {
+        encode(format, StatusCode::OK, &self)
+    }
+}
+
+impl Negotiate for Arc<Item> {
+    fn negotiate(self, format: ResponseFormat) -> Response {
+        encode(format, StatusCode::OK, &self)
+    }
+}
+
+impl Negotiate for ItemListResponse {
+    fn negotiate(self, format: ResponseFormat) -> Response {
+        encode(format, StatusCode::OK, &self)
+    }
+}
+
+impl Negotiate for ItemBatchResponse {
+    fn negotiate(self, format: ResponseFormat) -> Response {
+        encode(format, StatusCode::OK, &self)
+    }
+}
+
+impl Negotiate for UsageResponse {
+    fn negotiate(self, format: ResponseFormat) -> Response {
+        encode(format, StatusCode::OK, &self)
+    }
+}
+
+impl Negotiate for ItemHistoryResponse {
+    fn negotiate(self, format: ResponseFormat) -> Response {
+        encode(format, StatusCode::OK, &self)
+    }
+}
+
+impl IntoResponse for ApiError {
     fn into_response(self) -> Response {
-        let response = RejectionErrorResponse {
-            error: self.rejection,
-            message: self.message,
-        };
+        (self.status(), Json(self)).into_response()
+    }
+}
 
-        (self.status, Json(response)).into_response()
+// This enables using `?` on functions that return `Result<_, anyhow::Error>`
+// in handlers that return `Result<_, ApiError>`, as a server-side `internal` error.
+impl From<anyhow::Error> for ApiError {
+    fn from(error: anyhow::Error) -> Self {
+        crate::log_error!("Internal error: {error:#}");
+        Self::new(ErrorCode::Internal, format!("Error: {error}"))
+    }
+}
+
+impl From<JsonRejection> for ApiError {
+    fn from(error: JsonRejection) -> Self {
+        Self::new(ErrorCode::from_status(error.status()), error.body_text())
     }
 }
 
 impl VersionInfo {
+    #[must_use]
     pub fn to_string_pretty(&self) -> String {
         format!(
             "Version information:\n\
@@ -303,6 +960,26 @@ impl fmt::Display for VersionInfo {
     }
 }
 
+/// Check an item name against the naming rules and return the first violated rule.
+///
+/// Names are used as map keys and in URLs, so they are limited to a safe character set.
+/// The optional pattern from settings is checked separately since it depends on configuration.
+#[must_use]
+pub fn item_name_violation(name: &str) -> Option<String> {
+    if name.is_empty() {
+        Some("must not be empty".to_string())
+    } else if name.chars().count() > MAX_ITEM_NAME_LENGTH {
+        Some(format!("must be at most {MAX_ITEM_NAME_LENGTH} characters"))
+    } else if !name
+        .chars()
+        .all(|c| c.is_alphanumeric() || matches!(c, '-' | '_' | '.'))
+    {
+        Some("may only contain letters, numbers, '-', '_' and '.'".to_string())
+    } else {
+        None
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -310,8 +987,12 @@ mod tests {
     use axum::body::Body;
     use axum::extract::FromRequest;
     use http_body_util::BodyExt;
+    use proptest::prelude::*;
     use serde_json::Value;
 
+    use crate::negotiation::{Created, Negotiated};
+    use crate::types::IdStrategy;
+
     async fn response_json(response: Response) -> Value {
         let bytes = response
             .into_body()
@@ -325,91 +1006,84 @@ mod tests {
     #[test]
     fn constructors_build_expected_messages() {
         assert_eq!(MessageResponse::new_from_str("hello").message, "hello");
-        assert_eq!(AuthErrorResponse::new_from_str("denied").message, "denied");
 
-        let not_found = NotFoundResponse::new();
-        assert_eq!(not_found.error, "Not Found");
-        assert_eq!(not_found.message, "Path does not exist");
+        let error = ApiError::new(ErrorCode::NotFound, "Path does not exist");
+        assert_eq!(error.message, "Path does not exist");
+        assert_eq!(error.status(), StatusCode::NOT_FOUND);
+        assert_eq!(error.request_id, None);
     }
 
     #[tokio::test]
-    async fn create_item_response_maps_success_and_conflict_statuses() {
-        let item = Item {
-            id: 1234,
-            name: "created".to_string(),
-        };
+    async fn item_results_map_success_and_error_statuses() {
+        let item = Item::new("created".to_string(), ItemId::Numeric(1234), IdStrategy::Numeric).expect("valid item");
 
-        let response = CreateItemResponse::Created(item).into_response();
+        let created: Result<Created<Item>, ApiError> = Ok(Created(item.clone()));
+        let response = Negotiated(ResponseFormat::Json, created).into_response();
         assert_eq!(response.status(), StatusCode::CREATED);
         assert_eq!(response_json(response).await["name"], "created");
 
-        let response = CreateItemResponse::Error(MessageResponse::new_from_str("exists")).into_response();
-        assert_eq!(response.status(), StatusCode::CONFLICT);
-        assert_eq!(response_json(response).await["message"], "exists");
-    }
-
-    #[tokio::test]
-    async fn item_response_maps_found_and_missing_statuses() {
-        let item = Item {
-            id: 2345,
-            name: "found".to_string(),
-        };
-
-        let response = ItemResponse::Found(item).into_response();
+        let found: Result<Arc<Item>, ApiError> = Ok(Arc::new(item));
+        let response = Negotiated(ResponseFormat::Json, found).into_response();
         assert_eq!(response.status(), StatusCode::OK);
-        assert_eq!(response_json(response).await["name"], "found");
-
-        let response = ItemResponse::Error(MessageResponse::new_from_str("missing")).into_response();
-        assert_eq!(response.status(), StatusCode::NOT_FOUND);
-        assert_eq!(response_json(response).await["message"], "missing");
-    }
-
-    #[tokio::test]
-    async fn remove_item_response_maps_removed_and_missing_statuses() {
-        let item = Item {
-            id: 3456,
-            name: "removed".to_string(),
-        };
+        assert_eq!(response_json(response).await["name"], "created");
 
-        let response = RemoveItemResponse::Removed(item).into_response();
-        assert_eq!(response.status(), StatusCode::OK);
-        assert_eq!(response_json(response).await["name"], "removed");
+        let conflict: Result<Item, ApiError> = Err(ApiError::new(ErrorCode::Conflict, "exists"));
+        let response = Negotiated(ResponseFormat::Json, conflict).into_response();
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+        let body = response_json(response).await;
+        assert_eq!(body["code"], "conflict");
+        assert_eq!(body["message"], "exists");
 
-        let response = RemoveItemResponse::new_error("gone").into_response();
+        let missing: Result<Item, ApiError> = Err(ApiError::new(ErrorCode::NotFound, "missing"));
+        let response = Negotiated(ResponseFormat::Xml, missing).into_response();
         assert_eq!(response.status(), StatusCode::NOT_FOUND);
-        assert_eq!(response_json(response).await["message"], "gone");
+        assert_eq!(response.headers()[axum::http::header::CONTENT_TYPE], "application/xml");
     }
 
     #[tokio::test]
     async fn error_responses_map_to_expected_statuses() {
-        let response = AuthErrorResponse::new_from_str("bad key").into_response();
+        let response = ApiError::new(ErrorCode::Unauthorized, "bad key").into_response();
         assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
-        assert_eq!(response_json(response).await["message"], "bad key");
+        let body = response_json(response).await;
+        assert_eq!(body["code"], "unauthorized");
+        assert_eq!(body["message"], "bad key");
+        assert!(body.get("request_id").is_none());
+        assert!(body.get("fields").is_none());
 
-        let response = ServerError(anyhow::anyhow!("boom")).into_response();
+        let response = ApiError::from(anyhow::anyhow!("boom")).into_response();
         assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        let body = response_json(response).await;
+        assert_eq!(body["code"], "internal");
         assert!(
-            response_json(response)
-                .await
+            body["message"]
                 .as_str()
-                .expect("server error body should be a string")
+                .expect("message should be a string")
                 .contains("boom")
         );
     }
 
-    #[tokio::test]
-    async fn rejection_error_preserves_status_and_kind() {
-        let response = RejectionError {
-            status: StatusCode::BAD_REQUEST,
-            message: "malformed".to_string(),
-            rejection: "JsonSyntaxError".to_string(),
+    #[test]
+    fn error_codes_round_trip_through_status() {
+        for code in [
+            ErrorCode::BadRequest,
+            ErrorCode::Unauthorized,
+            ErrorCode::Forbidden,
+            ErrorCode::NotFound,
+            ErrorCode::MethodNotAllowed,
+            ErrorCode::Conflict,
+            ErrorCode::PreconditionFailed,
+            ErrorCode::PayloadTooLarge,
+            ErrorCode::UnsupportedMediaType,
+            ErrorCode::ValidationFailed,
+            ErrorCode::PreconditionRequired,
+            ErrorCode::RateLimited,
+            ErrorCode::Internal,
+            ErrorCode::Unavailable,
+        ] {
+            assert_eq!(ErrorCode::from_status(code.status()), code);
         }
-        .into_response();
-
-        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
-        let body = response_json(response).await;
-        assert_eq!(body["error"], "JsonSyntaxError");
-        assert_eq!(body["message"], "malformed");
+        assert_eq!(ErrorCode::from_status(StatusCode::IM_A_TEAPOT), ErrorCode::BadRequest);
+        assert_eq!(ErrorCode::from_status(StatusCode::BAD_GATEWAY), ErrorCode::Internal);
     }
 
     #[test]
@@ -424,7 +1098,7 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn json_rejection_conversion_labels_known_variants() {
+    async fn json_rejection_conversion_keeps_status() {
         let request = axum::http::Request::builder()
             .header("content-type", "application/json")
             .body(Body::from(r#"{"name":"broken",}"#))
@@ -432,10 +1106,115 @@ mod tests {
         let rejection = Json::<CreateItem>::from_request(request, &())
             .await
             .expect_err("malformed JSON should reject");
-        let rejection = RejectionError::from(rejection);
-        let response = rejection.into_response();
+        let response = ApiError::from(rejection).into_response();
 
         assert_eq!(response.status(), StatusCode::BAD_REQUEST);
-        assert_eq!(response_json(response).await["error"], "JsonSyntaxError");
+        assert_eq!(response_json(response).await["code"], "bad_request");
+    }
+
+    #[test]
+    fn create_item_validation_reports_every_violated_field() {
+        let valid = CreateItem {
+            name: "valid-name_1.0".to_string(),
+            id: Some(ItemId::Numeric(1234)),
+            tags: vec!["rust".to_string()],
+            metadata: HashMap::from([("owner".to_string(), "esgrove".to_string())]),
+            data: None,
+            ttl_seconds: Some(60),
+        };
+        assert!(valid.validate().is_empty());
+
+        let invalid = CreateItem {
+            name: "no spaces!".to_string(),
+            id: Some(ItemId::Numeric(1)),
+            tags: vec![String::new()],
+            metadata: HashMap::from([(String::new(), "value".to_string())]),
+            data: None,
+            ttl_seconds: Some(0),
+        };
+        let errors = invalid.validate();
+        let fields: Vec<&str> = errors.iter().map(|error| error.field.as_str()).collect();
+        assert_eq!(fields, vec!["name", "id", "ttl_seconds", "tags", "metadata"]);
+
+        let too_many_tags = CreateItem {
+            name: "tagged".to_string(),
+            tags: (0..=MAX_ITEM_TAGS).map(|index| format!("tag{index}")).collect(),
+            ..CreateItem::default()
+        };
+        assert_eq!(too_many_tags.validate()[0].message, "must have at most 16 tags");
+
+        let empty = CreateItem::default();
+        assert_eq!(empty.validate(), vec![FieldError::new("name", "must not be empty")]);
+
+        let long = CreateItem {
+            name: "a".repeat(MAX_ITEM_NAME_LENGTH + 1),
+            ..CreateItem::default()
+        };
+        assert_eq!(long.validate()[0].message, "must be at most 64 characters");
+    }
+
+    #[test]
+    fn item_name_violation_reports_specific_rule() {
+        assert_eq!(item_name_violation("valid-name_1.0"), None);
+        assert_eq!(item_name_violation("").as_deref(), Some("must not be empty"));
+        for name in ["no spaces", "tab\tname", "bell\u{7}", "ümläut!"] {
+            assert_eq!(
+                item_name_violation(name).as_deref(),
+                Some("may only contain letters, numbers, '-', '_' and '.'"),
+                "{name:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn data_size_is_compact_json_length() {
+        let item = CreateItem {
+            data: Some(serde_json::json!({"a": [1, 2]})),
+            ..CreateItem::default()
+        };
+
+        assert_eq!(item.data_size(), r#"{"a":[1,2]}"#.len());
+        assert_eq!(CreateItem::default().data_size(), 0);
+    }
+
+    #[tokio::test]
+    async fn validation_error_lists_fields() {
+        let response =
+            ApiError::validation(vec![FieldError::new("name", "bad"), FieldError::new("id", "bad")]).into_response();
+
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        let body = response_json(response).await;
+        assert_eq!(body["code"], "validation_failed");
+        assert_eq!(body["message"], "Invalid fields: name, id");
+        assert_eq!(body["fields"].as_array().map(Vec::len), Some(2));
+    }
+
+    proptest! {
+        #[test]
+        fn valid_payloads_become_items_that_keep_their_invariants(
+            name in "[A-Za-z0-9_.-]{1,32}",
+            id in prop::option::of(any::<u64>()),
+            tags in prop::collection::vec("[a-z]{1,32}", 0..=MAX_ITEM_TAGS),
+            ttl_seconds in prop::option::of(any::<u64>()),
+        ) {
+            let payload = CreateItem {
+                name,
+                id: id.map(ItemId::Numeric),
+                tags,
+                ttl_seconds,
+                ..CreateItem::default()
+            };
+            let errors = payload.validate();
+            let id_in_range = id.is_none_or(Item::is_valid_id);
+            let ttl_in_range = ttl_seconds.is_none_or(|ttl| (1..=MAX_ITEM_TTL_SECONDS).contains(&ttl));
+
+            prop_assert_eq!(errors.iter().any(|error| error.field == "id"), !id_in_range);
+            prop_assert_eq!(errors.iter().any(|error| error.field == "ttl_seconds"), !ttl_in_range);
+            if errors.is_empty() {
+                let item = payload.into_item(IdStrategy::Numeric, &AppState::new()).unwrap();
+                prop_assert_eq!(item.check_invariants(IdStrategy::Numeric), Ok(()));
+                prop_assert_eq!(item.expires_at.is_some(), ttl_seconds.is_some());
+            }
+        }
     }
 }
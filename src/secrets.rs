@@ -0,0 +1,220 @@
+//! Secret provider.
+//!
+//! Fetches secrets such as the api key from AWS Secrets Manager or SSM Parameter Store at startup,
+//! caches them, and refreshes them in the background so rotated secrets are picked up.
+//! Local runs read secrets from environment variables instead.
+
+use std::collections::HashMap;
+use std::env;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{Context, Result, anyhow};
+use arc_swap::ArcSwap;
+use tokio::task::JoinHandle;
+
+use crate::settings::{Settings, SettingsLoader, SharedSettings};
+use crate::types::{SecretsConfig, SecretsProvider};
+
+/// Cached secret values from the configured provider.
+#[derive(Debug)]
+pub struct SecretStore {
+    source: SecretSource,
+    /// Secret ids to fetch on every refresh.
+    ids: Vec<String>,
+    values: ArcSwap<HashMap<String, String>>,
+    refresh_interval: Duration,
+}
+
+/// Backend that secret values are fetched from.
+#[derive(Debug)]
+enum SecretSource {
+    Env,
+    SecretsManager(aws_sdk_secretsmanager::Client),
+    Ssm(aws_sdk_ssm::Client),
+}
+
+impl SecretStore {
+    /// Create a store for the configured provider and fetch every secret once.
+    ///
+    /// AWS credentials and region come from the standard AWS environment and config files.
+    /// Failing to fetch a secret here is an error, so the server does not start with a missing key.
+    pub async fn connect(config: &SecretsConfig) -> Result<Self> {
+        if config.provider != SecretsProvider::Env && config.api_key_id.is_none() {
+            anyhow::bail!(
+                "API_KEY_SECRET_ID is required for the {} secrets provider",
+                config.provider
+            );
+        }
+        let source = match config.provider {
+            SecretsProvider::Env => SecretSource::Env,
+            SecretsProvider::SecretsManager => {
+                SecretSource::SecretsManager(aws_sdk_secretsmanager::Client::new(&aws_config::load_from_env().await))
+            }
+            SecretsProvider::Ssm => SecretSource::Ssm(aws_sdk_ssm::Client::new(&aws_config::load_from_env().await)),
+        };
+
+        let ids: Vec<String> = config.api_key_id.iter().cloned().collect();
+        let mut values = HashMap::with_capacity(ids.len());
+        for id in &ids {
+            values.insert(id.clone(), source.fetch(id).await?);
+        }
+        Ok(Self {
+            source,
+            ids,
+            values: ArcSwap::from_pointee(values),
+            refresh_interval: Duration::from_secs(config.refresh_seconds),
+        })
+    }
+
+    /// Cached value for the secret id.
+    pub fn get(&self, id: &str) -> Option<String> {
+        self.values.load().get(id).cloned()
+    }
+
+    /// Replace settings values with their cached secrets.
+    pub fn apply(&self, settings: &mut Settings) {
+        if let Some(api_key) = settings.secrets.api_key_id.as_deref().and_then(|id| self.get(id)) {
+            settings.api_key = api_key;
+        }
+    }
+
+    /// Fetch every secret again and return whether any value changed.
+    ///
+    /// Secrets that fail to fetch are logged and keep their cached value.
+    pub async fn refresh(&self) -> bool {
+        let mut values = HashMap::clone(&self.values.load());
+        let mut changed = false;
+        for id in &self.ids {
+            match self.source.fetch(id).await {
+                Ok(value) => {
+                    if values.get(id) != Some(&value) {
+                        values.insert(id.clone(), value);
+                        changed = true;
+                    }
+                }
+                Err(error) => crate::log_error!("Keeping cached secret {id}: {error:#}"),
+            }
+        }
+        if changed {
+            self.values.store(Arc::new(values));
+        }
+        changed
+    }
+}
+
+impl SecretSource {
+    async fn fetch(&self, id: &str) -> Result<String> {
+        match self {
+            Self::Env => env::var(id).with_context(|| format!("Failed to read secret from env variable {id}")),
+            Self::SecretsManager(client) => client
+                .get_secret_value()
+                .secret_id(id)
+                .send()
+                .await
+                .with_context(|| format!("Failed to fetch secret {id} from Secrets Manager"))?
+                .secret_string()
+                .map(ToOwned::to_owned)
+                .ok_or_else(|| anyhow!("Secret {id} has no string value")),
+            Self::Ssm(client) => client
+                .get_parameter()
+                .name(id)
+                .with_decryption(true)
+                .send()
+                .await
+                .with_context(|| format!("Failed to fetch parameter {id} from SSM"))?
+                .parameter()
+                .and_then(|parameter| parameter.value())
+                .map(ToOwned::to_owned)
+                .ok_or_else(|| anyhow!("Parameter {id} has no value")),
+        }
+    }
+}
+
+/// Refresh secrets periodically and rebuild settings when a secret changes.
+///
+/// Returns `None` when there is nothing to refresh.
+pub fn spawn_refresh(
+    store: Arc<SecretStore>,
+    loader: SettingsLoader,
+    settings: SharedSettings,
+) -> Option<JoinHandle<()>> {
+    if store.ids.is_empty() || store.refresh_interval.is_zero() {
+        return None;
+    }
+    Some(tokio::spawn(async move {
+        let mut interval = tokio::time::interval(store.refresh_interval);
+        interval.tick().await;
+        loop {
+            interval.tick().await;
+            if store.refresh().await {
+                crate::log_info!("Secrets changed, reloading settings");
+                crate::settings::reload(&loader, &settings).ok();
+            }
+        }
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::settings::CliSettings;
+
+    fn env_store(values: &[(&str, &str)]) -> SecretStore {
+        SecretStore {
+            source: SecretSource::Env,
+            ids: values.iter().map(|(id, _)| (*id).to_string()).collect(),
+            values: ArcSwap::from_pointee(
+                values
+                    .iter()
+                    .map(|(id, value)| ((*id).to_string(), (*value).to_string()))
+                    .collect(),
+            ),
+            refresh_interval: Duration::from_secs(1),
+        }
+    }
+
+    #[tokio::test]
+    async fn env_provider_reads_env_variables() {
+        let store = SecretStore::connect(&SecretsConfig {
+            api_key_id: Some("PATH".to_string()),
+            ..SecretsConfig::default()
+        })
+        .await
+        .expect("PATH should be set");
+
+        assert_eq!(store.get("PATH"), env::var("PATH").ok());
+    }
+
+    #[tokio::test]
+    async fn aws_providers_require_a_secret_id() {
+        let config = SecretsConfig {
+            provider: SecretsProvider::Ssm,
+            ..SecretsConfig::default()
+        };
+
+        assert!(SecretStore::connect(&config).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn refresh_keeps_cached_value_on_error() {
+        let store = env_store(&[("AXUM_EXAMPLE_MISSING_SECRET", "cached")]);
+
+        assert!(!store.refresh().await);
+        assert_eq!(store.get("AXUM_EXAMPLE_MISSING_SECRET").as_deref(), Some("cached"));
+    }
+
+    #[test]
+    fn loader_applies_cached_api_key() {
+        let path = std::env::temp_dir().join(format!("axum-example-secrets-{}.toml", std::process::id()));
+        std::fs::write(&path, "[secrets]\napi_key_id = \"admin-api-key\"").expect("write config");
+        let store = Arc::new(env_store(&[("admin-api-key", "from-secret")]));
+        let loader = SettingsLoader::new(path.clone(), CliSettings::default()).with_secrets(store);
+
+        let settings = loader.load().expect("valid settings");
+
+        assert_eq!(settings.api_key, "from-secret");
+        std::fs::remove_file(&path).ok();
+    }
+}
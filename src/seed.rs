@@ -0,0 +1,176 @@
+//! Seed data loaded at startup.
+//!
+//! `seed_file` points to a JSON list of items, or a TOML file with `[[items]]` tables,
+//! where each item has the same fields as the `POST /items` body.
+//! Seeding only runs in local and development environments,
+//! so demo and test data never ends up in a shared deployment.
+
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+
+use crate::schemas::{CreateItem, FieldError};
+use crate::settings::Settings;
+use crate::types::{AppState, DuplicateItem, Environment};
+use crate::validation::Validate;
+
+/// TOML seed file layout, since a TOML document can not be a bare list.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SeedFile {
+    items: Vec<CreateItem>,
+}
+
+/// Load items from the configured seed file and return how many were added.
+///
+/// Does nothing without a seed file or outside local and development environments.
+/// An unreadable file or an invalid item is an error,
+/// so a broken fixture fails startup instead of leaving the data half loaded.
+pub fn load(state: &AppState, settings: &Settings) -> Result<usize> {
+    let Some(path) = &settings.seed_file else {
+        return Ok(0);
+    };
+    if !matches!(settings.env, Environment::Local | Environment::Development) {
+        crate::log_warn!(
+            "Ignoring seed file {} in the {} environment",
+            path.display(),
+            settings.env
+        );
+        return Ok(0);
+    }
+    let items = read(path)?;
+    let number_of_items = items.len();
+    insert(state, settings, items).with_context(|| format!("Invalid seed file {}", path.display()))?;
+    crate::log_info!("Loaded {number_of_items} items from seed file {}", path.display());
+    Ok(number_of_items)
+}
+
+/// Read seed items from a `.toml` file, or from JSON for any other extension.
+pub fn read(path: &Path) -> Result<Vec<CreateItem>> {
+    let contents =
+        std::fs::read_to_string(path).with_context(|| format!("Failed to read seed file {}", path.display()))?;
+    let is_toml = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("toml"));
+    let items = if is_toml {
+        toml::from_str::<SeedFile>(&contents)
+            .map(|file| file.items)
+            .map_err(anyhow::Error::from)
+    } else {
+        serde_json::from_str(&contents).map_err(anyhow::Error::from)
+    };
+    items.with_context(|| format!("Invalid seed file {}", path.display()))
+}
+
+/// Validate and add the items with the same rules as `POST /items`.
+fn insert(state: &AppState, settings: &Settings, items: Vec<CreateItem>) -> Result<()> {
+    for (index, payload) in items.into_iter().enumerate() {
+        let mut errors = payload.validate();
+        errors.extend(payload.settings_violations(settings));
+        if !errors.is_empty() {
+            anyhow::bail!("Item {index} ({}): {}", payload.name, describe(&errors));
+        }
+        let random_id = payload.id.is_none();
+        let mut item = payload.into_item(settings.id_strategy, state)?;
+        let inserted = if random_id {
+            state
+                .items()
+                .insert_item_with_random_id(&mut item, || state.generate_id(settings.id_strategy))
+        } else {
+            state.items().insert_item(&item)
+        };
+        match inserted {
+            Ok(()) => {}
+            Err(DuplicateItem::Name(name)) => anyhow::bail!("Item {index}: duplicate name {name}"),
+            Err(DuplicateItem::Id(id)) => anyhow::bail!("Item {index} ({}): duplicate id {id}", item.name),
+        }
+    }
+    Ok(())
+}
+
+fn describe(errors: &[FieldError]) -> String {
+    errors
+        .iter()
+        .map(|error| format!("{} {}", error.field, error.message))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::types::ItemId;
+
+    fn write_seed_file(name: &str, contents: &str) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(format!("axum-example-seed-{}-{name}", std::process::id()));
+        std::fs::write(&path, contents).expect("write seed file");
+        path
+    }
+
+    #[test]
+    fn loads_json_and_toml_seed_files() {
+        let json = write_seed_file(
+            "items.json",
+            r#"[{"name": "first", "id": 1234, "tags": ["demo"]}, {"name": "second"}]"#,
+        );
+        let toml = write_seed_file(
+            "items.toml",
+            "[[items]]\nname = \"third\"\nmetadata = { owner = \"esgrove\" }\n",
+        );
+
+        let state = AppState::new();
+        let settings = Settings {
+            seed_file: Some(json.clone()),
+            ..Settings::default()
+        };
+        assert_eq!(load(&state, &settings).unwrap(), 2);
+        assert_eq!(state.items().db.get("first").unwrap().id, ItemId::Numeric(1234));
+        assert_eq!(state.items().db.get("first").unwrap().tags, ["demo"]);
+
+        let settings = Settings {
+            seed_file: Some(toml.clone()),
+            ..Settings::default()
+        };
+        assert_eq!(load(&state, &settings).unwrap(), 1);
+        assert_eq!(state.items().db.get("third").unwrap().metadata["owner"], "esgrove");
+        assert_eq!(state.items().db.len(), 3);
+
+        std::fs::remove_file(json).ok();
+        std::fs::remove_file(toml).ok();
+    }
+
+    #[test]
+    fn seed_file_is_ignored_outside_local_and_development() {
+        let path = write_seed_file("ignored.json", r#"[{"name": "first"}]"#);
+        let state = AppState::new();
+        let settings = Settings {
+            seed_file: Some(path.clone()),
+            ..Settings::for_environment(Environment::Production)
+        };
+
+        assert_eq!(load(&state, &settings).unwrap(), 0);
+        assert!(state.items().db.is_empty());
+
+        std::fs::remove_file(path).ok();
+    }
+
+    #[test]
+    fn invalid_items_fail_seeding() {
+        let path = write_seed_file("invalid.json", r#"[{"name": "first"}, {"name": "first"}]"#);
+        let settings = Settings {
+            seed_file: Some(path.clone()),
+            ..Settings::default()
+        };
+        let error = load(&AppState::new(), &settings).unwrap_err();
+        assert!(format!("{error:#}").contains("duplicate name first"));
+
+        std::fs::write(&path, r#"[{"name": "bad name!"}]"#).expect("write seed file");
+        let error = load(&AppState::new(), &settings).unwrap_err();
+        assert!(format!("{error:#}").contains("Item 0 (bad name!)"));
+
+        std::fs::remove_file(path).ok();
+    }
+}
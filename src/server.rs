@@ -0,0 +1,575 @@
+//! HTTP server loop.
+//!
+//! Accepts TCP connections and serves the router through a hyper connection builder
+//! configured for the selected protocol,
+//! so the API can speak HTTP/1.1, HTTP/2, or cleartext HTTP/2 (h2c) with prior knowledge.
+//! `axum::serve` does not expose the hyper builder, which is why this module exists.
+//! Sockets passed in by `systemfd` or systemd socket activation are used instead of binding new ones,
+//! so `systemfd --no-pid -s http::3000 -- cargo watch -x run` restarts without "address in use" errors.
+//! With a TLS certificate and key, connections are served over rustls,
+//! and a separate plain HTTP listener can redirect clients to HTTPS.
+
+use std::future::Future;
+use std::net::SocketAddr;
+use std::path::Path;
+use std::pin::pin;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use axum::Router;
+use axum::body::Body;
+use axum::extract::ConnectInfo;
+use axum::http::header::HOST;
+use axum::http::uri::Authority;
+use axum::http::{Request, Uri};
+use axum::middleware::from_fn;
+use axum::response::{IntoResponse, Redirect, Response};
+use hyper::body::Incoming;
+use hyper_util::rt::{TokioExecutor, TokioIo, TokioTimer};
+use hyper_util::server::conn::auto::Builder;
+use hyper_util::server::graceful::GracefulShutdown;
+use hyper_util::service::TowerToHyperService;
+use listenfd::ListenFd;
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use tokio::net::{TcpListener, TcpSocket};
+use tokio_rustls::TlsAcceptor;
+use tokio_util::either::Either;
+use tower::ServiceExt;
+
+use crate::i18n;
+use crate::schemas::{ApiError, ErrorCode};
+use crate::types::{HttpProtocol, ServerConfig};
+use crate::{log_debug, log_warn};
+
+/// Delay before accepting again after a failed accept, for example when out of file descriptors.
+const ACCEPT_ERROR_BACKOFF: Duration = Duration::from_secs(1);
+
+/// Pending connections the kernel queues for a listener, same as `TcpListener::bind`.
+const LISTEN_BACKLOG: u32 = 1024;
+
+/// Time a client gets to finish the TLS handshake before the connection is closed.
+const TLS_HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Bind a listener to the address.
+///
+/// With `reuse_port`, the socket is bound with `SO_REUSEPORT`,
+/// so another process that also sets it can listen on the same port
+/// and the kernel spreads new connections between them.
+/// This lets a new version start next to the old one before the old one drains.
+/// `SO_REUSEPORT` is not available on Windows, where the option is ignored with a warning.
+pub fn bind(address: SocketAddr, reuse_port: bool) -> std::io::Result<TcpListener> {
+    let socket = if address.is_ipv4() {
+        TcpSocket::new_v4()?
+    } else {
+        TcpSocket::new_v6()?
+    };
+    #[cfg(unix)]
+    {
+        socket.set_reuseaddr(true)?;
+        socket.set_reuseport(reuse_port)?;
+    }
+    #[cfg(not(unix))]
+    if reuse_port {
+        log_warn!("SO_REUSEPORT is not supported on this platform, binding {address} without it");
+    }
+    socket.bind(address)?;
+    socket.listen(LISTEN_BACKLOG)
+}
+
+/// Take over the inherited socket at `index`, or bind a new listener to the address when there is none.
+///
+/// Inherited sockets come from the `LISTEN_FDS` environment variable set by `systemfd` or systemd,
+/// in the same order as the configured addresses.
+pub fn inherited_or_bind(
+    listen_fds: &mut ListenFd,
+    index: usize,
+    address: SocketAddr,
+    reuse_port: bool,
+) -> std::io::Result<TcpListener> {
+    match listen_fds.take_tcp_listener(index)? {
+        Some(listener) => {
+            listener.set_nonblocking(true)?;
+            TcpListener::from_std(listener)
+        }
+        None => bind(address, reuse_port),
+    }
+}
+
+/// Load the PEM certificate chain and private key for serving HTTPS.
+///
+/// ALPN offers the HTTP versions that the protocol accepts,
+/// so clients negotiate HTTP/2 as `h2` instead of needing prior knowledge.
+pub fn tls_acceptor(cert: &Path, key: &Path, protocol: HttpProtocol) -> Result<TlsAcceptor> {
+    let chain = CertificateDer::pem_file_iter(cert)
+        .and_then(Iterator::collect::<Result<Vec<_>, _>>)
+        .with_context(|| format!("Failed to read TLS certificates from {}", cert.display()))?;
+    anyhow::ensure!(!chain.is_empty(), "No TLS certificates in {}", cert.display());
+    let key = PrivateKeyDer::from_pem_file(key)
+        .with_context(|| format!("Failed to read TLS private key from {}", key.display()))?;
+    let mut config = rustls::ServerConfig::builder()
+        .with_no_client_auth()
+        .with_single_cert(chain, key)
+        .context("TLS private key does not match the certificate")?;
+    config.alpn_protocols = match protocol {
+        HttpProtocol::Auto => vec![b"h2".to_vec(), b"http/1.1".to_vec()],
+        HttpProtocol::Http1 => vec![b"http/1.1".to_vec()],
+        HttpProtocol::Http2 => vec![b"h2".to_vec()],
+    };
+    Ok(TlsAcceptor::from(Arc::new(config)))
+}
+
+/// Router that answers every request with a permanent redirect to the same URL over HTTPS.
+///
+/// The redirect keeps the host from the request and points to `https_port`,
+/// and 308 makes clients repeat the method and body instead of switching to GET.
+pub fn https_redirect(https_port: u16) -> Router {
+    Router::new()
+        .fallback(move |request: Request<Body>| async move {
+            redirect_to_https(request.headers().get(HOST), request.uri(), https_port)
+        })
+        .layer(from_fn(i18n::language_middleware))
+}
+
+/// Redirect response to the HTTPS URL of the request.
+fn redirect_to_https(host: Option<&axum::http::HeaderValue>, uri: &Uri, https_port: u16) -> Response {
+    let authority = host
+        .and_then(|host| host.to_str().ok())
+        .and_then(|host| host.parse::<Authority>().ok())
+        .or_else(|| uri.authority().cloned());
+    let Some(authority) = authority else {
+        return ApiError::new(ErrorCode::BadRequest, i18n::message("host_header_missing", &[])).into_response();
+    };
+    let port = if https_port == 443 {
+        String::new()
+    } else {
+        format!(":{https_port}")
+    };
+    let path = uri.path_and_query().map_or("/", |path| path.as_str());
+    Redirect::permanent(&format!("https://{}{port}{path}", authority.host())).into_response()
+}
+
+/// Serve the router on the listener until the shutdown signal resolves.
+///
+/// Each request gets the peer address as a `ConnectInfo<SocketAddr>` extension.
+/// With a TLS acceptor, every connection does the TLS handshake before its requests are read.
+/// After the signal, no new connections are accepted
+/// and open connections are shut down gracefully before this returns,
+/// waiting at most `shutdown_timeout_seconds` for their requests to finish.
+pub async fn serve<F>(
+    listener: TcpListener,
+    app: Router,
+    protocol: HttpProtocol,
+    config: ServerConfig,
+    tls: Option<TlsAcceptor>,
+    shutdown_signal: F,
+) -> Result<()>
+where
+    F: Future<Output = ()>,
+{
+    let builder = Arc::new(connection_builder(protocol, config));
+    let graceful = GracefulShutdown::new();
+    let mut shutdown_signal = pin!(shutdown_signal);
+
+    loop {
+        let (stream, remote_address) = tokio::select! {
+            accepted = listener.accept() => match accepted {
+                Ok(connection) => connection,
+                Err(error) => {
+                    log_warn!(error = %error, "failed to accept connection");
+                    tokio::time::sleep(ACCEPT_ERROR_BACKOFF).await;
+                    continue;
+                }
+            },
+            () = &mut shutdown_signal => break,
+        };
+
+        let service = app.clone().map_request(move |request: Request<Incoming>| {
+            let mut request = request.map(Body::new);
+            request.extensions_mut().insert(ConnectInfo(remote_address));
+            request
+        });
+        let builder = Arc::clone(&builder);
+        let watcher = graceful.watcher();
+        let tls = tls.clone();
+        tokio::spawn(async move {
+            let stream = match tls {
+                None => Either::Left(stream),
+                Some(acceptor) => match tokio::time::timeout(TLS_HANDSHAKE_TIMEOUT, acceptor.accept(stream)).await {
+                    Ok(Ok(stream)) => Either::Right(stream),
+                    Ok(Err(error)) => {
+                        log_debug!("TLS handshake with {remote_address} failed: {error}");
+                        return;
+                    }
+                    Err(_) => {
+                        log_debug!("TLS handshake with {remote_address} timed out");
+                        return;
+                    }
+                },
+            };
+            let connection = builder.serve_connection(TokioIo::new(stream), TowerToHyperService::new(service));
+            if let Err(error) = watcher.watch(connection).await {
+                log_debug!("failed to serve connection from {remote_address}: {error:#}");
+            }
+        });
+    }
+
+    drop(listener);
+    let timeout = config.shutdown_timeout();
+    if tokio::time::timeout(timeout, graceful.shutdown()).await.is_err() {
+        log_warn!(
+            "Connections still open {} seconds after shutdown, closing them",
+            timeout.as_secs()
+        );
+    }
+    Ok(())
+}
+
+/// Build the hyper connection builder for the selected protocol and tuning.
+fn connection_builder(protocol: HttpProtocol, config: ServerConfig) -> Builder<TokioExecutor> {
+    let mut builder = Builder::new(TokioExecutor::new());
+    // Header read timeouts and keep-alive pings need a timer to take effect.
+    builder
+        .http1()
+        .timer(TokioTimer::new())
+        .keep_alive(config.http1_keep_alive)
+        .header_read_timeout(config.header_read_timeout());
+    builder
+        .http2()
+        .timer(TokioTimer::new())
+        .keep_alive_interval(config.http2_keep_alive_interval())
+        .keep_alive_timeout(config.http2_keep_alive_timeout())
+        .max_concurrent_streams(config.http2_max_concurrent_streams);
+    match protocol {
+        HttpProtocol::Auto => builder,
+        HttpProtocol::Http1 => builder.http1_only(),
+        HttpProtocol::Http2 => builder.http2_only(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::path::PathBuf;
+
+    use axum::http::header::LOCATION;
+    use axum::http::{StatusCode, Version};
+    use axum::routing::get;
+    use http_body_util::{BodyExt, Empty};
+    use hyper::body::Bytes;
+    use rustls::pki_types::ServerName;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpStream;
+    use tokio::sync::oneshot;
+
+    async fn spawn_server(protocol: HttpProtocol) -> (SocketAddr, oneshot::Sender<()>) {
+        spawn_server_with_config(protocol, ServerConfig::default()).await
+    }
+
+    async fn spawn_server_with_config(
+        protocol: HttpProtocol,
+        config: ServerConfig,
+    ) -> (SocketAddr, oneshot::Sender<()>) {
+        spawn_server_with_tls(protocol, config, None).await
+    }
+
+    async fn spawn_server_with_tls(
+        protocol: HttpProtocol,
+        config: ServerConfig,
+        tls: Option<TlsAcceptor>,
+    ) -> (SocketAddr, oneshot::Sender<()>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.expect("listener should bind");
+        let address = listener.local_addr().expect("listener should have an address");
+        let app = Router::new().route(
+            "/",
+            get(
+                |ConnectInfo(peer): ConnectInfo<SocketAddr>, request: Request<Body>| async move {
+                    format!("{:?} {}", request.version(), peer.ip().is_loopback())
+                },
+            ),
+        );
+        let (shutdown_sender, shutdown_receiver) = oneshot::channel::<()>();
+        tokio::spawn(serve(listener, app, protocol, config, tls, async {
+            shutdown_receiver.await.ok();
+        }));
+        (address, shutdown_sender)
+    }
+
+    /// Self-signed certificate for `localhost` written to PEM files, and the certificate for the client to trust.
+    fn self_signed_certificate(name: &str) -> (PathBuf, PathBuf, CertificateDer<'static>) {
+        let certified = rcgen::generate_simple_self_signed(["localhost".to_string()]).unwrap();
+        let directory = std::env::temp_dir().join(format!("axum-example-tls-{name}-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let cert = directory.join("cert.pem");
+        let key = directory.join("key.pem");
+        std::fs::write(&cert, certified.cert.pem()).unwrap();
+        std::fs::write(&key, certified.signing_key.serialize_pem()).unwrap();
+        (cert, key, certified.cert.der().clone())
+    }
+
+    /// Connect over TLS trusting only the certificate, offering the ALPN protocol.
+    async fn tls_connect(
+        address: SocketAddr,
+        trusted: CertificateDer<'static>,
+        alpn: &[u8],
+    ) -> tokio_rustls::client::TlsStream<TcpStream> {
+        let mut roots = rustls::RootCertStore::empty();
+        roots.add(trusted).unwrap();
+        let mut config = rustls::ClientConfig::builder()
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+        config.alpn_protocols = vec![alpn.to_vec()];
+        let stream = TcpStream::connect(address).await.expect("client should connect");
+        tokio_rustls::TlsConnector::from(Arc::new(config))
+            .connect(ServerName::try_from("localhost").unwrap(), stream)
+            .await
+            .expect("TLS handshake should succeed")
+    }
+
+    async fn http1_request(address: SocketAddr) -> String {
+        let mut stream = TcpStream::connect(address).await.expect("client should connect");
+        stream
+            .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+            .await
+            .expect("request should write");
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.ok();
+        response
+    }
+
+    #[tokio::test]
+    async fn binds_the_address_without_inherited_sockets() {
+        let address: SocketAddr = "127.0.0.1:0".parse().unwrap();
+        let listener = inherited_or_bind(&mut ListenFd::empty(), 0, address, false).expect("listener should bind");
+
+        let bound = listener.local_addr().expect("listener should have an address");
+        assert!(bound.ip().is_loopback());
+        assert_ne!(bound.port(), 0);
+    }
+
+    #[tokio::test]
+    async fn auto_protocol_serves_http1_with_connect_info() {
+        let (address, _shutdown) = spawn_server(HttpProtocol::Auto).await;
+
+        let response = http1_request(address).await;
+
+        assert!(
+            response.starts_with("HTTP/1.1 200 OK"),
+            "unexpected response: {response}"
+        );
+        assert!(response.ends_with("HTTP/1.1 true"), "unexpected body: {response}");
+    }
+
+    #[tokio::test]
+    async fn http2_protocol_serves_h2c_with_prior_knowledge() {
+        let (address, _shutdown) = spawn_server(HttpProtocol::Http2).await;
+
+        let stream = TcpStream::connect(address).await.expect("client should connect");
+        let (mut sender, connection) =
+            hyper::client::conn::http2::handshake(TokioExecutor::new(), TokioIo::new(stream))
+                .await
+                .expect("h2c handshake should succeed");
+        tokio::spawn(connection);
+
+        let request = Request::builder()
+            .uri(format!("http://{address}/"))
+            .body(Empty::<Bytes>::new())
+            .expect("request should build");
+        let response = sender.send_request(request).await.expect("request should succeed");
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.version(), Version::HTTP_2);
+        let body = response
+            .into_body()
+            .collect()
+            .await
+            .expect("body should collect")
+            .to_bytes();
+        assert_eq!(body, "HTTP/2.0 true");
+    }
+
+    #[tokio::test]
+    async fn http2_protocol_rejects_http1_requests() {
+        let (address, _shutdown) = spawn_server(HttpProtocol::Http2).await;
+
+        let response = http1_request(address).await;
+
+        assert!(!response.starts_with("HTTP/1.1 200"), "unexpected response: {response}");
+    }
+
+    #[tokio::test]
+    async fn tuning_closes_idle_and_non_keep_alive_connections() {
+        let config = ServerConfig {
+            http1_keep_alive: false,
+            header_read_timeout_seconds: Some(1),
+            ..ServerConfig::default()
+        };
+        let (address, _shutdown) = spawn_server_with_config(HttpProtocol::Http1, config).await;
+
+        // Without keep-alive the server closes the connection after the response.
+        let mut stream = TcpStream::connect(address).await.expect("client should connect");
+        stream
+            .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n")
+            .await
+            .expect("request should write");
+        let mut response = String::new();
+        tokio::time::timeout(Duration::from_secs(5), stream.read_to_string(&mut response))
+            .await
+            .expect("connection should close")
+            .ok();
+        assert!(
+            response.starts_with("HTTP/1.1 200 OK"),
+            "unexpected response: {response}"
+        );
+
+        // A connection that never sends headers is closed after the header read timeout.
+        let mut stream = TcpStream::connect(address).await.expect("client should connect");
+        let mut buffer = Vec::new();
+        tokio::time::timeout(Duration::from_secs(5), stream.read_to_end(&mut buffer))
+            .await
+            .expect("idle connection should close")
+            .ok();
+    }
+
+    #[tokio::test]
+    async fn tls_serves_http1_after_the_handshake() {
+        let (cert, key, trusted) = self_signed_certificate("http1");
+        let acceptor = tls_acceptor(&cert, &key, HttpProtocol::Auto).expect("certificate and key should load");
+        let (address, _shutdown) =
+            spawn_server_with_tls(HttpProtocol::Auto, ServerConfig::default(), Some(acceptor)).await;
+
+        let mut stream = tls_connect(address, trusted, b"http/1.1").await;
+        assert_eq!(stream.get_ref().1.alpn_protocol(), Some(&b"http/1.1"[..]));
+        stream
+            .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+            .await
+            .expect("request should write");
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.ok();
+
+        assert!(
+            response.starts_with("HTTP/1.1 200 OK"),
+            "unexpected response: {response}"
+        );
+        assert!(response.ends_with("HTTP/1.1 true"), "unexpected body: {response}");
+        // Plain HTTP on the TLS port gets no response.
+        assert!(!http1_request(address).await.starts_with("HTTP/1.1 200"));
+    }
+
+    #[tokio::test]
+    async fn tls_negotiates_http2_with_alpn() {
+        let (cert, key, trusted) = self_signed_certificate("http2");
+        let acceptor = tls_acceptor(&cert, &key, HttpProtocol::Auto).expect("certificate and key should load");
+        let (address, _shutdown) =
+            spawn_server_with_tls(HttpProtocol::Auto, ServerConfig::default(), Some(acceptor)).await;
+
+        let stream = tls_connect(address, trusted, b"h2").await;
+        assert_eq!(stream.get_ref().1.alpn_protocol(), Some(&b"h2"[..]));
+        let (mut sender, connection) =
+            hyper::client::conn::http2::handshake(TokioExecutor::new(), TokioIo::new(stream))
+                .await
+                .expect("h2 handshake should succeed");
+        tokio::spawn(connection);
+        let request = Request::builder()
+            .uri(format!("https://localhost:{}/", address.port()))
+            .body(Empty::<Bytes>::new())
+            .expect("request should build");
+        let response = sender.send_request(request).await.expect("request should succeed");
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.version(), Version::HTTP_2);
+    }
+
+    #[test]
+    fn tls_acceptor_rejects_a_key_that_does_not_match() {
+        let (cert, _, _) = self_signed_certificate("mismatch-cert");
+        let (_, key, _) = self_signed_certificate("mismatch-key");
+
+        let error = tls_acceptor(&cert, &key, HttpProtocol::Auto)
+            .err()
+            .expect("key belongs to another certificate");
+        assert_eq!(error.to_string(), "TLS private key does not match the certificate");
+        let error = tls_acceptor(&key, &key, HttpProtocol::Auto)
+            .err()
+            .expect("key file has no certificates");
+        assert!(error.to_string().starts_with("No TLS certificates in"), "{error}");
+    }
+
+    #[tokio::test]
+    async fn plain_http_is_redirected_to_https() {
+        let redirect = |host: Option<&str>, port: u16, uri: &str| {
+            let mut request = Request::builder().uri(uri);
+            if let Some(host) = host {
+                request = request.header(HOST, host);
+            }
+            https_redirect(port).oneshot(request.body(Body::empty()).unwrap())
+        };
+
+        let response = redirect(Some("example.com:8080"), 8443, "/api/v1/items?limit=1")
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::PERMANENT_REDIRECT);
+        assert_eq!(
+            response.headers()[LOCATION],
+            "https://example.com:8443/api/v1/items?limit=1"
+        );
+
+        let response = redirect(Some("[::1]"), 443, "/").await.unwrap();
+        assert_eq!(response.headers()[LOCATION], "https://[::1]/");
+
+        let response = redirect(None, 443, "http://example.com/version").await.unwrap();
+        assert_eq!(response.headers()[LOCATION], "https://example.com/version");
+
+        let response = redirect(None, 443, "/version").await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn serve_returns_after_shutdown_signal() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.expect("listener should bind");
+
+        let result = tokio::time::timeout(
+            Duration::from_secs(5),
+            serve(
+                listener,
+                Router::new(),
+                HttpProtocol::Auto,
+                ServerConfig::default(),
+                None,
+                async {},
+            ),
+        )
+        .await
+        .expect("serve should stop after the shutdown signal");
+
+        assert!(result.is_ok());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn reuse_port_lets_two_listeners_share_a_port() {
+        let first = bind("127.0.0.1:0".parse().unwrap(), true).expect("listener should bind");
+        let address = first.local_addr().expect("listener should have an address");
+        let second = bind(address, true).expect("second listener should share the port");
+        assert_eq!(second.local_addr().unwrap(), address);
+        assert!(bind(address, false).is_err());
+
+        // The old listener stops and the new one keeps serving.
+        drop(first);
+        let app = Router::new().route("/", get(|| async { "new" }));
+        let (_shutdown_sender, shutdown_receiver) = oneshot::channel::<()>();
+        tokio::spawn(serve(
+            second,
+            app,
+            HttpProtocol::Http1,
+            ServerConfig::default(),
+            None,
+            async {
+                shutdown_receiver.await.ok();
+            },
+        ));
+        assert!(http1_request(address).await.ends_with("new"));
+    }
+}
@@ -0,0 +1,998 @@
+//! Layered application settings.
+//!
+//! `Settings` is the single source of configuration for the server, handlers and middleware.
+//! It is built by merging defaults, the config file, environment variables and CLI arguments,
+//! and rebuilt whenever the config file changes.
+
+use std::env;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+use arc_swap::ArcSwap;
+use figment::providers::Serialized;
+use figment::value::{Dict, Map};
+use figment::{Figment, Metadata, Profile, Provider};
+use notify::RecommendedWatcher;
+use serde::{Deserialize, Serialize};
+use strum::IntoEnumIterator;
+
+use crate::file_config;
+use crate::schemas::{DEFAULT_MAX_ITEM_DATA_BYTES, SettingChange};
+use crate::secrets::SecretStore;
+use crate::types::{
+    BackendConfig, BackupConfig, BodyLoggingConfig, DEFAULT_API_KEY, DeterministicConfig, DocsUi, Environment,
+    ErrorFormat, EvictionConfig, HttpProtocol, IdStrategy, IpFilterConfig, KafkaConfig, LogFileConfig, LogFormat,
+    MqttConfig, NatsConfig, QuotaConfig, RateLimitConfig, ReplicationConfig, SecretsConfig, ServerConfig, SqsConfig,
+    StorageConfig, TasksConfig, TlsConfig,
+};
+use crate::validation::NamePattern;
+
+/// Settings shared with handlers and middleware.
+///
+/// The config file watcher swaps in new settings when the file changes,
+/// so read them with `load()` for every use instead of holding on to a copy.
+pub type SharedSettings = Arc<ArcSwap<Settings>>;
+
+/// Placeholder shown instead of secret values.
+const REDACTED: &str = "<redacted>";
+
+/// Settings that are only read at startup, so changing them needs a restart.
+/// Nested keys under a listed table, such as `storage.initial_capacity`, are startup-only as well.
+const STARTUP_ONLY_KEYS: [&str; 22] = [
+    "host",
+    "port",
+    "http",
+    "server",
+    "tls",
+    "secrets",
+    "seed_file",
+    "record_file",
+    "state_file",
+    "storage",
+    "backend",
+    "kafka.brokers",
+    "nats.url",
+    "mqtt.host",
+    "mqtt.port",
+    "mqtt.client_id",
+    "sqs.queue_url",
+    "docs_uis",
+    "log_routes",
+    "log_format",
+    "log_file",
+    "deterministic",
+];
+
+/// Environment variables and the settings key they set.
+const ENV_VARIABLES: [(&str, &str, EnvValue); 73] = [
+    ("API_KEY", "api_key", EnvValue::String),
+    ("HOST", "host", EnvValue::List),
+    ("PORT", "port", EnvValue::Integer),
+    ("HTTP_PROTOCOL", "http", EnvValue::String),
+    ("HTTP1_KEEP_ALIVE", "server.http1_keep_alive", EnvValue::Boolean),
+    (
+        "HEADER_READ_TIMEOUT_SECONDS",
+        "server.header_read_timeout_seconds",
+        EnvValue::Integer,
+    ),
+    (
+        "HTTP2_KEEP_ALIVE_INTERVAL_SECONDS",
+        "server.http2_keep_alive_interval_seconds",
+        EnvValue::Integer,
+    ),
+    (
+        "HTTP2_KEEP_ALIVE_TIMEOUT_SECONDS",
+        "server.http2_keep_alive_timeout_seconds",
+        EnvValue::Integer,
+    ),
+    (
+        "HTTP2_MAX_CONCURRENT_STREAMS",
+        "server.http2_max_concurrent_streams",
+        EnvValue::Integer,
+    ),
+    (
+        "SHUTDOWN_TIMEOUT_SECONDS",
+        "server.shutdown_timeout_seconds",
+        EnvValue::Integer,
+    ),
+    (
+        "HARD_SHUTDOWN_TIMEOUT_SECONDS",
+        "server.hard_shutdown_timeout_seconds",
+        EnvValue::Integer,
+    ),
+    ("REUSE_PORT", "server.reuse_port", EnvValue::Boolean),
+    ("TLS_CERT", "tls.cert", EnvValue::String),
+    ("TLS_KEY", "tls.key", EnvValue::String),
+    ("TLS_REDIRECT_PORT", "tls.redirect_port", EnvValue::Integer),
+    (
+        "RATE_LIMIT_PER_SECOND",
+        "rate_limit.requests_per_second",
+        EnvValue::Integer,
+    ),
+    ("RATE_LIMIT_BURST", "rate_limit.burst", EnvValue::Integer),
+    ("CORS_ORIGINS", "cors_origins", EnvValue::List),
+    ("ITEM_ID_STRATEGY", "id_strategy", EnvValue::String),
+    ("ITEM_DATA_MAX_BYTES", "max_item_data_bytes", EnvValue::Integer),
+    ("ITEM_NAME_PATTERN", "item_name_pattern", EnvValue::String),
+    ("ADMIN_ALLOWED_NETWORKS", "admin_access.allowed", EnvValue::List),
+    ("ADMIN_DENIED_NETWORKS", "admin_access.denied", EnvValue::List),
+    ("TRUSTED_PROXIES", "admin_access.trusted_proxies", EnvValue::List),
+    ("SECRETS_PROVIDER", "secrets.provider", EnvValue::String),
+    ("API_KEY_SECRET_ID", "secrets.api_key_id", EnvValue::String),
+    ("SECRETS_REFRESH_SECONDS", "secrets.refresh_seconds", EnvValue::Integer),
+    ("DRAIN_DELAY_SECONDS", "drain_delay_seconds", EnvValue::Integer),
+    ("SEED_FILE", "seed_file", EnvValue::String),
+    ("RECORD_FILE", "record_file", EnvValue::String),
+    ("STATE_FILE", "state_file", EnvValue::String),
+    ("BACKUP_DIRECTORY", "backup.directory", EnvValue::String),
+    ("BACKUP_INTERVAL_SECONDS", "tasks.backup.schedule", EnvValue::Integer),
+    ("BACKUP_RETENTION", "backup.retention", EnvValue::Integer),
+    (
+        "STORAGE_INITIAL_CAPACITY",
+        "storage.initial_capacity",
+        EnvValue::Integer,
+    ),
+    ("STORAGE_SHARD_AMOUNT", "storage.shard_amount", EnvValue::Integer),
+    ("QUOTA_MAX_ITEMS", "quota.max_items", EnvValue::Integer),
+    ("QUOTA_MAX_DATA_BYTES", "quota.max_data_bytes", EnvValue::Integer),
+    (
+        "EVICTION_MAX_AGE_SECONDS",
+        "eviction.max_age_seconds",
+        EnvValue::Integer,
+    ),
+    (
+        "EVICTION_MAX_IDLE_SECONDS",
+        "eviction.max_idle_seconds",
+        EnvValue::Integer,
+    ),
+    ("EVICTION_MAX_ITEMS", "eviction.max_items", EnvValue::Integer),
+    ("BACKEND_PROVIDER", "backend.provider", EnvValue::String),
+    ("BACKEND_DIRECTORY", "backend.directory", EnvValue::String),
+    ("BACKEND_TABLE", "backend.table", EnvValue::String),
+    (
+        "BACKEND_CACHE_TTL_SECONDS",
+        "backend.cache_ttl_seconds",
+        EnvValue::Integer,
+    ),
+    ("REPLICATION_PEERS", "replication.peers", EnvValue::List),
+    ("KAFKA_BROKERS", "kafka.brokers", EnvValue::List),
+    ("KAFKA_TOPIC", "kafka.topic", EnvValue::String),
+    ("NATS_URL", "nats.url", EnvValue::String),
+    ("NATS_SUBJECT_PREFIX", "nats.subject_prefix", EnvValue::String),
+    ("MQTT_HOST", "mqtt.host", EnvValue::String),
+    ("MQTT_PORT", "mqtt.port", EnvValue::Integer),
+    ("MQTT_CLIENT_ID", "mqtt.client_id", EnvValue::String),
+    ("MQTT_TOPIC", "mqtt.topic", EnvValue::String),
+    ("MQTT_TOPIC_PER_ITEM", "mqtt.topic_per_item", EnvValue::Boolean),
+    ("SQS_QUEUE_URL", "sqs.queue_url", EnvValue::String),
+    ("RESPONSE_CACHE_TTL_MS", "response_cache_ttl_ms", EnvValue::Integer),
+    ("SLOW_REQUEST_MS", "slow_request_ms", EnvValue::Integer),
+    ("DOCS_REQUIRE_AUTH", "docs_require_auth", EnvValue::Boolean),
+    ("DOCS_UIS", "docs_uis", EnvValue::List),
+    ("LOG_ROUTES", "log_routes", EnvValue::Boolean),
+    ("LOG_FORMAT", "log_format", EnvValue::String),
+    ("LOG_FILE_DIRECTORY", "log_file.directory", EnvValue::String),
+    ("LOG_FILE_ROTATION", "log_file.rotation", EnvValue::String),
+    ("LOG_FILE_MAX_FILES", "log_file.max_files", EnvValue::Integer),
+    ("BODY_LOGGING_ENABLED", "body_logging.enabled", EnvValue::Boolean),
+    ("BODY_LOGGING_MAX_BYTES", "body_logging.max_bytes", EnvValue::Integer),
+    (
+        "BODY_LOGGING_REDACT_FIELDS",
+        "body_logging.redact_fields",
+        EnvValue::List,
+    ),
+    ("ERROR_FORMAT", "error_format", EnvValue::String),
+    ("RESPONSE_ENVELOPE", "response_envelope", EnvValue::Boolean),
+    ("TENANTS", "tenants", EnvValue::List),
+    ("DETERMINISTIC_SEED", "deterministic.seed", EnvValue::Integer),
+    ("DETERMINISTIC_TIME", "deterministic.time", EnvValue::String),
+];
+
+/// Effective application settings.
+///
+/// Built from layers where each layer overrides the ones before it:
+///
+/// 1. defaults for the runtime environment
+/// 2. the config file, with the table for the runtime environment merged over the base values
+/// 3. environment variables
+/// 4. CLI arguments
+///
+/// Values fetched from the secret provider then replace the settings they are configured for.
+/// The runtime environment only comes from `API_ENV`,
+/// since it decides both the defaults and the config file table.
+/// Listen addresses, protocol, server tuning, TLS, the secret provider, the seed, record and state files,
+/// storage sizing, the backend, the Kafka brokers, the NATS server, the MQTT broker and client id, the SQS queue,
+/// documentation UIs, the log format and file and the deterministic mode
+/// are read once at startup,
+/// everything else applies to running requests when the config file changes.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Settings {
+    /// Runtime environment from `API_ENV`.
+    pub env: Environment,
+    /// Api key for admin routes, `API_KEY`.
+    pub api_key: String,
+    /// Host IPs to listen to, `HOST` or `--host`. Empty listens on localhost.
+    pub host: Vec<String>,
+    /// Port to listen to, `PORT` or `--port`.
+    pub port: u16,
+    /// HTTP protocol versions to accept, `HTTP_PROTOCOL` or `--http`.
+    pub http: HttpProtocol,
+    /// Connection keep-alive, timeouts and HTTP/2 stream limits,
+    /// `HTTP1_KEEP_ALIVE`, `HEADER_READ_TIMEOUT_SECONDS`, `HTTP2_KEEP_ALIVE_INTERVAL_SECONDS`,
+    /// `HTTP2_KEEP_ALIVE_TIMEOUT_SECONDS`, `HTTP2_MAX_CONCURRENT_STREAMS` and `SHUTDOWN_TIMEOUT_SECONDS`.
+    pub server: ServerConfig,
+    /// Certificate and key that switch the listeners to HTTPS, and the port that redirects plain HTTP to it,
+    /// `TLS_CERT` or `--tls-cert`, `TLS_KEY` or `--tls-key`, and `TLS_REDIRECT_PORT`.
+    pub tls: TlsConfig,
+    /// Per-client request throttling, `RATE_LIMIT_PER_SECOND` and `RATE_LIMIT_BURST`.
+    pub rate_limit: RateLimitConfig,
+    /// Origins allowed to make cross-origin requests, `CORS_ORIGINS`. `*` allows any origin.
+    pub cors_origins: Vec<String>,
+    /// How new item ids are generated and validated, `ITEM_ID_STRATEGY`.
+    pub id_strategy: IdStrategy,
+    /// Largest accepted item `data` document as serialized JSON, `ITEM_DATA_MAX_BYTES`.
+    pub max_item_data_bytes: usize,
+    /// Regular expression that new item names must fully match on top of the built-in rules,
+    /// `ITEM_NAME_PATTERN`.
+    pub item_name_pattern: Option<NamePattern>,
+    /// Network restrictions for admin routes,
+    /// `ADMIN_ALLOWED_NETWORKS`, `ADMIN_DENIED_NETWORKS` and `TRUSTED_PROXIES`.
+    pub admin_access: IpFilterConfig,
+    /// Secret provider for the api key,
+    /// `SECRETS_PROVIDER`, `API_KEY_SECRET_ID` and `SECRETS_REFRESH_SECONDS`.
+    pub secrets: SecretsConfig,
+    /// Seconds between `POST /admin/drain` or a termination signal and closing the listeners, `DRAIN_DELAY_SECONDS`.
+    pub drain_delay_seconds: u64,
+    /// JSON or TOML file with items to load at startup in local and development environments, `SEED_FILE`.
+    pub seed_file: Option<PathBuf>,
+    /// File that every request is appended to for the `replay` subcommand, `RECORD_FILE`.
+    pub record_file: Option<PathBuf>,
+    /// Snapshot file that the items are saved to on shutdown and loaded from at startup, `STATE_FILE`.
+    pub state_file: Option<PathBuf>,
+    /// Backup directory and retention, `BACKUP_DIRECTORY` and `BACKUP_RETENTION`.
+    pub backup: BackupConfig,
+    /// Enable flags, schedules and jitter of the background tasks.
+    /// `BACKUP_INTERVAL_SECONDS` sets the backup task schedule.
+    pub tasks: TasksConfig,
+    /// Item map sizing, `STORAGE_INITIAL_CAPACITY` and `STORAGE_SHARD_AMOUNT`.
+    pub storage: StorageConfig,
+    /// Item count and data size limits of each item store, `QUOTA_MAX_ITEMS` and `QUOTA_MAX_DATA_BYTES`.
+    pub quota: QuotaConfig,
+    /// Largest age, idle time and number of items in every item store,
+    /// `EVICTION_MAX_AGE_SECONDS`, `EVICTION_MAX_IDLE_SECONDS` and `EVICTION_MAX_ITEMS`.
+    pub eviction: EvictionConfig,
+    /// External store behind the default collection,
+    /// `BACKEND_PROVIDER`, `BACKEND_DIRECTORY`, `BACKEND_TABLE` and `BACKEND_CACHE_TTL_SECONDS`.
+    pub backend: BackendConfig,
+    /// Peer instances that item changes are sent to, `REPLICATION_PEERS`.
+    pub replication: ReplicationConfig,
+    /// Kafka brokers and topic that item events are produced to, `KAFKA_BROKERS` and `KAFKA_TOPIC`.
+    pub kafka: KafkaConfig,
+    /// NATS server and subject prefix that item events are published to, `NATS_URL` and `NATS_SUBJECT_PREFIX`.
+    pub nats: NatsConfig,
+    /// MQTT broker and topics that item events are published to,
+    /// `MQTT_HOST`, `MQTT_PORT`, `MQTT_CLIENT_ID`, `MQTT_TOPIC` and `MQTT_TOPIC_PER_ITEM`.
+    pub mqtt: MqttConfig,
+    /// SQS queue that item commands are consumed from with the `sqs` cargo feature, `SQS_QUEUE_URL`.
+    pub sqs: SqsConfig,
+    /// Milliseconds that `GET /items` and `GET /item` responses are cached, `RESPONSE_CACHE_TTL_MS`.
+    /// Zero disables the cache.
+    pub response_cache_ttl_ms: u64,
+    /// Milliseconds after which a finished request is logged as slow, `SLOW_REQUEST_MS`.
+    /// Zero disables the warnings.
+    pub slow_request_ms: u64,
+    /// Require the api key for the documentation UIs and spec outside the local environment,
+    /// `DOCS_REQUIRE_AUTH`.
+    pub docs_require_auth: bool,
+    /// Documentation UIs to serve outside production, `DOCS_UIS`.
+    /// The raw spec is served even when the list is empty.
+    pub docs_uis: Vec<DocsUi>,
+    /// Log the route table at startup, `LOG_ROUTES`.
+    pub log_routes: bool,
+    /// Format of the stdout logs, `LOG_FORMAT` or `--log-format`.
+    pub log_format: LogFormat,
+    /// Rotated log files next to the stdout logs,
+    /// `LOG_FILE_DIRECTORY`, `LOG_FILE_ROTATION` and `LOG_FILE_MAX_FILES`.
+    pub log_file: LogFileConfig,
+    /// Request and response bodies logged at DEBUG,
+    /// `BODY_LOGGING_ENABLED`, `BODY_LOGGING_MAX_BYTES` and `BODY_LOGGING_REDACT_FIELDS`.
+    pub body_logging: BodyLoggingConfig,
+    /// Body format of error responses, `ERROR_FORMAT`.
+    /// Clients can ask for problem details with `Accept: application/problem+json` either way.
+    pub error_format: ErrorFormat,
+    /// Wrap JSON response bodies in `{"data": ..., "meta": ...}`, `RESPONSE_ENVELOPE`.
+    pub response_envelope: bool,
+    /// Tenant ids accepted in the `x-tenant-id` header, `TENANTS`.
+    /// Each tenant gets its own item store.
+    pub tenants: Vec<String>,
+    /// Seeded item ids and a fixed clock for tests and fixtures, `DETERMINISTIC_SEED` and `DETERMINISTIC_TIME`.
+    /// Not allowed in production.
+    pub deterministic: DeterministicConfig,
+}
+
+/// Settings given as CLI arguments.
+///
+/// Only arguments that were given override the other layers.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct CliSettings {
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub host: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub port: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub http: Option<HttpProtocol>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub log_format: Option<LogFormat>,
+    #[serde(skip_serializing_if = "CliTlsSettings::is_empty")]
+    pub tls: CliTlsSettings,
+}
+
+/// TLS files given as CLI arguments, merged into the `tls` table.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct CliTlsSettings {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cert: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub key: Option<PathBuf>,
+}
+
+impl CliTlsSettings {
+    const fn is_empty(&self) -> bool {
+        self.cert.is_none() && self.key.is_none()
+    }
+}
+
+/// Builds settings from all layers.
+///
+/// Keeps the config file path, CLI arguments and secrets
+/// so settings can be rebuilt when the file or a secret changes.
+#[derive(Debug, Clone)]
+pub struct SettingsLoader {
+    config_path: PathBuf,
+    cli: CliSettings,
+    secrets: Option<Arc<SecretStore>>,
+}
+
+/// Named settings layer so extraction errors tell where a bad value came from.
+struct Layer {
+    name: &'static str,
+    values: toml::Table,
+}
+
+/// How an environment variable value is parsed.
+#[derive(Debug, Clone, Copy)]
+enum EnvValue {
+    String,
+    Integer,
+    /// `true` or `false`
+    Boolean,
+    /// Comma-separated list
+    List,
+}
+
+impl Settings {
+    /// Default settings for the runtime environment.
+    #[must_use]
+    pub fn for_environment(env: Environment) -> Self {
+        Self {
+            env,
+            api_key: DEFAULT_API_KEY.to_string(),
+            host: Vec::new(),
+            port: 3000,
+            http: HttpProtocol::default(),
+            server: ServerConfig::default(),
+            tls: TlsConfig::default(),
+            rate_limit: RateLimitConfig::for_environment(env),
+            cors_origins: Vec::new(),
+            id_strategy: IdStrategy::default(),
+            max_item_data_bytes: DEFAULT_MAX_ITEM_DATA_BYTES,
+            item_name_pattern: None,
+            admin_access: IpFilterConfig::default(),
+            secrets: SecretsConfig::default(),
+            drain_delay_seconds: if env == Environment::Local { 0 } else { 10 },
+            seed_file: None,
+            record_file: None,
+            state_file: None,
+            backup: BackupConfig::default(),
+            tasks: TasksConfig::default(),
+            storage: StorageConfig::default(),
+            quota: QuotaConfig::default(),
+            eviction: EvictionConfig::default(),
+            backend: BackendConfig::default(),
+            replication: ReplicationConfig::default(),
+            kafka: KafkaConfig::default(),
+            nats: NatsConfig::default(),
+            mqtt: MqttConfig::default(),
+            sqs: SqsConfig::default(),
+            response_cache_ttl_ms: 1000,
+            slow_request_ms: 1000,
+            docs_require_auth: false,
+            docs_uis: DocsUi::iter().collect(),
+            log_routes: false,
+            log_format: if env == Environment::Local {
+                LogFormat::Pretty
+            } else {
+                LogFormat::Json
+            },
+            log_file: LogFileConfig::default(),
+            body_logging: BodyLoggingConfig::default(),
+            error_format: ErrorFormat::default(),
+            response_envelope: false,
+            tenants: Vec::new(),
+            deterministic: DeterministicConfig::default(),
+        }
+    }
+
+    /// Merge the layers on top of the environment defaults.
+    pub fn from_layers(env: Environment, file: toml::Table, env_vars: toml::Table, cli: &CliSettings) -> Result<Self> {
+        let figment = Figment::from(Serialized::defaults(Self::for_environment(env)))
+            .merge(Layer {
+                name: "config file",
+                values: file,
+            })
+            .merge(Layer {
+                name: "environment variables",
+                values: env_vars,
+            })
+            .merge(Serialized::defaults(cli))
+            .merge(Serialized::default("env", env));
+        let settings: Self = figment.extract().context("Invalid settings")?;
+        anyhow::ensure!(
+            env != Environment::Production || !settings.deterministic.is_enabled(),
+            "Deterministic mode is not allowed in production"
+        );
+        settings.tls.files()?;
+        Ok(settings)
+    }
+
+    /// Share settings with handlers and middleware.
+    #[must_use]
+    pub fn into_shared(self) -> SharedSettings {
+        Arc::new(ArcSwap::from_pointee(self))
+    }
+
+    /// Check if the origin is allowed to make cross-origin requests.
+    #[must_use]
+    pub fn allows_origin(&self, origin: &[u8]) -> bool {
+        self.cors_origins
+            .iter()
+            .any(|allowed| allowed == "*" || allowed.as_bytes() == origin)
+    }
+
+    /// Copy with secrets replaced by a placeholder, safe to print or return from the API.
+    #[must_use]
+    pub fn redacted(&self) -> Self {
+        Self {
+            api_key: REDACTED.to_string(),
+            ..self.clone()
+        }
+    }
+
+    /// List the settings that differ in `other`, with secrets redacted.
+    ///
+    /// Nested settings are compared key by key, for example `rate_limit.burst`.
+    #[must_use]
+    pub fn diff(&self, other: &Self) -> Vec<SettingChange> {
+        let mut old = serde_json::Map::new();
+        let mut new = serde_json::Map::new();
+        flatten_json("", serde_json::to_value(self).unwrap_or_default(), &mut old);
+        flatten_json("", serde_json::to_value(other).unwrap_or_default(), &mut new);
+        let mut keys: Vec<&String> = old.keys().chain(new.keys()).collect();
+        keys.sort();
+        keys.dedup();
+        keys.into_iter()
+            .filter(|key| old.get(*key) != new.get(*key))
+            .map(|key| {
+                let redact = |value: Option<&serde_json::Value>| {
+                    if key == "api_key" {
+                        serde_json::Value::from(REDACTED)
+                    } else {
+                        value.cloned().unwrap_or_default()
+                    }
+                };
+                SettingChange {
+                    key: key.clone(),
+                    old: redact(old.get(key)),
+                    new: redact(new.get(key)),
+                    restart_required: is_startup_only(key),
+                }
+            })
+            .collect()
+    }
+
+    /// Render as TOML with the api key redacted.
+    pub fn to_toml_redacted(&self) -> Result<String> {
+        toml::to_string_pretty(&self.redacted()).context("Failed to serialize settings")
+    }
+}
+
+impl SettingsLoader {
+    #[must_use]
+    pub const fn new(config_path: PathBuf, cli: CliSettings) -> Self {
+        Self {
+            config_path,
+            cli,
+            secrets: None,
+        }
+    }
+
+    /// Use cached secret values on top of the other layers.
+    #[must_use]
+    pub fn with_secrets(mut self, secrets: Arc<SecretStore>) -> Self {
+        self.secrets = Some(secrets);
+        self
+    }
+
+    #[must_use]
+    pub fn config_path(&self) -> &Path {
+        &self.config_path
+    }
+
+    /// Build settings from the current config file, environment variables, CLI arguments and secrets.
+    pub fn load(&self) -> Result<Settings> {
+        let env = Environment::from_env();
+        let file = file_config::read(&self.config_path, env)?;
+        let env_vars = env_layer(|name| env::var(name).ok())?;
+        let mut settings = Settings::from_layers(env, file, env_vars, &self.cli)?;
+        if let Some(secrets) = &self.secrets {
+            secrets.apply(&mut settings);
+        }
+        Ok(settings)
+    }
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self::for_environment(Environment::default())
+    }
+}
+
+impl Provider for Layer {
+    fn metadata(&self) -> Metadata {
+        Metadata::named(self.name)
+    }
+
+    fn data(&self) -> figment::Result<Map<Profile, Dict>> {
+        Serialized::defaults(&self.values).data()
+    }
+}
+
+/// Watch the config file and swap in rebuilt settings when it changes.
+///
+/// The watcher stops when the returned handle is dropped.
+pub fn watch(loader: SettingsLoader, settings: SharedSettings) -> Result<RecommendedWatcher> {
+    let path = loader.config_path().to_path_buf();
+    file_config::watch(&path, move || {
+        reload(&loader, &settings).ok();
+    })
+}
+
+/// Rebuild settings, swap them in if they changed, and return what changed.
+///
+/// Invalid settings are logged and returned as an error, and the previous values stay in use.
+pub fn reload(loader: &SettingsLoader, settings: &SharedSettings) -> Result<Vec<SettingChange>> {
+    let new_settings = loader.load().inspect_err(|error| {
+        crate::log_error!("Keeping previous settings: {error:#}");
+    })?;
+    let changes = settings.load().diff(&new_settings);
+    if !changes.is_empty() {
+        let keys: Vec<&str> = changes.iter().map(|change| change.key.as_str()).collect();
+        crate::log_info!(
+            "Reloaded settings from {}, changed: {}",
+            loader.config_path().display(),
+            keys.join(", ")
+        );
+        settings.store(Arc::new(new_settings));
+    }
+    Ok(changes)
+}
+
+/// Check if the dotted settings key is only read at startup.
+fn is_startup_only(key: &str) -> bool {
+    STARTUP_ONLY_KEYS.iter().any(|startup_key| {
+        key.strip_prefix(startup_key)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with('.'))
+    })
+}
+
+/// Flatten nested JSON objects into dotted keys.
+fn flatten_json(prefix: &str, value: serde_json::Value, output: &mut serde_json::Map<String, serde_json::Value>) {
+    match value {
+        serde_json::Value::Object(map) => {
+            for (key, value) in map {
+                let key = if prefix.is_empty() {
+                    key
+                } else {
+                    format!("{prefix}.{key}")
+                };
+                flatten_json(&key, value, output);
+            }
+        }
+        value => {
+            output.insert(prefix.to_string(), value);
+        }
+    }
+}
+
+/// Collect settings from environment variables.
+fn env_layer(lookup: impl Fn(&str) -> Option<String>) -> Result<toml::Table> {
+    let mut table = toml::Table::new();
+    for (name, key, kind) in ENV_VARIABLES {
+        let Some(value) = lookup(name) else {
+            continue;
+        };
+        let value = match kind {
+            EnvValue::String => toml::Value::String(value),
+            EnvValue::Integer => toml::Value::Integer(
+                value
+                    .trim()
+                    .parse()
+                    .with_context(|| format!("Invalid {name}: expected a number, got '{value}'"))?,
+            ),
+            EnvValue::Boolean => toml::Value::Boolean(
+                value
+                    .trim()
+                    .parse()
+                    .with_context(|| format!("Invalid {name}: expected true or false, got '{value}'"))?,
+            ),
+            EnvValue::List => toml::Value::Array(
+                value
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|item| !item.is_empty())
+                    .map(|item| toml::Value::String(item.to_string()))
+                    .collect(),
+            ),
+        };
+        insert_nested(&mut table, key, value);
+    }
+    Ok(table)
+}
+
+/// Insert a value using a dotted key such as `rate_limit.burst`.
+fn insert_nested(table: &mut toml::Table, key: &str, value: toml::Value) {
+    match key.split_once('.') {
+        Some((parent, rest)) => {
+            let entry = table
+                .entry(parent)
+                .or_insert_with(|| toml::Value::Table(toml::Table::new()));
+            if let toml::Value::Table(child) = entry {
+                insert_nested(child, rest, value);
+            }
+        }
+        None => {
+            table.insert(key.to_string(), value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::collections::HashMap;
+
+    fn table(contents: &str) -> toml::Table {
+        toml::from_str(contents).expect("valid TOML")
+    }
+
+    fn env_vars(vars: &[(&str, &str)]) -> toml::Table {
+        let vars: HashMap<String, String> = vars
+            .iter()
+            .map(|(name, value)| ((*name).to_string(), (*value).to_string()))
+            .collect();
+        env_layer(|name| vars.get(name).cloned()).expect("valid environment variables")
+    }
+
+    #[test]
+    fn defaults_depend_on_environment() {
+        let local = Settings::default();
+        let production = Settings::for_environment(Environment::Production);
+
+        assert_eq!(local.env, Environment::Local);
+        assert_eq!(local.api_key, DEFAULT_API_KEY);
+        assert_eq!(local.port, 3000);
+        assert_eq!(local.rate_limit.requests_per_second, 0);
+        assert!(production.rate_limit.requests_per_second > 0);
+        assert_eq!(local.drain_delay_seconds, 0);
+        assert_eq!(production.drain_delay_seconds, 10);
+        assert_eq!(local.log_format, LogFormat::Pretty);
+        assert_eq!(production.log_format, LogFormat::Json);
+    }
+
+    #[test]
+    fn later_layers_take_precedence() {
+        let file = table(
+            r#"
+            port = 4000
+            cors_origins = ["https://file.example"]
+
+            [rate_limit]
+            requests_per_second = 5
+            burst = 10
+            "#,
+        );
+        let env_vars = env_vars(&[("PORT", "5000"), ("RATE_LIMIT_BURST", "20")]);
+        let cli = CliSettings {
+            port: Some(6000),
+            ..CliSettings::default()
+        };
+
+        let settings = Settings::from_layers(Environment::Local, file.clone(), env_vars.clone(), &cli).unwrap();
+        assert_eq!(settings.port, 6000);
+        assert_eq!(settings.rate_limit.requests_per_second, 5);
+        assert_eq!(settings.rate_limit.burst, 20);
+        assert_eq!(settings.cors_origins, ["https://file.example"]);
+
+        let settings =
+            Settings::from_layers(Environment::Local, file.clone(), env_vars, &CliSettings::default()).unwrap();
+        assert_eq!(settings.port, 5000);
+
+        let settings =
+            Settings::from_layers(Environment::Local, file, toml::Table::new(), &CliSettings::default()).unwrap();
+        assert_eq!(settings.port, 4000);
+    }
+
+    #[test]
+    fn tls_files_merge_from_every_layer_and_need_each_other() {
+        let file = table(
+            r#"
+            [tls]
+            key = "file.key"
+            redirect_port = 8080
+            "#,
+        );
+        let cli = CliSettings {
+            tls: CliTlsSettings {
+                cert: Some(PathBuf::from("cli.pem")),
+                key: None,
+            },
+            ..CliSettings::default()
+        };
+
+        let settings = Settings::from_layers(Environment::Local, file.clone(), toml::Table::new(), &cli).unwrap();
+        assert_eq!(
+            settings.tls.files().unwrap(),
+            Some((Path::new("cli.pem"), Path::new("file.key")))
+        );
+        assert_eq!(settings.tls.redirect_port, Some(8080));
+
+        let error =
+            Settings::from_layers(Environment::Local, file, toml::Table::new(), &CliSettings::default()).unwrap_err();
+        assert!(error.to_string().contains("TLS certificate and key"), "{error}");
+    }
+
+    #[test]
+    fn environment_comes_only_from_api_env() {
+        let settings = Settings::from_layers(
+            Environment::Production,
+            table(r#"env = "Local""#),
+            toml::Table::new(),
+            &CliSettings::default(),
+        )
+        .unwrap();
+
+        assert_eq!(settings.env, Environment::Production);
+    }
+
+    #[test]
+    fn env_layer_parses_lists_numbers_and_nested_keys() {
+        let env_vars = env_vars(&[
+            ("HOST", "127.0.0.1, ::1"),
+            ("HTTP_PROTOCOL", "http2"),
+            ("RATE_LIMIT_PER_SECOND", "7"),
+            ("ADMIN_ALLOWED_NETWORKS", "10.0.0.0/8"),
+            ("HTTP1_KEEP_ALIVE", "false"),
+            ("HEADER_READ_TIMEOUT_SECONDS", "75"),
+            ("LOG_FORMAT", "logfmt"),
+        ]);
+
+        let settings = Settings::from_layers(
+            Environment::Local,
+            toml::Table::new(),
+            env_vars,
+            &CliSettings::default(),
+        )
+        .unwrap();
+
+        assert_eq!(settings.host, ["127.0.0.1", "::1"]);
+        assert_eq!(settings.http, HttpProtocol::Http2);
+        assert_eq!(settings.rate_limit.requests_per_second, 7);
+        assert_eq!(settings.admin_access.allowed, vec!["10.0.0.0/8".parse().unwrap()]);
+        assert!(!settings.server.http1_keep_alive);
+        assert_eq!(settings.server.header_read_timeout_seconds, Some(75));
+        assert_eq!(settings.log_format, LogFormat::Logfmt);
+    }
+
+    #[test]
+    fn deterministic_mode_is_read_from_env_and_rejected_in_production() {
+        let env_vars = env_vars(&[
+            ("DETERMINISTIC_SEED", "42"),
+            ("DETERMINISTIC_TIME", "2026-01-01T00:00:00Z"),
+        ]);
+
+        let settings = Settings::from_layers(
+            Environment::Test,
+            toml::Table::new(),
+            env_vars.clone(),
+            &CliSettings::default(),
+        )
+        .unwrap();
+        assert_eq!(settings.deterministic.seed, Some(42));
+        assert_eq!(
+            settings.deterministic.time.map(|time| time.to_rfc3339()).as_deref(),
+            Some("2026-01-01T00:00:00+00:00")
+        );
+
+        let error = Settings::from_layers(
+            Environment::Production,
+            toml::Table::new(),
+            env_vars,
+            &CliSettings::default(),
+        )
+        .expect_err("deterministic mode should fail in production");
+        assert!(format!("{error:#}").contains("Deterministic mode"));
+    }
+
+    #[test]
+    fn task_schedules_are_read_from_file_and_env() {
+        let file = table(
+            r#"
+            [tasks.state_log]
+            enabled = true
+            schedule = "0 */6 * * *"
+            jitter_seconds = 30
+            "#,
+        );
+
+        let settings = Settings::from_layers(
+            Environment::Local,
+            file,
+            env_vars(&[("BACKUP_INTERVAL_SECONDS", "900")]),
+            &CliSettings::default(),
+        )
+        .unwrap();
+
+        assert!(settings.tasks.state_log.enabled);
+        assert_eq!(settings.tasks.state_log.schedule.to_string(), "0 */6 * * *");
+        assert_eq!(settings.tasks.state_log.jitter_seconds, 30);
+        assert_eq!(settings.tasks.backup.schedule.to_string(), "15m");
+        assert_eq!(settings.tasks.expiry_sweep, TasksConfig::default().expiry_sweep);
+
+        let error = Settings::from_layers(
+            Environment::Local,
+            table("[tasks.backup]\nschedule = \"every day\""),
+            toml::Table::new(),
+            &CliSettings::default(),
+        )
+        .expect_err("invalid schedule should fail");
+        assert!(format!("{error:#}").contains("schedule"));
+    }
+
+    #[test]
+    fn invalid_values_are_errors() {
+        assert!(env_layer(|name| (name == "PORT").then(|| "http".to_string())).is_err());
+        assert!(env_layer(|name| (name == "HTTP1_KEEP_ALIVE").then(|| "yes".to_string())).is_err());
+
+        let error = Settings::from_layers(
+            Environment::Local,
+            table("unknown = 1"),
+            toml::Table::new(),
+            &CliSettings::default(),
+        )
+        .expect_err("unknown key should fail");
+        assert!(format!("{error:#}").contains("unknown"));
+
+        let error = Settings::from_layers(
+            Environment::Local,
+            toml::Table::new(),
+            env_vars(&[("TRUSTED_PROXIES", "not-a-network")]),
+            &CliSettings::default(),
+        )
+        .expect_err("invalid network should fail");
+        assert!(format!("{error:#}").contains("environment variables"));
+
+        let error = Settings::from_layers(
+            Environment::Local,
+            table(r#"item_name_pattern = "[a-z""#),
+            toml::Table::new(),
+            &CliSettings::default(),
+        )
+        .expect_err("invalid name pattern should fail");
+        assert!(format!("{error:#}").contains("item name pattern"));
+
+        let error = Settings::from_layers(
+            Environment::Local,
+            toml::Table::new(),
+            env_vars(&[("STORAGE_SHARD_AMOUNT", "3")]),
+            &CliSettings::default(),
+        )
+        .expect_err("shard amount that is not a power of two should fail");
+        assert!(format!("{error:#}").contains("power of two"));
+    }
+
+    #[test]
+    fn allows_listed_and_wildcard_origins() {
+        let settings = Settings {
+            cors_origins: vec!["https://example.com".to_string()],
+            ..Settings::default()
+        };
+        assert!(settings.allows_origin(b"https://example.com"));
+        assert!(!settings.allows_origin(b"https://evil.example"));
+
+        let settings = Settings {
+            cors_origins: vec!["*".to_string()],
+            ..Settings::default()
+        };
+        assert!(settings.allows_origin(b"https://evil.example"));
+    }
+
+    #[test]
+    fn redacted_toml_hides_api_key() {
+        let settings = Settings {
+            api_key: "secret-key".to_string(),
+            ..Settings::for_environment(Environment::Production)
+        };
+
+        let output = settings.to_toml_redacted().expect("settings should render");
+
+        assert!(!output.contains("secret-key"));
+        assert!(output.contains("api_key = \"<redacted>\""));
+        assert!(output.contains("env = \"Production\""));
+        assert!(output.contains("[rate_limit]"));
+    }
+
+    #[test]
+    fn diff_lists_nested_changes_and_redacts_api_key() {
+        let old = Settings::default();
+        let mut new = old.clone();
+        new.api_key = "new-secret".to_string();
+        new.port = 4000;
+        new.rate_limit.burst += 1;
+        new.secrets.api_key_id = Some("axum-example/api-key".to_string());
+        new.storage.initial_capacity = 64;
+
+        let changes = old.diff(&new);
+
+        let keys: Vec<&str> = changes.iter().map(|change| change.key.as_str()).collect();
+        assert_eq!(
+            keys,
+            [
+                "api_key",
+                "port",
+                "rate_limit.burst",
+                "secrets.api_key_id",
+                "storage.initial_capacity"
+            ]
+        );
+        assert_eq!(changes[0].new, REDACTED);
+        assert!(changes[1].restart_required);
+        assert!(!changes[2].restart_required);
+        // The secret store is connected once at startup.
+        assert!(changes[3].restart_required);
+        assert!(changes[4].restart_required);
+        assert!(old.diff(&old).is_empty());
+    }
+
+    #[test]
+    fn reload_keeps_previous_settings_when_file_is_invalid() {
+        let path = std::env::temp_dir().join(format!("axum-example-settings-{}.toml", std::process::id()));
+        let loader = SettingsLoader::new(path.clone(), CliSettings::default());
+        std::fs::write(&path, r#"cors_origins = ["https://one.example"]"#).expect("write config");
+        let settings = loader.load().expect("valid settings").into_shared();
+
+        std::fs::write(&path, r#"cors_origins = ["https://two.example"]"#).expect("write config");
+        let changes = reload(&loader, &settings).expect("valid settings");
+        assert_eq!(settings.load().cors_origins, ["https://two.example"]);
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].key, "cors_origins");
+
+        std::fs::write(&path, "cors_origins = 1").expect("write config");
+        assert!(reload(&loader, &settings).is_err());
+        assert_eq!(settings.load().cors_origins, ["https://two.example"]);
+
+        std::fs::remove_file(&path).ok();
+    }
+}
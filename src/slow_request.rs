@@ -0,0 +1,110 @@
+//! Slow request warnings.
+//!
+//! A request that takes longer than `slow_request_ms` to get its response
+//! is logged at WARN with its route, duration, status and request id,
+//! so creeping latency shows up well before requests hit the timeout.
+//! The route is the matched pattern like `/api/v1/item/{id}`, so the warnings group by route.
+//! The threshold is read on every request, so it can be tuned without a restart.
+
+use std::time::{Duration, Instant};
+
+use axum::extract::{MatchedPath, Request, State};
+use axum::middleware::Next;
+use axum::response::Response;
+
+use crate::settings::SharedSettings;
+
+/// Log requests that take longer than the configured threshold.
+pub async fn slow_request_middleware(State(settings): State<SharedSettings>, request: Request, next: Next) -> Response {
+    let threshold = Duration::from_millis(settings.load().slow_request_ms);
+    if threshold.is_zero() {
+        return next.run(request).await;
+    }
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map_or_else(|| request.uri().path().to_string(), |path| path.as_str().to_string());
+    let method = request.method().clone();
+
+    let started = Instant::now();
+    let response = next.run(request).await;
+    let elapsed = started.elapsed();
+    if elapsed > threshold {
+        crate::log_warn!(
+            request_id = crate::request_id::current(),
+            method = %method,
+            route = %route,
+            status = response.status().as_u16(),
+            duration_ms = elapsed.as_millis(),
+            "Slow request {method} {route} took {} ms, over the {} ms threshold",
+            elapsed.as_millis(),
+            threshold.as_millis()
+        );
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io::Write;
+    use std::sync::{Arc, Mutex};
+
+    use axum::Router;
+    use axum::body::Body;
+    use axum::middleware::from_fn_with_state;
+    use axum::routing::get;
+    use tower::ServiceExt;
+
+    use crate::settings::Settings;
+
+    /// Writer that collects the formatted events.
+    #[derive(Clone, Default)]
+    struct Output(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Output {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn requests_over_the_threshold_are_logged() {
+        let settings = Settings {
+            slow_request_ms: 20,
+            ..Settings::default()
+        }
+        .into_shared();
+        let app = Router::new()
+            .route("/fast", get(|| async {}))
+            .route(
+                "/slow/{id}",
+                get(|| async { tokio::time::sleep(Duration::from_millis(50)).await }),
+            )
+            .layer(from_fn_with_state(settings, slow_request_middleware));
+        let output = Output::default();
+        let writer = output.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_ansi(false)
+            .with_writer(move || writer.clone())
+            .finish();
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        for uri in ["/fast", "/slow/1"] {
+            let request = Request::builder().uri(uri).body(Body::empty()).unwrap();
+            app.clone().oneshot(request).await.unwrap();
+        }
+
+        let logs = String::from_utf8(output.0.lock().unwrap().clone()).unwrap();
+        assert_eq!(logs.lines().count(), 1, "{logs}");
+        assert!(logs.contains("WARN"));
+        assert!(logs.contains("Slow request GET /slow/{id} took"), "{logs}");
+        assert!(logs.contains("over the 20 ms threshold"));
+        assert!(logs.contains("status=200"));
+    }
+}
@@ -0,0 +1,194 @@
+//! State snapshots for backup and restore.
+//!
+//! A snapshot is the item list as gzip-compressed JSON,
+//! so a backup can be inspected with `gunzip` and `jq` and restored into another instance.
+
+use std::io::{Read, Write};
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use flate2::Compression;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use serde::{Deserialize, Serialize};
+
+use crate::schemas::RestoreMode;
+use crate::types::{Collection, Item, StorageUsage};
+use crate::version;
+
+/// Largest accepted snapshot, both compressed and decompressed.
+pub const MAX_SNAPSHOT_BYTES: usize = 64 * 1024 * 1024;
+
+/// File name extension of snapshots.
+pub const SNAPSHOT_FILE_SUFFIX: &str = ".json.gz";
+
+/// Items at one point in time.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Snapshot {
+    /// Version of the service that wrote the snapshot.
+    pub version: String,
+    pub created_at: DateTime<Utc>,
+    pub items: Vec<Item>,
+}
+
+/// Number of items restored from a snapshot and skipped as duplicates.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RestoreSummary {
+    pub restored: usize,
+    pub skipped: usize,
+}
+
+impl Snapshot {
+    /// Capture every item of the collection, sorted by name.
+    pub fn capture(collection: &Collection) -> Self {
+        let mut items: Vec<Item> = collection.db.iter().map(|entry| (**entry.value()).clone()).collect();
+        items.sort_by(|a, b| a.name.cmp(&b.name));
+        Self {
+            version: version::PACKAGE_VERSION.to_string(),
+            created_at: Utc::now(),
+            items,
+        }
+    }
+
+    /// Timestamped file name, so backups sort by age.
+    #[must_use]
+    pub fn file_name(&self) -> String {
+        format!(
+            "{}{}{SNAPSHOT_FILE_SUFFIX}",
+            snapshot_file_prefix(),
+            self.created_at.format("%Y%m%dT%H%M%SZ")
+        )
+    }
+
+    /// Serialize to gzip-compressed JSON.
+    pub fn to_gzip(&self) -> Result<Vec<u8>> {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        serde_json::to_writer(&mut encoder, self).context("Failed to serialize snapshot")?;
+        encoder.flush().context("Failed to compress snapshot")?;
+        encoder.finish().context("Failed to compress snapshot")
+    }
+
+    /// Read a snapshot from gzip-compressed JSON.
+    ///
+    /// Decompression stops at `MAX_SNAPSHOT_BYTES` so a small file can not expand without limit.
+    pub fn from_gzip(bytes: &[u8]) -> Result<Self> {
+        let mut json = Vec::new();
+        GzDecoder::new(bytes)
+            .take(MAX_SNAPSHOT_BYTES as u64 + 1)
+            .read_to_end(&mut json)
+            .context("Snapshot is not valid gzip")?;
+        if json.len() > MAX_SNAPSHOT_BYTES {
+            anyhow::bail!("Snapshot is larger than {MAX_SNAPSHOT_BYTES} bytes when decompressed");
+        }
+        serde_json::from_slice(&json).context("Snapshot is not valid JSON")
+    }
+
+    /// Usage of the collection after restoring the snapshot into it.
+    ///
+    /// `Merge` counts every item whose name is free, also those that would be skipped for a taken id.
+    #[must_use]
+    pub fn usage_after_restore(&self, collection: &Collection, mode: RestoreMode) -> StorageUsage {
+        let (base, restored) = match mode {
+            RestoreMode::Replace => (StorageUsage::default(), self.items.iter().collect::<Vec<_>>()),
+            RestoreMode::Merge => (
+                collection.usage(),
+                self.items
+                    .iter()
+                    .filter(|item| !collection.db.contains_key(&item.name))
+                    .collect(),
+            ),
+        };
+        StorageUsage {
+            num_items: base.num_items + restored.len(),
+            data_bytes: base.data_bytes + restored.iter().map(|item| item.data_size()).sum::<usize>(),
+        }
+    }
+
+    /// Load the snapshot items into the collection.
+    ///
+    /// `Replace` removes existing items first.
+    /// `Merge` keeps existing items and skips snapshot items whose name or id is already taken.
+    pub fn restore(&self, collection: &Collection, mode: RestoreMode) -> RestoreSummary {
+        if mode == RestoreMode::Replace {
+            collection.clear_items();
+        }
+        let mut summary = RestoreSummary::default();
+        for item in &self.items {
+            if collection.insert_item(item).is_ok() {
+                summary.restored += 1;
+            } else {
+                summary.skipped += 1;
+            }
+        }
+        summary
+    }
+}
+
+/// Start of every snapshot file name.
+#[must_use]
+pub fn snapshot_file_prefix() -> String {
+    format!("{}-backup-", version::PACKAGE_NAME)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::types::{AppState, IdStrategy, ItemId};
+
+    fn item(name: &str, id: u64) -> Item {
+        Item::new(name.to_string(), ItemId::Numeric(id), IdStrategy::Numeric).expect("valid item")
+    }
+
+    #[test]
+    fn gzip_round_trip_keeps_items() {
+        let state = AppState::new();
+        state.items().insert_item(&item("b", 2222)).unwrap();
+        state.items().insert_item(&item("a", 1111)).unwrap();
+
+        let snapshot = Snapshot::capture(state.items());
+        let restored = Snapshot::from_gzip(&snapshot.to_gzip().unwrap()).unwrap();
+
+        assert_eq!(restored, snapshot);
+        assert_eq!(restored.items[0].name, "a");
+    }
+
+    #[test]
+    fn invalid_data_is_an_error() {
+        assert!(Snapshot::from_gzip(b"not gzip").is_err());
+    }
+
+    #[test]
+    fn restore_replaces_or_merges_items() {
+        let source = AppState::new();
+        source.items().insert_item(&item("backed-up", 1111)).unwrap();
+        source.items().insert_item(&item("shared", 2222)).unwrap();
+        let snapshot = Snapshot::capture(source.items());
+
+        let merged = AppState::new();
+        merged.items().insert_item(&item("shared", 3333)).unwrap();
+        merged.items().insert_item(&item("local", 4444)).unwrap();
+        let summary = snapshot.restore(merged.items(), RestoreMode::Merge);
+        assert_eq!(
+            summary,
+            RestoreSummary {
+                restored: 1,
+                skipped: 1
+            }
+        );
+        assert_eq!(merged.items().db.len(), 3);
+        assert_eq!(merged.items().db.get("shared").unwrap().id, ItemId::Numeric(3333));
+
+        let replaced = AppState::new();
+        replaced.items().insert_item(&item("local", 4444)).unwrap();
+        let summary = snapshot.restore(replaced.items(), RestoreMode::Replace);
+        assert_eq!(
+            summary,
+            RestoreSummary {
+                restored: 2,
+                skipped: 0
+            }
+        );
+        assert!(!replaced.items().db.contains_key("local"));
+    }
+}
@@ -0,0 +1,234 @@
+//! `OpenAPI` spec diffing.
+//!
+//! The `diff-openapi` subcommand compares the spec of the current build against a stored JSON snapshot
+//! and lists the operations and schemas that were added, removed or changed.
+//! Removing an operation or a schema breaks existing clients,
+//! so those make the command fail and a release pipeline can stop on them.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use serde_json::Value;
+use strum::Display;
+
+/// Methods that can have an operation in an `OpenAPI` path item.
+const OPERATION_METHODS: [&str; 8] = ["get", "put", "post", "delete", "options", "head", "patch", "trace"];
+
+/// Differences between two specs.
+///
+/// Operations come before schemas, and in each group additions come after removals and changes.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct SpecDiff {
+    pub changes: Vec<SpecChange>,
+}
+
+/// Single operation or schema that differs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpecChange {
+    pub kind: ChangeKind,
+    /// Operation as `METHOD /path`, or `schema Name`.
+    pub target: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Display)]
+#[strum(serialize_all = "lowercase")]
+pub enum ChangeKind {
+    Added,
+    Removed,
+    Changed,
+}
+
+impl SpecDiff {
+    /// Compare the operations and component schemas of two specs.
+    #[must_use]
+    pub fn new(old: &Value, new: &Value) -> Self {
+        let mut diff = Self::default();
+        diff.compare(&operations(old), &operations(new));
+        diff.compare(&schemas(old), &schemas(new));
+        diff
+    }
+
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    /// Removed operations and schemas, which break existing clients.
+    pub fn breaking(&self) -> impl Iterator<Item = &SpecChange> {
+        self.changes.iter().filter(|change| change.kind == ChangeKind::Removed)
+    }
+
+    fn compare<K: Ord>(&mut self, old: &BTreeMap<K, (String, &Value)>, new: &BTreeMap<K, (String, &Value)>) {
+        for (key, (target, old_value)) in old {
+            let kind = match new.get(key) {
+                None => ChangeKind::Removed,
+                Some((_, new_value)) if new_value != old_value => ChangeKind::Changed,
+                Some(_) => continue,
+            };
+            self.changes.push(SpecChange {
+                kind,
+                target: target.clone(),
+            });
+        }
+        for (key, (target, _)) in new {
+            if !old.contains_key(key) {
+                self.changes.push(SpecChange {
+                    kind: ChangeKind::Added,
+                    target: target.clone(),
+                });
+            }
+        }
+    }
+}
+
+impl fmt::Display for SpecDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return writeln!(f, "No changes");
+        }
+        for change in &self.changes {
+            writeln!(f, "{:<8} {}", change.kind.to_string(), change.target)?;
+        }
+        writeln!(
+            f,
+            "{} changes, {} breaking",
+            self.changes.len(),
+            self.breaking().count()
+        )
+    }
+}
+
+/// Compare the current spec against the JSON snapshot in the file.
+pub fn diff_snapshot(path: &Path, current: &utoipa::openapi::OpenApi) -> Result<SpecDiff> {
+    let contents =
+        std::fs::read_to_string(path).with_context(|| format!("Failed to read OpenAPI snapshot {}", path.display()))?;
+    let snapshot: Value =
+        serde_json::from_str(&contents).with_context(|| format!("Invalid OpenAPI snapshot {}", path.display()))?;
+    let current = serde_json::to_value(current).context("Failed to serialize OpenAPI spec")?;
+    Ok(SpecDiff::new(&snapshot, &current))
+}
+
+/// Operations keyed by path and method.
+fn operations(spec: &Value) -> BTreeMap<(&str, &str), (String, &Value)> {
+    let Some(paths) = spec.get("paths").and_then(Value::as_object) else {
+        return BTreeMap::new();
+    };
+    paths
+        .iter()
+        .flat_map(|(path, item)| {
+            OPERATION_METHODS.iter().filter_map(move |method| {
+                item.get(*method).map(|operation| {
+                    (
+                        (path.as_str(), *method),
+                        (format!("{} {path}", method.to_uppercase()), operation),
+                    )
+                })
+            })
+        })
+        .collect()
+}
+
+/// Component schemas keyed by name.
+fn schemas(spec: &Value) -> BTreeMap<&str, (String, &Value)> {
+    let Some(schemas) = spec.pointer("/components/schemas").and_then(Value::as_object) else {
+        return BTreeMap::new();
+    };
+    schemas
+        .iter()
+        .map(|(name, schema)| (name.as_str(), (format!("schema {name}"), schema)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serde_json::json;
+
+    use crate::openapi::api_doc;
+    use crate::router::legacy_route_deprecations;
+    use crate::types::IdStrategy;
+
+    fn spec() -> Value {
+        json!({
+            "paths": {
+                "/items": {
+                    "get": { "operationId": "list_items" },
+                    "post": { "operationId": "create_item" }
+                },
+                "/item": {
+                    "get": { "operationId": "query_item" }
+                }
+            },
+            "components": {
+                "schemas": {
+                    "Item": { "type": "object" },
+                    "CreateItem": { "type": "object" }
+                }
+            }
+        })
+    }
+
+    #[test]
+    fn identical_specs_have_no_changes() {
+        assert!(SpecDiff::new(&spec(), &spec()).is_empty());
+        assert_eq!(SpecDiff::default().to_string(), "No changes\n");
+    }
+
+    #[test]
+    fn lists_added_removed_and_changed_operations_and_schemas() {
+        let old = spec();
+        let mut new = spec();
+        new["paths"]["/items"].as_object_mut().unwrap().remove("post");
+        new["paths"]["/item"]["get"]["deprecated"] = json!(true);
+        new["paths"]["/version"] = json!({ "get": { "operationId": "version" } });
+        new["components"]["schemas"]
+            .as_object_mut()
+            .unwrap()
+            .remove("CreateItem");
+        new["components"]["schemas"]["Version"] = json!({ "type": "object" });
+
+        let diff = SpecDiff::new(&old, &new);
+
+        let changes: Vec<(ChangeKind, &str)> = diff
+            .changes
+            .iter()
+            .map(|change| (change.kind, change.target.as_str()))
+            .collect();
+        assert_eq!(
+            changes,
+            [
+                (ChangeKind::Changed, "GET /item"),
+                (ChangeKind::Removed, "POST /items"),
+                (ChangeKind::Added, "GET /version"),
+                (ChangeKind::Removed, "schema CreateItem"),
+                (ChangeKind::Added, "schema Version"),
+            ]
+        );
+        assert_eq!(diff.breaking().count(), 2);
+        assert!(diff.to_string().ends_with("5 changes, 2 breaking\n"));
+    }
+
+    #[test]
+    fn current_spec_matches_its_own_snapshot() {
+        let spec = api_doc(IdStrategy::Numeric, &legacy_route_deprecations());
+        let path = std::env::temp_dir().join(format!("axum-example-snapshot-{}.json", std::process::id()));
+        std::fs::write(&path, spec.to_pretty_json().unwrap()).expect("write snapshot");
+
+        let diff = diff_snapshot(&path, &spec).expect("snapshot should be readable");
+        assert!(diff.is_empty(), "{diff}");
+
+        let diff = diff_snapshot(&path, &api_doc(IdStrategy::Uuid, &legacy_route_deprecations()))
+            .expect("snapshot should be readable");
+        assert_eq!(
+            diff.changes,
+            [SpecChange {
+                kind: ChangeKind::Changed,
+                target: "schema ItemId".to_string(),
+            }]
+        );
+        std::fs::remove_file(&path).ok();
+    }
+}
@@ -0,0 +1,373 @@
+//! SQS consumer for item commands, built with the `sqs` cargo feature.
+//!
+//! With `sqs.queue_url` set, a background task long polls the queue
+//! and applies every message to the default collection as an item command:
+//! `{"command": "create", "item": {...}}` with the same payload as `POST /items`,
+//! or `{"command": "delete", "name": "..."}`.
+//! Applied changes are published as item events with the `queue` actor.
+//! Applied messages and messages that can never be applied, like invalid JSON or a taken name, are deleted.
+//! Messages that fail on an internal or backend error stay in the queue
+//! and are received again after the visibility timeout,
+//! so a redrive policy on the queue can move messages that keep failing aside.
+//! Credentials and region come from the standard AWS environment like the other AWS clients,
+//! and requests use the SQS JSON protocol signed with `SigV4`.
+
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use anyhow::{Context, Result};
+use aws_config::SdkConfig;
+use aws_credential_types::provider::{ProvideCredentials, SharedCredentialsProvider};
+use aws_sigv4::http_request::{SignableBody, SignableRequest, SigningSettings, sign};
+use aws_sigv4::sign::v4;
+use axum::body::Bytes;
+use axum::http::header::CONTENT_TYPE;
+use axum::http::uri::Scheme;
+use axum::http::{Request, Uri};
+use http_body_util::{BodyExt, Full};
+use hyper_rustls::{HttpsConnector, HttpsConnectorBuilder};
+use hyper_util::client::legacy::Client;
+use hyper_util::client::legacy::connect::HttpConnector;
+use hyper_util::rt::TokioExecutor;
+use serde::Deserialize;
+use serde_json::json;
+use tokio::task::JoinHandle;
+
+use crate::events::{Actor, ItemChange};
+use crate::i18n;
+use crate::routing::admin::remove_stored_item;
+use crate::routing::routes::insert_new_item;
+use crate::schemas::{ApiError, CreateItem, ErrorCode};
+use crate::settings::SharedSettings;
+use crate::types::SharedState;
+use crate::validation::Validate;
+
+/// Seconds that a receive waits for messages, the SQS maximum.
+const WAIT_TIME_SECONDS: u32 = 20;
+
+/// Most messages received at once, the SQS maximum.
+const MAX_MESSAGES: u32 = 10;
+
+/// Delay before polling again after a failed receive.
+const RETRY_DELAY: Duration = Duration::from_secs(5);
+
+const JSON_PROTOCOL_CONTENT_TYPE: &str = "application/x-amz-json-1.0";
+
+type HttpClient = Client<HttpsConnector<HttpConnector>, Full<Bytes>>;
+
+/// Command in an SQS message body.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "command", rename_all = "snake_case")]
+pub enum ItemCommand {
+    /// Create an item like `POST /items`.
+    Create { item: CreateItem },
+    /// Remove the named item whatever its revision is.
+    Delete { name: String },
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ReceiveMessageResponse {
+    #[serde(default)]
+    messages: Vec<QueueMessage>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct QueueMessage {
+    message_id: String,
+    receipt_handle: String,
+    body: String,
+}
+
+/// Client for the two SQS actions the consumer needs.
+struct SqsClient {
+    http: HttpClient,
+    endpoint: Uri,
+    queue_url: String,
+    region: String,
+    credentials: SharedCredentialsProvider,
+}
+
+impl SqsClient {
+    fn new(queue_url: String, config: &SdkConfig) -> Result<Self> {
+        let url: Uri = queue_url.parse().context("Invalid SQS queue URL")?;
+        let endpoint = Uri::builder()
+            .scheme(url.scheme().cloned().unwrap_or(Scheme::HTTPS))
+            .authority(url.authority().cloned().context("SQS queue URL has no host")?)
+            .path_and_query("/")
+            .build()?;
+        let region = config.region().context("No AWS region for SQS")?.to_string();
+        let credentials = config.credentials_provider().context("No AWS credentials for SQS")?;
+        let connector = HttpsConnectorBuilder::new()
+            .with_native_roots()?
+            .https_or_http()
+            .enable_http1()
+            .build();
+        Ok(Self {
+            http: Client::builder(TokioExecutor::new()).build(connector),
+            endpoint,
+            queue_url,
+            region,
+            credentials,
+        })
+    }
+
+    /// Long poll the queue for messages.
+    async fn receive(&self) -> Result<Vec<QueueMessage>> {
+        let body = self
+            .call(
+                "ReceiveMessage",
+                &json!({
+                    "QueueUrl": self.queue_url,
+                    "MaxNumberOfMessages": MAX_MESSAGES,
+                    "WaitTimeSeconds": WAIT_TIME_SECONDS,
+                }),
+            )
+            .await?;
+        let response: ReceiveMessageResponse =
+            serde_json::from_slice(&body).context("Invalid SQS ReceiveMessage response")?;
+        Ok(response.messages)
+    }
+
+    /// Remove a handled message from the queue.
+    async fn delete(&self, receipt_handle: &str) -> Result<()> {
+        self.call(
+            "DeleteMessage",
+            &json!({ "QueueUrl": self.queue_url, "ReceiptHandle": receipt_handle }),
+        )
+        .await
+        .map(|_| ())
+    }
+
+    /// Send a signed JSON protocol request and return the response body.
+    async fn call(&self, action: &str, payload: &serde_json::Value) -> Result<Bytes> {
+        let body = Bytes::from(serde_json::to_vec(payload)?);
+        let target = format!("AmazonSQS.{action}");
+        let mut request = Request::post(self.endpoint.clone())
+            .header(CONTENT_TYPE, JSON_PROTOCOL_CONTENT_TYPE)
+            .header("x-amz-target", &target)
+            .body(Full::new(body.clone()))?;
+
+        let identity = self.credentials.provide_credentials().await?.into();
+        let params = v4::SigningParams::builder()
+            .identity(&identity)
+            .region(&self.region)
+            .name("sqs")
+            .time(SystemTime::now())
+            .settings(SigningSettings::default())
+            .build()?
+            .into();
+        let signable = SignableRequest::new(
+            "POST",
+            self.endpoint.to_string(),
+            [
+                (CONTENT_TYPE.as_str(), JSON_PROTOCOL_CONTENT_TYPE),
+                ("x-amz-target", target.as_str()),
+            ]
+            .into_iter(),
+            SignableBody::Bytes(&body),
+        )?;
+        let (instructions, _signature) = sign(signable, &params)?.into_parts();
+        instructions.apply_to_request_http1x(&mut request);
+
+        let response = self.http.request(request).await?;
+        let status = response.status();
+        let body = response.into_body().collect().await?.to_bytes();
+        anyhow::ensure!(
+            status.is_success(),
+            "SQS {action} failed with {status}: {}",
+            String::from_utf8_lossy(&body)
+        );
+        Ok(body)
+    }
+}
+
+/// Consume item commands from the SQS queue when `sqs.queue_url` is set.
+///
+/// The queue is only read at startup.
+pub fn spawn_consumer(state: &SharedState, settings: &SharedSettings) -> Option<JoinHandle<()>> {
+    let queue_url = settings.load().sqs.queue_url.clone()?;
+    let state = Arc::clone(state);
+    let settings = Arc::clone(settings);
+    Some(tokio::spawn(async move {
+        match SqsClient::new(queue_url, &aws_config::load_from_env().await) {
+            Ok(client) => consume(&client, &state, &settings).await,
+            Err(error) => crate::log_error!("Failed to create the SQS client: {error:#}"),
+        }
+    }))
+}
+
+/// Receive and apply messages until the task is stopped.
+async fn consume(client: &SqsClient, state: &SharedState, settings: &SharedSettings) {
+    crate::log_info!("Consuming item commands from {}", client.queue_url);
+    loop {
+        let messages = match client.receive().await {
+            Ok(messages) => messages,
+            Err(error) => {
+                crate::log_warn!("Failed to receive SQS messages: {error:#}");
+                tokio::time::sleep(RETRY_DELAY).await;
+                continue;
+            }
+        };
+        for message in messages {
+            match apply_command(state, settings, &message.body).await {
+                Ok(()) => {}
+                Err(error) if matches!(error.code, ErrorCode::Internal | ErrorCode::Unavailable) => {
+                    crate::log_warn!(
+                        "Failed to apply SQS message {}, leaving it for a retry: {}",
+                        message.message_id,
+                        error.message
+                    );
+                    continue;
+                }
+                Err(error) => crate::log_warn!("Dropping SQS message {}: {}", message.message_id, error.message),
+            }
+            if let Err(error) = client.delete(&message.receipt_handle).await {
+                crate::log_warn!("Failed to delete SQS message {}: {error:#}", message.message_id);
+            }
+        }
+    }
+}
+
+/// Apply the item command in a message body to the default collection.
+///
+/// # Errors
+/// Returns the same errors as the matching routes, and `bad_request` for a body that is not a command.
+pub async fn apply_command(state: &SharedState, settings: &SharedSettings, body: &str) -> Result<(), ApiError> {
+    let command: ItemCommand = serde_json::from_str(body)
+        .map_err(|error| ApiError::new(ErrorCode::BadRequest, format!("Invalid item command: {error}")))?;
+    match command {
+        ItemCommand::Create { item } => {
+            let errors = item.validate();
+            if !errors.is_empty() {
+                return Err(ApiError::validation(errors));
+            }
+            let item = insert_new_item(state, state.items(), settings, item).await?;
+            state.publish(Actor::Queue, ItemChange::Created { item });
+        }
+        ItemCommand::Delete { name } => {
+            let item = remove_stored_item(state, state.items(), &name, |_| true)
+                .await?
+                .ok_or_else(|| {
+                    ApiError::new(ErrorCode::NotFound, i18n::message("item_not_found", &[("name", &name)]))
+                })?;
+            state.publish(Actor::Queue, ItemChange::Removed { item });
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::Mutex;
+
+    use aws_config::Region;
+    use aws_credential_types::Credentials;
+    use axum::Router;
+    use axum::http::HeaderMap;
+    use axum::routing::post;
+    use tokio::net::TcpListener;
+    use tokio::sync::mpsc;
+
+    use crate::settings::Settings;
+    use crate::types::AppState;
+
+    /// Minimal SQS endpoint that hands out the messages once and forwards deleted receipt handles.
+    async fn spawn_fake_queue(messages: Vec<serde_json::Value>) -> (String, mpsc::UnboundedReceiver<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let pending = Arc::new(Mutex::new(Some(messages)));
+        let app = Router::new().route(
+            "/",
+            post(move |headers: HeaderMap, body: Bytes| async move {
+                assert!(
+                    headers["authorization"]
+                        .to_str()
+                        .unwrap()
+                        .starts_with("AWS4-HMAC-SHA256")
+                );
+                let request: serde_json::Value = serde_json::from_slice(&body).unwrap();
+                match headers["x-amz-target"].to_str().unwrap() {
+                    "AmazonSQS.ReceiveMessage" => {
+                        let messages = pending.lock().unwrap().take();
+                        if messages.is_none() {
+                            tokio::time::sleep(Duration::from_millis(50)).await;
+                        }
+                        json!({ "Messages": messages.unwrap_or_default() }).to_string()
+                    }
+                    "AmazonSQS.DeleteMessage" => {
+                        sender.send(request["ReceiptHandle"].as_str().unwrap().to_string()).ok();
+                        "{}".to_string()
+                    }
+                    target => panic!("Unexpected SQS action {target}"),
+                }
+            }),
+        );
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        (format!("http://{address}/000000000000/items"), receiver)
+    }
+
+    fn message(receipt_handle: &str, body: &serde_json::Value) -> serde_json::Value {
+        json!({ "MessageId": receipt_handle, "ReceiptHandle": receipt_handle, "Body": body.to_string() })
+    }
+
+    #[tokio::test]
+    async fn commands_are_applied_and_deleted_from_the_queue() {
+        let (queue_url, mut deleted) = spawn_fake_queue(vec![
+            message(
+                "create",
+                &json!({"command": "create", "item": {"name": "queued", "id": 1001}}),
+            ),
+            message("invalid", &json!({"command": "rename"})),
+            message("duplicate", &json!({"command": "create", "item": {"name": "queued"}})),
+            message(
+                "create-other",
+                &json!({"command": "create", "item": {"name": "other", "id": 1002}}),
+            ),
+            message("delete", &json!({"command": "delete", "name": "other"})),
+        ])
+        .await;
+        let config = SdkConfig::builder()
+            .region(Region::new("eu-north-1"))
+            .credentials_provider(SharedCredentialsProvider::new(Credentials::new(
+                "AKIDEXAMPLE",
+                "secret",
+                None,
+                None,
+                "test",
+            )))
+            .build();
+        let client = SqsClient::new(queue_url, &config).unwrap();
+        let state = AppState::new_shared_state();
+        let settings = Settings::default().into_shared();
+        let mut events = state.events().subscribe();
+        let consumer = {
+            let state = Arc::clone(&state);
+            tokio::spawn(async move { consume(&client, &state, &settings).await })
+        };
+
+        let mut receipt_handles = Vec::new();
+        while receipt_handles.len() < 5 {
+            let receipt_handle = tokio::time::timeout(Duration::from_secs(5), deleted.recv())
+                .await
+                .expect("message should be deleted")
+                .unwrap();
+            receipt_handles.push(receipt_handle);
+        }
+        consumer.abort();
+
+        assert_eq!(
+            receipt_handles,
+            ["create", "invalid", "duplicate", "create-other", "delete"]
+        );
+        assert!(state.items().db.contains_key("queued"));
+        assert!(!state.items().db.contains_key("other"));
+        let event = events.recv().await.unwrap();
+        assert_eq!(event.actor, Actor::Queue);
+        assert!(matches!(&event.change, ItemChange::Created { item } if item.name == "queued"));
+    }
+}
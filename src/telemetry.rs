@@ -29,6 +29,7 @@ const INSTRUMENTATION_SCOPE: &str = "axum-example";
 const SERVICE_NAME: &str = "axum-example";
 const UNKNOWN_METHOD: &str = "unknown";
 const UNKNOWN_ROUTE: &str = "unknown";
+const NO_FIELD: &str = "none";
 
 /// OpenTelemetry provider lifecycle plus shared metric instruments.
 #[derive(Debug)]
@@ -46,6 +47,11 @@ pub struct TelemetryMetrics {
     request_duration_ms: Histogram<u64>,
     in_progress_requests: Gauge<u64>,
     errors: Counter<u64>,
+    deprecated_usage: Counter<u64>,
+    items_expired: Counter<u64>,
+    items_evicted: Counter<u64>,
+    item_events: Counter<u64>,
+    item_events_skipped: Counter<u64>,
 }
 
 /// Fields recorded when a request completes.
@@ -187,6 +193,31 @@ impl TelemetryMetrics {
                 .with_description("Completed HTTP requests with error status codes.")
                 .with_unit("1")
                 .build(),
+            deprecated_usage: meter
+                .u64_counter("axum_example_deprecated_usage_total")
+                .with_description("Requests using a deprecated route or field.")
+                .with_unit("1")
+                .build(),
+            items_expired: meter
+                .u64_counter("axum_example_items_expired_total")
+                .with_description("Items removed after their TTL ran out.")
+                .with_unit("1")
+                .build(),
+            items_evicted: meter
+                .u64_counter("axum_example_items_evicted_total")
+                .with_description("Items removed by the eviction policy by reason.")
+                .with_unit("1")
+                .build(),
+            item_events: meter
+                .u64_counter("axum_example_item_events_total")
+                .with_description("Item events published by type.")
+                .with_unit("1")
+                .build(),
+            item_events_skipped: meter
+                .u64_counter("axum_example_item_events_skipped_total")
+                .with_description("Item events a consumer skipped after falling behind the event channel.")
+                .with_unit("1")
+                .build(),
         }
     }
 
@@ -221,6 +252,39 @@ impl TelemetryMetrics {
             );
         }
     }
+
+    /// Record a request using a deprecated route, or a deprecated field when `field` is set.
+    pub fn record_deprecated_usage(&self, route: &str, method: &str, field: Option<&str>) {
+        self.deprecated_usage.add(
+            1,
+            &[
+                KeyValue::new("route", normalized_route(route)),
+                KeyValue::new("method", normalized_method(method)),
+                KeyValue::new("field", field.unwrap_or(NO_FIELD).to_string()),
+            ],
+        );
+    }
+
+    /// Record items removed by the expiry sweeper.
+    pub fn record_items_expired(&self, count: u64) {
+        self.items_expired.add(count, &[]);
+    }
+
+    /// Record items removed by the eviction policy for the given reason.
+    pub fn record_items_evicted(&self, count: u64, reason: &'static str) {
+        self.items_evicted.add(count, &[KeyValue::new("reason", reason)]);
+    }
+
+    /// Record an item event published on the event bus.
+    pub fn record_item_event(&self, event_type: &'static str) {
+        self.item_events.add(1, &[KeyValue::new("type", event_type)]);
+    }
+
+    /// Record item events the named consumer missed because it fell behind.
+    pub fn record_item_events_skipped(&self, consumer: &'static str, count: u64) {
+        self.item_events_skipped
+            .add(count, &[KeyValue::new("consumer", consumer)]);
+    }
 }
 
 fn otlp_metrics_enabled() -> bool {
@@ -341,4 +405,19 @@ mod tests {
         assert!(body.contains("status_class=\"5xx\""));
         assert!(body.contains("status_code=\"503\""));
     }
+
+    #[test]
+    fn records_deprecated_route_and_field_usage() {
+        let telemetry = Telemetry::noop();
+        let metrics = telemetry.metrics();
+
+        metrics.record_deprecated_usage("/item", "get", None);
+        metrics.record_deprecated_usage("/items", "post", Some("id"));
+
+        let (body, _) = telemetry.render_prometheus().expect("metrics render");
+
+        assert!(body.contains("axum_example_deprecated_usage_total"));
+        assert!(body.contains("field=\"none\""));
+        assert!(body.contains("field=\"id\""));
+    }
 }
@@ -0,0 +1,171 @@
+//! Tenant isolation.
+//!
+//! Requests with an `x-tenant-id` header naming one of the configured `tenants`
+//! read and write a separate item store for that tenant,
+//! so the item and admin item routes of one tenant never see the items of another.
+//! Requests without the header use the default collection as before,
+//! and unknown tenants are rejected with `403 Forbidden`.
+//! The tenant is picked once per request and read with [`current`], like the request id,
+//! and every log event of the request is recorded inside a span with the `tenant_id` field.
+
+use axum::extract::{Request, State};
+use axum::http::HeaderName;
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use tracing::Instrument;
+
+use crate::i18n;
+use crate::schemas::{ApiError, ErrorCode};
+use crate::settings::SharedSettings;
+
+/// Header selecting the tenant of a request.
+pub const TENANT_HEADER: HeaderName = HeaderName::from_static("x-tenant-id");
+
+tokio::task_local! {
+    static TENANT: String;
+}
+
+/// Tenant of the request being handled, if it sent a tenant header inside [`tenant_middleware`].
+#[must_use]
+pub fn current() -> Option<String> {
+    TENANT.try_with(Clone::clone).ok()
+}
+
+/// Check the tenant header against the configured tenants and make the tenant available to the handlers.
+pub async fn tenant_middleware(State(settings): State<SharedSettings>, request: Request, next: Next) -> Response {
+    let Some(header) = request.headers().get(TENANT_HEADER) else {
+        return next.run(request).await;
+    };
+    let tenant = header.to_str().unwrap_or_default().to_string();
+    if !settings.load().tenants.contains(&tenant) {
+        crate::log_warn!("Rejected request for unknown tenant: {tenant:?}");
+        return ApiError::new(
+            ErrorCode::Forbidden,
+            i18n::message("unknown_tenant", &[("tenant", &tenant)]),
+        )
+        .into_response();
+    }
+    let span = tracing::info_span!("tenant", tenant_id = %tenant);
+    TENANT.scope(tenant, next.run(request).instrument(span)).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use axum::body::Body;
+    use axum::http::StatusCode;
+    use serde_json::json;
+
+    use crate::schemas::{ItemListResponse, RestoreResponse};
+    use crate::settings::Settings;
+    use crate::snapshot::Snapshot;
+    use crate::test_utils::TestApp;
+    use crate::types::{DEFAULT_API_KEY, IdStrategy, Item, ItemId};
+
+    fn tenant_app() -> TestApp {
+        TestApp::with_settings(Settings {
+            tenants: vec!["acme".to_string(), "globex".to_string()],
+            ..Settings::default()
+        })
+    }
+
+    fn request(method: &str, uri: &str, tenant: &str, body: Body) -> Request {
+        Request::builder()
+            .method(method)
+            .uri(uri)
+            .header(TENANT_HEADER, tenant)
+            .header("api-key", DEFAULT_API_KEY)
+            .header("content-type", "application/json")
+            .body(body)
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn tenants_have_separate_items() {
+        let app = tenant_app();
+        let create = |tenant: &str| {
+            request(
+                "POST",
+                "/items",
+                tenant,
+                Body::from(json!({"name": "shared", "id": 1234}).to_string()),
+            )
+        };
+        assert_eq!(app.send(create("acme")).await.status, StatusCode::CREATED);
+        assert_eq!(app.send(create("globex")).await.status, StatusCode::CREATED);
+        assert_eq!(app.send(create("acme")).await.status, StatusCode::CONFLICT);
+        assert!(app.get_items("").await.names.is_empty());
+
+        let response = app
+            .send(request("DELETE", "/admin/clear_items", "globex", Body::empty()))
+            .await;
+        assert_eq!(response.status, StatusCode::OK);
+        let response = app.send(request("GET", "/items", "acme", Body::empty())).await;
+        assert_eq!(response.json::<ItemListResponse>().names, ["shared"]);
+        let response = app.send(request("GET", "/items", "globex", Body::empty())).await;
+        assert!(response.json::<ItemListResponse>().names.is_empty());
+        let response = app
+            .send(request("GET", "/items/shared/history", "acme", Body::empty()))
+            .await;
+        assert_eq!(response.status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn tenant_backup_and_restore_leave_the_default_items_untouched() {
+        let app = tenant_app();
+        app.state
+            .items()
+            .insert_item(&Item::new("default".to_string(), ItemId::Numeric(1001), IdStrategy::Numeric).unwrap())
+            .unwrap();
+        let response = app
+            .send(request(
+                "POST",
+                "/items",
+                "acme",
+                Body::from(json!({"name": "acme", "id": 1002}).to_string()),
+            ))
+            .await;
+        assert_eq!(response.status, StatusCode::CREATED);
+
+        let response = app.send(request("GET", "/admin/backup", "acme", Body::empty())).await;
+        assert_eq!(response.status, StatusCode::OK);
+        let snapshot = Snapshot::from_gzip(&response.body).unwrap();
+        let names: Vec<_> = snapshot.items.iter().map(|item| item.name.as_str()).collect();
+        assert_eq!(names, ["acme"]);
+
+        let response = app
+            .send(request(
+                "POST",
+                "/admin/restore?mode=replace",
+                "globex",
+                Body::from(response.body),
+            ))
+            .await;
+        assert_eq!(response.status, StatusCode::OK);
+        assert_eq!(response.json::<RestoreResponse>().num_items, 1);
+        let response = app.send(request("GET", "/items", "globex", Body::empty())).await;
+        assert_eq!(response.json::<ItemListResponse>().names, ["acme"]);
+        assert_eq!(app.get_items("").await.names, ["default"]);
+
+        let response = app
+            .send(request("POST", "/admin/compact", "globex", Body::empty()))
+            .await;
+        assert_eq!(response.json::<serde_json::Value>()["num_items"], 1);
+    }
+
+    #[tokio::test]
+    async fn unknown_tenants_are_rejected() {
+        let app = tenant_app();
+        let response = app.send(request("GET", "/items", "initech", Body::empty())).await;
+        assert_eq!(response.status, StatusCode::FORBIDDEN);
+        let error: ApiError = response.json();
+        assert_eq!(error.code, ErrorCode::Forbidden);
+        assert_eq!(error.message, "Unknown tenant: initech");
+
+        let response = TestApp::new()
+            .send(request("GET", "/items", "acme", Body::empty()))
+            .await;
+        assert_eq!(response.status, StatusCode::FORBIDDEN);
+    }
+}
@@ -0,0 +1,146 @@
+//! Router test harness.
+//!
+//! `TestApp` builds the full router with its own state and settings,
+//! and sends requests with the api key and JSON content type already set,
+//! so tests only spell out what they are checking.
+
+use std::sync::Arc;
+
+use axum::Router;
+use axum::body::{Body, Bytes};
+use axum::http::header::CONTENT_TYPE;
+use axum::http::{HeaderMap, Method, Request, StatusCode};
+use http_body_util::BodyExt;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use tower::ServiceExt;
+
+use crate::router::build_router;
+use crate::schemas::ItemListResponse;
+use crate::settings::{Settings, SharedSettings};
+use crate::types::{AppState, Item, SharedState};
+
+/// Full router with the state and settings it was built from.
+pub struct TestApp {
+    pub router: Router,
+    pub state: SharedState,
+    pub settings: SharedSettings,
+}
+
+/// Response with the body already collected.
+#[derive(Debug)]
+pub struct TestResponse {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub body: Bytes,
+}
+
+impl TestApp {
+    /// App with default settings and empty state.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::with_settings(Settings::default())
+    }
+
+    #[must_use]
+    pub fn with_settings(settings: Settings) -> Self {
+        Self::with_state(Arc::new(AppState::new()), settings)
+    }
+
+    #[must_use]
+    pub fn with_state(state: SharedState, settings: Settings) -> Self {
+        let settings = settings.into_shared();
+        Self {
+            router: build_router(&state, &settings),
+            state,
+            settings,
+        }
+    }
+
+    /// `GET` with the api key.
+    pub async fn get(&self, uri: &str) -> TestResponse {
+        self.send(self.request(Method::GET, uri).body(Body::empty()).unwrap())
+            .await
+    }
+
+    /// `DELETE` with the api key.
+    pub async fn delete(&self, uri: &str) -> TestResponse {
+        self.send(self.request(Method::DELETE, uri).body(Body::empty()).unwrap())
+            .await
+    }
+
+    /// `POST` the value as JSON with the api key.
+    pub async fn post_json(&self, uri: &str, body: &(impl Serialize + Sync)) -> TestResponse {
+        let body = serde_json::to_vec(body).expect("test body should serialize");
+        self.send(
+            self.request(Method::POST, uri)
+                .header(CONTENT_TYPE, "application/json")
+                .body(Body::from(body))
+                .unwrap(),
+        )
+        .await
+    }
+
+    /// Send a request as is, for tests that need full control over the headers.
+    pub async fn send(&self, request: Request<Body>) -> TestResponse {
+        let response = self
+            .router
+            .clone()
+            .oneshot(request)
+            .await
+            .expect("router should not fail");
+        let status = response.status();
+        let headers = response.headers().clone();
+        let body = response
+            .into_body()
+            .collect()
+            .await
+            .expect("body should be readable")
+            .to_bytes();
+        TestResponse { status, headers, body }
+    }
+
+    /// Create an item from a JSON payload and return it, failing the test on any other status than `201`.
+    pub async fn create_item(&self, payload: serde_json::Value) -> Item {
+        let response = self.post_json("/api/v1/items", &payload).await;
+        assert_eq!(response.status, StatusCode::CREATED, "{}", response.text());
+        response.json()
+    }
+
+    /// List item names with the query string, like `tag=rust&limit=2`.
+    pub async fn get_items(&self, query: &str) -> ItemListResponse {
+        let uri = if query.is_empty() {
+            "/api/v1/items".to_string()
+        } else {
+            format!("/api/v1/items?{query}")
+        };
+        let response = self.get(&uri).await;
+        assert_eq!(response.status, StatusCode::OK, "{}", response.text());
+        response.json()
+    }
+
+    fn request(&self, method: Method, uri: &str) -> axum::http::request::Builder {
+        Request::builder()
+            .method(method)
+            .uri(uri)
+            .header("api-key", self.settings.load().api_key.as_str())
+    }
+}
+
+impl Default for TestApp {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TestResponse {
+    /// Decode the body as JSON.
+    pub fn json<T: DeserializeOwned>(&self) -> T {
+        serde_json::from_slice(&self.body)
+            .unwrap_or_else(|error| panic!("body should be JSON ({error}): {}", self.text()))
+    }
+
+    pub fn text(&self) -> &str {
+        std::str::from_utf8(&self.body).unwrap_or("<binary body>")
+    }
+}
@@ -2,31 +2,72 @@
 //!
 //! Type definitions for internal types and API configuration.
 
+use std::collections::{HashMap, HashSet};
 use std::env;
-use std::sync::Arc;
+use std::fmt;
+use std::hash::Hash;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
 use std::time::{Duration, Instant};
 
 use anyhow::{Context, anyhow};
 use axum::extract::FromRequestParts;
 use axum::http::request::Parts;
-use chrono::{DateTime, Utc};
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use chrono::{DateTime, TimeDelta, Utc};
 use dashmap::DashMap;
-use rand::RngExt;
+use dashmap::mapref::entry::Entry;
+use rand::rngs::StdRng;
+use rand::{Rng, RngExt, SeedableRng};
 use serde::{Deserialize, Serialize};
-use strum::{Display, EnumString};
+use strum::{Display, EnumIter, EnumString, IntoStaticStr};
 use tracing::level_filters::LevelFilter;
+use ulid::Ulid;
 use utoipa::ToSchema;
+use uuid::Uuid;
 
-use crate::schemas::AuthErrorResponse;
+use crate::backend::Backend;
+use crate::clock::Clock;
+use crate::deprecation::DeprecationRegistry;
+use crate::events::{Actor, EventBus, ItemChange, ItemEvent};
+use crate::history::ItemHistory;
+use crate::inflight::InflightRequests;
+use crate::ip_filter::IpNetwork;
+use crate::jobs::JobQueue;
+use crate::lifecycle::Lifecycle;
+use crate::scheduler::Schedule;
+use crate::schemas::{ApiError, ErrorCode, QuotaExceeded, QuotaResource};
+use crate::settings::SharedSettings;
 use crate::telemetry::Telemetry;
+use crate::tenant;
+use crate::webhooks::WebhookRegistry;
 
 // Thread-safe pointer to app state
 pub type SharedState = Arc<AppState>;
 
-// This should be stored for example in AWS Secrets Manager or similar,
-// for environment-specific API keys
+// Used for local runs, deployed environments fetch the key from a secret provider
 pub const DEFAULT_API_KEY: &str = "axum-api-key";
 
+/// Random bytes in a generated api key.
+const API_KEY_BYTES: usize = 32;
+
+/// Random id draws before giving up, only reached when nearly every id is taken.
+pub const MAX_RANDOM_ID_ATTEMPTS: usize = 100;
+
+/// Default number of items the maps are pre-allocated for.
+pub const DEFAULT_INITIAL_CAPACITY: usize = 8192;
+
+/// Name of the collection served by the `/items` routes.
+pub const DEFAULT_COLLECTION: &str = "default";
+
+/// Most named collections that can exist at once.
+pub const MAX_COLLECTIONS: usize = 100;
+
+/// Maps are shrunk automatically when less than this percentage of their capacity is in use.
+pub const COMPACT_OCCUPANCY_PERCENT: usize = 25;
+
 /// Logging level CLI parameter.
 #[derive(clap::ValueEnum, Clone, Debug, Default)]
 pub enum LogLevel {
@@ -38,160 +79,1647 @@ pub enum LogLevel {
     Error,
 }
 
-/// Runtime environment enum.
-#[derive(Debug, Default, PartialEq, Eq, Serialize, Deserialize, EnumString, Display)]
-#[strum(serialize_all = "UPPERCASE", ascii_case_insensitive)]
-pub enum Environment {
-    Production,
-    Test,
-    Development,
-    #[default]
-    Local,
+/// Format of the stdout logs.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq, Display, Serialize, Deserialize)]
+#[strum(serialize_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// Colored multi-line events with source locations, the default in the local environment.
+    Pretty,
+    /// One JSON object per event, the default outside the local environment.
+    Json,
+    /// One line of `key=value` pairs per event.
+    Logfmt,
+}
+
+/// HTTP protocol versions accepted by the server.
+///
+/// HTTP/2 over cleartext (h2c) uses prior knowledge,
+/// which is what internal load balancers speak to their targets.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq, Display, Serialize, Deserialize)]
+#[strum(serialize_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum HttpProtocol {
+    /// Detect HTTP/1.1 or HTTP/2 per connection.
+    #[default]
+    Auto,
+    /// Only accept HTTP/1.1 connections.
+    Http1,
+    /// Only accept HTTP/2 connections, including h2c.
+    Http2,
+}
+
+/// Where secrets such as the api key are fetched from.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Display)]
+#[serde(rename_all = "kebab-case")]
+#[strum(serialize_all = "kebab-case")]
+pub enum SecretsProvider {
+    /// Environment variables named by the secret id.
+    #[default]
+    Env,
+    /// AWS Secrets Manager secret strings.
+    SecretsManager,
+    /// AWS Systems Manager Parameter Store parameters, decrypted.
+    Ssm,
+}
+
+/// How ids are generated and validated for new items.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Display)]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase")]
+pub enum IdStrategy {
+    /// Random numbers between 1000 and 9999.
+    #[default]
+    Numeric,
+    /// Random UUID version 4.
+    Uuid,
+    /// Lexicographically sortable ULID.
+    Ulid,
+}
+
+/// Interactive documentation UI for the `OpenAPI` spec.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Display, EnumIter)]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase")]
+pub enum DocsUi {
+    /// Swagger UI at `/doc`.
+    Swagger,
+    /// Redoc at `/redoc`.
+    Redoc,
+    /// `RapiDoc` at `/rapidoc`.
+    Rapidoc,
+    /// Scalar at `/scalar`.
+    Scalar,
+}
+
+/// Body format of error responses.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Display)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum ErrorFormat {
+    /// The `ApiError` body with `code`, `message`, `request_id` and `fields`.
+    #[default]
+    ApiError,
+    /// RFC 7807 problem details as `application/problem+json`.
+    Problem,
+}
+
+/// How often the log file is rotated.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Display)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum LogRotation {
+    /// Start a new file every hour.
+    Hourly,
+    /// Start a new file every day.
+    #[default]
+    Daily,
+    /// Keep writing to one file.
+    Never,
+}
+
+/// Item identifier, the format depends on the configured `IdStrategy`.
+///
+/// Serialized as a JSON number for numeric ids and as a string otherwise.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ItemId {
+    Numeric(u64),
+    Uuid(Uuid),
+    Ulid(Ulid),
+}
+
+/// Runtime environment enum.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, EnumString, Display)]
+#[strum(serialize_all = "UPPERCASE", ascii_case_insensitive)]
+pub enum Environment {
+    Production,
+    Test,
+    Development,
+    #[default]
+    Local,
+}
+
+/// Shared state that simulates a database
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AppState {
+    /// The default collection served by the `/items` routes.
+    #[serde(flatten)]
+    items: Arc<Collection>,
+    /// Named collections created through the admin routes.
+    #[serde(skip)]
+    collections: DashMap<String, Arc<Collection>>,
+    /// Item stores of the tenants that have made requests.
+    #[serde(skip)]
+    tenants: DashMap<String, Arc<Collection>>,
+    /// Sizing for new named collections.
+    #[serde(skip)]
+    storage: StorageConfig,
+    #[serde(skip, default = "Instant::now")]
+    start_time: Instant,
+    start_time_utc: DateTime<Utc>,
+    #[serde(skip)]
+    pub(crate) telemetry: Telemetry,
+    #[serde(skip)]
+    deprecations: DeprecationRegistry,
+    #[serde(skip)]
+    lifecycle: Lifecycle,
+    #[serde(skip)]
+    inflight: InflightRequests,
+    #[serde(skip)]
+    events: EventBus,
+    #[serde(skip)]
+    history: ItemHistory,
+    #[serde(skip)]
+    webhooks: WebhookRegistry,
+    #[serde(skip)]
+    jobs: JobQueue,
+    #[serde(skip)]
+    clock: Clock,
+    #[serde(skip)]
+    id_generator: IdGenerator,
+    /// External store behind the default collection, if configured.
+    #[serde(skip)]
+    backend: Option<Backend>,
+}
+
+/// Items keyed by name, with their secondary indexes.
+///
+/// The default collection and every named collection are stored the same way.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Collection {
+    pub db: DashMap<String, Arc<Item>>,
+    /// Lookups by id and tag.
+    /// Not serialized since they only mirror `db`.
+    #[serde(skip)]
+    indexes: ItemIndexes,
+    /// Capacity the maps were created with, they are not shrunk automatically below twice this.
+    #[serde(skip)]
+    initial_capacity: usize,
+    /// Incremented on every item change, so cached reads can tell they are stale.
+    #[serde(skip)]
+    generation: AtomicU64,
+    /// Total size of the item `data` documents, for the storage quota.
+    #[serde(skip)]
+    data_bytes: AtomicUsize,
+    /// When each item was last read or written, for idle eviction.
+    #[serde(skip)]
+    last_access: DashMap<String, DateTime<Utc>>,
+}
+
+/// Named collection that already exists, or the most collections already exist.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CollectionError {
+    Exists(String),
+    TooMany,
+}
+
+/// Secondary indexes that are kept in step with the item map on every change.
+///
+/// The id index also keeps ids unique.
+/// Entries are only added or removed while the item's map entry is locked,
+/// so a concurrent insert and remove of the same name can not leave the indexes out of date.
+#[derive(Debug, Default)]
+pub struct ItemIndexes {
+    ids: DashMap<ItemId, String>,
+    tags: DashMap<String, HashSet<String>>,
+}
+
+/// Item name or id that is already taken.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DuplicateItem {
+    Name(String),
+    Id(ItemId),
+}
+
+/// Per-client request throttling settings.
+///
+/// Defaults depend on the environment.
+/// Zero requests per second turns throttling off.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RateLimitConfig {
+    /// Sustained requests per second allowed for each client IP.
+    pub requests_per_second: u32,
+    /// Requests a client can make at once before being throttled.
+    pub burst: u32,
+}
+
+/// Network restrictions for admin routes.
+///
+/// Empty lists impose no restrictions.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct IpFilterConfig {
+    /// Networks allowed to reach admin routes.
+    pub allowed: Vec<IpNetwork>,
+    /// Networks always rejected, even when also allowed.
+    pub denied: Vec<IpNetwork>,
+    /// Proxies whose `X-Forwarded-For` header is trusted.
+    pub trusted_proxies: Vec<IpNetwork>,
+}
+
+/// Secret provider settings.
+///
+/// The provider and secret ids are read at startup,
+/// secret values are refreshed in the background.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SecretsConfig {
+    /// Where secrets are fetched from.
+    pub provider: SecretsProvider,
+    /// Secret id, parameter name or env variable holding the api key.
+    /// Unset keeps the `api_key` setting.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub api_key_id: Option<String>,
+    /// Seconds between secret refreshes. Zero only fetches secrets at startup.
+    pub refresh_seconds: u64,
+}
+
+/// Item map sizing.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct StorageConfig {
+    /// Number of items the maps are pre-allocated for.
+    pub initial_capacity: usize,
+    /// Number of independently locked map shards.
+    /// Unset uses four per CPU core, rounded up to a power of two.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shard_amount: Option<ShardAmount>,
+}
+
+/// Map shard count, a power of two greater than one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "usize", into = "usize")]
+pub struct ShardAmount(usize);
+
+/// Storage limits for each item store.
+///
+/// The default collection, every named collection and every tenant store are limited separately.
+/// Unset limits are not enforced.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct QuotaConfig {
+    /// Most items in one store.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_items: Option<usize>,
+    /// Largest total size of the item `data` documents in one store.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_data_bytes: Option<usize>,
+}
+
+/// Current consumption of an item store.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StorageUsage {
+    pub num_items: usize,
+    /// Total size of the item `data` documents as compact JSON.
+    pub data_bytes: usize,
+}
+
+/// Global eviction policy for every item store, independent of per-item TTLs.
+///
+/// The `eviction_sweep` task removes items that are older or have been idle for longer than the limits.
+/// Creating an item in a full store evicts the least recently accessed items right away.
+/// Unset limits are not enforced.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EvictionConfig {
+    /// Seconds since an item was created after which it is evicted.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_age_seconds: Option<u64>,
+    /// Seconds since an item was last read or changed after which it is evicted.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_idle_seconds: Option<u64>,
+    /// Most items in one store, the least recently accessed items are evicted past it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_items: Option<usize>,
+}
+
+/// Why an item was evicted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, ToSchema, Display, IntoStaticStr)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum EvictionReason {
+    /// Older than `eviction.max_age_seconds`.
+    MaxAge,
+    /// Not accessed for `eviction.max_idle_seconds`.
+    MaxIdle,
+    /// Least recently accessed item of a store over `eviction.max_items`.
+    MaxItems,
+}
+
+/// External store that the default collection caches, see [`crate::backend`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BackendConfig {
+    /// Where items are stored. Unset keeps items only in memory.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provider: Option<BackendProvider>,
+    /// Directory of the `directory` backend.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub directory: Option<PathBuf>,
+    /// Table of the `dynamodb` backend.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub table: Option<String>,
+    /// Seconds before a cached item is read from the backend again. Zero keeps cached items until they change.
+    pub cache_ttl_seconds: u64,
+}
+
+/// Other instances that item changes are sent to, see [`crate::replication`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ReplicationConfig {
+    /// Base URLs of the peers, like `http://10.0.0.2:3000`. Empty disables replication.
+    #[serde(default)]
+    pub peers: Vec<String>,
+}
+
+/// NATS server that item events are published to, see [`crate::nats`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct NatsConfig {
+    /// Server URL like `nats://localhost:4222`. Unset disables publishing.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    /// Subject prefix that the event type is appended to.
+    pub subject_prefix: String,
+}
+
+/// Kafka cluster that item events are produced to, see [`crate::kafka`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct KafkaConfig {
+    /// Bootstrap brokers like `localhost:9092`. Empty disables producing.
+    #[serde(default)]
+    pub brokers: Vec<String>,
+    /// Topic that the events are produced to.
+    pub topic: String,
+}
+
+/// MQTT broker that item events are published to, see [`crate::mqtt`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MqttConfig {
+    /// Broker host name or IP. Unset disables publishing.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub host: Option<String>,
+    /// Broker port.
+    pub port: u16,
+    /// Client id, which must be unique for each instance. Unset generates one at startup.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_id: Option<String>,
+    /// Topic that events are published to, or the parent of the item topics.
+    pub topic: String,
+    /// Publish events about an item to `{topic}/{item name}` instead of `topic`.
+    pub topic_per_item: bool,
+}
+
+/// SQS queue that item commands are consumed from with the `sqs` cargo feature.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SqsConfig {
+    /// Queue URL like `https://sqs.eu-north-1.amazonaws.com/123456789012/items`. Unset disables consuming.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub queue_url: Option<String>,
+}
+
+/// Kind of external item store.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Display)]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase")]
+pub enum BackendProvider {
+    /// One JSON file per item in a directory.
+    Directory,
+    /// AWS `DynamoDB` table with the string partition key `name`.
+    Dynamodb,
+}
+
+/// Scheduled backup settings.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BackupConfig {
+    /// Directory that snapshots are written to. Unset disables scheduled backups.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub directory: Option<PathBuf>,
+    /// Newest backups to keep in the directory. Zero keeps every backup.
+    pub retention: usize,
+}
+
+/// Request and response body logging, see [`crate::body_logging`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BodyLoggingConfig {
+    /// Log bodies at DEBUG.
+    pub enabled: bool,
+    /// Longest logged body in bytes, longer bodies are cut.
+    pub max_bytes: usize,
+    /// JSON object fields whose values are replaced, compared case-insensitively.
+    pub redact_fields: Vec<String>,
+}
+
+/// Log file written next to the stdout logs.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LogFileConfig {
+    /// Directory that the log files are written to. Unset only logs to stdout.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub directory: Option<PathBuf>,
+    /// How often a new file is started.
+    pub rotation: LogRotation,
+    /// Newest files to keep in the directory. Zero keeps every file.
+    pub max_files: usize,
+}
+
+/// Scheduled background tasks, see [`crate::scheduler`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TasksConfig {
+    /// Write a backup to `backup.directory`, every hour by default.
+    pub backup: TaskConfig,
+    /// Remove expired items, every second by default.
+    pub expiry_sweep: TaskConfig,
+    /// Evict items by the `eviction` policy, every minute by default.
+    pub eviction_sweep: TaskConfig,
+    /// Log the item count and map capacity, disabled by default.
+    pub state_log: TaskConfig,
+}
+
+/// Schedule of one background task.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TaskConfig {
+    pub enabled: bool,
+    /// Interval like `30s` or `6h`, or a five-field cron expression in UTC.
+    pub schedule: Schedule,
+    /// Random delay of up to this many seconds added to every wait.
+    pub jitter_seconds: u64,
+}
+
+/// Deterministic mode for integration tests and recorded fixtures.
+///
+/// Unset fields keep the system clock and random ids.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DeterministicConfig {
+    /// Seed for the random number generator of new item ids.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,
+    /// Fixed time used as the current time, for example `2026-01-01T00:00:00Z`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub time: Option<DateTime<Utc>>,
+}
+
+/// Random source for new item ids, seeded in deterministic mode.
+#[derive(Debug, Default)]
+pub struct IdGenerator {
+    seeded: Option<Mutex<StdRng>>,
+}
+
+/// HTTP connection tuning for the hyper server.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ServerConfig {
+    /// Keep HTTP/1.1 connections open for further requests.
+    pub http1_keep_alive: bool,
+    /// Seconds a client has to send the complete request headers. Unset waits without limit.
+    /// This also closes HTTP/1.1 keep-alive connections that stay idle for longer,
+    /// so keep it above the idle timeout of a load balancer in front of the server.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub header_read_timeout_seconds: Option<u64>,
+    /// Seconds between HTTP/2 keep-alive pings. Unset disables the pings.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub http2_keep_alive_interval_seconds: Option<u64>,
+    /// Seconds to wait for a ping acknowledgement before closing the HTTP/2 connection.
+    pub http2_keep_alive_timeout_seconds: u64,
+    /// Most concurrent requests on one HTTP/2 connection.
+    pub http2_max_concurrent_streams: u32,
+    /// Seconds that open connections get to finish their requests at shutdown before they are closed.
+    pub shutdown_timeout_seconds: u64,
+    /// Seconds after the first shutdown signal to exit even if the shutdown has not finished.
+    /// Unset waits for every shutdown phase.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hard_shutdown_timeout_seconds: Option<u64>,
+    /// Bind with `SO_REUSEPORT` so a new process can listen on the same port before this one stops.
+    pub reuse_port: bool,
+}
+
+/// Certificate and key for serving HTTPS.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TlsConfig {
+    /// PEM file with the certificate chain, leaf certificate first.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cert: Option<PathBuf>,
+    /// PEM file with the private key of the leaf certificate.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key: Option<PathBuf>,
+    /// Port that answers plain HTTP requests with a redirect to HTTPS. Unset serves no redirect.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub redirect_port: Option<u16>,
+}
+
+/// Item information
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct Item {
+    pub id: ItemId,
+    #[schema(example = "esgrove")]
+    pub name: String,
+    /// Tags for grouping and filtering, sorted and unique.
+    #[serde(default)]
+    #[schema(example = json!(["example", "rust"]))]
+    pub tags: Vec<String>,
+    /// Free-form key-value metadata.
+    #[serde(default)]
+    #[schema(example = json!({"owner": "esgrove"}))]
+    pub metadata: HashMap<String, String>,
+    /// Free-form JSON document provided by the client.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schema(example = json!({"owner": {"name": "esgrove", "teams": ["core"]}}))]
+    pub data: Option<serde_json::Value>,
+    /// Incremented on every change, send it in `If-Match` when changing the item.
+    #[schema(example = 1)]
+    pub revision: u64,
+    /// When the item was created.
+    #[schema(example = "2026-06-10T09:00:00Z")]
+    pub created_at: DateTime<Utc>,
+    /// When the item was last changed, same as `created_at` for unchanged items.
+    #[schema(example = "2026-06-10T09:00:00Z")]
+    pub updated_at: DateTime<Utc>,
+    /// When the item expires and is removed, if it was created with a TTL.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schema(example = "2026-06-10T10:00:00Z")]
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+/// Item map capacity before and after shrinking.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Compaction {
+    pub capacity_before: usize,
+    pub capacity_after: usize,
+}
+
+/// Custom extractor for checking api key.
+///
+/// Note: requires the `SharedSettings` extension to be present in the route as well,
+/// so the correct api key can be accessed.
+pub struct ApiKeyExtractor;
+
+impl AppState {
+    #[allow(unused)]
+    #[must_use]
+    pub fn new() -> Self {
+        Self::new_with_telemetry(Telemetry::noop(), &StorageConfig::default())
+    }
+
+    #[must_use]
+    pub fn new_with_telemetry(telemetry: Telemetry, storage: &StorageConfig) -> Self {
+        Self {
+            items: Arc::new(Collection::new(storage)),
+            collections: DashMap::new(),
+            tenants: DashMap::new(),
+            storage: storage.clone(),
+            start_time: Instant::now(),
+            start_time_utc: Utc::now(),
+            telemetry,
+            deprecations: DeprecationRegistry::default(),
+            lifecycle: Lifecycle::default(),
+            inflight: InflightRequests::default(),
+            events: EventBus::default(),
+            history: ItemHistory::default(),
+            webhooks: WebhookRegistry::default(),
+            jobs: JobQueue::default(),
+            clock: Clock::default(),
+            id_generator: IdGenerator::default(),
+            backend: None,
+        }
+    }
+
+    /// Use a fixed clock and seeded ids when the deterministic mode is configured.
+    #[must_use]
+    pub fn with_deterministic(mut self, config: &DeterministicConfig) -> Self {
+        if let Some(time) = config.time {
+            self.clock = Clock::Fixed(time);
+            self.start_time_utc = time;
+        }
+        if let Some(seed) = config.seed {
+            self.id_generator = IdGenerator::seeded(seed);
+        }
+        self
+    }
+
+    /// Keep the default collection as a cache over the backend.
+    #[must_use]
+    pub fn with_backend(mut self, backend: Option<Backend>) -> Self {
+        self.backend = backend;
+        self
+    }
+
+    /// Replace the registry of deprecated routes and fields.
+    #[must_use]
+    pub fn with_deprecations(mut self, deprecations: DeprecationRegistry) -> Self {
+        self.deprecations = deprecations;
+        self
+    }
+
+    #[cfg(test)]
+    #[must_use]
+    pub fn new_shared_state() -> SharedState {
+        Arc::new(Self::new())
+    }
+
+    pub fn new_shared_state_from_env(
+        storage: &StorageConfig,
+        deterministic: &DeterministicConfig,
+        deprecations: DeprecationRegistry,
+        backend: Option<Backend>,
+    ) -> anyhow::Result<SharedState> {
+        Ok(Arc::new(
+            Self::new_with_telemetry(Telemetry::from_env()?, storage)
+                .with_deterministic(deterministic)
+                .with_deprecations(deprecations)
+                .with_backend(backend),
+        ))
+    }
+
+    /// The default collection.
+    pub fn items(&self) -> &Collection {
+        &self.items
+    }
+
+    /// External store behind the default collection.
+    #[must_use]
+    pub const fn backend(&self) -> Option<&Backend> {
+        self.backend.as_ref()
+    }
+
+    /// Items of the tenant of the current request, the default collection without a tenant.
+    ///
+    /// A tenant store is created on its first request.
+    #[must_use]
+    pub fn tenant_items(&self) -> Arc<Collection> {
+        tenant::current().map_or_else(
+            || Arc::clone(&self.items),
+            |tenant| {
+                Arc::clone(
+                    &self
+                        .tenants
+                        .entry(tenant)
+                        .or_insert_with(|| Arc::new(Collection::new(&self.storage))),
+                )
+            },
+        )
+    }
+
+    /// Item stores of every tenant that has made requests.
+    #[must_use]
+    pub fn tenant_stores(&self) -> Vec<Arc<Collection>> {
+        self.tenants.iter().map(|entry| Arc::clone(entry.value())).collect()
+    }
+
+    /// Collection with the given name, [`DEFAULT_COLLECTION`] for the default one.
+    #[must_use]
+    pub fn collection(&self, name: &str) -> Option<Arc<Collection>> {
+        if name == DEFAULT_COLLECTION {
+            return Some(Arc::clone(&self.items));
+        }
+        self.collections.get(name).map(|collection| Arc::clone(&collection))
+    }
+
+    /// Add an empty named collection.
+    pub fn create_collection(&self, name: &str) -> Result<Arc<Collection>, CollectionError> {
+        if name == DEFAULT_COLLECTION {
+            return Err(CollectionError::Exists(name.to_string()));
+        }
+        if self.collections.len() >= MAX_COLLECTIONS {
+            return Err(CollectionError::TooMany);
+        }
+        match self.collections.entry(name.to_string()) {
+            Entry::Occupied(_) => Err(CollectionError::Exists(name.to_string())),
+            Entry::Vacant(entry) => Ok(Arc::clone(&entry.insert(Arc::new(Collection::new(&self.storage))))),
+        }
+    }
+
+    /// Remove a named collection with all its items.
+    /// The default collection can not be dropped.
+    pub fn drop_collection(&self, name: &str) -> Option<Arc<Collection>> {
+        self.collections.remove(name).map(|(_, collection)| collection)
+    }
+
+    /// Names and collections, the default collection first and the named ones sorted by name.
+    pub fn collections(&self) -> Vec<(String, Arc<Collection>)> {
+        let mut named: Vec<(String, Arc<Collection>)> = self
+            .collections
+            .iter()
+            .map(|entry| (entry.key().clone(), Arc::clone(entry.value())))
+            .collect();
+        named.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+        let mut collections = vec![(DEFAULT_COLLECTION.to_string(), Arc::clone(&self.items))];
+        collections.extend(named);
+        collections
+    }
+
+    pub fn uptime(&self) -> Duration {
+        self.start_time.elapsed()
+    }
+
+    pub const fn start_time_utc(&self) -> DateTime<Utc> {
+        self.start_time_utc
+    }
+
+    pub const fn telemetry(&self) -> &Telemetry {
+        &self.telemetry
+    }
+
+    pub const fn deprecations(&self) -> &DeprecationRegistry {
+        &self.deprecations
+    }
+
+    /// Current time from the state's clock.
+    #[must_use]
+    pub fn now(&self) -> DateTime<Utc> {
+        self.clock.now()
+    }
+
+    /// Draw a new item id, from the seeded generator in deterministic mode.
+    #[must_use]
+    pub fn generate_id(&self, strategy: IdStrategy) -> ItemId {
+        self.id_generator.generate(strategy, self.now())
+    }
+
+    pub const fn lifecycle(&self) -> &Lifecycle {
+        &self.lifecycle
+    }
+
+    pub const fn inflight(&self) -> &InflightRequests {
+        &self.inflight
+    }
+
+    pub const fn events(&self) -> &EventBus {
+        &self.events
+    }
+
+    pub const fn webhooks(&self) -> &WebhookRegistry {
+        &self.webhooks
+    }
+
+    pub const fn jobs(&self) -> &JobQueue {
+        &self.jobs
+    }
+
+    pub const fn history(&self) -> &ItemHistory {
+        &self.history
+    }
+
+    /// Record an item change in the item history and publish it to the event subscribers,
+    /// timestamped with the state's clock.
+    ///
+    /// Changes made for a tenant are not published,
+    /// so the history and webhooks only cover the default collection.
+    pub fn publish(&self, actor: Actor, change: ItemChange) {
+        if tenant::current().is_some() {
+            return;
+        }
+        let event = ItemEvent {
+            occurred_at: self.now(),
+            actor,
+            change,
+        };
+        self.history.record(&event);
+        self.events.publish(event);
+    }
+
+    #[allow(unused)]
+    /// Serialize to pretty json.
+    pub fn to_json_pretty(&self) -> anyhow::Result<String> {
+        serde_json::to_string_pretty(self).context("Failed to serialize state")
+    }
+}
+
+impl Collection {
+    #[must_use]
+    pub fn new(storage: &StorageConfig) -> Self {
+        Self {
+            db: new_map(storage),
+            indexes: ItemIndexes {
+                ids: new_map(storage),
+                tags: DashMap::new(),
+            },
+            initial_capacity: storage.initial_capacity,
+            generation: AtomicU64::new(0),
+            data_bytes: AtomicUsize::new(0),
+            last_access: DashMap::new(),
+        }
+    }
+
+    /// Add a new item if both its name and id are free.
+    ///
+    /// The id is reserved in the index first and released again if the name is taken,
+    /// so concurrent creates can not end up with the same id.
+    pub fn insert_item(&self, item: &Item) -> Result<(), DuplicateItem> {
+        if !self.indexes.reserve_id(item) {
+            return Err(DuplicateItem::Id(item.id));
+        }
+        match self.db.entry(item.name.clone()) {
+            Entry::Occupied(_) => {
+                self.indexes.release_id(&item.id);
+                Err(DuplicateItem::Name(item.name.clone()))
+            }
+            Entry::Vacant(entry) => {
+                self.indexes.add_tags(item);
+                self.last_access.insert(item.name.clone(), item.updated_at);
+                entry.insert(Arc::new(item.clone()));
+                self.data_bytes.fetch_add(item.data_size(), Ordering::Relaxed);
+                self.bump_generation();
+                Ok(())
+            }
+        }
+    }
+
+    /// Add a new item, drawing a new random id while the current one is taken.
+    ///
+    /// Gives up with `DuplicateItem::Id` after `MAX_RANDOM_ID_ATTEMPTS` draws.
+    pub fn insert_item_with_random_id(
+        &self,
+        item: &mut Item,
+        mut new_id: impl FnMut() -> ItemId,
+    ) -> Result<(), DuplicateItem> {
+        let mut attempts = 1;
+        loop {
+            match self.insert_item(item) {
+                Err(DuplicateItem::Id(_)) if attempts < MAX_RANDOM_ID_ATTEMPTS => {
+                    item.id = new_id();
+                    attempts += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Add all items or none of them.
+    ///
+    /// Fails on the first name or id that is already taken, by a stored item or an earlier item in the batch,
+    /// and removes the items of the batch that were added before it.
+    /// Concurrent readers can briefly see those items before they are removed again.
+    pub fn insert_items(&self, items: &[Item]) -> Result<(), DuplicateItem> {
+        for (index, item) in items.iter().enumerate() {
+            if let Err(duplicate) = self.insert_item(item) {
+                for inserted in &items[..index] {
+                    self.remove_item_if(&inserted.name, |stored| stored.id == inserted.id);
+                }
+                return Err(duplicate);
+            }
+        }
+        Ok(())
+    }
+
+    /// Remove the named item if the predicate holds for it.
+    pub fn remove_item_if(&self, name: &str, predicate: impl FnOnce(&Item) -> bool) -> Option<Item> {
+        let Entry::Occupied(entry) = self.db.entry(name.to_string()) else {
+            return None;
+        };
+        if !predicate(entry.get()) {
+            return None;
+        }
+        self.indexes.remove(entry.get());
+        self.last_access.remove(name);
+        let item = entry.remove();
+        self.data_bytes.fetch_sub(item.data_size(), Ordering::Relaxed);
+        self.bump_generation();
+        self.compact_if_sparse();
+        Some(Arc::unwrap_or_clone(item))
+    }
+
+    /// Remove items that have expired by the given time and return them.
+    pub fn remove_expired_items(&self, now: DateTime<Utc>) -> Vec<Item> {
+        let expired: Vec<String> = self
+            .db
+            .iter()
+            .filter(|entry| entry.is_expired(now))
+            .map(|entry| entry.key().clone())
+            .collect();
+        expired
+            .iter()
+            .filter_map(|name| self.remove_item_if(name, |item| item.is_expired(now)))
+            .collect()
+    }
+
+    /// Remove items that the eviction policy no longer allows by the given time and return them.
+    pub fn remove_evicted_items(&self, policy: &EvictionConfig, now: DateTime<Utc>) -> Vec<(Item, EvictionReason)> {
+        let evicted: Vec<String> = self
+            .db
+            .iter()
+            .filter(|entry| policy.reason(entry, self.last_accessed(entry.key()), now).is_some())
+            .map(|entry| entry.key().clone())
+            .collect();
+        evicted
+            .iter()
+            .filter_map(|name| {
+                let last_access = self.last_accessed(name);
+                let mut reason = None;
+                let item = self.remove_item_if(name, |item| {
+                    reason = policy.reason(item, last_access, now);
+                    reason.is_some()
+                })?;
+                Some((item, reason?))
+            })
+            .collect()
+    }
+
+    /// Remove the least recently accessed items until at most `max_items` are left and return them.
+    ///
+    /// The item named `keep`, like one that was just created, is never removed.
+    pub fn remove_least_recently_used(&self, max_items: usize, keep: Option<&str>) -> Vec<Item> {
+        let excess = self.db.len().saturating_sub(max_items);
+        if excess == 0 {
+            return Vec::new();
+        }
+        let mut candidates: Vec<(DateTime<Utc>, String)> = self
+            .last_access
+            .iter()
+            .filter(|entry| keep != Some(entry.key().as_str()))
+            .map(|entry| (*entry.value(), entry.key().clone()))
+            .collect();
+        // Only the oldest accesses are needed, so partition instead of sorting every item.
+        if excess < candidates.len() {
+            candidates.select_nth_unstable(excess);
+            candidates.truncate(excess);
+        }
+        candidates
+            .into_iter()
+            .filter_map(|(_, name)| self.remove_item_if(&name, |_| true))
+            .collect()
+    }
+
+    /// Record that the named item was read, if it exists.
+    pub fn record_access(&self, name: &str, now: DateTime<Utc>) {
+        if let Some(mut last_access) = self.last_access.get_mut(name) {
+            *last_access = now;
+        }
+    }
+
+    /// When the named item was last read, or written if it has not been read since.
+    pub fn last_accessed(&self, name: &str) -> Option<DateTime<Utc>> {
+        self.last_access.get(name).map(|last_access| *last_access)
+    }
+
+    /// Remove all items and return how many there were.
+    pub fn clear_items(&self) -> usize {
+        let number_of_items = self.db.len();
+        self.db.clear();
+        self.indexes.clear();
+        self.last_access.clear();
+        self.data_bytes.store(0, Ordering::Relaxed);
+        self.bump_generation();
+        self.compact_if_sparse();
+        number_of_items
+    }
+
+    /// Release unused capacity from the item maps.
+    pub fn compact(&self) -> Compaction {
+        let capacity_before = self.db.capacity();
+        self.db.shrink_to_fit();
+        self.indexes.shrink_to_fit();
+        Compaction {
+            capacity_before,
+            capacity_after: self.db.capacity(),
+        }
+    }
+
+    /// Item with the given id.
+    pub fn get_item_by_id(&self, id: &ItemId) -> Option<Arc<Item>> {
+        let name = self.indexes.ids.get(id)?.clone();
+        self.db.get(&name).map(|item| Arc::clone(&item))
+    }
+
+    /// Items that have the given tag, in no particular order.
+    pub fn items_with_tag(&self, tag: &str) -> Vec<Arc<Item>> {
+        let names: Vec<String> = self
+            .indexes
+            .tags
+            .get(tag)
+            .map(|names| names.iter().cloned().collect())
+            .unwrap_or_default();
+        names
+            .iter()
+            .filter_map(|name| self.db.get(name).map(|item| Arc::clone(&item)))
+            .collect()
+    }
+
+    /// Check that the id and tag indexes match the stored items and return the first mismatch.
+    pub fn check_indexes(&self) -> Result<(), String> {
+        if self.indexes.ids.len() != self.db.len() {
+            return Err(format!(
+                "{} indexed ids for {} items",
+                self.indexes.ids.len(),
+                self.db.len()
+            ));
+        }
+        for entry in &self.db {
+            let item = entry.value();
+            if self.indexes.ids.get(&item.id).as_deref() != Some(&item.name) {
+                return Err(format!("id {} is not indexed for {}", item.id, item.name));
+            }
+            if let Some(tag) = item.tags.iter().find(|tag| {
+                self.indexes
+                    .tags
+                    .get(*tag)
+                    .is_none_or(|names| !names.contains(&item.name))
+            }) {
+                return Err(format!("tag {tag} is not indexed for {}", item.name));
+            }
+        }
+        for entry in &self.indexes.tags {
+            if let Some(name) = entry
+                .value()
+                .iter()
+                .find(|name| self.db.get(*name).is_none_or(|item| !item.has_tag(entry.key())))
+            {
+                return Err(format!("tag {} is indexed for {name} without the tag", entry.key()));
+            }
+        }
+        Ok(())
+    }
+
+    /// Number of items and the total size of their `data` documents.
+    pub fn usage(&self) -> StorageUsage {
+        StorageUsage {
+            num_items: self.db.len(),
+            data_bytes: self.data_bytes.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Counter that changes whenever an item is added or removed.
+    pub fn generation(&self) -> u64 {
+        self.generation.load(Ordering::Acquire)
+    }
+
+    fn bump_generation(&self) {
+        self.generation.fetch_add(1, Ordering::Release);
+    }
+
+    /// Compact the maps if they have grown past the initial capacity and are now mostly empty.
+    ///
+    /// Maps at or below twice the initial capacity are left alone,
+    /// so removals in a normally sized collection do not shrink and regrow the maps.
+    fn compact_if_sparse(&self) {
+        let capacity = self.db.capacity();
+        if capacity <= 2 * self.initial_capacity || self.db.len() * 100 >= capacity * COMPACT_OCCUPANCY_PERCENT {
+            return;
+        }
+        let compaction = self.compact();
+        crate::log_info!(
+            "Compacted item maps from capacity {} to {}",
+            compaction.capacity_before,
+            compaction.capacity_after
+        );
+    }
+}
+
+impl ItemIndexes {
+    /// Claim the item's id for its name, false if the id is already taken.
+    fn reserve_id(&self, item: &Item) -> bool {
+        match self.ids.entry(item.id) {
+            Entry::Occupied(_) => false,
+            Entry::Vacant(entry) => {
+                entry.insert(item.name.clone());
+                true
+            }
+        }
+    }
+
+    fn release_id(&self, id: &ItemId) {
+        self.ids.remove(id);
+    }
+
+    fn add_tags(&self, item: &Item) {
+        for tag in &item.tags {
+            self.tags.entry(tag.clone()).or_default().insert(item.name.clone());
+        }
+    }
+
+    /// Drop every index entry for the item.
+    fn remove(&self, item: &Item) {
+        self.release_id(&item.id);
+        for tag in &item.tags {
+            self.tags.remove_if_mut(tag, |_, names| {
+                names.remove(&item.name);
+                names.is_empty()
+            });
+        }
+    }
+
+    fn clear(&self) {
+        self.ids.clear();
+        self.tags.clear();
+    }
+
+    fn shrink_to_fit(&self) {
+        self.ids.shrink_to_fit();
+        self.tags.shrink_to_fit();
+    }
+}
+
+impl Default for AppState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Item {
+    /// Try to create new Item with given name and id.
+    /// Returns Err if id does not fit the id strategy.
+    pub fn new(name: String, id: ItemId, strategy: IdStrategy) -> anyhow::Result<Self> {
+        strategy.check(id).map_err(|message| anyhow!("ID {message}"))?;
+        Ok(Self::new_unchecked(name, id))
+    }
+
+    #[must_use]
+    pub fn new_with_random_id(name: String, strategy: IdStrategy) -> Self {
+        Self::new_unchecked(name, strategy.generate())
+    }
+
+    /// Set the creation and update time, for items created with a clock other than the system clock.
+    #[must_use]
+    pub const fn with_created_at(mut self, created_at: DateTime<Utc>) -> Self {
+        self.created_at = created_at;
+        self.updated_at = created_at;
+        self
+    }
+
+    /// Set tags, dropping duplicates.
+    #[must_use]
+    pub fn with_tags(mut self, mut tags: Vec<String>) -> Self {
+        tags.sort();
+        tags.dedup();
+        self.tags = tags;
+        self
+    }
+
+    /// Set key-value metadata.
+    #[must_use]
+    pub fn with_metadata(mut self, metadata: HashMap<String, String>) -> Self {
+        self.metadata = metadata;
+        self
+    }
+
+    /// Expire the item the given number of seconds after it was created.
+    #[must_use]
+    pub fn with_ttl(mut self, ttl_seconds: Option<u64>) -> Self {
+        self.expires_at = ttl_seconds
+            .and_then(|seconds| TimeDelta::try_seconds(i64::try_from(seconds).ok()?))
+            .and_then(|ttl| self.created_at.checked_add_signed(ttl));
+        self
+    }
+
+    /// Size of the `data` document serialized as compact JSON, or zero without one.
+    #[must_use]
+    pub fn data_size(&self) -> usize {
+        self.data
+            .as_ref()
+            .and_then(|data| serde_json::to_vec(data).ok())
+            .map_or(0, |bytes| bytes.len())
+    }
+
+    /// Check if the item has expired by the given time.
+    #[must_use]
+    pub fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        self.expires_at.is_some_and(|expires_at| expires_at <= now)
+    }
+
+    /// Set the JSON document.
+    #[must_use]
+    pub fn with_data(mut self, data: Option<serde_json::Value>) -> Self {
+        self.data = data;
+        self
+    }
+
+    /// Check if the item has the tag.
+    #[allow(unused)]
+    #[must_use]
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags
+            .binary_search_by(|existing| existing.as_str().cmp(tag))
+            .is_ok()
+    }
+
+    /// Check if a client provided id is in the allowed range.
+    #[must_use]
+    pub fn is_valid_id(id: u64) -> bool {
+        (1000..=9999).contains(&id)
+    }
+
+    /// Check the rules every stored item follows and return the first one that is broken.
+    pub fn check_invariants(&self, strategy: IdStrategy) -> Result<(), &'static str> {
+        strategy.check(self.id)?;
+        if self.revision == 0 {
+            return Err("revision must start from 1");
+        }
+        if self.updated_at < self.created_at {
+            return Err("updated_at must not be before created_at");
+        }
+        if !self.tags.is_sorted_by(|a, b| a < b) {
+            return Err("tags must be sorted and unique");
+        }
+        if self.expires_at.is_some_and(|expires_at| expires_at < self.created_at) {
+            return Err("expires_at must not be before created_at");
+        }
+        Ok(())
+    }
+
+    fn new_unchecked(name: String, id: ItemId) -> Self {
+        let now = Utc::now();
+        Self {
+            id,
+            name,
+            tags: Vec::new(),
+            metadata: HashMap::new(),
+            data: None,
+            revision: 1,
+            created_at: now,
+            updated_at: now,
+            expires_at: None,
+        }
+    }
+}
+
+impl IdStrategy {
+    /// Generate a new random id.
+    #[must_use]
+    pub fn generate(self) -> ItemId {
+        self.generate_with(&mut rand::rng(), Utc::now())
+    }
+
+    /// Generate a new id from the random source, with the time for the ULID timestamp.
+    pub fn generate_with(self, rng: &mut impl Rng, now: DateTime<Utc>) -> ItemId {
+        match self {
+            Self::Numeric => ItemId::Numeric(rng.random_range(1000..=9999)),
+            Self::Uuid => ItemId::Uuid(uuid::Builder::from_random_bytes(rng.random()).into_uuid()),
+            Self::Ulid => ItemId::Ulid(Ulid::from_parts(
+                u64::try_from(now.timestamp_millis()).unwrap_or_default(),
+                rng.random(),
+            )),
+        }
+    }
+
+    /// Check that a client provided id fits this strategy.
+    ///
+    /// Returns the violated rule for the error message.
+    pub fn check(self, id: ItemId) -> Result<(), &'static str> {
+        match (self, id) {
+            (Self::Numeric, ItemId::Numeric(id)) if Item::is_valid_id(id) => Ok(()),
+            (Self::Uuid, ItemId::Uuid(_)) | (Self::Ulid, ItemId::Ulid(_)) => Ok(()),
+            (Self::Numeric, _) => Err("must be between 1000 and 9999"),
+            (Self::Uuid, _) => Err("must be a UUID"),
+            (Self::Ulid, _) => Err("must be a ULID"),
+        }
+    }
+}
+
+impl DocsUi {
+    /// Path the UI is served at.
+    #[must_use]
+    pub const fn path(self) -> &'static str {
+        match self {
+            Self::Swagger => "/doc",
+            Self::Redoc => "/redoc",
+            Self::Rapidoc => "/rapidoc",
+            Self::Scalar => "/scalar",
+        }
+    }
+}
+
+impl fmt::Display for ItemId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Numeric(id) => write!(f, "{id}"),
+            Self::Uuid(id) => write!(f, "{id}"),
+            Self::Ulid(id) => write!(f, "{id}"),
+        }
+    }
+}
+
+impl LogLevel {
+    /// Convert CLI log level to tracing log level filter
+    #[must_use]
+    pub const fn to_filter(&self) -> LevelFilter {
+        match self {
+            Self::Trace => LevelFilter::TRACE,
+            Self::Debug => LevelFilter::DEBUG,
+            Self::Info => LevelFilter::INFO,
+            Self::Warn => LevelFilter::WARN,
+            Self::Error => LevelFilter::ERROR,
+        }
+    }
+}
+
+impl RateLimitConfig {
+    /// Check if throttling is turned on.
+    #[must_use]
+    pub const fn is_enabled(&self) -> bool {
+        self.requests_per_second > 0
+    }
+
+    /// Default throttling for the runtime environment.
+    ///
+    /// Local runs are not throttled so load testing on a laptop just works.
+    #[must_use]
+    pub const fn for_environment(env: Environment) -> Self {
+        match env {
+            Environment::Production => Self {
+                requests_per_second: 20,
+                burst: 40,
+            },
+            Environment::Test | Environment::Development => Self {
+                requests_per_second: 50,
+                burst: 100,
+            },
+            Environment::Local => Self {
+                requests_per_second: 0,
+                burst: 0,
+            },
+        }
+    }
+}
+
+impl QuotaConfig {
+    /// Check the usage a write would lead to against the limits.
+    pub fn check(&self, usage: StorageUsage) -> Result<(), QuotaExceeded> {
+        if let Some(limit) = self.max_items.filter(|limit| usage.num_items > *limit) {
+            return Err(QuotaExceeded {
+                resource: QuotaResource::Items,
+                limit,
+                requested: usage.num_items,
+            });
+        }
+        if let Some(limit) = self.max_data_bytes.filter(|limit| usage.data_bytes > *limit) {
+            return Err(QuotaExceeded {
+                resource: QuotaResource::DataBytes,
+                limit,
+                requested: usage.data_bytes,
+            });
+        }
+        Ok(())
+    }
 }
 
-/// Shared state that simulates a database
-#[derive(Debug, Serialize, Deserialize)]
-pub struct AppState {
-    pub db: DashMap<String, Item>,
-    #[serde(skip, default = "Instant::now")]
-    start_time: Instant,
-    start_time_utc: DateTime<Utc>,
-    #[serde(skip)]
-    pub(crate) telemetry: Telemetry,
-}
+impl EvictionConfig {
+    /// No limit is set, so nothing is ever evicted.
+    #[must_use]
+    pub const fn is_disabled(&self) -> bool {
+        self.max_age_seconds.is_none() && self.max_idle_seconds.is_none() && self.max_items.is_none()
+    }
 
-/// API config for passing settings to routes.
-#[derive(Debug, Serialize, Deserialize)]
-pub struct Config {
-    pub api_key: String,
-    pub env: Environment,
+    /// Why the item should be evicted at the given time, if it should.
+    ///
+    /// Items without a recorded access are idle since their last change.
+    #[must_use]
+    pub fn reason(
+        &self,
+        item: &Item,
+        last_access: Option<DateTime<Utc>>,
+        now: DateTime<Utc>,
+    ) -> Option<EvictionReason> {
+        let exceeds = |since: DateTime<Utc>, limit: Option<u64>| {
+            limit
+                .and_then(|seconds| TimeDelta::try_seconds(i64::try_from(seconds).ok()?))
+                .is_some_and(|limit| now - since >= limit)
+        };
+        if exceeds(item.created_at, self.max_age_seconds) {
+            Some(EvictionReason::MaxAge)
+        } else if exceeds(last_access.unwrap_or(item.updated_at), self.max_idle_seconds) {
+            Some(EvictionReason::MaxIdle)
+        } else {
+            None
+        }
+    }
 }
 
-/// Item information
-#[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd, Serialize, Deserialize, ToSchema)]
-pub struct Item {
-    #[schema(example = "1234")]
-    pub id: u64,
-    #[schema(example = "esgrove")]
-    pub name: String,
+impl Default for StorageConfig {
+    fn default() -> Self {
+        Self {
+            initial_capacity: DEFAULT_INITIAL_CAPACITY,
+            shard_amount: None,
+        }
+    }
 }
 
-/// Custom extractor for checking api key.
-///
-/// Note: requires the Config extension to be present in the route as well,
-/// so the correct api key can be accessed.
-pub struct ApiKeyExtractor;
+impl TryFrom<usize> for ShardAmount {
+    type Error = String;
 
-impl AppState {
-    #[allow(unused)]
-    pub fn new() -> Self {
-        Self::new_with_telemetry(Telemetry::noop())
+    fn try_from(shards: usize) -> Result<Self, Self::Error> {
+        if shards > 1 && shards.is_power_of_two() {
+            Ok(Self(shards))
+        } else {
+            Err(format!(
+                "shard amount must be a power of two greater than one, got {shards}"
+            ))
+        }
+    }
+}
+
+impl From<ShardAmount> for usize {
+    fn from(shards: ShardAmount) -> Self {
+        shards.0
     }
+}
 
-    pub fn new_with_telemetry(telemetry: Telemetry) -> Self {
+impl IdGenerator {
+    #[must_use]
+    pub fn seeded(seed: u64) -> Self {
         Self {
-            db: DashMap::with_capacity(8192),
-            start_time: Instant::now(),
-            start_time_utc: Utc::now(),
-            telemetry,
+            seeded: Some(Mutex::new(StdRng::seed_from_u64(seed))),
         }
     }
 
-    #[cfg(test)]
-    pub fn new_shared_state() -> SharedState {
-        Arc::new(Self::new())
+    /// Draw an id, from the thread-local random source when not seeded.
+    pub fn generate(&self, strategy: IdStrategy, now: DateTime<Utc>) -> ItemId {
+        if let Some(rng) = &self.seeded {
+            let mut rng = rng.lock().unwrap_or_else(PoisonError::into_inner);
+            return strategy.generate_with(&mut *rng, now);
+        }
+        strategy.generate_with(&mut rand::rng(), now)
     }
+}
 
-    pub fn new_shared_state_from_env() -> anyhow::Result<SharedState> {
-        Ok(Arc::new(Self::new_with_telemetry(Telemetry::from_env()?)))
+impl DeterministicConfig {
+    /// Check if either the seed or the time is set.
+    #[must_use]
+    pub const fn is_enabled(&self) -> bool {
+        self.seed.is_some() || self.time.is_some()
     }
+}
 
-    pub fn uptime(&self) -> Duration {
-        self.start_time.elapsed()
+impl BackendConfig {
+    #[must_use]
+    pub const fn cache_ttl(&self) -> Duration {
+        Duration::from_secs(self.cache_ttl_seconds)
     }
+}
 
-    pub const fn start_time_utc(&self) -> DateTime<Utc> {
-        self.start_time_utc
+impl Default for BackendConfig {
+    fn default() -> Self {
+        Self {
+            provider: None,
+            directory: None,
+            table: None,
+            cache_ttl_seconds: 60,
+        }
     }
+}
 
-    pub const fn telemetry(&self) -> &Telemetry {
-        &self.telemetry
+impl Default for BackupConfig {
+    fn default() -> Self {
+        Self {
+            directory: None,
+            retention: 24,
+        }
     }
+}
 
-    #[allow(unused)]
-    /// Serialize to pretty json.
-    pub fn to_json_pretty(&self) -> anyhow::Result<String> {
-        serde_json::to_string_pretty(self).context("Failed to serialize state")
+impl Default for BodyLoggingConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_bytes: 4096,
+            redact_fields: ["api_key", "password", "secret", "token"].map(String::from).to_vec(),
+        }
     }
 }
 
-impl Default for AppState {
+impl Default for LogFileConfig {
     fn default() -> Self {
-        Self::new()
+        Self {
+            directory: None,
+            rotation: LogRotation::default(),
+            max_files: 7,
+        }
     }
 }
 
-impl Item {
-    /// Try to create new Item with given name and id.
-    /// Returns Err if id is not valid.
-    pub fn new(name: String, id: u64) -> anyhow::Result<Self> {
-        if (1000..=10000).contains(&id) {
-            Ok(Self { id, name })
-        } else {
-            Err(anyhow!("ID must be between 1000 and 9999"))
+impl TaskConfig {
+    const fn every(interval: Duration, enabled: bool) -> Self {
+        Self {
+            enabled,
+            schedule: Schedule::Every(interval),
+            jitter_seconds: 0,
         }
     }
+}
 
-    pub fn new_with_random_id(name: String) -> Self {
-        let id: u64 = rand::rng().random_range(1000..=9999);
-        Self { id, name }
+impl Default for TasksConfig {
+    fn default() -> Self {
+        Self {
+            backup: TaskConfig::every(Duration::from_hours(1), true),
+            expiry_sweep: TaskConfig::every(Duration::from_secs(1), true),
+            eviction_sweep: TaskConfig::every(Duration::from_mins(1), true),
+            state_log: TaskConfig::every(Duration::from_mins(5), false),
+        }
     }
 }
 
-impl LogLevel {
-    /// Convert CLI log level to tracing log level filter
-    pub const fn to_filter(&self) -> LevelFilter {
-        match self {
-            Self::Trace => LevelFilter::TRACE,
-            Self::Debug => LevelFilter::DEBUG,
-            Self::Info => LevelFilter::INFO,
-            Self::Warn => LevelFilter::WARN,
-            Self::Error => LevelFilter::ERROR,
+impl ServerConfig {
+    pub fn header_read_timeout(&self) -> Option<Duration> {
+        self.header_read_timeout_seconds.map(Duration::from_secs)
+    }
+
+    pub fn http2_keep_alive_interval(&self) -> Option<Duration> {
+        self.http2_keep_alive_interval_seconds.map(Duration::from_secs)
+    }
+
+    #[must_use]
+    pub const fn http2_keep_alive_timeout(&self) -> Duration {
+        Duration::from_secs(self.http2_keep_alive_timeout_seconds)
+    }
+
+    #[must_use]
+    pub const fn shutdown_timeout(&self) -> Duration {
+        Duration::from_secs(self.shutdown_timeout_seconds)
+    }
+
+    #[must_use]
+    pub fn hard_shutdown_timeout(&self) -> Option<Duration> {
+        self.hard_shutdown_timeout_seconds.map(Duration::from_secs)
+    }
+}
+
+impl Default for ServerConfig {
+    /// Same as the hyper defaults, except that headers can take any time to arrive.
+    fn default() -> Self {
+        Self {
+            http1_keep_alive: true,
+            header_read_timeout_seconds: None,
+            http2_keep_alive_interval_seconds: None,
+            http2_keep_alive_timeout_seconds: 20,
+            http2_max_concurrent_streams: 200,
+            shutdown_timeout_seconds: 30,
+            hard_shutdown_timeout_seconds: None,
+            reuse_port: false,
         }
     }
 }
 
-impl Config {
-    #[allow(unused)]
-    pub const fn new(api_key: String, env: Environment) -> Self {
-        Self { api_key, env }
+impl TlsConfig {
+    /// Certificate and key files when both are set, so the server speaks HTTPS.
+    ///
+    /// # Errors
+    /// Returns an error when only one of them is set, so a half-configured server does not serve plain HTTP.
+    pub fn files(&self) -> anyhow::Result<Option<(&Path, &Path)>> {
+        match (self.cert.as_deref(), self.key.as_deref()) {
+            (Some(cert), Some(key)) => Ok(Some((cert, key))),
+            (None, None) => Ok(None),
+            _ => Err(anyhow!("Serving HTTPS needs both the TLS certificate and key")),
+        }
     }
+}
 
-    /// Try to get values from env variables or otherwise use defaults.
-    pub fn new_from_env() -> Self {
+impl Default for NatsConfig {
+    fn default() -> Self {
         Self {
-            api_key: env::var("API_KEY").unwrap_or_else(|_| DEFAULT_API_KEY.to_string()),
-            env: Environment::from_env(),
+            url: None,
+            subject_prefix: "axum-example.items".to_string(),
         }
     }
 }
 
-impl Environment {
-    /// Try to read runtime environment from env variable or otherwise use default.
-    pub fn from_env() -> Self {
-        env::var("API_ENV").map_or_else(|_| Self::default(), |value| value.parse().unwrap_or_default())
+impl Default for KafkaConfig {
+    fn default() -> Self {
+        Self {
+            brokers: Vec::new(),
+            topic: "axum-example.items".to_string(),
+        }
+    }
+}
+
+impl Default for MqttConfig {
+    fn default() -> Self {
+        Self {
+            host: None,
+            port: 1883,
+            client_id: None,
+            topic: "axum-example/items".to_string(),
+            topic_per_item: true,
+        }
     }
 }
 
-impl Default for Config {
+impl Default for SecretsConfig {
     fn default() -> Self {
         Self {
-            api_key: DEFAULT_API_KEY.to_string(),
-            env: Environment::default(),
+            provider: SecretsProvider::default(),
+            api_key_id: None,
+            refresh_seconds: 300,
         }
     }
 }
 
+impl Environment {
+    /// Try to read runtime environment from env variable or otherwise use default.
+    #[must_use]
+    pub fn from_env() -> Self {
+        env::var("API_ENV").map_or_else(|_| Self::default(), |value| value.parse().unwrap_or_default())
+    }
+}
+
 /// This implements a custom Axum extractor for checking the api key in routes.
 /// `FromRequestParts` is used here since this does not need access to the request body.
 /// We only need to check the request headers for the api key.
@@ -199,36 +1727,65 @@ impl<S> FromRequestParts<S> for ApiKeyExtractor
 where
     S: Send + Sync,
 {
-    type Rejection = AuthErrorResponse;
+    type Rejection = ApiError;
 
     async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
-        let config = parts
+        let settings = parts
             .extensions
-            .get::<Arc<Config>>()
-            .ok_or_else(|| AuthErrorResponse::new_from_str("Config extension missing from route"))?;
+            .get::<SharedSettings>()
+            .ok_or_else(|| ApiError::new(ErrorCode::Internal, "Settings extension missing from route"))?;
 
         match parts.headers.get("api-key").and_then(|key| key.to_str().ok()) {
-            Some(api_key) if api_key == config.api_key => Ok(Self),
-            Some(api_key) => {
+            Some(api_key) if api_key == settings.load().api_key => Ok(Self),
+            Some(_) => {
                 crate::log_warn!("Invalid API key: {} {}", parts.method.as_str(), parts.uri.path());
-                Err(AuthErrorResponse::new(format!("Invalid API key: '{api_key}'")))
+                Err(ApiError::new(
+                    ErrorCode::Unauthorized,
+                    crate::i18n::message("api_key_invalid", &[]),
+                ))
             }
             None => {
                 crate::log_warn!("Missing API key header: {} {}", parts.method.as_str(), parts.uri.path());
-                Err(AuthErrorResponse::new_from_str("Missing api-key header"))
+                Err(ApiError::new(
+                    ErrorCode::Unauthorized,
+                    crate::i18n::message("api_key_missing", &[]),
+                ))
             }
         }
     }
 }
 
+/// New random api key for `API_KEY`.
+///
+/// 32 bytes from the cryptographically secure thread-local generator, as URL-safe base64 without padding,
+/// so the key can be used in headers and config files as is.
+#[must_use]
+pub fn generate_api_key() -> String {
+    let mut bytes = [0_u8; API_KEY_BYTES];
+    rand::fill(&mut bytes);
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+/// Create an empty map sized by the storage settings.
+fn new_map<K: Eq + Hash, V>(storage: &StorageConfig) -> DashMap<K, V> {
+    let Some(shards) = storage.shard_amount else {
+        return DashMap::with_capacity(storage.initial_capacity);
+    };
+    DashMap::with_capacity_and_shard_amount(storage.initial_capacity, shards.into())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     use axum::RequestPartsExt;
-    use axum::http::Request;
+    use axum::http::{Request, StatusCode};
     use axum::response::IntoResponse;
+    use chrono::TimeZone;
     use http_body_util::BodyExt;
+    use proptest::prelude::*;
+
+    use crate::settings::Settings;
 
     #[test]
     fn environment_round_trips_through_strum() {
@@ -262,39 +1819,256 @@ mod tests {
     }
 
     #[test]
-    fn config_default_uses_local_environment_and_default_key() {
-        let config = Config::default();
+    fn rate_limit_defaults_are_stricter_in_production() {
+        let production = RateLimitConfig::for_environment(Environment::Production);
+        let development = RateLimitConfig::for_environment(Environment::Development);
 
-        assert_eq!(config.api_key, DEFAULT_API_KEY);
-        assert_eq!(config.env, Environment::Local);
+        assert!(production.requests_per_second > 0);
+        assert!(production.requests_per_second < development.requests_per_second);
+        assert!(production.burst >= production.requests_per_second);
     }
 
     #[test]
     fn item_constructor_accepts_valid_ids_and_rejects_out_of_range_ids() {
-        let item = Item::new("valid".to_string(), 1000).expect("lower bound should be valid");
+        let item = Item::new("valid".to_string(), ItemId::Numeric(1000), IdStrategy::Numeric)
+            .expect("lower bound should be valid");
         assert_eq!(item.name, "valid");
-        assert_eq!(item.id, 1000);
+        assert_eq!(item.id, ItemId::Numeric(1000));
 
-        let item = Item::new("valid".to_string(), 10_000).expect("upper bound should be valid");
-        assert_eq!(item.id, 10_000);
+        let item = Item::new("valid".to_string(), ItemId::Numeric(9999), IdStrategy::Numeric)
+            .expect("upper bound should be valid");
+        assert_eq!(item.id, ItemId::Numeric(9999));
 
-        let error = Item::new("invalid".to_string(), 999).expect_err("low id should fail");
+        let error = Item::new("invalid".to_string(), ItemId::Numeric(999), IdStrategy::Numeric)
+            .expect_err("low id should fail");
         assert!(error.to_string().contains("ID must be between 1000 and 9999"));
 
-        let error = Item::new("invalid".to_string(), 10_001).expect_err("high id should fail");
+        let error = Item::new("invalid".to_string(), ItemId::Numeric(10_000), IdStrategy::Numeric)
+            .expect_err("high id should fail");
         assert!(error.to_string().contains("ID must be between 1000 and 9999"));
     }
 
     #[test]
-    fn app_state_serializes_database_without_runtime_fields() {
+    fn id_strategies_generate_and_enforce_their_format() {
+        for strategy in [IdStrategy::Numeric, IdStrategy::Uuid, IdStrategy::Ulid] {
+            let item = Item::new_with_random_id("generated".to_string(), strategy);
+            assert_eq!(strategy.check(item.id), Ok(()), "{strategy}");
+        }
+
+        let uuid = IdStrategy::Uuid.generate();
+        assert!(Item::new("numeric".to_string(), uuid, IdStrategy::Numeric).is_err());
+        let error = Item::new("uuid".to_string(), ItemId::Numeric(1234), IdStrategy::Uuid).expect_err("numeric id");
+        assert_eq!(error.to_string(), "ID must be a UUID");
+        assert_eq!(IdStrategy::Ulid.check(uuid), Err("must be a ULID"));
+    }
+
+    #[test]
+    fn item_ids_serialize_as_numbers_or_strings() {
+        assert_eq!(serde_json::to_string(&ItemId::Numeric(1234)).unwrap(), "1234");
+
+        let ulid = IdStrategy::Ulid.generate();
+        let json = serde_json::to_string(&ulid).unwrap();
+        assert_eq!(json, format!("\"{ulid}\""));
+        assert_eq!(serde_json::from_str::<ItemId>(&json).unwrap(), ulid);
+
+        let random = IdStrategy::Uuid.generate();
+        let json = serde_json::to_string(&random).unwrap();
+        assert_eq!(serde_json::from_str::<ItemId>(&json).unwrap(), random);
+    }
+
+    #[test]
+    fn tags_are_sorted_and_unique() {
+        let item = Item::new_with_random_id("tagged".to_string(), IdStrategy::Numeric).with_tags(vec![
+            "rust".to_string(),
+            "axum".to_string(),
+            "rust".to_string(),
+        ]);
+
+        assert_eq!(item.tags, ["axum", "rust"]);
+        assert!(item.has_tag("rust"));
+        assert!(!item.has_tag("tokio"));
+    }
+
+    #[test]
+    fn seeded_id_generator_repeats_the_same_ids() {
+        let now = Utc.with_ymd_and_hms(2026, 1, 1, 0, 0, 0).unwrap();
+        for strategy in [IdStrategy::Numeric, IdStrategy::Uuid, IdStrategy::Ulid] {
+            let first = IdGenerator::seeded(42);
+            let second = IdGenerator::seeded(42);
+            let ids: Vec<ItemId> = (0..3).map(|_| first.generate(strategy, now)).collect();
+
+            assert_eq!(ids, (0..3).map(|_| second.generate(strategy, now)).collect::<Vec<_>>());
+            assert_ne!(ids[0], ids[1], "{strategy}");
+            assert!(ids.iter().all(|id| strategy.check(*id).is_ok()), "{strategy}");
+        }
+        assert_ne!(
+            IdGenerator::seeded(1).generate(IdStrategy::Uuid, now),
+            IdGenerator::seeded(2).generate(IdStrategy::Uuid, now)
+        );
+    }
+
+    #[test]
+    fn deterministic_state_uses_the_fixed_time() {
+        let time = Utc.with_ymd_and_hms(2026, 1, 1, 0, 0, 0).unwrap();
+        let state = AppState::new().with_deterministic(&DeterministicConfig {
+            seed: Some(7),
+            time: Some(time),
+        });
+
+        assert_eq!(state.now(), time);
+        assert_eq!(state.start_time_utc, time);
+        assert_eq!(
+            state.generate_id(IdStrategy::Numeric),
+            IdGenerator::seeded(7).generate(IdStrategy::Numeric, time)
+        );
+        assert!(AppState::new().now() > time);
+    }
+
+    #[test]
+    fn app_state_keeps_item_names_and_ids_unique() {
         let state = AppState::new();
-        state.db.insert(
-            "stored".to_string(),
-            Item {
-                id: 4321,
-                name: "stored".to_string(),
-            },
+        let item = |name: &str, id| Item::new(name.to_string(), ItemId::Numeric(id), IdStrategy::Numeric).unwrap();
+
+        assert_eq!(state.items().insert_item(&item("first", 1111)), Ok(()));
+        assert_eq!(
+            state.items().insert_item(&item("second", 1111)),
+            Err(DuplicateItem::Id(ItemId::Numeric(1111)))
+        );
+        assert_eq!(
+            state.items().insert_item(&item("first", 2222)),
+            Err(DuplicateItem::Name("first".to_string()))
+        );
+        // The id of a rejected duplicate name is released again.
+        assert_eq!(state.items().insert_item(&item("second", 2222)), Ok(()));
+
+        assert!(state.items().remove_item_if("first", |_| true).is_some());
+        assert_eq!(state.items().insert_item(&item("third", 1111)), Ok(()));
+        assert_eq!(state.items().clear_items(), 2);
+        assert_eq!(state.items().insert_item(&item("fourth", 2222)), Ok(()));
+    }
+
+    #[test]
+    fn indexes_follow_inserts_and_removals() {
+        let state = AppState::new();
+        let item = |name: &str, id, tags: &[&str]| {
+            Item::new(name.to_string(), ItemId::Numeric(id), IdStrategy::Numeric)
+                .unwrap()
+                .with_tags(tags.iter().map(ToString::to_string).collect())
+        };
+        state
+            .items()
+            .insert_item(&item("first", 1111, &["rust", "axum"]))
+            .unwrap();
+        state.items().insert_item(&item("second", 2222, &["rust"])).unwrap();
+
+        assert_eq!(
+            state.items().get_item_by_id(&ItemId::Numeric(2222)).unwrap().name,
+            "second"
         );
+        assert!(state.items().get_item_by_id(&ItemId::Numeric(3333)).is_none());
+        let mut names: Vec<String> = state
+            .items()
+            .items_with_tag("rust")
+            .iter()
+            .map(|item| item.name.clone())
+            .collect();
+        names.sort();
+        assert_eq!(names, ["first", "second"]);
+
+        state.items().remove_item_if("first", |_| true).unwrap();
+        assert!(state.items().get_item_by_id(&ItemId::Numeric(1111)).is_none());
+        assert!(state.items().items_with_tag("axum").is_empty());
+        assert!(!state.items().indexes.tags.contains_key("axum"));
+        assert_eq!(state.items().items_with_tag("rust").len(), 1);
+
+        state.items().clear_items();
+        assert!(state.items().items_with_tag("rust").is_empty());
+    }
+
+    #[test]
+    fn storage_settings_size_the_maps() {
+        let storage = StorageConfig {
+            initial_capacity: 16,
+            shard_amount: Some(ShardAmount::try_from(4).unwrap()),
+        };
+        let state = AppState::new_with_telemetry(Telemetry::noop(), &storage);
+        assert!(state.items().db.capacity() >= 16);
+        assert!(state.items().db.capacity() < DEFAULT_INITIAL_CAPACITY);
+        state
+            .items()
+            .insert_item(&Item::new_with_random_id("first".to_string(), IdStrategy::Numeric))
+            .unwrap();
+        assert!(state.items().db.contains_key("first"));
+
+        assert!(ShardAmount::try_from(1).is_err());
+        assert!(ShardAmount::try_from(6).is_err());
+    }
+
+    #[test]
+    fn sparse_maps_are_compacted() {
+        let state = AppState::new();
+        let count = 4 * DEFAULT_INITIAL_CAPACITY as u64;
+        for id in 0..count {
+            state
+                .items()
+                .insert_item(&Item::new_unchecked(format!("item-{id}"), ItemId::Numeric(id)))
+                .unwrap();
+        }
+        let grown = state.items().db.capacity();
+        assert!(grown > 2 * DEFAULT_INITIAL_CAPACITY);
+
+        // Removing a few items keeps the capacity, apart from slots left behind as tombstones.
+        for id in 0..10 {
+            state.items().remove_item_if(&format!("item-{id}"), |_| true).unwrap();
+        }
+        assert!(state.items().db.capacity() + 10 >= grown);
+
+        // Dropping below the occupancy threshold shrinks the maps.
+        for id in 10..count - 100 {
+            state.items().remove_item_if(&format!("item-{id}"), |_| true).unwrap();
+        }
+        assert!(state.items().db.capacity() < grown);
+        assert_eq!(state.items().db.len(), 100);
+
+        let compaction = state.items().compact();
+        assert!(compaction.capacity_after <= compaction.capacity_before);
+        assert!(compaction.capacity_after >= 100);
+    }
+
+    #[test]
+    fn expired_items_are_removed() {
+        let state = AppState::new();
+        let item = |name: &str, id, ttl_seconds| {
+            Item::new(name.to_string(), ItemId::Numeric(id), IdStrategy::Numeric)
+                .unwrap()
+                .with_ttl(ttl_seconds)
+        };
+        let short = item("short", 1111, Some(60));
+        let expires_at = short.expires_at.expect("item with TTL expires");
+        assert_eq!(expires_at - short.created_at, TimeDelta::seconds(60));
+        state.items().insert_item(&short).unwrap();
+        state.items().insert_item(&item("long", 2222, Some(3600))).unwrap();
+        state.items().insert_item(&item("forever", 3333, None)).unwrap();
+
+        assert!(state.items().remove_expired_items(Utc::now()).is_empty());
+
+        let expired = state.items().remove_expired_items(expires_at);
+        assert_eq!(expired.len(), 1);
+        assert_eq!(expired[0].name, "short");
+        assert_eq!(state.items().db.len(), 2);
+        // The expired id is free again.
+        assert_eq!(state.items().insert_item(&item("reused", 1111, None)), Ok(()));
+    }
+
+    #[test]
+    fn app_state_serializes_database_without_runtime_fields() {
+        let state = AppState::new();
+        state
+            .items()
+            .insert_item(
+                &Item::new("stored".to_string(), ItemId::Numeric(4321), IdStrategy::Numeric).expect("valid item"),
+            )
+            .expect("new item");
 
         let json = state.to_json_pretty().expect("state should serialize");
 
@@ -305,14 +2079,14 @@ mod tests {
 
     #[tokio::test]
     async fn api_key_extractor_accepts_valid_key() {
-        let config = Arc::new(Config::default());
+        let settings = Settings::default().into_shared();
         let request = Request::builder()
             .uri("/admin/clear_items")
-            .header("api-key", &config.api_key)
+            .header("api-key", DEFAULT_API_KEY)
             .body(())
             .expect("request should build");
         let (mut parts, ()) = request.into_parts();
-        parts.extensions.insert(config);
+        parts.extensions.insert(settings);
 
         let extracted = parts
             .extract::<ApiKeyExtractor>()
@@ -323,7 +2097,7 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn api_key_extractor_rejects_missing_config_extension() {
+    async fn api_key_extractor_rejects_missing_settings_extension() {
         let request = Request::builder()
             .uri("/admin/clear_items")
             .header("api-key", DEFAULT_API_KEY)
@@ -332,9 +2106,10 @@ mod tests {
         let (mut parts, ()) = request.into_parts();
 
         let Err(rejection) = parts.extract::<ApiKeyExtractor>().await else {
-            panic!("missing config extension should reject");
+            panic!("missing settings extension should reject");
         };
         let response = rejection.into_response();
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
         let body = response
             .into_body()
             .collect()
@@ -343,6 +2118,179 @@ mod tests {
             .to_bytes();
         let body: serde_json::Value = serde_json::from_slice(&body).expect("body should be json");
 
-        assert_eq!(body["message"], "Config extension missing from route");
+        assert_eq!(body["code"], "internal");
+        assert_eq!(body["message"], "Settings extension missing from route");
+    }
+
+    #[test]
+    fn generated_api_keys_are_random_and_header_safe() {
+        let key = generate_api_key();
+        assert_eq!(key.len(), 43);
+        assert!(key.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_'));
+        assert_ne!(key, generate_api_key());
+    }
+
+    fn id_strategy() -> impl Strategy<Value = IdStrategy> {
+        prop_oneof![
+            Just(IdStrategy::Numeric),
+            Just(IdStrategy::Uuid),
+            Just(IdStrategy::Ulid)
+        ]
+    }
+
+    fn item_id() -> impl Strategy<Value = ItemId> {
+        prop_oneof![
+            any::<u64>().prop_map(ItemId::Numeric),
+            any::<u128>().prop_map(|bits| ItemId::Uuid(Uuid::from_u128(bits))),
+            any::<u128>().prop_map(|bits| ItemId::Ulid(Ulid(bits))),
+        ]
+    }
+
+    fn valid_item() -> impl Strategy<Value = Item> {
+        (
+            "[a-z][a-z0-9-]{0,15}",
+            1000..=9999_u64,
+            prop::collection::vec("[a-z]{1,6}", 0..4),
+            prop::collection::hash_map("[a-z]{1,6}", ".{0,8}", 0..3),
+            prop::option::of(".{0,16}"),
+            prop::option::of(0..=1_000_000_u64),
+            0..4_000_000_000_000_i64,
+        )
+            .prop_map(|(name, id, tags, metadata, data, ttl_seconds, created_at)| {
+                Item::new(name, ItemId::Numeric(id), IdStrategy::Numeric)
+                    .expect("id is in range")
+                    .with_created_at(DateTime::from_timestamp_millis(created_at).expect("timestamp is in range"))
+                    .with_tags(tags)
+                    .with_metadata(metadata)
+                    .with_data(data.map(serde_json::Value::String))
+                    .with_ttl(ttl_seconds)
+            })
+    }
+
+    /// Small name and id pools, so generated batches collide often.
+    fn name_and_id() -> impl Strategy<Value = (String, u64)> {
+        ("[a-e]", 1000..1005_u64)
+    }
+
+    #[test]
+    fn tls_files_need_both_the_certificate_and_key() {
+        let both = TlsConfig {
+            cert: Some(PathBuf::from("cert.pem")),
+            key: Some(PathBuf::from("key.pem")),
+            redirect_port: None,
+        };
+        assert_eq!(
+            both.files().unwrap(),
+            Some((Path::new("cert.pem"), Path::new("key.pem")))
+        );
+        assert_eq!(TlsConfig::default().files().unwrap(), None);
+
+        let only_cert = TlsConfig {
+            key: None,
+            ..both.clone()
+        };
+        let only_key = TlsConfig { cert: None, ..both };
+        assert!(only_cert.files().is_err());
+        assert!(only_key.files().is_err());
+    }
+
+    proptest! {
+        #[test]
+        fn numeric_ids_are_accepted_only_in_range(id in any::<u64>()) {
+            let item = Item::new("ranged".to_string(), ItemId::Numeric(id), IdStrategy::Numeric);
+
+            prop_assert_eq!(item.is_ok(), (1000..=9999).contains(&id));
+        }
+
+        #[test]
+        fn ids_are_accepted_only_by_their_own_strategy(id in item_id(), strategy in id_strategy()) {
+            let fits = match (strategy, id) {
+                (IdStrategy::Numeric, ItemId::Numeric(id)) => Item::is_valid_id(id),
+                (IdStrategy::Uuid, ItemId::Uuid(_)) | (IdStrategy::Ulid, ItemId::Ulid(_)) => true,
+                _ => false,
+            };
+
+            prop_assert_eq!(strategy.check(id).is_ok(), fits);
+        }
+
+        #[test]
+        fn generated_items_keep_their_invariants(seed in any::<u64>(), strategy in id_strategy()) {
+            let state = AppState::new().with_deterministic(&DeterministicConfig {
+                seed: Some(seed),
+                time: None,
+            });
+            let item = Item::new_unchecked("generated".to_string(), state.generate_id(strategy));
+
+            prop_assert_eq!(item.check_invariants(strategy), Ok(()));
+        }
+
+        #[test]
+        fn item_ids_round_trip_through_json(id in item_id()) {
+            let json = serde_json::to_string(&id).unwrap();
+
+            prop_assert_eq!(serde_json::from_str::<ItemId>(&json).unwrap(), id);
+        }
+
+        #[test]
+        fn items_round_trip_through_json(item in valid_item()) {
+            prop_assert_eq!(item.check_invariants(IdStrategy::Numeric), Ok(()));
+
+            let json = serde_json::to_string(&item).unwrap();
+
+            prop_assert_eq!(serde_json::from_str::<Item>(&json).unwrap(), item);
+        }
+
+        #[test]
+        fn stored_names_and_ids_stay_unique(
+            operations in prop::collection::vec((name_and_id(), any::<bool>()), 1..40)
+        ) {
+            let state = AppState::new();
+            let mut stored: HashMap<String, u64> = HashMap::new();
+
+            for ((name, id), remove) in operations {
+                if remove {
+                    let removed = state.items().remove_item_if(&name, |_| true);
+                    prop_assert_eq!(removed.map(|item| item.id), stored.remove(&name).map(ItemId::Numeric));
+                } else {
+                    let free = !stored.contains_key(&name) && !stored.values().any(|stored_id| *stored_id == id);
+                    let inserted = state.items().insert_item(&Item::new_unchecked(name.clone(), ItemId::Numeric(id)));
+                    prop_assert_eq!(inserted.is_ok(), free);
+                    if free {
+                        stored.insert(name, id);
+                    }
+                }
+                prop_assert_eq!(state.items().check_indexes(), Ok(()));
+            }
+            prop_assert_eq!(state.items().db.len(), stored.len());
+        }
+
+        #[test]
+        fn batch_insert_adds_all_items_or_none(
+            existing in prop::collection::vec(name_and_id(), 0..4),
+            batch in prop::collection::vec(name_and_id(), 0..6),
+        ) {
+            let state = AppState::new();
+            for (name, id) in existing {
+                let _ = state.items().insert_item(&Item::new_unchecked(name, ItemId::Numeric(id)));
+            }
+            let mut before: Vec<String> = state.items().db.iter().map(|entry| entry.key().clone()).collect();
+            before.sort();
+            let batch: Vec<Item> = batch
+                .into_iter()
+                .map(|(name, id)| Item::new_unchecked(name, ItemId::Numeric(id)))
+                .collect();
+
+            let result = state.items().insert_items(&batch);
+
+            let mut after: Vec<String> = state.items().db.iter().map(|entry| entry.key().clone()).collect();
+            after.sort();
+            if result.is_ok() {
+                prop_assert_eq!(after.len(), before.len() + batch.len());
+                prop_assert!(batch.iter().all(|item| state.items().get_item_by_id(&item.id).is_some()));
+            } else {
+                prop_assert_eq!(after, before);
+            }
+            prop_assert_eq!(state.items().check_indexes(), Ok(()));
+        }
     }
 }
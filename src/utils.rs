@@ -19,7 +19,10 @@ pub async fn shutdown_signal() {
             .await;
     };
 
-    #[cfg(not(unix))]
+    #[cfg(windows)]
+    let terminate = crate::windows::stop_requested();
+
+    #[cfg(not(any(unix, windows)))]
     let terminate = std::future::pending::<()>();
 
     tokio::select! {
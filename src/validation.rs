@@ -0,0 +1,201 @@
+//! Request payload validation.
+//!
+//! `ValidatedJson` deserializes a JSON body like `Json` and then runs the payload's `Validate` rules,
+//! so handlers only ever see valid input.
+//! Violations are returned as a `422 Unprocessable Entity` `ApiError` listing every failed field.
+
+use std::fmt;
+
+use anyhow::Context;
+use axum::Json;
+use axum::extract::{FromRequest, Request};
+use regex::Regex;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::schemas::{ApiError, FieldError};
+
+/// JSON extractor that also validates the payload.
+#[derive(Debug, Clone)]
+pub struct ValidatedJson<T>(pub T);
+
+/// Regular expression that the whole item name must match, from settings.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct NamePattern {
+    pattern: String,
+    regex: Regex,
+}
+
+/// Validation rules for a request payload.
+pub trait Validate {
+    /// Return every violated rule, or an empty list for a valid payload.
+    fn validate(&self) -> Vec<FieldError>;
+}
+
+impl<T> ValidatedJson<T>
+where
+    T: DeserializeOwned + Validate,
+{
+    /// Deserialize and validate a JSON body that was already read,
+    /// with the same rejections as the extractor after its content type check.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, ApiError> {
+        let Json(payload) = Json::<T>::from_bytes(bytes)?;
+        Self::validated(payload)
+    }
+
+    fn validated(payload: T) -> Result<Self, ApiError> {
+        let errors = payload.validate();
+        if errors.is_empty() {
+            Ok(Self(payload))
+        } else {
+            Err(ApiError::validation(errors))
+        }
+    }
+}
+
+impl NamePattern {
+    /// Check if the whole name matches the pattern.
+    #[must_use]
+    pub fn is_match(&self, name: &str) -> bool {
+        self.regex.is_match(name)
+    }
+}
+
+impl TryFrom<String> for NamePattern {
+    type Error = anyhow::Error;
+
+    fn try_from(pattern: String) -> Result<Self, Self::Error> {
+        let regex = Regex::new(&format!("^(?:{pattern})$"))
+            .with_context(|| format!("Invalid item name pattern '{pattern}'"))?;
+        Ok(Self { pattern, regex })
+    }
+}
+
+impl From<NamePattern> for String {
+    fn from(pattern: NamePattern) -> Self {
+        pattern.pattern
+    }
+}
+
+impl PartialEq for NamePattern {
+    fn eq(&self, other: &Self) -> bool {
+        self.pattern == other.pattern
+    }
+}
+
+impl Eq for NamePattern {}
+
+impl fmt::Display for NamePattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.pattern)
+    }
+}
+
+impl<S, T> FromRequest<S> for ValidatedJson<T>
+where
+    S: Send + Sync,
+    T: DeserializeOwned + Validate,
+{
+    type Rejection = ApiError;
+
+    async fn from_request(request: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let Json(payload) = Json::<T>::from_request(request, state).await?;
+        Self::validated(payload)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use axum::body::Body;
+    use axum::http::StatusCode;
+    use axum::response::IntoResponse;
+    use http_body_util::BodyExt;
+    use serde::Deserialize;
+
+    #[derive(Debug, Deserialize)]
+    struct Payload {
+        count: u32,
+    }
+
+    impl Validate for Payload {
+        fn validate(&self) -> Vec<FieldError> {
+            if self.count > 10 {
+                vec![FieldError::new("count", "must be at most 10")]
+            } else {
+                Vec::new()
+            }
+        }
+    }
+
+    fn json_request(body: &'static str) -> Request {
+        Request::builder()
+            .header("content-type", "application/json")
+            .body(Body::from(body))
+            .expect("request should build")
+    }
+
+    #[tokio::test]
+    async fn valid_payload_is_extracted() {
+        let ValidatedJson(payload) = ValidatedJson::<Payload>::from_request(json_request(r#"{"count":3}"#), &())
+            .await
+            .expect("payload should be valid");
+
+        assert_eq!(payload.count, 3);
+    }
+
+    #[tokio::test]
+    async fn invalid_payload_lists_field_errors() {
+        let rejection = ValidatedJson::<Payload>::from_request(json_request(r#"{"count":11}"#), &())
+            .await
+            .expect_err("payload should be invalid");
+        let response = rejection.into_response();
+
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["code"], "validation_failed");
+        assert_eq!(body["fields"][0]["field"], "count");
+        assert_eq!(body["fields"][0]["message"], "must be at most 10");
+    }
+
+    #[test]
+    fn from_bytes_matches_extractor_rejections() {
+        let ValidatedJson(payload) =
+            ValidatedJson::<Payload>::from_bytes(br#"{"count":3}"#).expect("payload should be valid");
+        assert_eq!(payload.count, 3);
+
+        let rejection =
+            ValidatedJson::<Payload>::from_bytes(br#"{"count":11}"#).expect_err("payload should be invalid");
+        assert_eq!(rejection.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+        let rejection = ValidatedJson::<Payload>::from_bytes(br#"{"count":"#).expect_err("payload should be malformed");
+        assert_eq!(rejection.status(), StatusCode::BAD_REQUEST);
+
+        let rejection = ValidatedJson::<Payload>::from_bytes(br#"{"count":-1}"#).expect_err("count should not parse");
+        assert_eq!(rejection.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    }
+
+    #[test]
+    fn name_pattern_matches_whole_name() {
+        let pattern = NamePattern::try_from("[a-z]+-[0-9]+".to_string()).expect("valid pattern");
+
+        assert!(pattern.is_match("item-1"));
+        assert!(!pattern.is_match("item-1-copy"));
+        assert!(!pattern.is_match("Item-1"));
+        assert_eq!(pattern.to_string(), "[a-z]+-[0-9]+");
+        assert!(NamePattern::try_from("[a-z".to_string()).is_err());
+    }
+
+    #[tokio::test]
+    async fn malformed_json_keeps_json_rejection() {
+        let rejection = ValidatedJson::<Payload>::from_request(json_request(r#"{"count":"#), &())
+            .await
+            .expect_err("payload should be malformed");
+        let response = rejection.into_response();
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+}
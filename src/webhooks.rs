@@ -0,0 +1,513 @@
+//! Webhook subscriptions and delivery.
+//!
+//! `POST /admin/webhooks` registers a callback URL for item events.
+//! The delivery worker subscribes to the item event bus
+//! and posts every matching event as JSON to each webhook,
+//! signed with the webhook secret so receivers can check where it came from.
+//! Failed deliveries are retried with exponential backoff,
+//! and the latest attempts are kept for `GET /admin/webhooks/{id}/deliveries`.
+//! At most [`MAX_CONCURRENT_DELIVERIES`] deliveries run at once, retries included,
+//! so once that many are waiting on slow receivers the worker stops reading events
+//! and falls behind the event bus instead of piling up tasks.
+//!
+//! The signature is `sha256=` followed by the hex HMAC-SHA256 of `{timestamp}.{body}`,
+//! where the timestamp is the Unix time in the `X-Webhook-Timestamp` header,
+//! so a captured request cannot be replayed later with a new timestamp.
+
+use std::collections::VecDeque;
+use std::fmt::Write;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Duration;
+
+use axum::body::Bytes;
+use axum::http::header::CONTENT_TYPE;
+use axum::http::{HeaderName, Method, Request};
+use chrono::Utc;
+use dashmap::DashMap;
+use hmac::{Hmac, KeyInit, Mac};
+use http_body_util::Full;
+use hyper_util::client::legacy::Client;
+use hyper_util::client::legacy::connect::HttpConnector;
+use hyper_util::rt::TokioExecutor;
+use sha2::Sha256;
+use tokio::sync::Semaphore;
+use tokio::task::JoinHandle;
+use ulid::Ulid;
+
+use crate::events::{Actor, ItemEvent, next_event};
+use crate::schemas::{CreateWebhook, Webhook, WebhookDelivery};
+use crate::types::SharedState;
+
+/// Header with the HMAC signature of the delivery.
+pub const SIGNATURE_HEADER: HeaderName = HeaderName::from_static("x-webhook-signature");
+
+/// Header with the Unix timestamp included in the signature.
+pub const TIMESTAMP_HEADER: HeaderName = HeaderName::from_static("x-webhook-timestamp");
+
+/// Delivery attempts kept per webhook.
+pub const MAX_DELIVERY_HISTORY: usize = 100;
+
+/// Deliveries in progress at once, including the ones waiting to retry.
+pub const MAX_CONCURRENT_DELIVERIES: usize = 64;
+
+/// How long one delivery attempt may take before it counts as failed.
+const DELIVERY_TIMEOUT: Duration = Duration::from_secs(10);
+
+type HttpClient = Client<HttpConnector, Full<Bytes>>;
+
+/// Registered webhooks and their delivery history.
+#[derive(Debug, Default)]
+pub struct WebhookRegistry {
+    subscriptions: DashMap<String, Arc<Subscription>>,
+}
+
+/// How many times and how often a failed delivery is retried.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    /// Delay before the second attempt, doubled for every attempt after that.
+    pub initial_delay: Duration,
+    pub max_delay: Duration,
+}
+
+#[derive(Debug)]
+struct Subscription {
+    webhook: Webhook,
+    secret: String,
+    deliveries: Mutex<VecDeque<WebhookDelivery>>,
+}
+
+impl WebhookRegistry {
+    /// Add a webhook with a new id.
+    #[must_use]
+    pub fn register(&self, request: CreateWebhook) -> Webhook {
+        let webhook = Webhook {
+            id: Ulid::generate().to_string(),
+            url: request.url,
+            events: request.events,
+            created_at: Utc::now(),
+        };
+        self.subscriptions.insert(
+            webhook.id.clone(),
+            Arc::new(Subscription {
+                webhook: webhook.clone(),
+                secret: request.secret,
+                deliveries: Mutex::new(VecDeque::new()),
+            }),
+        );
+        webhook
+    }
+
+    /// Registered webhooks, oldest first.
+    #[must_use]
+    pub fn list(&self) -> Vec<Webhook> {
+        let mut webhooks: Vec<Webhook> = self.subscriptions.iter().map(|entry| entry.webhook.clone()).collect();
+        webhooks.sort_by(|a, b| a.id.cmp(&b.id));
+        webhooks
+    }
+
+    /// Remove the webhook, deliveries that are already running still finish.
+    #[must_use]
+    pub fn remove(&self, id: &str) -> Option<Webhook> {
+        self.subscriptions
+            .remove(id)
+            .map(|(_, subscription)| subscription.webhook.clone())
+    }
+
+    /// The webhook and its recent delivery attempts, newest first.
+    #[must_use]
+    pub fn deliveries(&self, id: &str) -> Option<(Webhook, Vec<WebhookDelivery>)> {
+        let subscription = self.subscriptions.get(id)?;
+        let deliveries = subscription
+            .deliveries
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .iter()
+            .rev()
+            .cloned()
+            .collect();
+        Some((subscription.webhook.clone(), deliveries))
+    }
+
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.subscriptions.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.subscriptions.is_empty()
+    }
+
+    /// Webhooks that want this event.
+    /// Changes received from peers are delivered by the instance that made them.
+    fn matching(&self, event: &ItemEvent) -> Vec<Arc<Subscription>> {
+        if event.actor == Actor::Replication {
+            return Vec::new();
+        }
+        let event_type = event.change.event_type();
+        self.subscriptions
+            .iter()
+            .filter(|entry| entry.webhook.events.is_empty() || entry.webhook.events.contains(&event_type))
+            .map(|entry| Arc::clone(entry.value()))
+            .collect()
+    }
+}
+
+impl RetryPolicy {
+    /// Delay before the given attempt, which must be at least 2.
+    #[must_use]
+    pub fn delay_before(&self, attempt: u32) -> Duration {
+        let doublings = attempt.saturating_sub(2).min(31);
+        self.initial_delay.saturating_mul(1 << doublings).min(self.max_delay)
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 5,
+            initial_delay: Duration::from_secs(1),
+            max_delay: Duration::from_mins(1),
+        }
+    }
+}
+
+impl Subscription {
+    fn record(&self, delivery: WebhookDelivery) {
+        let mut deliveries = self.deliveries.lock().unwrap_or_else(PoisonError::into_inner);
+        if deliveries.len() == MAX_DELIVERY_HISTORY {
+            deliveries.pop_front();
+        }
+        deliveries.push_back(delivery);
+    }
+}
+
+/// Signature header value for a delivery body sent at the given Unix timestamp.
+#[must_use]
+pub fn sign(secret: &str, timestamp: i64, body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body);
+    let digest = mac.finalize().into_bytes();
+    digest.iter().fold(String::from("sha256="), |mut signature, byte| {
+        let _ = write!(signature, "{byte:02x}");
+        signature
+    })
+}
+
+/// Deliver item events to the registered webhooks.
+///
+/// Every delivery runs in its own task,
+/// so a slow or failing receiver does not hold up the others,
+/// and the worker waits for a free slot once `max_concurrent` deliveries are running.
+pub fn spawn_delivery_worker(state: &SharedState, retry: RetryPolicy, max_concurrent: usize) -> JoinHandle<()> {
+    let mut receiver = state.events().subscribe();
+    let metrics = state.telemetry().metrics();
+    let state = Arc::clone(state);
+    let client: HttpClient = Client::builder(TokioExecutor::new()).build_http();
+    let slots = Arc::new(Semaphore::new(max_concurrent));
+    tokio::spawn(async move {
+        while let Some(event) = next_event(&mut receiver, "webhooks", &metrics).await {
+            let subscriptions = state.webhooks().matching(&event);
+            if subscriptions.is_empty() {
+                continue;
+            }
+            let body = match serde_json::to_vec(&event) {
+                Ok(body) => Bytes::from(body),
+                Err(error) => {
+                    crate::log_error!("Failed to serialize item event: {error}");
+                    continue;
+                }
+            };
+            for subscription in subscriptions {
+                let Ok(slot) = Arc::clone(&slots).acquire_owned().await else {
+                    return;
+                };
+                let delivery = deliver(client.clone(), subscription, event.clone(), body.clone(), retry);
+                tokio::spawn(async move {
+                    delivery.await;
+                    drop(slot);
+                });
+            }
+        }
+    })
+}
+
+/// Post the event until the receiver accepts it or the attempts run out.
+async fn deliver(
+    client: HttpClient,
+    subscription: Arc<Subscription>,
+    event: ItemEvent,
+    body: Bytes,
+    retry: RetryPolicy,
+) {
+    for attempt in 1..=retry.max_attempts {
+        if attempt > 1 {
+            tokio::time::sleep(retry.delay_before(attempt)).await;
+        }
+        let (status, error) = match send(&client, &subscription, body.clone()).await {
+            Ok(status) => (Some(status), None),
+            Err(error) => (None, Some(error)),
+        };
+        let succeeded = status.is_some_and(|status| (200..300).contains(&status));
+        subscription.record(WebhookDelivery {
+            event_type: event.change.event_type(),
+            occurred_at: event.occurred_at,
+            attempt,
+            attempted_at: Utc::now(),
+            status,
+            error,
+            succeeded,
+        });
+        if succeeded {
+            return;
+        }
+    }
+    crate::log_warn!(
+        "Webhook {} gave up on {} event after {} attempts",
+        subscription.webhook.id,
+        event.change.event_type(),
+        retry.max_attempts
+    );
+}
+
+/// Send one signed delivery and return the response status.
+async fn send(client: &HttpClient, subscription: &Subscription, body: Bytes) -> Result<u16, String> {
+    let timestamp = Utc::now().timestamp();
+    let request = Request::builder()
+        .method(Method::POST)
+        .uri(&subscription.webhook.url)
+        .header(CONTENT_TYPE, "application/json")
+        .header(TIMESTAMP_HEADER, timestamp)
+        .header(SIGNATURE_HEADER, sign(&subscription.secret, timestamp, &body))
+        .body(Full::new(body))
+        .map_err(|error| error.to_string())?;
+    match tokio::time::timeout(DELIVERY_TIMEOUT, client.request(request)).await {
+        Ok(Ok(response)) => Ok(response.status().as_u16()),
+        Ok(Err(error)) => Err(error.to_string()),
+        Err(_) => Err(format!("No response in {} seconds", DELIVERY_TIMEOUT.as_secs())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use axum::Router;
+    use axum::http::{HeaderMap, StatusCode};
+    use axum::routing::post;
+    use serde_json::{Value, json};
+
+    use crate::events::{ItemChange, ItemEventType};
+    use crate::test_utils::TestApp;
+
+    fn fast_retries() -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: 3,
+            initial_delay: Duration::from_millis(10),
+            max_delay: Duration::from_millis(20),
+        }
+    }
+
+    #[test]
+    fn signature_matches_known_hmac() {
+        // echo -n '1700000000.{}' | openssl dgst -sha256 -hmac 'secret'
+        assert_eq!(
+            sign("secret", 1_700_000_000, b"{}"),
+            "sha256=b8569b78799ff9e3cbff0fc2d63a33a2b57f3282abd07c37ae5e8e7d79a5f163"
+        );
+    }
+
+    #[test]
+    fn retry_delay_doubles_up_to_the_maximum() {
+        let retry = RetryPolicy::default();
+        assert_eq!(retry.delay_before(2), Duration::from_secs(1));
+        assert_eq!(retry.delay_before(3), Duration::from_secs(2));
+        assert_eq!(retry.delay_before(5), Duration::from_secs(8));
+        assert_eq!(retry.delay_before(20), Duration::from_mins(1));
+    }
+
+    #[test]
+    fn registry_filters_by_event_type_and_keeps_recent_deliveries() {
+        let registry = WebhookRegistry::default();
+        let all = registry.register(CreateWebhook {
+            url: "http://localhost/all".to_string(),
+            secret: "0123456789abcdef".to_string(),
+            events: Vec::new(),
+        });
+        let _ = registry.register(CreateWebhook {
+            url: "http://localhost/created".to_string(),
+            secret: "0123456789abcdef".to_string(),
+            events: vec![ItemEventType::Created],
+        });
+        let cleared = ItemEvent {
+            occurred_at: Utc::now(),
+            actor: Actor::Admin,
+            change: ItemChange::Cleared { count: 0 },
+        };
+        let matching = registry.matching(&cleared);
+        assert_eq!(matching.len(), 1);
+        assert_eq!(matching[0].webhook, all);
+
+        for attempt in 1..=u32::try_from(MAX_DELIVERY_HISTORY).unwrap() + 1 {
+            matching[0].record(WebhookDelivery {
+                event_type: ItemEventType::Cleared,
+                occurred_at: cleared.occurred_at,
+                attempt,
+                attempted_at: Utc::now(),
+                status: Some(200),
+                error: None,
+                succeeded: true,
+            });
+        }
+        let (_, deliveries) = registry.deliveries(&all.id).unwrap();
+        assert_eq!(deliveries.len(), MAX_DELIVERY_HISTORY);
+        assert_eq!(deliveries[0].attempt, 101);
+
+        assert_eq!(registry.remove(&all.id), Some(all));
+        assert_eq!(registry.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn failed_deliveries_are_retried_and_signed() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let hook = Router::new().route(
+            "/hook",
+            post({
+                let calls = Arc::clone(&calls);
+                let requests = Arc::clone(&requests);
+                move |headers: HeaderMap, body: Bytes| async move {
+                    requests.lock().unwrap().push((headers, body));
+                    if calls.fetch_add(1, Ordering::SeqCst) == 0 {
+                        StatusCode::SERVICE_UNAVAILABLE
+                    } else {
+                        StatusCode::NO_CONTENT
+                    }
+                }
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let hook_server = tokio::spawn(async move { axum::serve(listener, hook).await });
+
+        let app = TestApp::new();
+        let worker = spawn_delivery_worker(&app.state, fast_retries(), MAX_CONCURRENT_DELIVERIES);
+        let response = app
+            .post_json(
+                "/admin/webhooks",
+                &json!({ "url": format!("http://{address}/hook"), "secret": "0123456789abcdef", "events": ["created"] }),
+            )
+            .await;
+        assert_eq!(response.status, StatusCode::CREATED, "{}", response.text());
+        let webhook: Webhook = response.json();
+        assert!(!response.text().contains("0123456789abcdef"));
+
+        app.create_item(json!({ "name": "hooked" })).await;
+        app.delete("/admin/clear_items").await;
+
+        let mut deliveries = Vec::new();
+        for _ in 0..100 {
+            let response = app.get(&format!("/admin/webhooks/{}/deliveries", webhook.id)).await;
+            deliveries = response.json::<Value>()["deliveries"].as_array().unwrap().clone();
+            if deliveries.len() == 2 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        worker.abort();
+        hook_server.abort();
+
+        assert_eq!(deliveries.len(), 2, "{deliveries:?}");
+        assert_eq!(deliveries[0]["attempt"], 2);
+        assert_eq!(deliveries[0]["status"], 204);
+        assert_eq!(deliveries[0]["succeeded"], true);
+        assert_eq!(deliveries[1]["status"], 503);
+        assert_eq!(deliveries[1]["event_type"], "created");
+
+        let requests = requests.lock().unwrap().clone();
+        assert_eq!(requests.len(), 2);
+        let (headers, body) = &requests[1];
+        let timestamp: i64 = headers[TIMESTAMP_HEADER].to_str().unwrap().parse().unwrap();
+        assert_eq!(
+            headers[SIGNATURE_HEADER].to_str().unwrap(),
+            sign("0123456789abcdef", timestamp, body)
+        );
+        let event: Value = serde_json::from_slice(body).unwrap();
+        assert_eq!(event["type"], "created");
+        assert_eq!(event["item"]["name"], "hooked");
+    }
+
+    #[tokio::test]
+    async fn deliveries_beyond_the_limit_wait_for_a_free_slot() {
+        let running = Arc::new(AtomicUsize::new(0));
+        let most_running = Arc::new(AtomicUsize::new(0));
+        let hook = Router::new().route(
+            "/hook",
+            post({
+                let running = Arc::clone(&running);
+                let most_running = Arc::clone(&most_running);
+                move || async move {
+                    let now_running = running.fetch_add(1, Ordering::SeqCst) + 1;
+                    most_running.fetch_max(now_running, Ordering::SeqCst);
+                    tokio::time::sleep(Duration::from_millis(20)).await;
+                    running.fetch_sub(1, Ordering::SeqCst);
+                    StatusCode::NO_CONTENT
+                }
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let hook_server = tokio::spawn(async move { axum::serve(listener, hook).await });
+
+        let app = TestApp::new();
+        let worker = spawn_delivery_worker(&app.state, fast_retries(), 2);
+        let webhook = app.state.webhooks().register(CreateWebhook {
+            url: format!("http://{address}/hook"),
+            secret: "0123456789abcdef".to_string(),
+            events: vec![ItemEventType::Created],
+        });
+        for index in 0..6 {
+            app.create_item(json!({ "name": format!("limited-{index}") })).await;
+        }
+
+        let mut deliveries = Vec::new();
+        for _ in 0..200 {
+            deliveries = app.state.webhooks().deliveries(&webhook.id).unwrap().1;
+            if deliveries.len() == 6 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        worker.abort();
+        hook_server.abort();
+
+        assert_eq!(deliveries.len(), 6);
+        assert!(deliveries.iter().all(|delivery| delivery.succeeded));
+        assert!(most_running.load(Ordering::SeqCst) <= 2);
+    }
+
+    #[tokio::test]
+    async fn registration_rejects_invalid_urls_and_short_secrets() {
+        let app = TestApp::new();
+        let response = app
+            .post_json(
+                "/admin/webhooks",
+                &json!({ "url": "https://example.com/hook", "secret": "short" }),
+            )
+            .await;
+        assert_eq!(response.status, StatusCode::UNPROCESSABLE_ENTITY);
+        let fields: Vec<String> = response.json::<Value>()["fields"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|field| field["field"].as_str().unwrap().to_string())
+            .collect();
+        assert_eq!(fields, ["url", "secret"]);
+        assert!(app.state.webhooks().is_empty());
+    }
+}
@@ -0,0 +1,153 @@
+//! Windows service run mode.
+//!
+//! The `windows-service` subcommand hands the process to the service control manager,
+//! which calls back into the service main on its own thread to run the server.
+//! Stop and Shutdown controls are delivered as a shutdown signal,
+//! so the service drains and stops like the server does on Ctrl+C,
+//! reporting the stop as pending until the server has finished.
+
+use std::ffi::OsString;
+use std::sync::{LazyLock, Mutex, OnceLock, PoisonError};
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use tokio::sync::Notify;
+use windows_service::service::{
+    ServiceControl, ServiceControlAccept, ServiceExitCode, ServiceState, ServiceStatus, ServiceType,
+};
+use windows_service::service_control_handler::{self, ServiceControlHandlerResult, ServiceStatusHandle};
+use windows_service::{define_windows_service, service_dispatcher};
+
+/// Name the service is registered with, as in `sc.exe create axum-example`.
+pub const SERVICE_NAME: &str = env!("CARGO_PKG_NAME");
+
+/// How long the service control manager waits for the stop to progress before it considers the service hung.
+const STOP_WAIT_HINT: Duration = Duration::from_secs(60);
+
+/// Exit code reported to the service control manager when the server fails.
+const FAILED_EXIT_CODE: u32 = 1;
+
+type Server = Box<dyn FnOnce() -> Result<()> + Send>;
+
+/// Server to run from the service main, set before the dispatcher starts.
+static SERVER: Mutex<Option<Server>> = Mutex::new(None);
+
+/// Stop and Shutdown controls that have not been taken by a shutdown signal yet.
+static STOP: LazyLock<Notify> = LazyLock::new(Notify::new);
+
+/// Status handle for reporting the stop progress from the control handler.
+static STATUS: OnceLock<ServiceStatusHandle> = OnceLock::new();
+
+define_windows_service!(ffi_service_main, service_main);
+
+/// Run the server as a Windows service, blocking until the service has stopped.
+///
+/// Fails when the process was not started by the service control manager.
+pub fn run(server: impl FnOnce() -> Result<()> + Send + 'static) -> Result<()> {
+    *SERVER.lock().unwrap_or_else(PoisonError::into_inner) = Some(Box::new(server));
+    service_dispatcher::start(SERVICE_NAME, ffi_service_main)
+        .context("Failed to connect to the service control manager, run the service with sc.exe start")
+}
+
+/// Resolve on the next Stop or Shutdown control.
+///
+/// A control that arrives while nothing is waiting is kept for the next call.
+pub async fn stop_requested() {
+    STOP.notified().await;
+}
+
+/// Deliver a Stop or Shutdown control to the shutdown signal.
+pub fn request_stop() {
+    STOP.notify_one();
+}
+
+// The signature is fixed by define_windows_service
+#[allow(clippy::needless_pass_by_value)]
+fn service_main(_arguments: Vec<OsString>) {
+    if let Err(error) = run_service() {
+        crate::log_error!("Windows service failed: {error:#}");
+    }
+}
+
+fn run_service() -> Result<()> {
+    let handle = service_control_handler::register(SERVICE_NAME, handle_control)
+        .context("Failed to register the service control handler")?;
+    STATUS.set(handle).ok();
+    handle.set_service_status(status(ServiceState::Running, ServiceExitCode::Win32(0)))?;
+    let server = SERVER
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .take()
+        .context("Windows service started without a server")?;
+    let result = server();
+    let exit_code = match &result {
+        Ok(()) => ServiceExitCode::Win32(0),
+        Err(_) => ServiceExitCode::ServiceSpecific(FAILED_EXIT_CODE),
+    };
+    handle.set_service_status(status(ServiceState::Stopped, exit_code))?;
+    result
+}
+
+fn handle_control(control: ServiceControl) -> ServiceControlHandlerResult {
+    match control {
+        ServiceControl::Stop | ServiceControl::Shutdown => {
+            if let Some(handle) = STATUS.get()
+                && let Err(error) =
+                    handle.set_service_status(status(ServiceState::StopPending, ServiceExitCode::Win32(0)))
+            {
+                crate::log_warn!("Failed to report the service stop: {error}");
+            }
+            request_stop();
+            ServiceControlHandlerResult::NoError
+        }
+        ServiceControl::Interrogate => ServiceControlHandlerResult::NoError,
+        _ => ServiceControlHandlerResult::NotImplemented,
+    }
+}
+
+/// Service status for the state, accepting Stop and Shutdown while running.
+fn status(state: ServiceState, exit_code: ServiceExitCode) -> ServiceStatus {
+    let controls_accepted = if state == ServiceState::Running {
+        ServiceControlAccept::STOP | ServiceControlAccept::SHUTDOWN
+    } else {
+        ServiceControlAccept::empty()
+    };
+    ServiceStatus {
+        service_type: ServiceType::OWN_PROCESS,
+        current_state: state,
+        controls_accepted,
+        exit_code,
+        checkpoint: 0,
+        wait_hint: if state == ServiceState::StopPending {
+            STOP_WAIT_HINT
+        } else {
+            Duration::ZERO
+        },
+        process_id: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn stop_controls_are_shutdown_signals() {
+        request_stop();
+
+        tokio::time::timeout(Duration::from_secs(5), crate::utils::shutdown_signal())
+            .await
+            .expect("a stop control should be a shutdown signal");
+    }
+
+    #[test]
+    fn only_a_running_service_accepts_controls() {
+        let running = status(ServiceState::Running, ServiceExitCode::Win32(0));
+        let stopping = status(ServiceState::StopPending, ServiceExitCode::Win32(0));
+
+        assert!(running.controls_accepted.contains(ServiceControlAccept::STOP));
+        assert!(running.controls_accepted.contains(ServiceControlAccept::SHUTDOWN));
+        assert!(stopping.controls_accepted.is_empty());
+        assert_eq!(stopping.wait_hint, STOP_WAIT_HINT);
+    }
+}
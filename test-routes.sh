@@ -83,12 +83,14 @@ get() {
 delete() {
     local url="$1"
     local api_key="$2"
+    local if_match="${3:-*}"
     if [ "$SILENT" = false ]; then
         print_cyan "DELETE: $1"
     fi
     response=$(curl -s -X DELETE --http2-prior-knowledge \
         -H "Content-Type: application/json" \
         -H "api-key: $api_key" \
+        -H "If-Match: $if_match" \
         -w '{"http_code":%{http_code},"time_namelookup":%{time_namelookup},"time_connect":%{time_connect},"time_pretransfer":%{time_pretransfer},"time_redirect":%{time_redirect},"time_starttransfer":%{time_starttransfer},"time_total":%{time_total}}' \
         -o response.json \
         "$url")
@@ -192,6 +194,9 @@ done
 
 get "$URL:$PORT/items"
 get "$URL:$PORT/items?skip=1&limit=2"
+get "$URL:$PORT/items?created_after=2020-01-01T00:00:00Z"
+post "$URL:$PORT/items/query" '{"names":["esgrove","five","missing"]}'
+get "$URL:$PORT/usage"
 
 print_yellow 'Using GET with admin routes results in 405 "Method Not Allowed":'
 get "$URL:$PORT/admin/remove/pizzalover"
@@ -200,10 +205,23 @@ print_yellow "Admin routes require api key:"
 delete "$URL:$PORT/admin/remove/pizzalover"
 
 delete "$URL:$PORT/admin/remove/pizzalover" "$API_KEY"
-delete "$URL:$PORT/admin/remove/pizzalover9000" "$API_KEY"
+
+print_yellow 'Removing with a stale revision results in 412 "Precondition Failed":'
+delete "$URL:$PORT/admin/remove/pizzalover9000" "$API_KEY" 2
+delete "$URL:$PORT/admin/remove/pizzalover9000" "$API_KEY" 1
+
+get "$URL:$PORT/items/pizzalover9000/history"
 
 get "$URL:$PORT/items"
 
+get "$URL:$PORT/admin/config" "$API_KEY"
+
+post "$URL:$PORT/admin/collections" '{"name":"archive"}' "$API_KEY"
+post "$URL:$PORT/collections/archive/items" '{"name":"esgrove"}'
+get "$URL:$PORT/collections/archive/items/esgrove"
+get "$URL:$PORT/admin/collections" "$API_KEY"
+delete "$URL:$PORT/admin/collections/archive" "$API_KEY"
+
 delete "$URL:$PORT/admin/clear_items" "$API_KEY"
 
 get "$URL:$PORT/items"
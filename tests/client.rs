@@ -0,0 +1,87 @@
+//! Typed client round trip against a server running the library router.
+
+use std::sync::Arc;
+
+use axum::http::StatusCode;
+use axum_example::router::build_router;
+use axum_example::server::serve;
+use axum_example::settings::Settings;
+use axum_example::types::{AppState, DEFAULT_API_KEY, HttpProtocol, ServerConfig};
+use axum_example::version;
+use axum_example_client::{Client, ClientError, CreateItem, ErrorCode, ItemBatchQuery, ListItems};
+
+#[tokio::test]
+async fn typed_client_round_trip_against_running_server() {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    let (shutdown, shutdown_signal) = tokio::sync::oneshot::channel::<()>();
+    let server = tokio::spawn(serve(
+        listener,
+        build_router(&Arc::new(AppState::new()), &Settings::default().into_shared()),
+        HttpProtocol::default(),
+        ServerConfig::default(),
+        None,
+        async {
+            shutdown_signal.await.ok();
+        },
+    ));
+
+    let client = Client::new(&format!("http://{address}")).with_api_key(DEFAULT_API_KEY);
+    assert_eq!(client.health().await.unwrap().status, "ok");
+    assert_eq!(client.version().await.unwrap().version, version::PACKAGE_VERSION);
+
+    let created = client
+        .create_item(&CreateItem {
+            name: "typed".to_string(),
+            tags: vec!["client".to_string()],
+            ..CreateItem::default()
+        })
+        .await
+        .unwrap();
+    assert_eq!(created.revision, 1);
+    assert_eq!(client.query_item("typed").await.unwrap(), created);
+    let batch = client
+        .query_items(&ItemBatchQuery {
+            names: vec!["typed".to_string(), "untyped".to_string()],
+        })
+        .await
+        .unwrap();
+    assert_eq!(batch.items, vec![created.clone()]);
+    assert_eq!(batch.missing, vec!["untyped"]);
+    let page = client
+        .list_items(&ListItems {
+            tag: Some("client".to_string()),
+            ..ListItems::default()
+        })
+        .await
+        .unwrap();
+    assert_eq!(page.names, vec!["typed"]);
+    let usage = client.usage().await.unwrap();
+    assert_eq!(usage.num_items, 1);
+    assert_eq!(usage.max_items, None);
+
+    let Err(ClientError::Api { status, error }) = client.remove_item("typed", Some(7)).await else {
+        panic!("stale revision should be rejected");
+    };
+    assert_eq!(status, StatusCode::PRECONDITION_FAILED);
+    assert_eq!(error.code, ErrorCode::PreconditionFailed);
+    assert!(error.request_id.is_some());
+    assert_eq!(
+        client.remove_item("typed", Some(created.revision)).await.unwrap().name,
+        "typed"
+    );
+
+    let Err(ClientError::Api { error, .. }) = client.query_item("typed").await else {
+        panic!("removed item should not be found");
+    };
+    assert_eq!(error.code, ErrorCode::NotFound);
+    let Err(ClientError::Api { error, .. }) = client.create_item(&CreateItem::default()).await else {
+        panic!("empty name should be invalid");
+    };
+    assert_eq!(error.code, ErrorCode::ValidationFailed);
+    assert_eq!(error.fields[0].field, "name");
+    assert_eq!(client.clear_items().await.unwrap().message, "Removed 0 items");
+
+    shutdown.send(()).ok();
+    server.await.unwrap().unwrap();
+}